@@ -0,0 +1,48 @@
+//! Batch insert of deposits into the database configured in the file named
+//! by `BRIDGE_CONFIG` (default `config.json`). Use a disposable database:
+//! every iteration inserts new rows under the `bench` scanner name.
+
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use glitch_bridge::args::Args;
+use glitch_bridge::bench::synthetic_deposits;
+use glitch_bridge::{BridgeStore, Config};
+use log::LevelFilter;
+
+const RECIPIENT: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+
+fn db_insert(c: &mut Criterion) {
+    let config_path = PathBuf::from(std::env::var("BRIDGE_CONFIG").unwrap_or_else(|_| "config.json".to_string()));
+    if !config_path.exists() {
+        eprintln!("Skipping db_insert: {} not found", config_path.display());
+        return;
+    }
+
+    let config = Config::new(Args {
+        config: config_path,
+        loglevel: LevelFilter::Off,
+        read_only: false,
+        command: None,
+    });
+    let store = BridgeStore::new(config.db);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("insert_txs");
+    group.sample_size(10);
+
+    for size in [10_u32, 100, 1000] {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.to_async(&runtime).iter_batched(
+                || synthetic_deposits(size, RECIPIENT, 1).1,
+                |deposits| store.insert_txs("bench", deposits),
+                criterion::BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, db_insert);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use glitch_bridge::decoder::decode_deposit;
+use serde_json::json;
+use web3::types::Log;
+
+const RECIPIENT: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+
+/// `TransferToGlitch(address,string,uint256)` log as returned by eth_getLogs.
+fn deposit_log() -> Log {
+    let mut data = Vec::new();
+    data.extend_from_slice(&[0_u8; 31]);
+    data.push(0x40);
+    let mut amount = [0_u8; 32];
+    amount[24..].copy_from_slice(&1_000_000_000_000_000_000_u64.to_be_bytes());
+    data.extend_from_slice(&amount);
+    data.extend_from_slice(&[0_u8; 31]);
+    data.push(RECIPIENT.len() as u8);
+    let mut recipient = RECIPIENT.as_bytes().to_vec();
+    recipient.resize(64, 0);
+    data.extend_from_slice(&recipient);
+
+    serde_json::from_value(json!({
+        "address": "0x4b9a9b7ea8eab5ef2a1a3bd5f5d2e3b4a8e1c0f1",
+        "topics": [
+            "0x6bf4b6e6ff6ec4c7e7b0d6e5eeeb8e6f5b8c1a2d3e4f5a6b7c8d9e0f1a2b3c4d",
+            "0x000000000000000000000000b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0"
+        ],
+        "data": format!("0x{}", hex::encode(data)),
+        "blockNumber": "0x10",
+        "transactionHash": "0x7a1c9e3f5b2d4a6c8e0f1b3d5a7c9e1f3b5d7a9c1e3f5b7d9a1c3e5f7b9d1a3c",
+        "logIndex": "0x0",
+        "removed": false
+    }))
+    .unwrap()
+}
+
+fn decoding(c: &mut Criterion) {
+    let log = deposit_log();
+    c.bench_function("decode_deposit", |b| b.iter(|| decode_deposit(black_box(&log))));
+
+    let logs: Vec<Log> = (0..1000).map(|_| deposit_log()).collect();
+    c.bench_function("decode_deposit x1000", |b| {
+        b.iter(|| {
+            logs.iter()
+                .map(|log| decode_deposit(black_box(log)))
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+criterion_group!(benches, decoding);
+criterion_main!(benches);
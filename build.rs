@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    // On a branch, HEAD only names it: commits move the branch ref, loose
+    // or packed, and HEAD stays the same. A missing file would rerun this
+    // on every build, so a ref only packed so far is watched through its
+    // directory, where the next commit writes it.
+    if let Some(branch) = fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|branch| branch.trim().to_string()))
+    {
+        let loose = Path::new(".git").join(branch);
+        match (loose.exists(), loose.parent()) {
+            (true, _) => println!("cargo:rerun-if-changed={}", loose.display()),
+            (false, Some(directory)) if directory.exists() => {
+                println!("cargo:rerun-if-changed={}", directory.display())
+            }
+            _ => {}
+        }
+        if Path::new(".git/packed-refs").exists() {
+            println!("cargo:rerun-if-changed=.git/packed-refs");
+        }
+    }
+}
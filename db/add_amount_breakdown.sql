@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN amount_breakdown TEXT NULL;
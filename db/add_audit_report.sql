@@ -0,0 +1,9 @@
+CREATE TABLE audit_report (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	supply_ok BOOLEAN NULL,
+	fee_drift_ok BOOLEAN NOT NULL,
+	stuck_txs INT UNSIGNED NOT NULL,
+	passed BOOLEAN NOT NULL,
+	report TEXT NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP()
+);
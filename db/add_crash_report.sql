@@ -0,0 +1,10 @@
+CREATE TABLE crash_report (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	message TEXT NOT NULL,
+	location VARCHAR(255),
+	thread VARCHAR(255),
+	tx_id INT UNSIGNED NULL,
+	backtrace TEXT,
+	build_version VARCHAR(50) NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP()
+);
@@ -0,0 +1,9 @@
+CREATE TABLE decoder_divergence (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	tx_eth_hash VARCHAR(66),
+	log_index BIGINT UNSIGNED NULL,
+	block_number BIGINT UNSIGNED NULL,
+	difference TEXT NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP()
+);
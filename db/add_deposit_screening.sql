@@ -0,0 +1,11 @@
+CREATE TABLE deposit_screening (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	tx_eth_hash VARCHAR(66) NOT NULL,
+	screener VARCHAR(50) NOT NULL,
+	verdict enum('allow', 'hold', 'error') NOT NULL,
+	risk VARCHAR(255) NULL,
+	detail TEXT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	INDEX deposit_screening_tx_eth_hash (tx_eth_hash)
+);
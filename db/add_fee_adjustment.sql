@@ -0,0 +1,14 @@
+CREATE TABLE fee_adjustment (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	amount VARCHAR(255) NOT NULL,
+	reason TEXT NOT NULL,
+	requested_by VARCHAR(255) NOT NULL,
+	reference VARCHAR(255) NULL,
+	accumulated_before VARCHAR(255) NOT NULL,
+	accumulated_after VARCHAR(255) NOT NULL,
+	fee_transaction_id INT UNSIGNED NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	UNIQUE INDEX fee_adjustment_reference (scanner_name, reference),
+	CONSTRAINT fk_fee_adjustment_transaction FOREIGN KEY (fee_transaction_id) REFERENCES fee_transaction (id)
+);
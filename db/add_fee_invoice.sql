@@ -0,0 +1,13 @@
+CREATE TABLE fee_invoice (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	fee_transaction_id INT UNSIGNED NOT NULL,
+	scanner_name VARCHAR(255) NOT NULL,
+	period_start TIMESTAMP NOT NULL,
+	period_end TIMESTAMP NOT NULL,
+	tx_count INT UNSIGNED NOT NULL,
+	total_volume VARCHAR(255) NOT NULL,
+	fee_amount VARCHAR(255) NOT NULL,
+	glitch_tx_hash VARCHAR(66) NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	CONSTRAINT fk_fee_invoice_transaction FOREIGN KEY (fee_transaction_id) REFERENCES fee_transaction (id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE fee_policy (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	business_fee_percentage VARCHAR(255) NOT NULL,
+	fee_destination VARCHAR(255) NOT NULL,
+	effective_from TIMESTAMP NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	INDEX fee_policy_scanner_effective_from (scanner_name, effective_from)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE fee_transaction
+ADD COLUMN confirmation enum('CONFIRMED', 'DISCREPANCY') NULL,
+ADD COLUMN discrepancy TEXT NULL;
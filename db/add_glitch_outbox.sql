@@ -0,0 +1,24 @@
+-- Payout decisions, committed together with the claim of their tx and sent
+-- by the submitter of the pipeline. A tx paid after a rejected attempt has
+-- a row per attempt. The recipient is encrypted like the one of the tx.
+CREATE TABLE glitch_outbox (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	tx_id INT UNSIGNED NOT NULL,
+	tx_version INT UNSIGNED NOT NULL,
+	scanner_name VARCHAR(50) NOT NULL,
+	recipient TEXT NOT NULL,
+	amount DECIMAL(38, 0) NOT NULL,
+	business_fee_amount DECIMAL(38, 0) NOT NULL,
+	business_fee_percentage VARCHAR(255) NOT NULL,
+	estimated_fee DECIMAL(38, 0) NOT NULL,
+	amount_breakdown TEXT NOT NULL,
+	tx_detected_at BIGINT NOT NULL,
+	state enum('PENDING', 'SUBMITTING', 'INCLUDED', 'NOT_INCLUDED', 'UNKNOWN', 'FAILED') NOT NULL DEFAULT 'PENDING',
+	extrinsic_hash VARCHAR(66) NULL,
+	block_hash VARCHAR(66) NULL,
+	error TEXT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	updated_at TIMESTAMP NULL ON UPDATE CURRENT_TIMESTAMP(),
+	INDEX glitch_outbox_state (scanner_name, state),
+	INDEX glitch_outbox_tx_id (tx_id)
+);
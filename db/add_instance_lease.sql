@@ -0,0 +1,5 @@
+CREATE TABLE instance_lease (
+	name VARCHAR(50) NOT NULL PRIMARY KEY,
+	holder VARCHAR(66) NOT NULL,
+	expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE tx
+ADD COLUMN network_fee VARCHAR(255) NULL,
+ADD COLUMN network_fee_estimated VARCHAR(255) NULL;
@@ -0,0 +1,16 @@
+-- Rows created before pipelines existed carry no contract address to tell
+-- their pipeline by, so they can only be assigned when there is a single
+-- scanner. With more than one the subquery below returns several rows and
+-- the migration stops here, before changing anything: add the columns and
+-- assign each row its pipeline by hand instead.
+DO (SELECT name FROM scanner_state);
+
+ALTER TABLE tx
+ADD COLUMN scanner_name VARCHAR(50) NULL;
+
+ALTER TABLE fee_transaction
+ADD COLUMN scanner_name VARCHAR(50) NULL;
+
+-- Rows created before pipelines existed belong to the only scanner.
+UPDATE tx SET scanner_name = (SELECT name FROM scanner_state) WHERE scanner_name IS NULL;
+UPDATE fee_transaction SET scanner_name = (SELECT name FROM scanner_state) WHERE scanner_name IS NULL;
@@ -0,0 +1,12 @@
+ALTER TABLE tx
+ADD COLUMN eth_block_number BIGINT UNSIGNED NULL,
+MODIFY COLUMN `state` enum('TO_PROCESS', 'PROCESSING', 'PROCESSED', 'SUSPICIOUS', 'QUARANTINED') DEFAULT 'TO_PROCESS';
+
+CREATE TABLE reorg_quarantine (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	from_block BIGINT UNSIGNED NOT NULL,
+	to_block BIGINT UNSIGNED NOT NULL,
+	until TIMESTAMP NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP()
+);
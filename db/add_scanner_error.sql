@@ -0,0 +1,9 @@
+CREATE TABLE scanner_error (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	kind enum('RPC', 'DECODE', 'SKIPPED_BLOCK') NOT NULL,
+	block_number BIGINT UNSIGNED NULL,
+	message TEXT NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	INDEX scanner_error_time (time)
+);
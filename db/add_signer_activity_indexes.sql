@@ -0,0 +1,10 @@
+-- Lookups of the signer activity monitor, matching each outgoing transfer
+-- of a signer with the payout or fee settlement that made it.
+ALTER TABLE tx
+ADD INDEX tx_extrinsic_hash (extrinsic_hash);
+
+ALTER TABLE glitch_outbox
+ADD INDEX glitch_outbox_extrinsic_hash (extrinsic_hash);
+
+ALTER TABLE fee_transaction
+ADD INDEX fee_transaction_hash (hash);
@@ -0,0 +1,13 @@
+-- Logs the scanners ignored and why, kept for the pipelines with
+-- `record_skipped_logs`, so a deposit never picked up can be traced.
+CREATE TABLE skipped_log (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	scanner_name VARCHAR(50) NOT NULL,
+	reason enum('UNKNOWN_EVENT', 'UNDECODABLE', 'FILTERED_SENDER') NOT NULL,
+	tx_eth_hash VARCHAR(66) NULL,
+	log_index BIGINT UNSIGNED NULL,
+	block_number BIGINT UNSIGNED NULL,
+	detail TEXT NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	INDEX skipped_log_tx_eth_hash (tx_eth_hash)
+);
@@ -0,0 +1,6 @@
+-- Substrate sources store SS58 addresses (up to 48 chars) in these columns.
+ALTER TABLE scanner_state
+MODIFY COLUMN monitor_address VARCHAR(66) NOT NULL;
+
+ALTER TABLE tx
+MODIFY COLUMN from_eth_address VARCHAR(66) NOT NULL;
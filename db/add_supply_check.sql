@@ -0,0 +1,8 @@
+CREATE TABLE supply_check (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	locked VARCHAR(255) NOT NULL,
+	minted VARCHAR(255) NOT NULL,
+	delta VARCHAR(255) NOT NULL,
+	within_tolerance BOOLEAN NOT NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP()
+);
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+MODIFY COLUMN `state` enum('TO_PROCESS', 'PROCESSING', 'PROCESSED', 'SUSPICIOUS') DEFAULT 'TO_PROCESS';
@@ -0,0 +1,11 @@
+-- Copy of the token registry of the configuration, rewritten at startup.
+CREATE TABLE token (
+	symbol VARCHAR(20) NOT NULL PRIMARY KEY,
+	eth_address VARCHAR(42) NOT NULL,
+	decimals TINYINT UNSIGNED NOT NULL,
+	min_amount DECIMAL(38, 0) NULL,
+	max_amount DECIMAL(38, 0) NULL,
+	business_fee DOUBLE NULL,
+	glitch_asset_id INT UNSIGNED NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP() ON UPDATE CURRENT_TIMESTAMP()
+);
@@ -0,0 +1,9 @@
+CREATE TABLE top_up_request (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	hot_wallet VARCHAR(48) NOT NULL,
+	amount VARCHAR(255) NOT NULL,
+	method VARCHAR(20) NOT NULL,
+	reference TEXT,
+	error TEXT,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP()
+);
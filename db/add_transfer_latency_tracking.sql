@@ -0,0 +1,10 @@
+-- Rows older than this migration have no submitted_at or finalized_at and
+-- would all look late, so only the rows inserted after it are checked
+-- against the SLA: the existing ones get sla_alerted = TRUE.
+ALTER TABLE tx
+ADD COLUMN submitted_at TIMESTAMP NULL,
+ADD COLUMN finalized_at TIMESTAMP NULL,
+ADD COLUMN sla_alerted BOOLEAN NOT NULL DEFAULT TRUE;
+
+ALTER TABLE tx
+ALTER COLUMN sla_alerted SET DEFAULT FALSE;
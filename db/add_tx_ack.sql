@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN ack_tx_hash VARCHAR(66) NULL;
@@ -0,0 +1,5 @@
+-- Unix time of the block of the deposit, the day it counts against the
+-- daily cap of its recipient. NULL for rows stored before, counted by the
+-- day they were detected.
+ALTER TABLE tx
+ADD COLUMN eth_block_time BIGINT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN eth_fee DECIMAL(38, 0) NULL;
@@ -0,0 +1,31 @@
+-- Lifecycle events of the txs, numbered by `sequence` in the order they
+-- happened. Triggers record them so every state change gets one, whether
+-- the bridge or an operator made it. Creating the triggers needs the
+-- TRIGGER privilege, and SUPER or log_bin_trust_function_creators when
+-- binary logging is on.
+CREATE TABLE tx_event (
+	sequence BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	tx_id INT UNSIGNED NOT NULL,
+	scanner_name VARCHAR(50) NULL,
+	tx_eth_hash VARCHAR(66) NOT NULL,
+	previous_state VARCHAR(20) NULL,
+	state VARCHAR(20) NOT NULL,
+	tx_glitch_hash VARCHAR(66) NULL,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	INDEX tx_event_tx_id (tx_id)
+);
+
+-- Last event delivered to each consumer.
+CREATE TABLE event_cursor (
+	name VARCHAR(50) NOT NULL PRIMARY KEY,
+	last_sequence BIGINT UNSIGNED NOT NULL
+);
+
+CREATE TRIGGER tx_event_on_insert AFTER INSERT ON tx FOR EACH ROW
+	INSERT INTO tx_event (tx_id, scanner_name, tx_eth_hash, previous_state, state, tx_glitch_hash)
+	VALUES (NEW.id, NEW.scanner_name, NEW.tx_eth_hash, NULL, NEW.state, NEW.tx_glitch_hash);
+
+CREATE TRIGGER tx_event_on_update AFTER UPDATE ON tx FOR EACH ROW
+	INSERT INTO tx_event (tx_id, scanner_name, tx_eth_hash, previous_state, state, tx_glitch_hash)
+	SELECT NEW.id, NEW.scanner_name, NEW.tx_eth_hash, OLD.state, NEW.state, NEW.tx_glitch_hash FROM DUAL
+	WHERE NOT (OLD.state <=> NEW.state);
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN event_version TINYINT UNSIGNED NOT NULL DEFAULT 1;
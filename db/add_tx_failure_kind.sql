@@ -0,0 +1,5 @@
+-- Why the dry run of the payout of a tx failed, cleared when the tx is
+-- claimed again. Txs whose recipient can't be paid, or whose payout the
+-- runtime rejects, are also held.
+ALTER TABLE tx
+ADD COLUMN failure_kind enum('INSUFFICIENT_FUNDS', 'BAD_DESTINATION', 'WOULD_REAP_SIGNER', 'REJECTED') NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN labels VARCHAR(255) NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN version INT UNSIGNED NOT NULL DEFAULT 0;
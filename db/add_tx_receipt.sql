@@ -0,0 +1,4 @@
+ALTER TABLE tx
+ADD COLUMN glitch_block_number INT UNSIGNED NULL,
+ADD COLUMN extrinsic_index INT UNSIGNED NULL,
+ADD COLUMN transfer_event TEXT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE tx
+ADD COLUMN source_gas_used BIGINT UNSIGNED NULL,
+ADD COLUMN source_gas_price DECIMAL(38, 0) NULL,
+ADD COLUMN source_value DECIMAL(38, 0) NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+ADD COLUMN unlock_at TIMESTAMP NULL DEFAULT NULL;
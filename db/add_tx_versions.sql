@@ -0,0 +1,5 @@
+ALTER TABLE tx
+ADD COLUMN decoder_version VARCHAR(50) NULL,
+ADD COLUMN payout_version VARCHAR(50) NULL,
+ADD INDEX tx_decoder_version (decoder_version),
+ADD INDEX tx_payout_version (payout_version);
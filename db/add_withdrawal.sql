@@ -0,0 +1,15 @@
+CREATE TABLE withdrawal (
+	id INT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+	event_id VARCHAR(66) NOT NULL,
+	scanner_name VARCHAR(50) NOT NULL,
+	from_glitch_address VARCHAR(66) NOT NULL,
+	to_eth_address VARCHAR(42) NOT NULL,
+	amount DECIMAL(38, 0) NOT NULL,
+	glitch_block_number INT UNSIGNED NOT NULL,
+	state ENUM('TO_PROCESS', 'PROCESSING', 'PROCESSED', 'FAILED') NOT NULL DEFAULT 'TO_PROCESS',
+	eth_tx_hash VARCHAR(66) NULL,
+	error TEXT NULL,
+	version INT UNSIGNED NOT NULL DEFAULT 0,
+	time TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP(),
+	UNIQUE INDEX withdrawal_event_id (event_id)
+);
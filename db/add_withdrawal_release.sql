@@ -0,0 +1,3 @@
+ALTER TABLE withdrawal
+MODIFY COLUMN `state` enum('TO_PROCESS', 'PROCESSING', 'PROPOSED', 'PROCESSED', 'FAILED') NOT NULL DEFAULT 'TO_PROCESS',
+ADD COLUMN safe_tx_hash VARCHAR(66) NULL;
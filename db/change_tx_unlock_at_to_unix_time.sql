@@ -0,0 +1,10 @@
+-- unlock_at as Unix time: a TIMESTAMP can't hold one past 2038, those were
+-- stored as NULL and paid right away. Copying the values locks writes, so
+-- run it with --allow-locking while the bridge is stopped.
+ALTER TABLE tx
+ADD COLUMN unlock_at_unix BIGINT NULL;
+UPDATE tx SET unlock_at_unix = UNIX_TIMESTAMP(unlock_at) WHERE unlock_at IS NOT NULL;
+ALTER TABLE tx
+DROP COLUMN unlock_at;
+ALTER TABLE tx
+RENAME COLUMN unlock_at_unix TO unlock_at;
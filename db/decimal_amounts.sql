@@ -0,0 +1,7 @@
+-- Fails on amounts that are not plain integers of up to 38 digits; find them
+-- first with: SELECT id, amount FROM tx WHERE amount NOT REGEXP '^[0-9]{1,38}$';
+ALTER TABLE tx
+MODIFY COLUMN amount DECIMAL(38, 0) NULL,
+MODIFY COLUMN business_fee_amount DECIMAL(38, 0) NULL,
+MODIFY COLUMN network_fee DECIMAL(38, 0) NULL,
+MODIFY COLUMN network_fee_estimated DECIMAL(38, 0) NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE tx
+MODIFY COLUMN to_glitch_address VARCHAR(255) NULL;
@@ -0,0 +1,92 @@
+//! Feeds crafted `TransferToGlitch` logs into the deposit decoder. Everything
+//! in a log but the contract address and topic is controlled by the
+//! depositor, so no input may panic, and whatever is decoded must be safe to
+//! store: recipients kept TO_PROCESS passed the sanitation, the ones held
+//! fit their column.
+//!
+//! Input: one flags byte (bit 0: transaction hash present, bit 1: block
+//! number present, bits 2-3: number of topics), the topics, 32 bytes each,
+//! then the log data.
+
+#![no_main]
+
+use glitch_bridge::decoder::{
+    decode_deposit, DepositEvent, DEPOSIT_EVENT_SIGNATURE, EVENT_VERSION_V1, EVENT_VERSION_V2, STATE_SUSPICIOUS,
+    STATE_TO_PROCESS,
+};
+use glitch_bridge::types::normalize_glitch_address;
+use libfuzzer_sys::fuzz_target;
+use web3::types::{Bytes, Log, H160, H256, U64};
+
+/// Width of `tx.to_glitch_address`.
+const GLITCH_ADDRESS_COLUMN_LEN: usize = 48;
+/// A `0x`-prefixed 32 byte hex public key.
+const MAX_GLITCH_ADDRESS_LEN: usize = 66;
+const GLITCH_SS58_PREFIX: u16 = 42;
+
+const UNLOCK_EVENT_SIGNATURE: &str = "TransferToGlitch(address,string,uint256,uint256)";
+const ETH_FEE_EVENT_SIGNATURE: &str = "TransferToGlitch(address,string,uint256,uint256,uint256)";
+
+fn log_from(input: &[u8], address: H160, event_topic: Option<H256>) -> Option<Log> {
+    let (&flags, mut rest) = input.split_first()?;
+
+    let mut topics: Vec<H256> = event_topic.into_iter().collect();
+    for _ in 0..(flags >> 2) & 0b11 {
+        let (topic, tail) = rest.split_at(rest.len().min(32));
+        if topic.len() < 32 {
+            return None;
+        }
+        topics.push(H256::from_slice(topic));
+        rest = tail;
+    }
+
+    Some(Log {
+        address,
+        topics,
+        data: Bytes(rest.to_vec()),
+        block_hash: None,
+        block_number: (flags & 0b10 != 0).then(|| U64::from(16)),
+        transaction_hash: (flags & 0b1 != 0).then(H256::zero),
+        transaction_index: None,
+        log_index: None,
+        transaction_log_index: None,
+        log_type: None,
+        removed: None,
+    })
+}
+
+fuzz_target!(|input: &[u8]| {
+    let address = H160::repeat_byte(0x4b);
+    let events = [
+        DepositEvent::new(EVENT_VERSION_V1, address, DEPOSIT_EVENT_SIGNATURE),
+        DepositEvent::new(EVENT_VERSION_V2, address, UNLOCK_EVENT_SIGNATURE),
+        DepositEvent::new(EVENT_VERSION_V2, address, ETH_FEE_EVENT_SIGNATURE),
+    ];
+
+    let mut results = Vec::new();
+    if let Some(log) = log_from(input, address, None) {
+        results.push(decode_deposit(&log));
+    }
+    for event in events.iter() {
+        if let Some(log) = log_from(input, address, Some(event.topic)) {
+            assert!(event.matches(&log));
+            results.push(event.decode(&log));
+        }
+    }
+
+    for deposit in results.into_iter().flatten() {
+        match deposit.state {
+            STATE_TO_PROCESS => {
+                assert!(deposit.glitch_address.len() <= MAX_GLITCH_ADDRESS_LEN);
+                assert!(!deposit.glitch_address.chars().any(char::is_control));
+                assert!(deposit.note.is_none());
+            }
+            STATE_SUSPICIOUS => {
+                assert!(deposit.glitch_address.chars().count() <= GLITCH_ADDRESS_COLUMN_LEN);
+                assert!(deposit.note.is_some());
+            }
+            state => panic!("Deposit decoded as {state}"),
+        }
+        let _ = normalize_glitch_address(&deposit.glitch_address, Some(GLITCH_SS58_PREFIX));
+    }
+});
@@ -0,0 +1,36 @@
+//! Feeds arbitrary recipients into the Glitch address parsers, as they come
+//! from deposit events and the API. No input may panic, and a normalized
+//! address must parse back to the same key and normalize to itself.
+//!
+//! Input: the network prefix, two bytes little endian, then the address.
+
+#![no_main]
+
+use glitch_bridge::types::{
+    account_id_from_ss58, normalize_glitch_address, parse_glitch_address, public_to_ss58, ss58_prefix_of,
+};
+use libfuzzer_sys::fuzz_target;
+
+/// Largest prefix SS58 can encode.
+const MAX_SS58_PREFIX: u16 = 16_383;
+
+fuzz_target!(|input: &[u8]| {
+    if input.len() < 2 {
+        return;
+    }
+    let prefix = u16::from_le_bytes([input[0], input[1]]) % (MAX_SS58_PREFIX + 1);
+    let address = String::from_utf8_lossy(&input[2..]);
+
+    let _ = ss58_prefix_of(&address);
+    let _ = account_id_from_ss58(&address);
+    if let Ok(public) = parse_glitch_address(&address, None) {
+        assert_eq!(parse_glitch_address(&public_to_ss58(&public, None), None), Ok(public));
+    }
+
+    if let Ok(normalized) = normalize_glitch_address(&address, Some(prefix)) {
+        let public = parse_glitch_address(&address, Some(prefix)).unwrap();
+        assert_eq!(parse_glitch_address(&normalized, Some(prefix)), Ok(public));
+        assert_eq!(ss58_prefix_of(&normalized), Some(prefix));
+        assert_eq!(normalize_glitch_address(&normalized, Some(prefix)), Ok(normalized));
+    }
+});
@@ -0,0 +1,22 @@
+#!/bin/sh
+# Runs every fuzz target, needs cargo-fuzz and a nightly toolchain
+# (`cargo install cargo-fuzz`).
+#
+#   fuzz/run.sh            each target for FUZZ_SECONDS (default 600)
+#   fuzz/run.sh --short    CI mode: each target for 30 seconds
+#
+# Exits non-zero on the first crash; the input is left in
+# fuzz/artifacts/<target>/ and replays with `cargo +nightly fuzz run <target> <file>`.
+set -e
+
+cd "$(dirname "$0")/.."
+
+seconds="${FUZZ_SECONDS:-600}"
+if [ "$1" = "--short" ]; then
+    seconds=30
+fi
+
+for target in $(cargo +nightly fuzz list); do
+    echo "Fuzzing $target for ${seconds}s"
+    cargo +nightly fuzz run "$target" -- -max_total_time="$seconds" -max_len=4096
+done
@@ -0,0 +1,698 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use clap::ValueEnum;
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{error, info, warn, LevelFilter};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use sp_core::{ed25519, Pair};
+use tokio::time::{Duration, Instant};
+use tracing::{info_span, Instrument};
+
+use crate::bulk::{self, BulkAction, BulkRequest};
+use crate::config::{HttpApi, Token};
+use crate::database::DatabaseEngine;
+use crate::decoder::STATE_CANCELLED;
+use crate::logger::{self, ACCESS_LOG_TARGET};
+use crate::metrics;
+use crate::quote::{QuoteError, Quoter};
+use crate::scheduler::Scheduler;
+
+const DEFAULT_PUBLIC_REQUESTS_PER_MINUTE: u32 = 60;
+/// Signature of the exact body of a deposit status response, and the key
+/// that made it, both `0x`-prefixed hex.
+const SIGNATURE_HEADER: &str = "x-bridge-signature";
+const SIGNING_KEY_HEADER: &str = "x-bridge-signing-key";
+/// Echoed back, or generated when the client doesn't send one, and logged
+/// with the access record of the request.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Fixed one minute window per client IP for the unauthenticated routes.
+struct RateLimiter {
+    requests_per_minute: u32,
+    windows: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_minute: u32) -> Self {
+        RateLimiter {
+            requests_per_minute,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn allow(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, (start, _)| now.duration_since(*start) < Duration::from_secs(60));
+
+        let (_, count) = windows.entry(ip).or_insert((now, 0));
+        *count += 1;
+        *count <= self.requests_per_minute
+    }
+}
+
+struct ApiState {
+    admin_token: Option<String>,
+    /// Read path store, serving the public and the read-only admin routes.
+    database_engine: Arc<DatabaseEngine>,
+    /// Write path store, only for the admin routes changing txs.
+    admin_database_engine: Arc<DatabaseEngine>,
+    scheduler: Arc<Scheduler>,
+    tokens: Vec<Token>,
+    rate_limiter: RateLimiter,
+    status_signer: Option<ed25519::Pair>,
+    quoters: Arc<Vec<Quoter>>,
+}
+
+fn json_response(status: StatusCode, value: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(value.to_string()))
+        .unwrap()
+}
+
+fn status_signer(key_env: &str) -> ed25519::Pair {
+    let secret = std::env::var(key_env).unwrap_or_else(|_| panic!("The status signing key {key_env} is not set!"));
+    ed25519::Pair::from_string(secret.trim(), None).expect("Invalid status signing key!")
+}
+
+/// Signs the body with the status key, if there is one, stamping it first
+/// so an old claim can't pass for a current one.
+fn signed_json_response(status: StatusCode, mut value: Value, signer: Option<&ed25519::Pair>) -> Response<Body> {
+    let signer = match signer {
+        Some(signer) => signer,
+        None => return json_response(status, value),
+    };
+
+    value["signed_at"] = json!(chrono::Utc::now().timestamp());
+    let body = value.to_string();
+    let signature = signer.sign(body.as_bytes());
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, format!("0x{}", hex::encode(signature.0)))
+        .header(SIGNING_KEY_HEADER, format!("0x{}", hex::encode(signer.public().0)))
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn is_admin(req: &Request<Body>, state: &ApiState) -> bool {
+    let expected = match &state.admin_token {
+        Some(token) => format!("Bearer {token}"),
+        None => return false,
+    };
+
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value == expected)
+        .unwrap_or(false)
+}
+
+/// Extracts the tx id from paths like `/admin/txs/{id}/{action}`.
+fn admin_tx_id(path: &str, action: &str) -> Option<u128> {
+    path.strip_prefix("/admin/txs/")?
+        .strip_suffix(action)?
+        .strip_suffix('/')?
+        .parse()
+        .ok()
+}
+
+/// Extracts the quarantine id from paths like `/admin/quarantines/{id}/release`.
+fn admin_quarantine_id(path: &str) -> Option<u128> {
+    path.strip_prefix("/admin/quarantines/")?
+        .strip_suffix("/release")?
+        .parse()
+        .ok()
+}
+
+/// Extracts the action from paths like `/admin/txs/bulk/{action}`.
+fn admin_bulk_action(path: &str) -> Option<BulkAction> {
+    let action = path.strip_prefix("/admin/txs/bulk/")?;
+    BulkAction::from_str(action, true).ok()
+}
+
+/// Extracts the module from paths like `/admin/log-levels/{module}`.
+fn admin_log_module(path: &str) -> Option<&str> {
+    path.strip_prefix("/admin/log-levels/").filter(|module| !module.is_empty())
+}
+
+/// Splits paths like `/admin/jobs/{name}/{action}` into name and action.
+fn admin_job_action(path: &str) -> Option<(&str, &str)> {
+    path.strip_prefix("/admin/jobs/")?.rsplit_once('/')
+}
+
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    req.uri().query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
+
+/// Accepts `0x`-prefixed 32 byte hashes only, normalized to lowercase.
+fn parse_eth_tx_hash(path: &str) -> Option<String> {
+    let hash = path.strip_prefix("/deposit/")?.to_lowercase();
+    let digits = hash.strip_prefix("0x")?;
+    (digits.len() == 64 && digits.chars().all(|c| c.is_ascii_hexdigit())).then(|| hash)
+}
+
+/// Public view of a deposit. Only exposes what is already visible on chain,
+/// never internal errors or review notes.
+async fn handle_deposit_status(path: &str, state: &ApiState) -> Response<Body> {
+    let tx_eth_hash = match parse_eth_tx_hash(path) {
+        Some(hash) => hash,
+        None => {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Expected a 0x-prefixed transaction hash" }),
+            )
+        }
+    };
+
+    let deposit = match state.database_engine.deposit_status(&tx_eth_hash).await {
+        Some(deposit) => deposit,
+        None => {
+            return signed_json_response(
+                StatusCode::NOT_FOUND,
+                json!({ "tx_eth_hash": tx_eth_hash, "error": "Deposit not found" }),
+                state.status_signer.as_ref(),
+            )
+        }
+    };
+
+    let status = match deposit.state.as_str() {
+        "PROCESSED" => "paid",
+        "TO_PROCESS" | "PROCESSING" => "confirmed",
+        STATE_CANCELLED => "cancelled",
+        _ => "detected",
+    };
+
+    // Where exchanges find the payout on Glitch without trusting our hashes.
+    let receipt = deposit.extrinsic_index.map(|extrinsic_index| {
+        json!({
+            "block_hash": deposit.tx_glitch_hash,
+            "block_number": deposit.glitch_block_number,
+            "extrinsic_index": extrinsic_index,
+            "event": deposit.transfer_event.as_deref().and_then(|event| serde_json::from_str::<Value>(event).ok()),
+        })
+    });
+
+    signed_json_response(
+        StatusCode::OK,
+        json!({
+            "tx_eth_hash": tx_eth_hash,
+            "status": status,
+            "tx_glitch_hash": deposit.tx_glitch_hash,
+            "detected_at": deposit.detected_at,
+            "paid_at": deposit.paid_at,
+            "unlock_at": deposit.unlock_at,
+            "receipt": receipt,
+        }),
+        state.status_signer.as_ref(),
+    )
+}
+
+/// Body of `POST /quote`. `amount` is in the smallest unit of the token;
+/// `network` picks the pipeline when several bridge the token.
+#[derive(Deserialize, Debug)]
+struct QuoteRequest {
+    token: String,
+    amount: String,
+    network: Option<String>,
+    recipient: Option<String>,
+}
+
+async fn handle_quote(req: Request<Body>, state: &ApiState) -> Response<Body> {
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Error reading the quote request body: {}", e);
+            return json_response(StatusCode::BAD_REQUEST, json!({ "error": "Unreadable body" }));
+        }
+    };
+    let request: QuoteRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(e) => return json_response(StatusCode::BAD_REQUEST, json!({ "error": format!("Invalid body: {e}") })),
+    };
+    let amount = match request.amount.parse::<u128>() {
+        Ok(amount) if amount > 0 => amount,
+        _ => {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Expected the amount as a positive integer string" }),
+            )
+        }
+    };
+
+    let quoter = state.quoters.iter().find(|quoter| {
+        quoter.token_symbol() == Some(request.token.as_str())
+            && request.network.as_ref().map_or(true, |network| &quoter.network == network)
+    });
+    let quoter = match quoter {
+        Some(quoter) => quoter,
+        None => {
+            return json_response(
+                StatusCode::NOT_FOUND,
+                json!({ "error": format!("No pipeline bridges {}", request.token) }),
+            )
+        }
+    };
+
+    match quoter.quote(&state.database_engine, amount, request.recipient.as_deref()).await {
+        Ok(quote) => json_response(StatusCode::OK, json!(quote)),
+        Err(QuoteError::BadRequest(e)) => json_response(StatusCode::BAD_REQUEST, json!({ "error": e })),
+        Err(QuoteError::Unavailable(e)) => {
+            warn!("Quote of {} unavailable: {}", quoter.network, e);
+            json_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({ "error": "Quotes are unavailable, try again later" }),
+            )
+        }
+    }
+}
+
+fn handle_job_action(req: &Request<Body>, state: &ApiState, name: &str, action: &str) -> Response<Body> {
+    let job = match state.scheduler.job(name) {
+        Some(job) => job,
+        None => {
+            return json_response(
+                StatusCode::NOT_FOUND,
+                json!({ "error": format!("No job named {name}") }),
+            )
+        }
+    };
+
+    match action {
+        "pause" => job.pause(),
+        "resume" => job.resume(),
+        "interval" => match query_param(req, "ms").and_then(|ms| ms.parse::<u64>().ok()) {
+            Some(ms) if ms > 0 => job.set_interval(Duration::from_millis(ms)),
+            _ => {
+                return json_response(
+                    StatusCode::BAD_REQUEST,
+                    json!({ "error": "Expected a positive `ms` query parameter" }),
+                )
+            }
+        },
+        _ => return json_response(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+    }
+
+    json_response(StatusCode::OK, json!({ "job": name, "paused": job.is_paused() }))
+}
+
+fn log_levels_response() -> Response<Body> {
+    let (root, modules) = logger::levels();
+    let modules: BTreeMap<String, String> = modules
+        .into_iter()
+        .map(|(module, level)| (module, level.to_string()))
+        .collect();
+    json_response(StatusCode::OK, json!({ "root": root.to_string(), "modules": modules }))
+}
+
+/// Sets the level of a module from the `level` query parameter; `default`
+/// drops the override and the module logs at the global level again.
+fn handle_log_level(req: &Request<Body>, module: &str) -> Response<Body> {
+    let level = match query_param(req, "level").as_deref() {
+        Some("default") => None,
+        Some(level) => match level.parse::<LevelFilter>() {
+            Ok(level) => Some(level),
+            Err(_) => {
+                return json_response(
+                    StatusCode::BAD_REQUEST,
+                    json!({ "error": format!("Invalid level {level}") }),
+                )
+            }
+        },
+        None => {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "Expected a `level` query parameter" }),
+            )
+        }
+    };
+
+    if let Err(e) = logger::set_module_level(module, level) {
+        return json_response(StatusCode::BAD_REQUEST, json!({ "error": e }));
+    }
+    warn!("Log level of {} set to {:?} by an operator.", module, level);
+    log_levels_response()
+}
+
+/// Previews the txs a bulk action would change unless the body says
+/// `"dry_run": false`, so nothing is touched by mistake.
+async fn handle_bulk_action(req: Request<Body>, state: &ApiState, action: BulkAction) -> Response<Body> {
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Error reading the bulk request body: {}", e);
+            return json_response(StatusCode::BAD_REQUEST, json!({ "error": "Unreadable body" }));
+        }
+    };
+    let request: BulkRequest = if body.is_empty() {
+        BulkRequest::default()
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                return json_response(StatusCode::BAD_REQUEST, json!({ "error": format!("Invalid body: {e}") }))
+            }
+        }
+    };
+
+    let dry_run = request.dry_run.unwrap_or(true);
+    let token_pipelines = request.filter.token_pipelines(
+        state.quoters.iter().map(|quoter| (quoter.network.as_str(), quoter.token_symbol())),
+    );
+    let ids = bulk::apply(
+        &state.admin_database_engine,
+        action,
+        &request.filter,
+        token_pipelines,
+        request.note,
+        dry_run,
+    )
+    .await;
+    if !dry_run {
+        info!("Bulk {:?} applied by an operator to {} tx(s).", action, ids.len());
+    }
+
+    json_response(
+        StatusCode::OK,
+        json!({ "action": action, "dry_run": dry_run, "count": ids.len(), "ids": ids }),
+    )
+}
+
+/// Body of `POST /admin/txs/{id}/cancel`.
+#[derive(Deserialize, Debug)]
+struct CancelRequest {
+    note: String,
+}
+
+/// Cancels a deposit not yet submitted for payout, with a required note
+/// kept as its error. A payer that already claimed it wins, and the cancel
+/// is refused with the state it found.
+async fn handle_cancel(req: Request<Body>, state: &ApiState, id: u128) -> Response<Body> {
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Error reading the cancel request body: {}", e);
+            return json_response(StatusCode::BAD_REQUEST, json!({ "error": "Unreadable body" }));
+        }
+    };
+    let note = match serde_json::from_slice::<CancelRequest>(&body) {
+        Ok(request) if !request.note.trim().is_empty() => request.note.trim().to_string(),
+        Ok(_) => return json_response(StatusCode::BAD_REQUEST, json!({ "error": "A note is required" })),
+        Err(e) => {
+            return json_response(StatusCode::BAD_REQUEST, json!({ "error": format!("Invalid body: {e}") }))
+        }
+    };
+
+    match state.admin_database_engine.cancel_tx(id, &format!("Cancelled by an operator: {note}")).await {
+        Ok(()) => {
+            warn!("Tx {} cancelled by an operator: {}", id, note);
+            json_response(StatusCode::OK, json!({ "id": id, "state": STATE_CANCELLED }))
+        }
+        Err(Some(current)) => json_response(
+            StatusCode::CONFLICT,
+            json!({ "error": format!("Tx {id} is {current} and can't be cancelled"), "state": current }),
+        ),
+        Err(None) => json_response(StatusCode::NOT_FOUND, json!({ "error": format!("No tx with id {id}") })),
+    }
+}
+
+async fn handle_admin(req: Request<Body>, state: Arc<ApiState>) -> Response<Body> {
+    if !is_admin(&req, &state) {
+        warn!("Rejected admin request to {}", req.uri().path());
+        return json_response(StatusCode::UNAUTHORIZED, json!({ "error": "Unauthorized" }));
+    }
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    match (&method, path.as_str()) {
+        (&Method::GET, "/admin/txs/suspicious") => {
+            let txs = state.database_engine.suspicious_txs().await;
+            json_response(StatusCode::OK, json!(txs))
+        }
+        (&Method::GET, "/admin/events") => {
+            let after = query_param(&req, "after")
+                .and_then(|after| after.parse().ok())
+                .unwrap_or(0_u64);
+            let limit = query_param(&req, "limit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(100_u32)
+                .min(1000);
+            let events = state.database_engine.tx_events(after, limit).await;
+            json_response(StatusCode::OK, json!(events))
+        }
+        (&Method::GET, "/admin/scanner-errors") => {
+            let limit = query_param(&req, "limit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(100_u32)
+                .min(1000);
+            let errors = state
+                .database_engine
+                .scanner_errors(query_param(&req, "scanner"), limit)
+                .await;
+            json_response(StatusCode::OK, json!(errors))
+        }
+        (&Method::GET, "/admin/fees/network") => {
+            let hours = query_param(&req, "hours")
+                .and_then(|hours| hours.parse().ok())
+                .unwrap_or(24_u32)
+                .min(24 * 90);
+            let stats = state
+                .database_engine
+                .network_fee_stats(query_param(&req, "scanner").as_deref(), hours)
+                .await;
+            json_response(StatusCode::OK, json!(stats))
+        }
+        (&Method::GET, "/admin/jobs") => json_response(StatusCode::OK, json!(state.scheduler.jobs())),
+        (&Method::GET, "/admin/log-levels") => log_levels_response(),
+        (&Method::POST, _) if admin_log_module(&path).is_some() => {
+            handle_log_level(&req, admin_log_module(&path).unwrap())
+        }
+        (&Method::POST, _) if admin_job_action(&path).is_some() => {
+            let (name, action) = admin_job_action(&path).unwrap();
+            handle_job_action(&req, &state, name, action)
+        }
+        (&Method::POST, _) if admin_bulk_action(&path).is_some() => {
+            let action = admin_bulk_action(&path).unwrap();
+            handle_bulk_action(req, &state, action).await
+        }
+        (&Method::POST, _) if admin_tx_id(&path, "release").is_some() => {
+            let id = admin_tx_id(&path, "release").unwrap();
+            if state.admin_database_engine.release_suspicious_tx(id).await {
+                info!("Suspicious tx {} released by an operator.", id);
+                json_response(StatusCode::OK, json!({ "id": id, "state": "TO_PROCESS" }))
+            } else {
+                json_response(
+                    StatusCode::NOT_FOUND,
+                    json!({ "error": format!("No suspicious tx with id {id}") }),
+                )
+            }
+        }
+        (&Method::POST, _) if admin_tx_id(&path, "cancel").is_some() => {
+            let id = admin_tx_id(&path, "cancel").unwrap();
+            handle_cancel(req, &state, id).await
+        }
+        (&Method::POST, _) if admin_quarantine_id(&path).is_some() => {
+            let id = admin_quarantine_id(&path).unwrap();
+            if state.admin_database_engine.end_quarantine(id).await {
+                let released = state.admin_database_engine.release_expired_quarantines().await;
+                info!("Quarantine {} ended by an operator, {} tx(s) released.", id, released);
+                json_response(StatusCode::OK, json!({ "id": id, "released": released }))
+            } else {
+                json_response(
+                    StatusCode::NOT_FOUND,
+                    json!({ "error": format!("No active quarantine with id {id}") }),
+                )
+            }
+        }
+        _ => json_response(StatusCode::NOT_FOUND, json!({ "error": "Not found" })),
+    }
+}
+
+/// The client's request id when it is short and printable, a new one
+/// otherwise.
+fn request_id(req: &Request<Body>) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()))
+}
+
+/// Path with the ids and hashes in it replaced, so the latency metric has
+/// one series per endpoint, e.g. `/admin/txs/{id}/cancel`.
+fn route_label(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with("0x") {
+                "{hash}"
+            } else if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Handles the request inside a span carrying its id, then records its
+/// latency and an access record: who asked, for what, how long it took and
+/// what it got.
+async fn handle(
+    req: Request<Body>,
+    state: Arc<ApiState>,
+    remote_ip: IpAddr,
+) -> Result<Response<Body>, Infallible> {
+    let started = Instant::now();
+    let request_id = request_id(&req);
+    let (method, path) = (req.method().clone(), req.uri().path().to_string());
+    let caller = match (path.starts_with("/admin/"), is_admin(&req, &state)) {
+        (_, true) => "admin",
+        (true, false) => "unauthorized",
+        (false, false) => "public",
+    };
+
+    let mut response = route(req, state, remote_ip)
+        .instrument(info_span!("request", id = %request_id))
+        .await;
+
+    let latency = started.elapsed();
+    let status = response.status();
+    // Probes of random paths would otherwise add a series each.
+    let route = match status {
+        StatusCode::NOT_FOUND => "unmatched".to_string(),
+        _ => route_label(&path),
+    };
+    metrics::API_REQUEST_DURATION
+        .with_label_values(&[&route, method.as_str(), status.as_str()])
+        .observe(latency.as_secs_f64());
+    info!(
+        target: ACCESS_LOG_TARGET,
+        "{}",
+        json!({
+            "request_id": request_id,
+            "remote_ip": remote_ip.to_string(),
+            "caller": caller,
+            "method": method.as_str(),
+            "path": path,
+            "status": status.as_u16(),
+            "latency_ms": latency.as_millis() as u64,
+        })
+    );
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(response)
+}
+
+async fn route(req: Request<Body>, state: Arc<ApiState>, remote_ip: IpAddr) -> Response<Body> {
+    if req.uri().path().starts_with("/admin/") {
+        return handle_admin(req, state).await;
+    }
+
+    if req.method() == Method::GET && req.uri().path().starts_with("/deposit/") {
+        if !state.rate_limiter.allow(remote_ip) {
+            return json_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                json!({ "error": "Too many requests" }),
+            );
+        }
+        return handle_deposit_status(req.uri().path(), &state).await;
+    }
+
+    if req.method() == Method::POST && req.uri().path() == "/quote" {
+        if !state.rate_limiter.allow(remote_ip) {
+            return json_response(
+                StatusCode::TOO_MANY_REQUESTS,
+                json!({ "error": "Too many requests" }),
+            );
+        }
+        return handle_quote(req, &state).await;
+    }
+
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/tokens") => json_response(StatusCode::OK, json!(state.tokens)),
+        (&Method::GET, "/deposit-signing-key") => match &state.status_signer {
+            Some(signer) => json_response(
+                StatusCode::OK,
+                json!({ "algorithm": "ed25519", "public_key": format!("0x{}", hex::encode(signer.public().0)) }),
+            ),
+            None => json_response(StatusCode::NOT_FOUND, json!({ "error": "Status responses are not signed" })),
+        },
+        (&Method::GET, "/metrics") => {
+            let (content_type, body) = metrics::gather();
+            Response::builder()
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap(),
+    }
+}
+
+/// Serves the API. Reads go through `database_engine`, which should be the
+/// read-only user's, and only the token guarded admin routes that change txs
+/// use `admin_database_engine`.
+pub async fn serve(
+    api_config: HttpApi,
+    database_engine: Arc<DatabaseEngine>,
+    admin_database_engine: Arc<DatabaseEngine>,
+    tokens: Vec<Token>,
+    scheduler: Arc<Scheduler>,
+    quoters: Arc<Vec<Quoter>>,
+) {
+    let address: SocketAddr = match api_config.listen_address.parse() {
+        Ok(address) => address,
+        Err(e) => {
+            error!("Invalid API listen address {}: {e}", api_config.listen_address);
+            return;
+        }
+    };
+
+    let state = Arc::new(ApiState {
+        admin_token: api_config.admin_token,
+        database_engine,
+        admin_database_engine,
+        scheduler,
+        tokens,
+        rate_limiter: RateLimiter::new(
+            api_config
+                .public_requests_per_minute
+                .unwrap_or(DEFAULT_PUBLIC_REQUESTS_PER_MINUTE),
+        ),
+        status_signer: api_config.status_signing_key_env.as_deref().map(status_signer),
+        quoters,
+    });
+
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let state = state.clone();
+        let remote_ip = conn.remote_addr().ip();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| handle(req, state.clone(), remote_ip)))
+        }
+    });
+
+    info!("HTTP API listening on {}", address);
+
+    if let Err(e) = Server::bind(&address).serve(make_service).await {
+        error!("HTTP API stopped: {e}");
+    }
+}
@@ -1,18 +1,161 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use dialoguer::{theme::ColorfulTheme, Input};
 use log::LevelFilter;
 use std::{self, fmt::Debug, io::Error};
 
+use crate::bulk::BulkAction;
+
 /// Glitch blockchain bridge.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
-    /// Configuration file to use
+    /// Configuration file to use. Any setting can also be given, or
+    /// overridden, with GLITCH_BRIDGE__<PATH> environment variables, e.g.
+    /// GLITCH_BRIDGE__DB__PASSWORD or GLITCH_BRIDGE__NETWORKS__0__WS_NODE
     #[clap(short, long, value_parser, default_value = "config.json")]
     pub config: std::path::PathBuf,
     /// Level of logs, can be (OFF, ERROR, WARN, INFO, DEBUG, TRACE)
     #[clap(short, long, default_value = "INFO")]
     pub loglevel: LevelFilter,
+    /// Standby mode: connect to everything, scan and record deposits, but
+    /// never submit an extrinsic or transaction. For warm standbys and for
+    /// validating a restored database before going active
+    #[clap(long)]
+    pub read_only: bool,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Maintenance commands. Without one the bridge runs normally.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Dump scanner state, pending txs and fee counters into a signed snapshot
+    ExportState {
+        #[clap(value_parser)]
+        output: std::path::PathBuf,
+    },
+    /// Restore a snapshot created with export-state into the configured database
+    ImportState {
+        #[clap(value_parser)]
+        input: std::path::PathBuf,
+    },
+    /// Compare the live database schema with the one this version expects
+    /// and report any drift. Exits with an error if they differ
+    SchemaCheck,
+    /// Apply a migration from db/ without locking busy tables. Only shows
+    /// how each statement would run unless --apply is given
+    Migrate {
+        #[clap(value_parser)]
+        file: std::path::PathBuf,
+        /// Run the statements instead of listing them
+        #[clap(long)]
+        apply: bool,
+        /// Also run changes that lock writes while they copy the table, only
+        /// taken when no bridge is connected to the database
+        #[clap(long)]
+        allow_locking: bool,
+    },
+    /// Export the invoices of the business fee payments as CSV, or JSON if
+    /// the output file ends in .json
+    ExportFeeInvoices {
+        #[clap(value_parser)]
+        output: std::path::PathBuf,
+        /// Only export the invoices of this network (pipeline)
+        #[clap(long)]
+        network: Option<String>,
+    },
+    /// Apply the manual fee adjustments of a CSV with the header
+    /// network,amount,requested_by,reason,reference. Only lists them unless
+    /// --apply is given
+    ImportFeeAdjustments {
+        #[clap(value_parser)]
+        input: std::path::PathBuf,
+        /// Change the fee counters instead of listing the adjustments
+        #[clap(long)]
+        apply: bool,
+    },
+    /// Requeue, hold or release every tx matching the filters. Only lists
+    /// the matching txs unless --apply is given
+    BulkTxs {
+        #[clap(value_enum)]
+        action: BulkAction,
+        /// Only txs in this state
+        #[clap(long)]
+        state: Option<String>,
+        /// Only txs of this network (pipeline)
+        #[clap(long)]
+        network: Option<String>,
+        /// Only txs created at or after this UTC date (YYYY-MM-DD[ HH:MM:SS])
+        #[clap(long)]
+        from: Option<String>,
+        /// Only txs created before this UTC date (YYYY-MM-DD[ HH:MM:SS])
+        #[clap(long)]
+        to: Option<String>,
+        /// Only txs of the networks (pipelines) of this token symbol
+        #[clap(long)]
+        token: Option<String>,
+        /// Only txs whose error matches this SQL LIKE pattern, e.g. %timeout%
+        #[clap(long)]
+        error_pattern: Option<String>,
+        /// Replace the error of the changed txs with this note
+        #[clap(long)]
+        note: Option<String>,
+        /// Change the txs instead of listing them
+        #[clap(long)]
+        apply: bool,
+    },
+    /// List the Glitch addresses paid by many distinct Ethereum senders in
+    /// a recent window, for manual review
+    DuplicateRecipients {
+        /// How far back to look
+        #[clap(long, default_value = "60")]
+        window_in_minutes: u64,
+        /// Only addresses paid by at least this many distinct senders
+        #[clap(long, default_value = "5")]
+        min_senders: usize,
+    },
+    /// Fetch the logs of a deposit transaction again, decode them with the
+    /// current decoder and compare them field by field with the stored rows
+    VerifyDeposit {
+        #[clap(value_parser)]
+        tx_eth_hash: String,
+        /// Network (pipeline) of the deposit, if no row was stored for it
+        #[clap(long)]
+        network: Option<String>,
+    },
+    /// Compare the tokens held by the bridge contract of each network with
+    /// what the bridge owes and write a solvency attestation (JSON)
+    AttestReserves {
+        #[clap(value_parser, default_value = "attestation.json")]
+        output: std::path::PathBuf,
+        /// Environment variable with an ed25519 seed to sign the attestation
+        #[clap(long)]
+        signing_key_env: Option<String>,
+    },
+    /// Write Grafana dashboards over the bridge metrics into a directory
+    Dashboards {
+        #[clap(value_parser, default_value = "dashboards")]
+        output: std::path::PathBuf,
+    },
+    /// Insert synthetic deposits and measure how fast the payer pays them out
+    /// against a dev Glitch node
+    LoadTest {
+        /// Name of the network (pipeline) to load
+        #[clap(long)]
+        network: String,
+        /// Number of synthetic deposits
+        #[clap(long, default_value = "100")]
+        deposits: u32,
+        /// Glitch address receiving every payout
+        #[clap(long)]
+        recipient: String,
+        /// Amount of each deposit, in the smallest unit
+        #[clap(long, default_value = "1000000000000000000")]
+        amount: u128,
+        /// Give up after this many seconds
+        #[clap(long, default_value = "600")]
+        timeout_in_secs: u64,
+    },
 }
 
 pub fn request_private_keys() -> Result<String, Error> {
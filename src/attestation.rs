@@ -0,0 +1,177 @@
+//! Solvency attestation: the tokens the bridge contract of each pipeline
+//! holds on its network against what the bridge owes back, every deposit
+//! not cancelled minus the withdrawals already released. Balances are read
+//! at the last block the scanner of the pipeline stored, so the ledger and
+//! the balance cover the same deposits, and that block is recorded in the
+//! artifact so anyone can check them.
+
+use std::fs;
+use std::path::Path;
+
+use log::{error, info};
+use serde::Serialize;
+use serde_json::json;
+use sp_core::{ed25519, Pair};
+use web3::api::{Eth, Namespace};
+use web3::types::{BlockId, BlockNumber};
+
+use crate::config::Network;
+use crate::database::DatabaseEngine;
+use crate::glitch_scanner::withdrawals_scanner_name;
+use crate::supply_check::token_balance;
+use crate::types::{eth_transport, to_hex};
+use crate::version::BUILD_VERSION;
+
+/// Times the ledger is read again when the scanner moves on while reading it.
+const LEDGER_READ_ATTEMPTS: usize = 3;
+
+/// Reserve and liabilities of a pipeline. Amounts are decimal strings in the
+/// smallest unit of the token.
+#[derive(Debug, Serialize)]
+pub struct PipelineReserve {
+    pub name: String,
+    pub network: String,
+    pub chain_id: Option<u64>,
+    pub token_address: String,
+    /// The contract holding the tokens: the custody address, or the monitor
+    /// address without one.
+    pub holder_address: String,
+    /// Last block scanned, the balance is read at it.
+    pub block_number: u64,
+    pub block_hash: String,
+    /// Head of the network when the attestation was made.
+    pub latest_block_number: u64,
+    pub balance: String,
+    pub deposited: String,
+    pub withdrawals_released: String,
+    pub liabilities: String,
+    /// Balance minus liabilities, negative when undercollateralized.
+    pub surplus: String,
+    pub solvent: bool,
+}
+
+async fn pipeline_reserve(network: &Network, database_engine: &DatabaseEngine) -> Result<PipelineReserve, String> {
+    let token_address = network
+        .token_address
+        .clone()
+        .ok_or_else(|| format!("{} has no token_address", network.name))?;
+    let holder_address = network.custody_address.clone().unwrap_or_else(|| network.monitor_address.clone());
+
+    let transport = eth_transport(&network.eth_node_url(), network.eth_headers())
+        .await
+        .map_err(|e| format!("Error connecting with {} network: {e:?}", network.network))?;
+    let eth = Eth::new(transport);
+
+    let latest_block_number = eth
+        .block_number()
+        .await
+        .map_err(|e| format!("Error reading the latest block of {}: {e:?}", network.network))?
+        .as_u64();
+
+    // The ledger only counts when the scanner didn't move on while reading it.
+    let mut ledger = None;
+    for _ in 0..LEDGER_READ_ATTEMPTS {
+        let scanned = database_engine.get_last_block(&network.name).await;
+        let totals = database_engine
+            .ledger_totals(&network.name, &withdrawals_scanner_name(&network.name))
+            .await;
+        if database_engine.get_last_block(&network.name).await == scanned {
+            ledger = Some((scanned, totals));
+            break;
+        }
+    }
+    let (scanned, (deposited, released)) =
+        ledger.ok_or_else(|| format!("The scanner of {} kept moving while reading the ledger", network.name))?;
+
+    let block = eth
+        .block(BlockId::Number(BlockNumber::Number(scanned.into())))
+        .await
+        .map_err(|e| format!("Error reading block {} of {}: {e:?}", scanned, network.network))?
+        .ok_or_else(|| format!("No block {} on {}", scanned, network.network))?;
+    let (block_number, block_hash) = match (block.number, block.hash) {
+        (Some(number), Some(hash)) => (number.as_u64(), hash),
+        _ => return Err(format!("Block {} of {} is pending", scanned, network.network)),
+    };
+
+    // At the block hash, so a reorg makes the call fail instead of mixing blocks.
+    let balance = token_balance(&eth, network, &token_address, &holder_address, Some(BlockId::Hash(block_hash))).await?;
+    let liabilities = deposited.saturating_sub(released);
+    let surplus = balance as i128 - liabilities as i128;
+
+    Ok(PipelineReserve {
+        name: network.name.clone(),
+        network: network.network.clone(),
+        chain_id: network.chain_id,
+        token_address,
+        holder_address,
+        block_number,
+        block_hash: to_hex(block_hash),
+        latest_block_number,
+        balance: balance.to_string(),
+        deposited: deposited.to_string(),
+        withdrawals_released: released.to_string(),
+        liabilities: liabilities.to_string(),
+        surplus: surplus.to_string(),
+        solvent: surplus >= 0,
+    })
+}
+
+fn signer(key_env: &str) -> ed25519::Pair {
+    let secret = std::env::var(key_env).unwrap_or_else(|_| panic!("The attestation signing key {key_env} is not set!"));
+    ed25519::Pair::from_string(secret.trim(), None).expect("Invalid attestation signing key!")
+}
+
+/// Writes the solvency attestation of every pipeline to `output` as JSON,
+/// signed with the ed25519 key in `signing_key_env` when given: the
+/// signature covers the compact JSON of the `attestation` field. Returns
+/// whether every pipeline could be read and is solvent.
+pub async fn attest_reserves(
+    networks: &[Network],
+    database_engine: &DatabaseEngine,
+    output: &Path,
+    signing_key_env: Option<&str>,
+) -> bool {
+    let mut pipelines = Vec::new();
+    let mut errors = Vec::new();
+    for network in networks {
+        match pipeline_reserve(network, database_engine).await {
+            Ok(reserve) => {
+                info!(
+                    "{}: balance {}, liabilities {}, surplus {}",
+                    reserve.name, reserve.balance, reserve.liabilities, reserve.surplus
+                );
+                pipelines.push(reserve);
+            }
+            Err(e) => {
+                error!("{e}");
+                errors.push(json!({ "name": network.name, "error": e }));
+            }
+        }
+    }
+
+    let solvent = errors.is_empty() && pipelines.iter().all(|reserve| reserve.solvent);
+    let attestation = json!({
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "bridge_version": BUILD_VERSION,
+        "solvent": solvent,
+        "pipelines": pipelines,
+        "errors": errors,
+    });
+
+    let mut artifact = json!({ "attestation": attestation });
+    if let Some(key_env) = signing_key_env {
+        let signer = signer(key_env);
+        let signature = signer.sign(attestation.to_string().as_bytes());
+        artifact["signature"] = json!({
+            "algorithm": "ed25519",
+            "public_key": format!("0x{}", hex::encode(signer.public().0)),
+            "value": format!("0x{}", hex::encode(signature.0)),
+        });
+    }
+
+    fs::write(output, serde_json::to_string_pretty(&artifact).unwrap())
+        .expect("Error while writing the attestation file!");
+    info!("Solvency attestation written to {} (solvent: {})", output.display(), solvent);
+
+    solvent
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use chrono::{Timelike, Utc};
+use log::{info, warn};
+
+use crate::config::{Audit, Network, Notification, SupplyCheck};
+use crate::database::DatabaseEngine;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+use crate::supply_check::evaluate_supply;
+
+const DEFAULT_HOUR_UTC: u32 = 2;
+const DEFAULT_STUCK_AFTER_IN_MINUTES: u64 = 60;
+
+/// Whether today's audit is due: past the configured hour and not run yet
+/// today (UTC), so a restart doesn't run it twice.
+async fn is_audit_due(audit: &Audit, database_engine: &DatabaseEngine) -> bool {
+    let now = Utc::now();
+    if now.hour() < audit.hour_utc.unwrap_or(DEFAULT_HOUR_UTC) {
+        return false;
+    }
+
+    match database_engine.last_audit_time().await {
+        Some(last) => last.date_naive() < now.date_naive(),
+        None => true,
+    }
+}
+
+/// Runs every check and returns `(supply ok, fee drift ok, stuck txs, report)`.
+/// The supply is `None` when it isn't configured or couldn't be read.
+async fn run_audit(
+    audit: &Audit,
+    supply_check: Option<&SupplyCheck>,
+    networks: &[Network],
+    database_engine: &DatabaseEngine,
+) -> (Option<bool>, bool, usize, String) {
+    let mut report = Vec::new();
+
+    let supply_ok = match supply_check {
+        Some(supply_check) => {
+            let tolerance_in_wei = (supply_check.tolerance * (10_f64).powf(18.0)) as u128;
+            match evaluate_supply(networks, database_engine, tolerance_in_wei).await {
+                Ok(state) => {
+                    report.push(format!(
+                        "Supply: {} (locked on Ethereum {}, bridged to Glitch {}, delta {})",
+                        if state.within_tolerance { "OK" } else { "DRIFTED" },
+                        state.locked,
+                        state.minted,
+                        state.delta
+                    ));
+                    Some(state.within_tolerance)
+                }
+                Err(e) => {
+                    report.push(format!("Supply: NOT CHECKED ({e})"));
+                    None
+                }
+            }
+        }
+        None => {
+            report.push("Supply: not configured".to_string());
+            None
+        }
+    };
+
+    let mut fee_drift_ok = true;
+    for (scanner_name, accumulated, unsettled) in database_engine.fee_drift().await {
+        let matches = accumulated.parse::<u128>().ok() == unsettled.parse::<u128>().ok();
+        fee_drift_ok &= matches;
+        report.push(format!(
+            "Fees of {}: {} (counter {}, unsettled txs and adjustments {})",
+            scanner_name,
+            if matches { "OK" } else { "DRIFTED" },
+            accumulated,
+            unsettled
+        ));
+    }
+
+    let stuck_after_in_minutes = audit
+        .stuck_after_in_minutes
+        .unwrap_or(DEFAULT_STUCK_AFTER_IN_MINUTES);
+    let stuck = database_engine.stuck_txs(60 * stuck_after_in_minutes).await;
+    if stuck.is_empty() {
+        report.push(format!("Stuck txs: none older than {stuck_after_in_minutes} minutes"));
+    } else {
+        let ids = stuck
+            .iter()
+            .map(|(id, state)| format!("{id} ({state})"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        report.push(format!(
+            "Stuck txs: {} older than {} minutes: {}",
+            stuck.len(),
+            stuck_after_in_minutes,
+            ids
+        ));
+    }
+
+    (supply_ok, fee_drift_ok, stuck.len(), report.join("\n"))
+}
+
+/// Daily audit combining the supply invariant, the fee counters and the
+/// stuck txs into one report, stored in `audit_report` and sent to the
+/// operators whether it passes or not.
+pub async fn run_nightly_audit(
+    audit: Audit,
+    supply_check: Option<SupplyCheck>,
+    networks: Vec<Network>,
+    database_engine: Arc<DatabaseEngine>,
+    smtp_config: Notification,
+    mut ticker: Ticker,
+) {
+    info!("Nightly audit scheduled!");
+
+    loop {
+        ticker.tick().await;
+
+        if !is_audit_due(&audit, &database_engine).await {
+            continue;
+        }
+
+        let (supply_ok, fee_drift_ok, stuck_txs, report) =
+            run_audit(&audit, supply_check.as_ref(), &networks, &database_engine).await;
+        let passed = supply_ok != Some(false) && fee_drift_ok && stuck_txs == 0;
+
+        database_engine
+            .insert_audit_report(supply_ok, fee_drift_ok, stuck_txs, passed, &report)
+            .await;
+
+        let subject = if passed {
+            info!("Nightly audit passed:\n{}", report);
+            "Bridge nightly audit: OK"
+        } else {
+            warn!("Nightly audit found issues:\n{}", report);
+            "Bridge nightly audit: issues found!"
+        };
+        notify(&smtp_config, subject, &report).await;
+    }
+}
@@ -1,70 +1,37 @@
+use std::sync::Arc;
 use std::time::Instant;
 
-use chrono::Local;
-use lettre::{
-    message::header::ContentType,
-    transport::smtp::authentication::Credentials,
-    Message,
-    SmtpTransport,
-    Transport,
-};
+use chrono::Utc;
 use log::info;
-use serde_json::json;
-use sp_core::{ crypto::Pair, sr25519 };
-use substrate_api_client::{
-    rpc::WsRpcClient,
-    AccountId,
-    Api,
-    BaseExtrinsicParams,
-    PlainTip,
-    PlainTipExtrinsicParams,
-};
-use reqwest::Error;
+use sp_core::{ crypto::{ Pair, Ss58Codec }, sr25519 };
+use substrate_api_client::{ rpc::WsRpcClient, AccountId, Api, PlainTipExtrinsicParams };
 use tokio::time::Duration;
 use num_format::{ Locale, ToFormattedString };
 
-use crate::config::Notification;
-
-pub fn build_email(emails_to: Vec<String>, message: &str, from: &str, env: &str) -> Message {
-    let mut email_builder = Message::builder();
-
-    for email_to in emails_to {
-        email_builder = email_builder.to(email_to.parse().unwrap());
-        info!("Notification will be sent to: {}", email_to);
-    }
-
-    email_builder
-        .from(from.parse().unwrap())
-        .subject(format!("[{}] GLCH allocation is bridge now is low!", env))
-        .header(ContentType::TEXT_PLAIN)
-        .body(message.to_string())
-        .unwrap()
-}
-
-pub async fn send_slack_notify(msg: &str, slack_webhook_url: &str, env: &str) -> Result<(), Error> {
-    let client = reqwest::Client::new();
-    let body = json!({
-        "text": format!("[{}] {}", env, msg)
-    });
-
-    client.post(slack_webhook_url).json(&body).send().await?;
-
-    Ok(())
-}
+use crate::config::{ Config, Network, Notification, TopUp };
+use crate::database::DatabaseEngine;
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::{ Scheduler, Ticker };
+use crate::top_up::top_up_if_needed;
+use crate::types::GlitchApi;
 
 pub async fn check_balance_and_notify(
-    api: &Api<sr25519::Pair, WsRpcClient, BaseExtrinsicParams<PlainTip>>,
+    api: &GlitchApi,
     signer_account_id: &AccountId,
     smtp_config: Notification,
-    creds: &Credentials,
+    decimals: u8,
     low_balance_in_wei: f64,
     last_email_sent: &mut Instant,
     email_delay: &Duration
-) {
+) -> u128 {
     let signer_free_balance = match api.get_account_data(signer_account_id).unwrap() {
         Some(data) => data.free,
         None => 0_u128,
     };
+    metrics::SIGNER_BALANCE
+        .with_label_values(&[&signer_account_id.to_ss58check()])
+        .set(signer_free_balance as f64 / 10_f64.powi(decimals as i32));
 
     let now = Instant::now();
 
@@ -75,70 +42,88 @@ pub async fn check_balance_and_notify(
         let message = format!(
             "GLCH allocation in the new bridge now is lower than {} GLCH, please quickly top it up to prevent any delays in user journey. The current balance is {} GLCH. Timestamp: {}",
             (smtp_config.low_balance as i64).to_formatted_string(&Locale::en),
-            (signer_free_balance / (10_u128).pow(18)).to_formatted_string(&Locale::en),
+            (signer_free_balance / (10_u128).pow(decimals as u32)).to_formatted_string(&Locale::en),
             get_current_timestamp_in_expected_format()
         );
-        let email = build_email(
-            smtp_config.send_to.clone(),
-            &message,
-            smtp_config.from.as_str(),
-            &smtp_config.env
-        );
 
-        let mailer: SmtpTransport = SmtpTransport::relay(smtp_config.host.as_str())
-            .unwrap()
-            .credentials(creds.clone())
-            .build();
+        if notify(&smtp_config, "GLCH allocation is bridge now is low!", &message).await {
+            *last_email_sent = now;
+        }
+    }
+
+    signer_free_balance
+}
 
-        match mailer.send(&email) {
-            Ok(_) => {
-                info!("Email sent successfully!");
-                *last_email_sent = now;
-            }
-            Err(e) => info!("Could not send email: {e:?}"),
+/// Watches the free balance of the signer of a pipeline, alerting when it is
+/// low and topping it up if configured.
+pub struct BalanceMonitor {
+    glitch_node: String,
+    glitch_pk: String,
+    glitch_genesis_hash: Option<String>,
+    smtp_config: Notification,
+    decimals: u8,
+    top_up: Option<TopUp>,
+    database_engine: Arc<DatabaseEngine>,
+    ticker: Ticker,
+}
+
+impl BalanceMonitor {
+    pub fn new(
+        config: &Config,
+        network_config: &Network,
+        database_engine: Arc<DatabaseEngine>,
+        scheduler: &Scheduler
+    ) -> Self {
+        Self {
+            glitch_node: network_config.glitch_node_url(),
+            glitch_pk: network_config.glitch_private_key(config),
+            glitch_genesis_hash: config.glitch_genesis_hash.clone(),
+            smtp_config: config.notifications.clone(),
+            decimals: config.glitch_decimals.unwrap_or(18),
+            top_up: config.top_up.clone(),
+            database_engine,
+            ticker: scheduler.ticker(
+                format!("balance_monitor:{}", network_config.name),
+                Duration::from_millis(5000),
+                Duration::from_millis(500)
+            ),
         }
+    }
 
-        match send_slack_notify(&message, &smtp_config.slack_webhook, &smtp_config.env).await {
-            Ok(_) => {
-                info!("Slack notification sent successfully!");
-            }
-            Err(e) => info!("Could not send slack notification: {e:?}"),
+    /// A read-only instance only watches, it never tops up.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        if read_only {
+            self.top_up = None;
         }
+        self
     }
-}
 
-pub async fn monitor_balance(glitch_node: String, glitch_pk: String, smtp_config: Notification) {
-    info!("Balance monitoring system running now!");
-    let client = WsRpcClient::new(&glitch_node);
-    let signer: sr25519::Pair = Pair::from_string(&glitch_pk, None).unwrap();
-    let signer_account_id = AccountId::from(signer.public());
-    let api = Api::<_, _, PlainTipExtrinsicParams>
-        ::new(client)
-        .map(|api| api.set_signer(signer))
-        .unwrap();
+    pub async fn run(mut self) {
+        info!("Balance monitoring system running now!");
+        let client = WsRpcClient::new(&self.glitch_node);
+        let signer: sr25519::Pair = Pair::from_string(&self.glitch_pk, None).unwrap();
+        let signer_account_id = AccountId::from(signer.public());
+        let api = Api::<_, _, PlainTipExtrinsicParams>
+            ::new(client)
+            .map(|api| api.set_signer(signer))
+            .unwrap();
 
-    let mut interval = tokio::time::interval(Duration::from_millis(5000));
-    let mut last_email_sent = Instant::now();
-    let email_delay = Duration::from_secs(60 * smtp_config.delay_in_minutes);
+        let mut last_email_sent = Instant::now();
+        let email_delay = Duration::from_secs(60 * self.smtp_config.delay_in_minutes);
 
-    let creds = Credentials::new(smtp_config.user.clone(), smtp_config.password.clone());
+        let low_balance_in_wei = self.smtp_config.low_balance * (10_f64).powi(self.decimals as i32);
 
-    let low_balance_in_wei = smtp_config.low_balance * (10_f64).powf(18.0);
+        loop {
+            self.ticker.tick().await;
+            let balance = check_balance_and_notify(&api, &signer_account_id, self.smtp_config.clone(), self.decimals, low_balance_in_wei, &mut last_email_sent, &email_delay).await;
 
-    loop {
-        tokio::select! {
-            _ = interval.tick() => check_balance_and_notify(&api, &signer_account_id, smtp_config.clone(), &creds, low_balance_in_wei, &mut last_email_sent, &email_delay).await
+            if let Some(top_up) = &self.top_up {
+                top_up_if_needed(top_up, &self.glitch_node, self.glitch_genesis_hash.as_deref(), &signer_account_id, balance, &self.smtp_config, &self.database_engine).await;
+            }
         }
     }
 }
 
 fn get_current_timestamp_in_expected_format() -> String {
-    let now = Local::now();
-    let datetime_str = now.format("%T %d/%m/%Y").to_string();
-    let offset = now.offset().local_minus_utc();
-    let offset_hours = offset / 3600;
-
-    let timezone_str = format!("UTC{:+}", offset_hours);
-
-    format!("{} [{}]", datetime_str, timezone_str)
+    format!("{} [UTC]", Utc::now().format("%T %d/%m/%Y"))
 }
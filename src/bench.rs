@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use log::{error, info};
+use rand::Rng;
+use tokio::time::{sleep, Duration};
+use web3::types::{H160, H256, U256};
+
+use crate::bridge::Bridge;
+use crate::config::Config;
+use crate::database::DatabaseEngine;
+use crate::decoder::{Deposit, EVENT_VERSION_V1, STATE_TO_PROCESS};
+use crate::types::to_hex;
+
+/// Parameters of a load test run against a dev Glitch node.
+pub struct LoadTest {
+    pub network: String,
+    pub deposits: u32,
+    pub recipient: String,
+    pub amount: u128,
+    pub timeout_in_secs: u64,
+}
+
+/// Deposits with random hashes, all sent from a random address so the run
+/// can be told apart from real traffic and from previous runs.
+pub fn synthetic_deposits(count: u32, recipient: &str, amount: u128) -> (String, Vec<Deposit>) {
+    let mut rng = rand::thread_rng();
+    let sender = to_hex(H160::from(rng.gen::<[u8; 20]>()));
+
+    let deposits = (0..count)
+        .map(|_| Deposit {
+            tx_eth_hash: to_hex(H256::from(rng.gen::<[u8; 32]>())),
+            from_eth_address: sender.clone(),
+            amount: U256::from(amount),
+            glitch_address: recipient.to_string(),
+            state: STATE_TO_PROCESS,
+            note: None,
+            block_number: None,
+            block_time: None,
+            unlock_at: None,
+            eth_fee: None,
+            event_version: EVENT_VERSION_V1,
+            labels: Vec::new(),
+        })
+        .collect();
+
+    (sender, deposits)
+}
+
+/// Inserts the synthetic deposits, runs the payer of the network in process
+/// and reports how fast they are paid out.
+pub async fn run_load_test(mut config: Config, load_test: LoadTest) {
+    config.networks.retain(|network| network.name == load_test.network);
+    if config.networks.is_empty() {
+        error!("No network named {} in the configuration.", load_test.network);
+        return;
+    }
+
+    let database_engine = Arc::new(DatabaseEngine::new(config.db.clone()));
+    let (sender, deposits) =
+        synthetic_deposits(load_test.deposits, &load_test.recipient, load_test.amount);
+
+    let started = Instant::now();
+    database_engine.insert_txs(&load_test.network, deposits).await;
+    info!(
+        "Inserted {} synthetic deposits from {} in {:?}",
+        load_test.deposits,
+        sender,
+        started.elapsed()
+    );
+
+    let bridge = Bridge::builder()
+        .config(config)
+        .store(database_engine.clone())
+        .scanners(false)
+        .fee_payers(false)
+        .monitors(false)
+        .build();
+    tokio::task::spawn(bridge.run());
+
+    let started = Instant::now();
+    let timeout = Duration::from_secs(load_test.timeout_in_secs);
+    let mut processed = 0;
+
+    while started.elapsed() < timeout {
+        sleep(Duration::from_secs(1)).await;
+
+        let (total, done) = database_engine.count_txs_from(&sender).await;
+        if done != processed {
+            processed = done;
+            info!("{}/{} deposits paid after {:?}", processed, total, started.elapsed());
+        }
+        if processed >= total {
+            break;
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    info!(
+        "Load test finished: {}/{} deposits paid in {:.1}s ({:.2} deposits/s)",
+        processed,
+        load_test.deposits,
+        elapsed,
+        processed as f64 / elapsed
+    );
+}
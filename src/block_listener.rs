@@ -1,17 +1,346 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::compliance::{screen_deposits, ComplianceScreening};
 use crate::config;
-use crate::database::DatabaseEngine;
-use futures::StreamExt;
+use crate::database::{DatabaseEngine, ScannerErrorKind};
+use crate::decoder::{Deposit, DepositEvent, SanityChecks, STATE_SUSPICIOUS, STATE_TO_PROCESS};
+use crate::log_cache::{log_keys, LogKey, RecentLogs};
+use crate::metrics;
+use crate::notifications::notify;
+use crate::reorg::{apply_quarantines, handle_reorg, HeadHistory};
+use crate::rpc::ThrottledRpc;
+use crate::shadow_decoder::ShadowDecoder;
+use crate::skipped_logs::{record_skipped_log, SkipReason, SkippedLog};
+use crate::source_tx::record_source_txs;
+use crate::types::{eth_transport, to_hex, EthTransport};
+use futures::stream::{self, BoxStream, StreamExt};
 use log::{error, info, warn};
-use regex::Regex;
+use serde_json::json;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
 use web3::api::{Eth, EthSubscribe, Namespace};
-use web3::signing::keccak256;
-use web3::transports::WebSocket;
-use web3::types::{BlockNumber, FilterBuilder, Log, H160, H256, U64};
+use web3::error::TransportError;
+use web3::transports::Either;
+use web3::types::{BlockHeader, BlockId, BlockNumber, Filter, FilterBuilder, Log, H160, H256, U256, U64};
+use web3::Transport;
+
+/// Blocks per `eth_getLogs` call when catching up.
+const DEFAULT_CATCH_UP_CHUNK_BLOCKS: u64 = 2_000;
+const DEFAULT_DECODE_WORKERS: usize = 2;
+const CATCH_UP_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_CATCH_UP_RETRY_DELAY: Duration = Duration::from_secs(300);
+/// Time between head polls of a node reached over HTTP.
+const HEAD_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Builds the `eth_getLogs` filter for deposits: the monitored contracts, the
+/// event topics and any extra indexed-topic constraints from the config, so
+/// the node only returns logs the bridge will actually decode.
+fn deposit_filter(
+    network_config: &config::Network,
+    from_block: BlockNumber,
+    to_block: BlockNumber,
+) -> Result<Filter, String> {
+    let events = DepositEvent::for_network(network_config);
+    let mut addresses: Vec<H160> = events.iter().map(|event| event.address).collect();
+    addresses.dedup();
+    let mut topics: Vec<H256> = events.iter().map(|event| event.topic).collect();
+    topics.dedup();
+
+    let indexed_topic = |position: usize| -> Result<Option<Vec<H256>>, String> {
+        network_config
+            .indexed_topics
+            .as_ref()
+            .and_then(|topics| topics.get(position).cloned().flatten())
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| value.parse().map_err(|e| format!("Invalid indexed topic value {value}: {e:?}")))
+                    .collect()
+            })
+            .transpose()
+    };
+
+    Ok(FilterBuilder::default()
+        .address(addresses)
+        .from_block(from_block)
+        .to_block(to_block)
+        .topics(
+            Some(topics),
+            indexed_topic(0)?,
+            indexed_topic(1)?,
+            indexed_topic(2)?,
+        )
+        .build())
+}
+
+/// Decodes the logs and flags the deposits failing the sanity checks,
+/// alerting the operators about each of them.
+async fn decode_deposits(
+    logs: Vec<Log>,
+    network_config: &config::Network,
+    sanity_checks: &SanityChecks,
+    smtp_config: &config::Notification,
+    database_engine: &DatabaseEngine,
+) -> Vec<Deposit> {
+    let mut deposits: Vec<Deposit> = Vec::with_capacity(logs.len());
+    let events: Vec<(DepositEvent, Option<ShadowDecoder>)> = DepositEvent::for_network(network_config)
+        .into_iter()
+        .map(|event| {
+            let shadow_decoder = ShadowDecoder::for_network(network_config, &event);
+            (event, shadow_decoder)
+        })
+        .collect();
+
+    let record_skipped_logs = network_config.record_skipped_logs.unwrap_or(false);
+    for log in logs.iter() {
+        let event = events.iter().find(|(event, _)| event.matches(log));
+        let decoded = match event {
+            Some((event, _)) => event.decode(log),
+            None => Err(format!(
+                "Log of {:?} from {:?} matches no deposit event",
+                log.transaction_hash, log.address
+            )),
+        };
+        let skip_reason = match event {
+            Some(_) => SkipReason::Undecodable,
+            None => SkipReason::UnknownEvent,
+        };
+
+        if let Some(difference) = event
+            .and_then(|(_, shadow_decoder)| shadow_decoder.as_ref())
+            .and_then(|shadow_decoder| shadow_decoder.divergence(log, &decoded))
+        {
+            warn!(
+                "Decoders diverge on log {:?} of {:?} on {}: {}",
+                log.log_index, log.transaction_hash, network_config.network, difference
+            );
+            metrics::DECODER_DIVERGENCES
+                .with_label_values(&[&network_config.name])
+                .inc();
+            database_engine
+                .insert_decoder_divergence(
+                    &network_config.name,
+                    log.transaction_hash.map(to_hex),
+                    log.log_index.map(|index| index.as_u64()),
+                    log.block_number.map(|number| number.as_u64()),
+                    &difference,
+                )
+                .await;
+        }
+
+        match decoded {
+            Ok(deposit) => match sanity_checks.screen(deposit) {
+                Ok(deposit) => deposits.push(deposit),
+                Err(mut skipped) => {
+                    skipped.log_index = log.log_index.map(|index| index.as_u64());
+                    record_skipped_log(skipped, record_skipped_logs, database_engine).await;
+                }
+            },
+            Err(e) => {
+                error!("Error decoding a deposit on {}: {}", network_config.network, e);
+                database_engine
+                    .insert_scanner_error(
+                        &network_config.name,
+                        ScannerErrorKind::Decode,
+                        log.block_number.map(|number| number.as_u64()),
+                        &e,
+                    )
+                    .await;
+                let skipped = SkippedLog::of_log(&network_config.name, log, skip_reason, e);
+                record_skipped_log(skipped, record_skipped_logs, database_engine).await;
+            }
+        }
+    }
+    alert_suspicious(deposits.iter().filter(|d| d.state == STATE_SUSPICIOUS), network_config, smtp_config).await;
+
+    deposits
+}
+
+async fn alert_suspicious<'a>(
+    deposits: impl Iterator<Item = &'a Deposit>,
+    network_config: &config::Network,
+    smtp_config: &config::Notification,
+) {
+    for deposit in deposits {
+        let message = format!(
+            "Deposit {} on {} flagged as suspicious: {}. It will not be paid until an operator releases it.",
+            deposit.tx_eth_hash,
+            network_config.network,
+            deposit.note.as_deref().unwrap_or_default()
+        );
+        warn!("{}", message);
+        metrics::SUSPICIOUS_DEPOSITS
+            .with_label_values(&[&network_config.name])
+            .inc();
+        notify(smtp_config, "Suspicious deposit detected!", &message).await;
+    }
+}
+
+/// Applies the daily cap to deposits about to be inserted, by the time of
+/// their blocks. Must run while holding the insert lock of the pipeline,
+/// which the head listener and the catch up share, so two batches never
+/// count against the same stored totals.
+async fn apply_daily_cap(
+    mut deposits: Vec<Deposit>,
+    eth: &Eth<EthTransport>,
+    rpc: &ThrottledRpc,
+    network_config: &config::Network,
+    sanity_checks: &SanityChecks,
+    smtp_config: &config::Notification,
+    database_engine: &DatabaseEngine,
+) -> Vec<Deposit> {
+    if !sanity_checks.has_daily_cap() {
+        return deposits;
+    }
+
+    let mut block_times: HashMap<u64, Option<i64>> = HashMap::new();
+    for deposit in deposits.iter_mut().filter(|deposit| deposit.state == STATE_TO_PROCESS) {
+        let number = match deposit.block_number {
+            Some(number) => number,
+            None => continue,
+        };
+        if !block_times.contains_key(&number) {
+            let block = rpc
+                .call("eth_getBlockByNumber", || eth.block(BlockId::Number(BlockNumber::Number(U64::from(number)))))
+                .await;
+            let time = match block {
+                Ok(Some(block)) => Some(block.timestamp.low_u64() as i64),
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Error reading the time of block {} of {}, the cap counts it today: {e}", number, network_config.name);
+                    None
+                }
+            };
+            block_times.insert(number, time);
+        }
+        deposit.block_time = block_times[&number];
+    }
+
+    let held = sanity_checks.apply_daily_cap(database_engine, &mut deposits).await;
+    alert_suspicious(held.iter().map(|index| &deposits[*index]), network_config, smtp_config).await;
+    deposits
+}
+
+/// Whether the TO_PROCESS queue is deeper than `max_backlog`. Once paused,
+/// scanning only resumes when the queue is down to half of it, so it doesn't
+/// flap around the threshold.
+async fn is_backlogged(
+    network_config: &config::Network,
+    database_engine: &DatabaseEngine,
+    paused: bool,
+) -> bool {
+    let max_backlog = match network_config.max_backlog {
+        Some(max_backlog) => max_backlog,
+        None => return false,
+    };
+
+    let depth = database_engine
+        .count_txs_to_process(&network_config.name)
+        .await;
+    metrics::QUEUE_DEPTH
+        .with_label_values(&[&network_config.name])
+        .set(depth as i64);
+
+    if paused {
+        depth > max_backlog / 2
+    } else {
+        depth > max_backlog
+    }
+}
+
+/// Compares the chain id of the node with the configured one, if any.
+async fn check_chain_id(eth: &Eth<EthTransport>, rpc: &ThrottledRpc, network_config: &config::Network) -> Result<(), String> {
+    let expected = match network_config.chain_id {
+        Some(chain_id) => chain_id,
+        None => return Ok(()),
+    };
+
+    let chain_id = rpc
+        .call("eth_chainId", || eth.chain_id())
+        .await
+        .map_err(|e| format!("Error reading the chain id: {e:?}"))?;
+
+    if chain_id != U256::from(expected) {
+        return Err(format!(
+            "The node of {} is on chain {}, {} expected",
+            network_config.network, chain_id, expected
+        ));
+    }
+    Ok(())
+}
+
+/// New heads of the node: its `eth_subscribe` subscription over a
+/// websocket, or the latest block polled every `HEAD_POLL_INTERVAL` over
+/// HTTP. A failed poll ends the stream, so the connection is reopened.
+async fn new_heads(
+    transport: &EthTransport,
+    rpc: &Arc<ThrottledRpc>,
+    network_config: &config::Network,
+) -> web3::Result<BoxStream<'static, web3::Result<BlockHeader>>> {
+    let http = match transport {
+        Either::Left(ws) => {
+            let subscribe = EthSubscribe::new(ws.clone());
+            let subscription = rpc.call("eth_subscribe", || subscribe.subscribe_new_heads()).await?;
+            return Ok(subscription.boxed());
+        }
+        Either::Right(http) => http.clone(),
+    };
+
+    let (rpc, network) = (rpc.clone(), network_config.network.clone());
+    let heads = stream::unfold(None, move |last: Option<U64>| {
+        let (http, rpc, network) = (http.clone(), rpc.clone(), network.clone());
+        async move {
+            loop {
+                sleep(HEAD_POLL_INTERVAL).await;
+                let latest = rpc
+                    .call("eth_getBlockByNumber", || {
+                        http.execute("eth_getBlockByNumber", vec![json!("latest"), json!(false)])
+                    })
+                    .await
+                    .and_then(|head| {
+                        serde_json::from_value::<BlockHeader>(head).map_err(|e| web3::Error::Decoder(e.to_string()))
+                    });
+                match latest {
+                    Ok(head) if head.number.is_none() || head.number <= last => continue,
+                    Ok(head) => {
+                        let number = head.number;
+                        return Some((Ok(head), number));
+                    }
+                    Err(e) => {
+                        warn!("Error polling the head of {}: {e}", network);
+                        return None;
+                    }
+                }
+            }
+        }
+    });
+    Ok(heads.boxed())
+}
+
+/// What scanning a pipeline needs, shared by the head listener and the
+/// catch up.
+#[derive(Clone)]
+pub struct ScanContext {
+    pub network_config: config::Network,
+    pub sanity_checks: SanityChecks,
+    pub compliance: Option<Arc<ComplianceScreening>>,
+    pub smtp_config: config::Notification,
+    pub rpc: Arc<ThrottledRpc>,
+    pub database_engine: Arc<DatabaseEngine>,
+    pub recent_logs: Arc<RecentLogs>,
+    /// Held from the daily cap to the insert.
+    pub insert_lock: Arc<Mutex<()>>,
+}
 
 pub async fn listen_blocks_v2(
     network_config: config::Network,
+    sanity_checks: SanityChecks,
+    compliance: Option<Arc<ComplianceScreening>>,
+    smtp_config: config::Notification,
+    rpc: Arc<ThrottledRpc>,
+    new_head_timeout: Duration,
     database_engine: Arc<DatabaseEngine>,
 ) {
     info!(
@@ -19,70 +348,276 @@ pub async fn listen_blocks_v2(
         network_config.network
     );
 
+    let recent_logs = Arc::new(RecentLogs::new(
+        network_config.name.clone(),
+        network_config.recent_logs_cache_size,
+    ));
+    // Held from the daily cap to the insert, by the head listener and the
+    // catch up alike.
+    let insert_lock = Arc::new(Mutex::new(()));
+    let context = ScanContext {
+        network_config: network_config.clone(),
+        sanity_checks: sanity_checks.clone(),
+        compliance: compliance.clone(),
+        smtp_config: smtp_config.clone(),
+        rpc: rpc.clone(),
+        database_engine: database_engine.clone(),
+        recent_logs: recent_logs.clone(),
+        insert_lock: insert_lock.clone(),
+    };
+    let mut head_history = HeadHistory::new(
+        network_config
+            .reorg_quarantine
+            .as_ref()
+            .and_then(|reorg_quarantine| reorg_quarantine.history_size),
+    );
+    // First block not scanned: skipped while the payout backlog was too
+    // deep, or whose deposits could not be read or stored. Caught up with
+    // `catch_up_ranges` before the next head.
+    let mut unscanned_from: Option<U64> = None;
+    let mut paused = false;
+    let mut chain_id_alerted = false;
+
     loop {
-        match WebSocket::new(&network_config.ws_node).await {
+        let eth_node_url = network_config.eth_node_url();
+        let connect = eth_transport(&eth_node_url, network_config.eth_headers());
+        let connection = tokio::time::timeout(rpc.timeout(), connect)
+            .await
+            .unwrap_or_else(|_| Err(web3::Error::Transport(TransportError::Message("connection timed out".to_string()))));
+        match connection {
             Ok(transport) => {
+                // Checked on every (re)connection: the endpoint may resolve to
+                // another node each time.
+                if let Err(e) = check_chain_id(&Eth::new(transport.clone()), &rpc, &network_config).await {
+                    error!("{}. Not scanning {}.", e, network_config.name);
+                    database_engine
+                        .insert_scanner_error(&network_config.name, ScannerErrorKind::Rpc, None, &e)
+                        .await;
+                    if !chain_id_alerted {
+                        notify(&smtp_config, "Bridge connected to the wrong chain!", &e).await;
+                        chain_id_alerted = true;
+                    }
+                    sleep(Duration::from_secs(60)).await;
+                    continue;
+                }
+                chain_id_alerted = false;
+
                 info!(
-                    "WebSocket connection for {} is now open!",
+                    "Connection for {} is now open!",
                     &network_config.network
                 );
 
-                tokio::task::spawn(catch_up_v2(
-                    transport.clone(),
-                    network_config.name.clone(),
-                    network_config.network.clone(),
-                    network_config.monitor_address.clone(),
-                    database_engine.clone(),
-                ));
+                tokio::task::spawn(catch_up_v2(transport.clone(), context.clone()));
 
-                let subscribe = EthSubscribe::new(transport);
+                let mut subscription = match new_heads(&transport, &rpc, &network_config).await {
+                    Ok(subscription) => subscription,
+                    Err(e) => {
+                        error!("Error subscribing to the heads of {}: {e}", network_config.network);
+                        database_engine
+                            .insert_scanner_error(
+                                &network_config.name,
+                                ScannerErrorKind::Rpc,
+                                None,
+                                &format!("eth_subscribe failed: {e}"),
+                            )
+                            .await;
+                        warn!(
+                            "Restarting the {} network listening.",
+                            network_config.network
+                        );
+                        continue;
+                    }
+                };
 
-                let mut subscription = subscribe.subscribe_new_heads().await.unwrap();
-
-                while let Some(b) = subscription.next().await {
+                // A connection can hang without closing, so a silent one is
+                // reopened once no head arrived within the budget.
+                loop {
+                    let b = match tokio::time::timeout(new_head_timeout, subscription.next()).await {
+                        Ok(Some(b)) => b,
+                        Ok(None) => break,
+                        Err(_) => {
+                            warn!(
+                                "No new head from {} in {:?}, reopening the connection.",
+                                network_config.network, new_head_timeout
+                            );
+                            metrics::RPC_TIMEOUTS
+                                .with_label_values(&[&network_config.name])
+                                .inc();
+                            break;
+                        }
+                    };
                     let block: U64 =
                         b.as_ref().unwrap().number.unwrap() - network_config.confirmations;
                     info!(
                         "New block in {}: {:?}",
                         &network_config.network,
-                        b.unwrap().number.unwrap()
+                        b.as_ref().unwrap().number.unwrap()
                     );
 
-                    let eth = Eth::new(subscribe.transport());
+                    let eth = Eth::new(transport.clone());
+
+                    if let Some(reorg_quarantine) = &network_config.reorg_quarantine {
+                        if let Some(reorged) = head_history.observe(&eth, b.as_ref().unwrap()).await {
+                            handle_reorg(
+                                &network_config,
+                                reorg_quarantine,
+                                reorged,
+                                &smtp_config,
+                                &database_engine,
+                            )
+                            .await;
+                        }
+                    }
+
+                    if is_backlogged(&network_config, &database_engine, paused).await {
+                        if !paused {
+                            warn!(
+                                "Payout backlog of {} is too deep, scanning paused until it drains.",
+                                network_config.name
+                            );
+                            paused = true;
+                            unscanned_from.get_or_insert(block);
+                        }
+                        continue;
+                    }
+                    paused = false;
 
-                    let address: H160 = network_config.monitor_address.parse().unwrap();
-                    let topic_bytes =
-                        keccak256("TransferToGlitch(address,string,uint256)".as_bytes());
+                    // The blocks skipped go through the chunked catch up; the
+                    // marker is only cleared once all of them are stored.
+                    if let Some(unscanned) = unscanned_from.filter(|unscanned| *unscanned < block) {
+                        info!(
+                            "Scanning of {} resumed from block {}",
+                            network_config.name, unscanned
+                        );
+                        if let Err(unprocessed) =
+                            catch_up_ranges(&eth, unscanned.as_u64(), block.as_u64() - 1, &context).await
+                        {
+                            error!(
+                                "Scanning of {} stopped at block {}, it will be retried.",
+                                network_config.name, unprocessed
+                            );
+                            database_engine
+                                .insert_scanner_error(
+                                    &network_config.name,
+                                    ScannerErrorKind::SkippedBlock,
+                                    Some(unprocessed),
+                                    &format!("Blocks {unprocessed} to {} not stored", block.as_u64() - 1),
+                                )
+                                .await;
+                            unscanned_from = Some(U64::from(unprocessed));
+                            continue;
+                        }
+                    }
+                    unscanned_from = None;
+                    let from_block = block;
 
-                    let filter = FilterBuilder::default()
-                        .address(vec![address])
-                        .from_block(BlockNumber::Number(block))
-                        .to_block(BlockNumber::Number(block))
-                        .topics(Some(vec![H256::from(topic_bytes)]), None, None, None)
-                        .build();
+                    let filter = match deposit_filter(
+                        &network_config,
+                        BlockNumber::Number(from_block),
+                        BlockNumber::Number(block),
+                    ) {
+                        Ok(filter) => filter,
+                        Err(e) => {
+                            error!("Error building the deposit filter of {}: {e}", network_config.name);
+                            database_engine
+                                .insert_scanner_error(
+                                    &network_config.name,
+                                    ScannerErrorKind::SkippedBlock,
+                                    Some(from_block.as_u64()),
+                                    &format!("Deposit filter not built: {e}"),
+                                )
+                                .await;
+                            unscanned_from = Some(from_block);
+                            continue;
+                        }
+                    };
 
-                    match eth.logs(filter).await {
+                    match rpc.call("eth_getLogs", || eth.logs(filter.clone())).await {
                         Ok(logs) => {
                             info!("{} transactions found in block {}", logs.len(), block);
+                            let logs = recent_logs.retain_new(logs);
+                            let keys = log_keys(&logs);
+                            let deposits = decode_deposits(
+                                logs,
+                                &network_config,
+                                &sanity_checks,
+                                &smtp_config,
+                                &database_engine,
+                            )
+                            .await;
+                            let deposits =
+                                apply_quarantines(deposits, &network_config, &database_engine).await;
+                            let deposits =
+                                screen_deposits(deposits, &network_config, compliance.as_deref(), &database_engine).await;
 
-                            database_engine
+                            let insert_guard = insert_lock.lock().await;
+                            let deposits = apply_daily_cap(
+                                deposits,
+                                &eth,
+                                &rpc,
+                                &network_config,
+                                &sanity_checks,
+                                &smtp_config,
+                                &database_engine,
+                            )
+                            .await;
+                            let tx_eth_hashes = deposits.iter().map(|deposit| deposit.tx_eth_hash.clone()).collect();
+                            let stored = database_engine
                                 .update_block_and_insert_txs(
                                     network_config.name.clone(),
                                     block.as_u32(),
-                                    logs,
+                                    deposits,
                                 )
                                 .await;
+                            drop(insert_guard);
+                            if !stored {
+                                error!(
+                                    "The deposits of blocks {} to {} of {} were not stored, they will be scanned again.",
+                                    from_block, block, network_config.name
+                                );
+                                database_engine
+                                    .insert_scanner_error(
+                                        &network_config.name,
+                                        ScannerErrorKind::SkippedBlock,
+                                        Some(from_block.as_u64()),
+                                        &format!("Deposits of blocks {from_block} to {block} not stored"),
+                                    )
+                                    .await;
+                                unscanned_from = Some(from_block);
+                                continue;
+                            }
+                            recent_logs.remember(keys);
+                            record_source_txs(&eth, &rpc, &network_config, tx_eth_hashes, &database_engine).await;
                         }
                         Err(e) => {
-                            error!("Error obtaining contract logs on the Ethereum network: {e}")
+                            error!("Error obtaining contract logs on the Ethereum network: {e}");
+                            database_engine
+                                .insert_scanner_error(
+                                    &network_config.name,
+                                    ScannerErrorKind::SkippedBlock,
+                                    Some(from_block.as_u64()),
+                                    &format!("eth_getLogs failed: {e}"),
+                                )
+                                .await;
+                            unscanned_from = Some(from_block);
                         }
                     };
                 }
             }
-            Err(e) => error!(
-                "Error connecting with {} network: {:?}",
-                network_config.network, e
-            ),
+            Err(e) => {
+                error!(
+                    "Error connecting with {} network: {:?}",
+                    network_config.network, e
+                );
+                database_engine
+                    .insert_scanner_error(
+                        &network_config.name,
+                        ScannerErrorKind::Rpc,
+                        None,
+                        &format!("Connection failed: {e:?}"),
+                    )
+                    .await;
+            }
         }
 
         warn!(
@@ -92,75 +627,243 @@ pub async fn listen_blocks_v2(
     }
 }
 
-pub async fn catch_up_v2(
-    ws: WebSocket,
-    scanner_name: String,
-    network: String,
-    monitor_address: String,
-    database_engine: Arc<DatabaseEngine>,
-) {
-    let eth = Eth::new(ws);
+/// Catches up from the last scanned block to the head in ranges of
+/// `catch_up_chunk_blocks`, as three stages connected by bounded channels:
+/// fetching the logs of a range, decoding them on up to `decode_workers`
+/// tasks and inserting the deposits in range order. A slow stage holds the
+/// others back instead of piling logs up in memory. A range that fails stops
+/// the stages and the catch up is retried from it, with backoff.
+pub async fn catch_up_v2(transport: EthTransport, context: ScanContext) {
+    let ScanContext { network_config, rpc, database_engine, .. } = &context;
+    let eth = Eth::new(transport);
 
     if !database_engine
         .exists_network_state(
-            scanner_name.as_str(),
-            network.as_str(),
-            monitor_address.as_str(),
+            network_config.name.as_str(),
+            network_config.network.as_str(),
+            network_config.monitor_address.as_str(),
         )
         .await
     {
         return;
     }
 
-    let last_scanned_block = database_engine.get_last_block(scanner_name.as_str()).await;
-    let address: H160 = monitor_address.parse().unwrap();
-    let topic_bytes = keccak256("TransferToGlitch(address,string,uint256)".as_bytes());
-    let from_block = BlockNumber::Number(U64::from(last_scanned_block + 1));
-
-    info!(
-        "Starting catch up from block {} to current block.",
-        last_scanned_block + 1
-    );
-
-    let filter = FilterBuilder::default()
-        .address(vec![address])
-        .from_block(from_block)
-        .topics(Some(vec![H256::from(topic_bytes)]), None, None, None)
-        .to_block(BlockNumber::Latest)
-        .build();
-
-    let result_logs: Result<Vec<Log>, web3::Error> = eth.logs(filter).await;
-    let mut logs_to_persist: Vec<Log> = Vec::new();
-
-    match result_logs {
-        Ok(mut result) => {
-            if result.is_empty() {
-                info!("No past transactions were found for processing.");
-            } else {
-                info!("{} transactions were found.", result.len());
+    let last_scanned_block = database_engine
+        .get_last_block(network_config.name.as_str())
+        .await as u64;
+    let head = match rpc.call("eth_blockNumber", || eth.block_number()).await {
+        Ok(head) => head.as_u64(),
+        Err(e) => {
+            error!("Error reading the head of {} to catch up: {e}", network_config.network);
+            database_engine
+                .insert_scanner_error(
+                    &network_config.name,
+                    ScannerErrorKind::Rpc,
+                    Some(last_scanned_block + 1),
+                    &format!("Catch up eth_blockNumber failed: {e}"),
+                )
+                .await;
+            return;
+        }
+    };
+    if head <= last_scanned_block {
+        info!("No past transactions were found for processing.");
+        return;
+    }
 
-                logs_to_persist.append(&mut result);
+    // The head listener moves the last block past the catch up, so the
+    // ranges not stored are retried here until they are.
+    let mut from = last_scanned_block + 1;
+    let mut attempt = 0;
+    loop {
+        info!("Starting catch up from block {} to block {}.", from, head);
+        match catch_up_ranges(&eth, from, head, &context).await {
+            Ok(found) => {
+                info!("Finish catch up, {} deposits found.", found);
+                return;
+            }
+            Err(unprocessed) => {
+                let delay = CATCH_UP_RETRY_DELAY
+                    .saturating_mul(2_u32.saturating_pow(attempt))
+                    .min(MAX_CATCH_UP_RETRY_DELAY);
+                error!(
+                    "Catch up of {} stopped at block {}, retrying in {:?}.",
+                    network_config.name, unprocessed, delay
+                );
+                database_engine
+                    .insert_scanner_error(
+                        &network_config.name,
+                        ScannerErrorKind::SkippedBlock,
+                        Some(unprocessed),
+                        &format!("Catch up stopped, blocks {unprocessed} to {head} will be retried"),
+                    )
+                    .await;
+                from = unprocessed;
+                attempt += 1;
+                sleep(delay).await;
             }
         }
-        Err(e) => match e {
-            web3::Error::Rpc(error) => {
-                println!("{:?}", error.code);
+    }
+}
 
-                let regex = Regex::new("0[xX][0-9a-fA-F]+").unwrap();
+/// Runs the catch up pipeline over `from..=head`. Returns the deposits found
+/// or, when a range could not be fetched, decoded or stored, its first block:
+/// nothing from there on was stored.
+async fn catch_up_ranges(eth: &Eth<EthTransport>, from: u64, head: u64, context: &ScanContext) -> Result<usize, u64> {
+    let ScanContext {
+        network_config,
+        sanity_checks,
+        compliance,
+        smtp_config,
+        rpc,
+        database_engine,
+        recent_logs,
+        insert_lock,
+    } = context;
+    let chunk_blocks = network_config
+        .catch_up_chunk_blocks
+        .unwrap_or(DEFAULT_CATCH_UP_CHUNK_BLOCKS)
+        .max(1);
+    let decode_workers = network_config.decode_workers.unwrap_or(DEFAULT_DECODE_WORKERS).max(1);
+    let (logs_sender, mut logs_receiver) = mpsc::channel::<(u64, u64, Vec<Log>)>(decode_workers);
+    let (decoding_sender, mut decoding_receiver) =
+        mpsc::channel::<(u64, u64, JoinHandle<(Vec<LogKey>, Vec<Deposit>)>)>(decode_workers);
 
-                let result: Vec<String> = regex
-                    .find_iter(error.message.as_str())
-                    .map(|mat| mat.as_str().to_string())
-                    .collect();
+    let fetcher = {
+        let (eth, rpc, recent_logs) = (eth.clone(), rpc.clone(), recent_logs.clone());
+        let (network_config, database_engine) = (network_config.clone(), database_engine.clone());
+        tokio::spawn(async move {
+            let mut from = from;
+            while from <= head {
+                let to = (from + chunk_blocks - 1).min(head);
+                let filter = match deposit_filter(
+                    &network_config,
+                    BlockNumber::Number(U64::from(from)),
+                    BlockNumber::Number(U64::from(to)),
+                ) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        error!("Error building the deposit filter of {}: {e}", network_config.name);
+                        database_engine
+                            .insert_scanner_error(
+                                &network_config.name,
+                                ScannerErrorKind::SkippedBlock,
+                                Some(from),
+                                &format!("Deposit filter not built for blocks {from} to {to}: {e}"),
+                            )
+                            .await;
+                        return;
+                    }
+                };
+                match rpc.call("eth_getLogs", || eth.logs(filter.clone())).await {
+                    Ok(logs) => {
+                        if !logs.is_empty() {
+                            info!("{} transactions were found in blocks {} to {}.", logs.len(), from, to);
+                        }
+                        if logs_sender.send((from, to, recent_logs.retain_new(logs))).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error obtaining the logs of blocks {} to {} on {}: {e}", from, to, network_config.network);
+                        database_engine
+                            .insert_scanner_error(
+                                &network_config.name,
+                                ScannerErrorKind::Rpc,
+                                Some(from),
+                                &format!("Catch up eth_getLogs failed for blocks {from} to {to}: {e}"),
+                            )
+                            .await;
+                        return;
+                    }
+                }
+                from = to + 1;
+            }
+        })
+    };
 
-                let without_prefix = result[0].trim_start_matches("0x");
-                println!("{:?}", u64::from_str_radix(without_prefix, 16));
+    // Each range is decoded on its own task; the handles go through the
+    // channel in range order, so the inserts keep that order.
+    let decoder = {
+        let (network_config, sanity_checks, smtp_config, database_engine) = (
+            network_config.clone(),
+            sanity_checks.clone(),
+            smtp_config.clone(),
+            database_engine.clone(),
+        );
+        tokio::spawn(async move {
+            while let Some((from, to, logs)) = logs_receiver.recv().await {
+                let (network_config, sanity_checks, smtp_config, database_engine) = (
+                    network_config.clone(),
+                    sanity_checks.clone(),
+                    smtp_config.clone(),
+                    database_engine.clone(),
+                );
+                let decoding = tokio::spawn(async move {
+                    let keys = log_keys(&logs);
+                    let deposits =
+                        decode_deposits(logs, &network_config, &sanity_checks, &smtp_config, &database_engine).await;
+                    (keys, deposits)
+                });
+                if decoding_sender.send((from, to, decoding)).await.is_err() {
+                    return;
+                }
             }
-            _ => panic!("{e:?}"),
-        },
-    }
+        })
+    };
 
-    database_engine.insert_txs(logs_to_persist).await;
+    let mut found = 0;
+    let mut unprocessed = from;
+    while let Some((from, to, decoding)) = decoding_receiver.recv().await {
+        let (keys, deposits) = match decoding.await {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                error!("Decoding of blocks {} to {} of {} failed: {e}", from, to, network_config.name);
+                database_engine
+                    .insert_scanner_error(
+                        &network_config.name,
+                        ScannerErrorKind::Decode,
+                        Some(from),
+                        &format!("Catch up decoding failed for blocks {from} to {to}: {e}"),
+                    )
+                    .await;
+                break;
+            }
+        };
+        found += deposits.len();
+        let deposits = apply_quarantines(deposits, network_config, database_engine).await;
+        let deposits = screen_deposits(deposits, network_config, compliance.as_deref(), database_engine).await;
+
+        let insert_guard = insert_lock.lock().await;
+        let deposits = apply_daily_cap(
+            deposits,
+            eth,
+            rpc,
+            network_config,
+            sanity_checks,
+            smtp_config,
+            database_engine,
+        )
+        .await;
+        let tx_eth_hashes = deposits.iter().map(|deposit| deposit.tx_eth_hash.clone()).collect();
+        let stored = database_engine
+            .insert_txs(&network_config.name, deposits)
+            .await;
+        drop(insert_guard);
+        if !stored {
+            break;
+        }
+        recent_logs.remember(keys);
+        record_source_txs(eth, rpc, network_config, tx_eth_hashes, database_engine).await;
+        unprocessed = to + 1;
+    }
+    // Stops the stages still running after a failed range.
+    drop(decoding_receiver);
+    let _ = tokio::join!(fetcher, decoder);
 
-    info!("Finish catch up.");
+    if unprocessed <= head {
+        Err(unprocessed)
+    } else {
+        Ok(found)
+    }
 }
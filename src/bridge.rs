@@ -0,0 +1,602 @@
+use crate::api;
+use crate::audit::run_nightly_audit;
+use crate::balance_monitor::BalanceMonitor;
+use crate::config::Config;
+use crate::contract_check::validate_contracts;
+use crate::database::DatabaseEngine;
+use crate::db_health::monitor_database;
+use crate::duplicate_recipients::monitor_duplicate_recipients;
+use crate::eth_ack::acknowledge_deposits;
+use crate::eth_withdrawals::release_withdrawals;
+use crate::failover::hold_instance_lease;
+use crate::fee_policy::record_configured_policy;
+use crate::finality_monitor::monitor_finality;
+use crate::glitch::{ FeePayer, Payer };
+use crate::glitch_scanner::{ withdrawals_scanner_name, GlitchScanner };
+use crate::queue_monitor::monitor_queue_age;
+use crate::quote::Quoter;
+use crate::reconcile::reconcile_processing_txs;
+use crate::recipient_locks::RecipientLocks;
+use crate::reorg::release_expired_quarantines;
+use crate::scanner::deposit_source;
+use crate::scheduler::Scheduler;
+use crate::signer_activity::{ monitor_signer_activity, signer_activity_scanner_name };
+use crate::sla_monitor::monitor_sla;
+use crate::stuck_sweeper::sweep_stuck_rows;
+use crate::supervisor::{ RestartPolicy, Stage, Supervisor };
+use crate::supply_check::{check_supply_invariant, is_checked};
+use crate::upgrade_monitor::monitor_upgrades;
+use crate::version::BUILD_VERSION;
+use crate::webhooks::deliver_webhooks;
+use log::{info, warn};
+use std::collections::HashSet;
+use std::sync::{ Arc, Mutex };
+use tokio::time::Duration;
+
+/// Entry point of the bridge. Every component is enabled by default; embedders
+/// can turn off the ones they don't need (e.g. run only the scanners).
+pub struct Bridge {
+    config: Config,
+    database_engine: Arc<DatabaseEngine>,
+    /// Store of the read path, the same as `database_engine` without a
+    /// read-only user configured.
+    read_database_engine: Arc<DatabaseEngine>,
+    scheduler: Arc<Scheduler>,
+    scanners: bool,
+    payers: bool,
+    fee_payers: bool,
+    monitors: bool,
+    read_only: bool,
+}
+
+pub struct BridgeBuilder {
+    config: Option<Config>,
+    database_engine: Option<Arc<DatabaseEngine>>,
+    scanners: bool,
+    payers: bool,
+    fee_payers: bool,
+    monitors: bool,
+    read_only: bool,
+}
+
+impl BridgeBuilder {
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Uses an existing store instead of creating one from the config.
+    pub fn store(mut self, database_engine: Arc<DatabaseEngine>) -> Self {
+        self.database_engine = Some(database_engine);
+        self
+    }
+
+    pub fn scanners(mut self, enabled: bool) -> Self {
+        self.scanners = enabled;
+        self
+    }
+
+    pub fn payers(mut self, enabled: bool) -> Self {
+        self.payers = enabled;
+        self
+    }
+
+    pub fn fee_payers(mut self, enabled: bool) -> Self {
+        self.fee_payers = enabled;
+        self
+    }
+
+    /// Balance, SLA and supply monitors plus the HTTP API and the webhook
+    /// delivery, which only one instance may run.
+    pub fn monitors(mut self, enabled: bool) -> Self {
+        self.monitors = enabled;
+        self
+    }
+
+    /// Standby mode: scanning and monitoring only. Payers, fee payers, Ethereum
+    /// acks, withdrawal releases, top-ups and webhooks are off whatever else
+    /// is enabled, so nothing is sent to any chain or consumer.
+    pub fn read_only(mut self, enabled: bool) -> Self {
+        self.read_only = enabled;
+        self
+    }
+
+    pub fn build(self) -> Bridge {
+        let config = self.config.expect("A configuration is required to build the bridge!");
+        let database_engine = self
+            .database_engine
+            .unwrap_or_else(|| Arc::new(
+                DatabaseEngine::new(config.db.clone())
+                    .with_notifications(config.notifications.clone())
+                    .with_query_timeout(config.timeouts.clone().unwrap_or_default().db_query())
+                    .with_read_only(self.read_only)
+            ));
+        let read_database_engine = match config.db.read_only {
+            Some(_) => Arc::new(
+                DatabaseEngine::new(config.db.read_path())
+                    .with_notifications(config.notifications.clone())
+                    .with_query_timeout(config.timeouts.clone().unwrap_or_default().db_query())
+                    .with_read_only(true)
+            ),
+            None => database_engine.clone(),
+        };
+
+        Bridge {
+            config,
+            database_engine,
+            read_database_engine,
+            scheduler: Arc::new(Scheduler::default()),
+            scanners: self.scanners,
+            payers: self.payers && !self.read_only,
+            fee_payers: self.fee_payers && !self.read_only,
+            monitors: self.monitors,
+            read_only: self.read_only,
+        }
+    }
+}
+
+impl Bridge {
+    pub fn builder() -> BridgeBuilder {
+        BridgeBuilder {
+            config: None,
+            database_engine: None,
+            scanners: true,
+            payers: true,
+            fee_payers: true,
+            monitors: true,
+            read_only: false,
+        }
+    }
+
+    pub fn store(&self) -> Arc<DatabaseEngine> {
+        self.database_engine.clone()
+    }
+
+    /// Periodic jobs of the running bridge, to pause or retune them.
+    pub fn scheduler(&self) -> Arc<Scheduler> {
+        self.scheduler.clone()
+    }
+
+    pub async fn run(self) {
+        let config = self.config;
+        let database_engine = self.database_engine;
+        let read_database_engine = self.read_database_engine;
+        let scheduler = self.scheduler;
+
+        info!("Bridge version {}", BUILD_VERSION);
+        if self.read_only {
+            warn!("Read-only mode: deposits are scanned and recorded, nothing is paid out or submitted.");
+        }
+        validate_contracts(&config.networks).await;
+        let mut supervisor = Supervisor::new();
+        info!("Scanner running...");
+
+        info!("Found {} network{}to listen!", config.networks.len(), if config.networks.len() > 1 {
+            "s "
+        } else {
+            " "
+        });
+
+        supervisor.supervise(
+            "db_health",
+            Stage::Service,
+            RestartPolicy::Always,
+            {
+                let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+                move || monitor_database(
+                    database_engine.clone(),
+                    scheduler.ticker(
+                        "db_health".to_string(),
+                        Duration::from_secs(15),
+                        Duration::from_secs(1)
+                    )
+                )
+            }
+        );
+
+        if !self.read_only {
+            let (database_engine, smtp_config, scheduler) =
+                (database_engine.clone(), config.notifications.clone(), scheduler.clone());
+            supervisor.supervise("instance_lease", Stage::Service, RestartPolicy::Backoff, move || {
+                hold_instance_lease(
+                    database_engine.clone(),
+                    smtp_config.clone(),
+                    scheduler.ticker(
+                        "instance_lease".to_string(),
+                        Duration::from_secs(10),
+                        Duration::from_secs(1)
+                    )
+                )
+            });
+        }
+
+        if let (true, false, Some(webhooks)) = (self.monitors, self.read_only, config.webhooks.clone()) {
+            let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+            supervisor.supervise("webhooks", Stage::Service, RestartPolicy::Backoff, move || {
+                deliver_webhooks(
+                    webhooks.clone(),
+                    database_engine.clone(),
+                    scheduler.ticker(
+                        "webhooks".to_string(),
+                        Duration::from_secs(2),
+                        Duration::from_millis(200)
+                    )
+                )
+            });
+        }
+
+        // Requeueing reconciles and pays, so it runs with the payers.
+        if let (true, Some(stuck_sweeper)) = (self.payers, config.stuck_sweeper.clone()) {
+            let (config, database_engine, scheduler) = (config.clone(), database_engine.clone(), scheduler.clone());
+            supervisor.supervise("stuck_sweeper", Stage::Service, RestartPolicy::Backoff, move || {
+                sweep_stuck_rows(
+                    stuck_sweeper.clone(),
+                    config.clone(),
+                    database_engine.clone(),
+                    scheduler.ticker(
+                        "stuck_sweeper".to_string(),
+                        Duration::from_secs(60),
+                        Duration::from_secs(5)
+                    )
+                )
+            });
+        }
+
+        if self.monitors {
+            if let Some(api_config) = config.api.clone() {
+                let (database_engine, read_database_engine) = (database_engine.clone(), read_database_engine.clone());
+                let (tokens, scheduler) = (config.tokens.clone().unwrap_or_default(), scheduler.clone());
+                let quoters = Arc::new(
+                    config.networks.iter().map(|network_config| Quoter::new(&config, network_config)).collect::<Vec<_>>()
+                );
+                supervisor.supervise("api", Stage::Service, RestartPolicy::Always, move || {
+                    api::serve(
+                        api_config.clone(),
+                        read_database_engine.clone(),
+                        database_engine.clone(),
+                        tokens.clone(),
+                        scheduler.clone(),
+                        quoters.clone()
+                    )
+                });
+            }
+
+            // Without an SLA the monitor has nothing to do and would only end.
+            if config.notifications.sla_in_minutes.is_some() {
+                let (database_engine, smtp_config, scheduler) =
+                    (database_engine.clone(), config.notifications.clone(), scheduler.clone());
+                supervisor.supervise("sla_monitor", Stage::Service, RestartPolicy::Backoff, move || {
+                    monitor_sla(
+                        database_engine.clone(),
+                        smtp_config.clone(),
+                        scheduler.ticker(
+                            "sla_monitor".to_string(),
+                            Duration::from_secs(60),
+                            Duration::from_secs(5)
+                        )
+                    )
+                });
+            }
+
+            {
+                let queue_age = config.queue_age.clone().unwrap_or_default();
+                let networks: Vec<String> = config.networks.iter().map(|network| network.name.clone()).collect();
+                let (read_database_engine, smtp_config, scheduler) =
+                    (read_database_engine.clone(), config.notifications.clone(), scheduler.clone());
+                supervisor.supervise("queue_monitor", Stage::Service, RestartPolicy::Backoff, move || {
+                    monitor_queue_age(
+                        queue_age.clone(),
+                        networks.clone(),
+                        read_database_engine.clone(),
+                        smtp_config.clone(),
+                        scheduler.ticker(
+                            "queue_monitor".to_string(),
+                            Duration::from_secs(60),
+                            Duration::from_secs(5)
+                        )
+                    )
+                });
+            }
+
+            if let Some(supply_check) = config.supply_check.clone() {
+                let checked_networks: Vec<_> =
+                    config.networks.iter().filter(|network| is_checked(network)).cloned().collect();
+                if checked_networks.is_empty() {
+                    warn!("No network has a token_address and a custody_address, the supply check is off.");
+                } else {
+                    let (database_engine, smtp_config, scheduler) =
+                        (database_engine.clone(), config.notifications.clone(), scheduler.clone());
+                    supervisor.supervise("supply_check", Stage::Service, RestartPolicy::Backoff, move || {
+                        check_supply_invariant(
+                            supply_check.clone(),
+                            checked_networks.clone(),
+                            database_engine.clone(),
+                            smtp_config.clone(),
+                            scheduler.ticker(
+                                "supply_check".to_string(),
+                                Duration::from_secs(60 * supply_check.interval_in_minutes),
+                                Duration::from_secs(5)
+                            )
+                        )
+                    });
+                }
+            }
+
+            if let Some(duplicate_recipients) = config.duplicate_recipients.clone() {
+                let networks: Vec<String> = config.networks.iter().map(|network| network.name.clone()).collect();
+                let (read_database_engine, smtp_config, scheduler) =
+                    (read_database_engine.clone(), config.notifications.clone(), scheduler.clone());
+                supervisor.supervise("duplicate_recipients", Stage::Service, RestartPolicy::Backoff, move || {
+                    monitor_duplicate_recipients(
+                        duplicate_recipients.clone(),
+                        networks.clone(),
+                        read_database_engine.clone(),
+                        smtp_config.clone(),
+                        scheduler.ticker(
+                            "duplicate_recipients".to_string(),
+                            Duration::from_secs(300),
+                            Duration::from_secs(5)
+                        )
+                    )
+                });
+            }
+
+            if let Some(audit) = config.audit.clone() {
+                let (supply_check, networks) = (config.supply_check.clone(), config.networks.clone());
+                let (database_engine, smtp_config, scheduler) =
+                    (database_engine.clone(), config.notifications.clone(), scheduler.clone());
+                supervisor.supervise("audit", Stage::Service, RestartPolicy::Backoff, move || {
+                    run_nightly_audit(
+                        audit.clone(),
+                        supply_check.clone(),
+                        networks.clone(),
+                        database_engine.clone(),
+                        smtp_config.clone(),
+                        scheduler.ticker(
+                            "audit".to_string(),
+                            Duration::from_secs(60),
+                            Duration::from_secs(5)
+                        )
+                    )
+                });
+            }
+        }
+
+        if self.payers && config.networks.iter().any(|n| n.reorg_quarantine.is_some()) {
+            let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+            supervisor.supervise("quarantine_release", Stage::Service, RestartPolicy::Backoff, move || {
+                release_expired_quarantines(
+                    database_engine.clone(),
+                    scheduler.ticker(
+                        "quarantine_release".to_string(),
+                        Duration::from_secs(60),
+                        Duration::from_secs(5)
+                    )
+                )
+            });
+        }
+
+        // Like the fee policies, the registry is only written by instances
+        // allowed to write.
+        if let (false, Some(tokens)) = (self.read_only, &config.tokens) {
+            database_engine.record_tokens(tokens).await;
+        }
+
+        // The configured fees become policies before anything is charged.
+        if self.payers || self.fee_payers {
+            for network_config in config.networks.iter() {
+                record_configured_policy(&config, network_config, &database_engine).await;
+            }
+        }
+
+        // Txs left PROCESSING by the previous run are settled before any new
+        // payout, so none of them is paid twice.
+        if self.payers {
+            for network_config in config.networks.iter() {
+                reconcile_processing_txs(&config, network_config, &database_engine).await;
+            }
+        }
+
+        let recipient_locks = Arc::new(RecipientLocks::default());
+        // Pipelines sharing a signer share its activity monitor.
+        let mut watched_signers = HashSet::new();
+
+        for network_config in config.networks.iter() {
+            if self.scanners {
+                let (config, network_config) = (config.clone(), network_config.clone());
+                let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+                supervisor.supervise(
+                    format!("scanner:{}", network_config.name),
+                    Stage::Ingest,
+                    RestartPolicy::Backoff,
+                    move || deposit_source(&config, &network_config, database_engine.clone(), &scheduler).run()
+                );
+            }
+
+            if let (true, Some(withdrawals)) = (self.scanners, network_config.glitch_withdrawals.clone()) {
+                let (network_config, database_engine, scheduler) =
+                    (network_config.clone(), database_engine.clone(), scheduler.clone());
+                supervisor.supervise(
+                    withdrawals_scanner_name(&network_config.name),
+                    Stage::Ingest,
+                    RestartPolicy::Backoff,
+                    move || GlitchScanner::new(
+                        network_config.clone(),
+                        withdrawals.clone(),
+                        database_engine.clone(),
+                        scheduler.ticker(
+                            withdrawals_scanner_name(&network_config.name),
+                            Duration::from_secs(6),
+                            Duration::from_millis(500)
+                        )
+                    ).run()
+                );
+            }
+
+            if self.payers {
+                let (config, network_config) = (config.clone(), network_config.clone());
+                let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+                let recipient_locks = recipient_locks.clone();
+                supervisor.supervise_draining(
+                    format!("payer:{}", network_config.name),
+                    Stage::Payout,
+                    RestartPolicy::Backoff,
+                    move |stop| Payer::new(
+                        &config,
+                        &network_config,
+                        database_engine.clone(),
+                        &scheduler,
+                        recipient_locks.clone()
+                    ).run(stop)
+                );
+            }
+
+            if self.fee_payers {
+                let (config, network_config) = (config.clone(), network_config.clone());
+                let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+                supervisor.supervise(
+                    format!("fee_payer:{}", network_config.name),
+                    Stage::Fees,
+                    RestartPolicy::Backoff,
+                    move || FeePayer::new(
+                        &config,
+                        &network_config,
+                        database_engine.clone(),
+                        &scheduler
+                    ).run()
+                );
+            }
+
+            if let (true, Some(finality_stall)) = (self.payers || self.fee_payers, config.finality_stall.clone()) {
+                let (network_config, smtp_config, scheduler) =
+                    (network_config.clone(), config.notifications.clone(), scheduler.clone());
+                let paused = Arc::new(Mutex::new(Vec::new()));
+                supervisor.supervise(
+                    format!("finality_monitor:{}", network_config.name),
+                    Stage::Service,
+                    RestartPolicy::Backoff,
+                    move || monitor_finality(
+                        finality_stall.clone(),
+                        network_config.clone(),
+                        smtp_config.clone(),
+                        scheduler.clone(),
+                        paused.clone(),
+                        scheduler.ticker(
+                            format!("finality_monitor:{}", network_config.name),
+                            Duration::from_secs(10),
+                            Duration::from_secs(1)
+                        )
+                    )
+                );
+            }
+
+            if let (true, Some(eth_ack)) = (self.payers, network_config.eth_ack.clone()) {
+                let (network_config, database_engine, scheduler) =
+                    (network_config.clone(), database_engine.clone(), scheduler.clone());
+                supervisor.supervise(
+                    format!("eth_ack:{}", network_config.name),
+                    Stage::Payout,
+                    RestartPolicy::Backoff,
+                    move || acknowledge_deposits(
+                        eth_ack.clone(),
+                        network_config.clone(),
+                        database_engine.clone(),
+                        scheduler.ticker(
+                            format!("eth_ack:{}", network_config.name),
+                            Duration::from_secs(30),
+                            Duration::from_secs(3)
+                        )
+                    )
+                );
+            }
+
+            if let (true, Some(release)) =
+                (self.payers, network_config.glitch_withdrawals.as_ref().and_then(|w| w.release.clone()))
+            {
+                let (network_config, database_engine, scheduler) =
+                    (network_config.clone(), database_engine.clone(), scheduler.clone());
+                supervisor.supervise(
+                    format!("withdrawal_release:{}", network_config.name),
+                    Stage::Payout,
+                    RestartPolicy::Backoff,
+                    move || release_withdrawals(
+                        release.clone(),
+                        network_config.clone(),
+                        database_engine.clone(),
+                        scheduler.ticker(
+                            format!("withdrawal_release:{}", network_config.name),
+                            Duration::from_secs(15),
+                            Duration::from_secs(2)
+                        )
+                    )
+                );
+            }
+
+            if self.monitors {
+                {
+                    let (config, network_config) = (config.clone(), network_config.clone());
+                    let (database_engine, scheduler, read_only) = (database_engine.clone(), scheduler.clone(), self.read_only);
+                    supervisor.supervise(
+                        format!("balance_monitor:{}", network_config.name),
+                        Stage::Service,
+                        RestartPolicy::Backoff,
+                        move || BalanceMonitor::new(
+                            &config,
+                            &network_config,
+                            database_engine.clone(),
+                            &scheduler
+                        ).with_read_only(read_only).run()
+                    );
+                }
+
+                let glitch_pk = network_config.glitch_private_key(&config);
+                if let (Some(signer_activity), true) = (config.signer_activity.clone(), watched_signers.insert(glitch_pk.clone())) {
+                    let (network_config, canary, smtp_config) =
+                        (network_config.clone(), config.canary.clone(), config.notifications.clone());
+                    let (database_engine, scheduler) = (database_engine.clone(), scheduler.clone());
+                    supervisor.supervise(
+                        signer_activity_scanner_name(&network_config.name),
+                        Stage::Service,
+                        RestartPolicy::Backoff,
+                        move || monitor_signer_activity(
+                            signer_activity.clone(),
+                            network_config.clone(),
+                            glitch_pk.clone(),
+                            canary.clone(),
+                            smtp_config.clone(),
+                            database_engine.clone(),
+                            scheduler.ticker(
+                                signer_activity_scanner_name(&network_config.name),
+                                Duration::from_secs(30),
+                                Duration::from_secs(5)
+                            )
+                        )
+                    );
+                }
+
+                if let Some(proxy_watch) = &network_config.proxy {
+                    let interval = Duration::from_secs(proxy_watch.interval_in_seconds.unwrap_or(60));
+                    let (network_config, smtp_config, scheduler) =
+                        (network_config.clone(), config.notifications.clone(), scheduler.clone());
+                    supervisor.supervise(
+                        format!("upgrade_monitor:{}", network_config.name),
+                        Stage::Service,
+                        RestartPolicy::Backoff,
+                        move || monitor_upgrades(
+                            network_config.clone(),
+                            smtp_config.clone(),
+                            scheduler.clone(),
+                            scheduler.ticker(
+                                format!("upgrade_monitor:{}", network_config.name),
+                                interval,
+                                Duration::from_secs(5)
+                            )
+                        )
+                    );
+                }
+            }
+        }
+
+        supervisor.run().await;
+    }
+}
@@ -0,0 +1,108 @@
+use clap::ValueEnum;
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::database::DatabaseEngine;
+
+/// State change applied to every tx matching a filter.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Back to TO_PROCESS from SUSPICIOUS or QUARANTINED, clearing the
+    /// error. PROCESSING txs may have been submitted and are left to the
+    /// reconciliation
+    Requeue,
+    /// TO_PROCESS or QUARANTINED to SUSPICIOUS, out of the payout queue
+    Hold,
+    /// SUSPICIOUS back to TO_PROCESS
+    Release,
+}
+
+impl BulkAction {
+    /// States a tx may be in for the action to apply. PROCESSED txs are
+    /// never touched.
+    pub fn from_states(&self) -> &'static [&'static str] {
+        match self {
+            BulkAction::Requeue => &["TO_PROCESS", "SUSPICIOUS", "QUARANTINED"],
+            BulkAction::Hold => &["TO_PROCESS", "QUARANTINED"],
+            BulkAction::Release => &["SUSPICIOUS"],
+        }
+    }
+
+    pub fn to_state(&self) -> &'static str {
+        match self {
+            BulkAction::Requeue | BulkAction::Release => "TO_PROCESS",
+            BulkAction::Hold => "SUSPICIOUS",
+        }
+    }
+}
+
+/// Every field narrows the selection; an empty filter matches every tx the
+/// action applies to. Dates are `YYYY-MM-DD[ HH:MM:SS]` in UTC,
+/// `token` is a token symbol, matching the txs of the pipelines of that
+/// token, and `error_pattern` is a SQL `LIKE` pattern; it can't match errors
+/// stored encrypted (`db.encryption_key_env`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TxFilter {
+    pub state: Option<String>,
+    pub scanner: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub token: Option<String>,
+    pub error_pattern: Option<String>,
+}
+
+impl TxFilter {
+    /// Names of the pipelines the token filter matches, out of the
+    /// `(name, token symbol)` of every pipeline; None without a token filter.
+    pub fn token_pipelines<'a>(
+        &self,
+        pipelines: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    ) -> Option<Vec<String>> {
+        let token = self.token.as_deref()?;
+        Some(
+            pipelines
+                .into_iter()
+                .filter(|(_, symbol)| *symbol == Some(token))
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        )
+    }
+}
+
+/// Body of `POST /admin/txs/bulk/{action}`.
+#[derive(Deserialize, Debug, Default)]
+pub struct BulkRequest {
+    #[serde(flatten)]
+    pub filter: TxFilter,
+    pub note: Option<String>,
+    pub dry_run: Option<bool>,
+}
+
+/// Applies the action to the matching txs, or only lists them on a dry run.
+/// `token_pipelines` resolves the token filter, see
+/// `TxFilter::token_pipelines`. Returns the ids of the txs changed (or that
+/// would be).
+pub async fn apply(
+    database_engine: &DatabaseEngine,
+    action: BulkAction,
+    filter: &TxFilter,
+    token_pipelines: Option<Vec<String>>,
+    note: Option<String>,
+    dry_run: bool,
+) -> Vec<u128> {
+    let ids = database_engine
+        .txs_by_filter(action.from_states(), filter, token_pipelines)
+        .await;
+
+    if dry_run || ids.is_empty() {
+        info!("Bulk {:?}: {} tx(s) match {:?}", action, ids.len(), filter);
+        return ids;
+    }
+
+    let changed = database_engine
+        .bulk_set_state(&ids, action.from_states(), action.to_state(), note)
+        .await;
+    info!("Bulk {:?} changed {} tx(s) matching {:?}", action, changed.len(), filter);
+    changed
+}
@@ -0,0 +1,124 @@
+use log::{error, info};
+
+use crate::args::Command;
+use crate::attestation;
+use crate::bench::{run_load_test, LoadTest};
+use crate::bulk::{self, TxFilter};
+use crate::config::Config;
+use crate::dashboards;
+use crate::database::DatabaseEngine;
+use crate::duplicate_recipients;
+use crate::fee_adjustments;
+use crate::migrate;
+use crate::reporting;
+use crate::schema;
+use crate::snapshot;
+use crate::verify_deposit;
+
+/// Runs a maintenance command to completion instead of starting the bridge.
+pub async fn run(command: Command, config: Config) {
+    let database_engine = DatabaseEngine::new(config.db.clone());
+    // The reports only read, so they go through the read-only user if any.
+    let read_database_engine = DatabaseEngine::new(config.db.read_path()).with_read_only(true);
+    let glitch_pk = config.glitch_private_key.clone().unwrap();
+
+    match command {
+        Command::ExportState { output } => {
+            snapshot::export_state(&read_database_engine, &glitch_pk, &output).await
+        }
+        Command::ImportState { input } => {
+            if let Err(e) = snapshot::import_state(&database_engine, &glitch_pk, &input).await {
+                error!("{e}");
+                std::process::exit(1);
+            }
+        }
+        Command::SchemaCheck => {
+            if !schema::check_schema(&database_engine).await {
+                std::process::exit(1);
+            }
+        }
+        Command::Migrate {
+            file,
+            apply,
+            allow_locking,
+        } => {
+            if !migrate::migrate(&database_engine, &file, apply, allow_locking).await {
+                std::process::exit(1);
+            }
+        }
+        Command::ExportFeeInvoices { output, network } => {
+            reporting::export_fee_invoices(&read_database_engine, &config, network, &output).await
+        }
+        Command::ImportFeeAdjustments { input, apply } => {
+            if !fee_adjustments::import_fee_adjustments(&database_engine, &input, apply).await {
+                std::process::exit(1);
+            }
+        }
+        Command::BulkTxs {
+            action,
+            state,
+            network,
+            from,
+            to,
+            token,
+            error_pattern,
+            note,
+            apply,
+        } => {
+            let filter = TxFilter {
+                state,
+                scanner: network,
+                from,
+                to,
+                token,
+                error_pattern,
+            };
+            let token_pipelines = filter.token_pipelines(
+                config.networks.iter().map(|network| (network.name.as_str(), network.token.as_deref())),
+            );
+            let ids = bulk::apply(&database_engine, action, &filter, token_pipelines, note, !apply).await;
+            info!("{:?}", ids);
+            if !apply {
+                info!("Dry run, nothing changed. Run again with --apply to change these txs.");
+            }
+        }
+        Command::DuplicateRecipients {
+            window_in_minutes,
+            min_senders,
+        } => {
+            duplicate_recipients::report_duplicate_recipients(&read_database_engine, window_in_minutes, min_senders).await
+        }
+        Command::VerifyDeposit { tx_eth_hash, network } => {
+            if !verify_deposit::verify_deposit(&config, &read_database_engine, &tx_eth_hash, network).await {
+                std::process::exit(1);
+            }
+        }
+        Command::AttestReserves { output, signing_key_env } => {
+            if !attestation::attest_reserves(&config.networks, &read_database_engine, &output, signing_key_env.as_deref()).await {
+                std::process::exit(1);
+            }
+        }
+        Command::Dashboards { output } => {
+            if let Err(e) = dashboards::write_dashboards(&output) {
+                error!("Error writing the dashboards to {:?}: {}", output, e);
+                std::process::exit(1);
+            }
+        }
+        Command::LoadTest {
+            network,
+            deposits,
+            recipient,
+            amount,
+            timeout_in_secs,
+        } => {
+            let load_test = LoadTest {
+                network,
+                deposits,
+                recipient,
+                amount,
+                timeout_in_secs,
+            };
+            run_load_test(config, load_test).await
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Compliance (KYT) screening of deposits before they can be paid out. Each
+//! screener decides whether a deposit may go ahead or is held; held
+//! deposits, and those whose screening failed or timed out, are stored as
+//! SUSPICIOUS so an operator releases them. Every result is recorded in the
+//! `deposit_screening` table, and a deposit seen again on a rescan gets the
+//! recorded verdicts instead of a new screening.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, StreamExt};
+use log::warn;
+use serde_json::{json, Value};
+use sp_core::sr25519::Public;
+use tokio::time::Duration;
+use web3::types::U256;
+
+use crate::config::{Compliance, Network};
+use crate::database::DatabaseEngine;
+use crate::decoder::{Deposit, STATE_SUSPICIOUS, STATE_TO_PROCESS};
+use crate::metrics;
+use crate::types::parse_glitch_address;
+
+const DEFAULT_TIMEOUT_IN_SECS: u64 = 10;
+/// Deposits of a batch screened at once.
+const SCREENING_CONCURRENCY: usize = 16;
+
+/// What a screener decided about a deposit.
+#[derive(Debug, Clone)]
+pub struct Screening {
+    pub hold: bool,
+    /// Risk rating or score reported by the screener, if any.
+    pub risk: Option<String>,
+    pub reason: Option<String>,
+    /// Answer of the screener as recorded.
+    pub detail: Value,
+}
+
+/// A source of compliance decisions, e.g. a KYT provider or local rules.
+pub trait Screener: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Screens a deposit of the pipeline `network`. An error holds it.
+    fn screen<'a>(&'a self, network: &'a Network, deposit: &'a Deposit) -> BoxFuture<'a, Result<Screening, String>>;
+}
+
+/// Holds deposits from or to listed addresses, or above an amount.
+/// Recipients are compared by account, whatever their SS58 prefix.
+pub struct RulesScreener {
+    blocked_senders: HashSet<String>,
+    blocked_recipients: HashSet<Public>,
+    max_amount: Option<u128>,
+}
+
+impl Screener for RulesScreener {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn screen<'a>(&'a self, _network: &'a Network, deposit: &'a Deposit) -> BoxFuture<'a, Result<Screening, String>> {
+        let reason = if self.blocked_senders.contains(&deposit.from_eth_address.to_lowercase()) {
+            Some("blocked sender".to_string())
+        } else if parse_glitch_address(&deposit.glitch_address, None)
+            .map_or(false, |recipient| self.blocked_recipients.contains(&recipient))
+        {
+            Some("blocked recipient".to_string())
+        } else {
+            self.max_amount
+                .filter(|max_amount| deposit.amount > U256::from(*max_amount))
+                .map(|max_amount| format!("amount over {max_amount}"))
+        };
+
+        let screening = Screening {
+            hold: reason.is_some(),
+            risk: None,
+            detail: json!({ "reason": reason }),
+            reason,
+        };
+        async move { Ok(screening) }.boxed()
+    }
+}
+
+/// Asks an HTTP screening service. The deposit is POSTed as JSON (`network`,
+/// `chain_id`, `tx_hash`, `from_address`, `to_address`, `amount`) and the
+/// service answers `{"decision": "allow" | "hold", "risk": ..., "reason":
+/// ...}`; any other decision holds the deposit. An adapter in front of the
+/// provider (Chainalysis KYT, TRM, ...) maps its own API to this one.
+pub struct HttpScreener {
+    url: String,
+    api_key: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Screener for HttpScreener {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn screen<'a>(&'a self, network: &'a Network, deposit: &'a Deposit) -> BoxFuture<'a, Result<Screening, String>> {
+        async move {
+            let body = json!({
+                "network": network.name,
+                "chain_id": network.chain_id,
+                "tx_hash": deposit.tx_eth_hash,
+                "from_address": deposit.from_eth_address,
+                "to_address": deposit.glitch_address,
+                "amount": deposit.amount.to_string(),
+            });
+
+            let mut request = self.client.post(&self.url).json(&body);
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let response = request.send().await.map_err(|e| format!("Screening request error: {e}"))?;
+            if !response.status().is_success() {
+                return Err(format!("Screening service answered {}", response.status()));
+            }
+            let text = response.text().await.map_err(|e| format!("Screening response error: {e}"))?;
+            let detail: Value =
+                serde_json::from_str(&text).map_err(|e| format!("Unreadable screening response: {e}"))?;
+
+            let decision = detail["decision"].as_str().unwrap_or_default().to_lowercase();
+            Ok(Screening {
+                hold: decision != "allow",
+                risk: match &detail["risk"] {
+                    Value::Null => None,
+                    Value::String(risk) => Some(risk.clone()),
+                    risk => Some(risk.to_string()),
+                },
+                reason: detail["reason"]
+                    .as_str()
+                    .map(str::to_string)
+                    .or_else(|| (decision != "allow").then(|| format!("decision {decision:?}"))),
+                detail,
+            })
+        }
+        .boxed()
+    }
+}
+
+/// The screeners configured, run in order on every new deposit.
+#[derive(Clone)]
+pub struct ComplianceScreening {
+    screeners: Vec<Arc<dyn Screener>>,
+    timeout: Duration,
+}
+
+impl ComplianceScreening {
+    pub fn new(compliance: &Compliance) -> Self {
+        let mut screeners: Vec<Arc<dyn Screener>> = Vec::new();
+
+        let lowercase = |addresses: &Option<Vec<String>>| -> HashSet<String> {
+            addresses.iter().flatten().map(|address| address.to_lowercase()).collect()
+        };
+        if compliance.blocked_senders.is_some()
+            || compliance.blocked_recipients.is_some()
+            || compliance.max_amount.is_some()
+        {
+            screeners.push(Arc::new(RulesScreener {
+                blocked_senders: lowercase(&compliance.blocked_senders),
+                blocked_recipients: compliance
+                    .blocked_recipients
+                    .iter()
+                    .flatten()
+                    .map(|address| {
+                        parse_glitch_address(address, None)
+                            .unwrap_or_else(|e| panic!("Invalid blocked recipient: {e}"))
+                    })
+                    .collect(),
+                max_amount: compliance.max_amount,
+            }));
+        }
+
+        if let Some(url) = &compliance.url {
+            let api_key = compliance.api_key_env.as_ref().map(|key_env| {
+                std::env::var(key_env).unwrap_or_else(|_| panic!("The screening API key {key_env} is not set!"))
+            });
+            screeners.push(Arc::new(HttpScreener {
+                url: url.clone(),
+                api_key,
+                client: reqwest::Client::new(),
+            }));
+        }
+
+        Self {
+            screeners,
+            timeout: Duration::from_secs(compliance.timeout_in_secs.unwrap_or(DEFAULT_TIMEOUT_IN_SECS)),
+        }
+    }
+
+    /// Screening of the pipeline, if configured.
+    pub fn for_config(compliance: Option<&Compliance>) -> Option<Arc<Self>> {
+        compliance.map(|compliance| Arc::new(Self::new(compliance)))
+    }
+
+    /// Why the deposit is held, None if every screener allows it. A deposit
+    /// screened before keeps its verdicts.
+    async fn hold_reason(&self, network: &Network, deposit: &Deposit, database_engine: &DatabaseEngine) -> Option<String> {
+        if let Some(verdicts) = database_engine
+            .deposit_screening_verdicts(&network.name, &deposit.tx_eth_hash)
+            .await
+            .filter(|verdicts| !verdicts.is_empty())
+        {
+            return verdicts
+                .iter()
+                .any(|verdict| verdict != "allow")
+                .then(|| "held by an earlier screening".to_string());
+        }
+
+        for screener in self.screeners.iter() {
+            let result = tokio::time::timeout(self.timeout, screener.screen(network, deposit))
+                .await
+                .unwrap_or_else(|_| Err(format!("screening timed out after {:?}", self.timeout)));
+
+            let (verdict, held) = match &result {
+                Ok(screening) if !screening.hold => ("allow", None),
+                Ok(screening) => (
+                    "hold",
+                    Some(format!(
+                        "held by {} screening: {}",
+                        screener.name(),
+                        screening.reason.as_deref().unwrap_or("no reason given")
+                    )),
+                ),
+                Err(e) => ("error", Some(format!("{} screening failed: {}", screener.name(), e))),
+            };
+            metrics::SCREENINGS.with_label_values(&[&network.name, verdict]).inc();
+
+            let (risk, detail) = match result {
+                Ok(screening) => (screening.risk, screening.detail.to_string()),
+                Err(e) => (None, json!({ "error": e }).to_string()),
+            };
+            database_engine
+                .insert_deposit_screening(&network.name, &deposit.tx_eth_hash, screener.name(), verdict, risk, detail)
+                .await;
+
+            if held.is_some() {
+                return held;
+            }
+        }
+        None
+    }
+}
+
+/// Screens the deposits about to be stored as TO_PROCESS, several at once,
+/// and stores the held ones as SUSPICIOUS with the reason as note.
+pub async fn screen_deposits(
+    deposits: Vec<Deposit>,
+    network_config: &Network,
+    compliance: Option<&ComplianceScreening>,
+    database_engine: &DatabaseEngine,
+) -> Vec<Deposit> {
+    let compliance = match compliance {
+        Some(compliance) => compliance,
+        None => return deposits,
+    };
+
+    stream::iter(deposits)
+        .map(|mut deposit| async move {
+            if deposit.state == STATE_TO_PROCESS {
+                if let Some(reason) = compliance.hold_reason(network_config, &deposit, database_engine).await {
+                    warn!("Deposit {} on {} {}", deposit.tx_eth_hash, network_config.name, reason);
+                    deposit.state = STATE_SUSPICIOUS;
+                    deposit.note = Some(match deposit.note.take() {
+                        Some(note) => format!("{note}; {reason}"),
+                        None => reason,
+                    });
+                }
+            }
+            deposit
+        })
+        .buffered(SCREENING_CONCURRENCY)
+        .collect()
+        .await
+}
@@ -1,19 +1,147 @@
 use crate::args::{ request_private_keys, Args };
+use crate::proxy;
+use crate::types::{ account_id_to_ss58, check_ss58_prefix, parse_glitch_address, ss58_prefix_of };
+use chrono::{ DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc };
 use log::{ error, info };
+use reqwest::Url;
 use serde_derive::{ Deserialize, Serialize };
+use serde_json::{ Map, Value };
+use sp_core::crypto::{ AccountId32, Ss58AddressFormat, Ss58Codec };
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::fs::File;
 use std::io::Read;
+use std::time::Duration;
+
+/// Length of the intervals given in days.
+pub const SECONDS_PER_DAY: u64 = 86_400;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub glitch_private_key: Option<String>,
-    pub glitch_fee_address: String,
+    pub glitch_fee_address: FeeDestination,
+    /// Fee destination of each pipeline, by network name. Pipelines not
+    /// listed here (nor with their own `glitch_fee_address`) use the global one.
+    pub fee_destinations: Option<BTreeMap<String, FeeDestination>>,
     pub interval_days_for_transfer: u32,
+    /// Time between business fee transfers as a humantime string ("12h",
+    /// "3d", "1d 6h"), over `interval_days_for_transfer`.
+    pub fee_interval: Option<String>,
+    /// Native balance, in plancks, the fee payer always leaves in the signer
+    /// after the fee transfer and its network fee. Defaults to the
+    /// existential deposit, so the account is never reaped.
+    pub fee_payer_min_reserve: Option<u128>,
     pub business_fee: f64,
-    pub glitch_gas: bool,
+    /// UTC date (YYYY-MM-DD[ HH:MM:SS]) from which a business fee or fee
+    /// destination different from the recorded ones applies. It must not be
+    /// past nor before the latest recorded policy. Defaults to when the
+    /// bridge starts with them.
+    pub fee_effective_from: Option<String>,
+    /// How fractional plancks of the business fee are rounded. Defaults to
+    /// the bridge's favor.
+    pub rounding: Option<Rounding>,
+    pub glitch_gas: GlitchGas,
+    /// Network prefix of Glitch SS58 addresses. Recipients and fee
+    /// destinations with another prefix are rejected, except generic
+    /// Substrate ones (42); those and hex public keys are stored in this format.
+    pub glitch_ss58_prefix: Option<u16>,
+    /// Decimals of the native Glitch token the signers hold. Defaults to 18.
+    pub glitch_decimals: Option<u8>,
+    /// Genesis hash of the Glitch chain. Nothing is signed for a node on
+    /// another chain.
+    pub glitch_genesis_hash: Option<String>,
+    /// Never run two payouts to the same Glitch address at once, across all
+    /// pipelines.
+    pub serialize_payouts_per_recipient: Option<bool>,
+    /// Submit payouts waiting only for inclusion and track their finalization
+    /// in the background, so the payer moves on to the next tx sooner.
+    pub async_finalization: Option<bool>,
+    /// Run the payers on the finalized heads of Glitch instead of every 5
+    /// seconds.
+    pub head_ticks: Option<HeadTicks>,
     pub db: Database,
+    /// Each entry is an independent pipeline: its own contract, scanner name,
+    /// Glitch endpoint and, optionally, its own fee policy and signer.
+    #[serde(alias = "pipelines")]
     pub networks: Vec<Network>,
     pub notifications: Notification,
+    pub canary: Option<Canary>,
+    pub api: Option<HttpApi>,
+    pub webhooks: Option<Webhooks>,
+    pub supply_check: Option<SupplyCheck>,
+    pub rpc_retry: Option<RpcRetry>,
+    pub timeouts: Option<Timeouts>,
+    pub proxy: Option<Proxy>,
+    pub top_up: Option<TopUp>,
+    pub crash_reporting: Option<CrashReporting>,
+    pub audit: Option<Audit>,
+    pub queue_age: Option<QueueAge>,
+    pub tokens: Option<Vec<Token>>,
+    pub finality_stall: Option<FinalityStall>,
+    pub stuck_sweeper: Option<StuckSweeper>,
+    pub signer_activity: Option<SignerActivity>,
+    pub duplicate_recipients: Option<DuplicateRecipients>,
+    pub compliance: Option<Compliance>,
+    /// Log level by module (`scanner`, `database`, `glitch`, `fee` or a
+    /// module path) over the `--loglevel` of the command line.
+    pub log_levels: Option<BTreeMap<String, String>>,
+    pub tag_rules: Option<Vec<TagRule>>,
+    /// How many Glitch blocks back the startup reconciliation of PROCESSING
+    /// txs searches for their payouts. Defaults to a day.
+    pub reconciliation_lookback_blocks: Option<u32>,
+}
+
+/// Network fee deducted from the payouts: `true` the exact estimate, `false`
+/// none, `"auto"` the exact estimate or, when estimating fails or times out,
+/// the average fee actually paid by the pipeline recently.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum GlitchGas {
+    Flag(bool),
+    Mode(GlitchGasMode),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GlitchGasMode {
+    Auto,
+}
+
+/// Labels the deposits matching every given condition. Amounts are in the
+/// token's smallest unit; `tokens` are token contract addresses.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TagRule {
+    pub label: String,
+    pub min_amount: Option<String>,
+    pub max_amount: Option<String>,
+    pub senders: Option<Vec<String>>,
+    pub tokens: Option<Vec<String>>,
+    pub action: Option<TagAction>,
+}
+
+/// How the payer treats deposits with the label.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagAction {
+    /// Paid before the untagged deposits.
+    Priority,
+    /// No business fee is charged.
+    FeeExempt,
+    /// Held as SUSPICIOUS until an operator releases it.
+    ManualReview,
+}
+
+/// Rounding of amounts that don't divide into whole plancks.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// The payout is floored: the fraction is kept by the bridge.
+    #[default]
+    BridgeFavor,
+    /// The business fee is floored: the fraction goes to the user.
+    UserFavor,
+    /// Half a planck or more goes to the bridge.
+    Nearest,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -23,6 +151,84 @@ pub struct Database {
     pub database: String,
     pub username: String,
     pub password: String,
+    /// Environment variable with the hex encoded 32 byte AES-GCM key used
+    /// to encrypt recipients and error messages of the txs at rest
+    pub encryption_key_env: Option<String>,
+    pub failover: Option<DatabaseFailover>,
+    pub retry: Option<DatabaseRetry>,
+    /// User with only SELECT grants for the read path: the public API, the
+    /// queue and duplicate recipient monitors and the report commands.
+    /// Without one they share the write path's user.
+    pub read_only: Option<ReadOnlyUser>,
+}
+
+impl Database {
+    /// The database as seen by the read path. When a read-only user is set
+    /// the failover is left out: taking it over writes the instance lease,
+    /// so it stays with the write path.
+    pub fn read_path(&self) -> Database {
+        match &self.read_only {
+            Some(read_only) => Database {
+                username: read_only.username.clone(),
+                password: read_only.password.clone(),
+                failover: None,
+                read_only: None,
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReadOnlyUser {
+    pub username: String,
+    pub password: String,
+}
+
+/// How getting a connection is retried before the database in use is taken
+/// as down: then the failover is taken over or, without one, another round
+/// of attempts starts after a delay doubling every round, up to
+/// `max_delay_in_ms`. Defaults to 5 attempts 5 seconds apart.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DatabaseRetry {
+    pub max_attempts: Option<u32>,
+    pub base_delay_in_ms: Option<u64>,
+    pub max_delay_in_ms: Option<u64>,
+    /// Growth of the delay after every failed attempt, 1 keeps it fixed.
+    pub backoff_factor: Option<f64>,
+}
+
+impl DatabaseRetry {
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(5).max(1)
+    }
+
+    /// Delay after the failed `attempt`, counted from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.base_delay_in_ms.unwrap_or(5_000) as f64;
+        let factor = self.backoff_factor.unwrap_or(1.0).max(1.0);
+        let delay = base * factor.powi(attempt.saturating_sub(1).min(64) as i32);
+        Duration::from_millis((delay as u64).min(self.max_delay_in_ms.unwrap_or(300_000)))
+    }
+
+    /// Delay after the failed `round` of attempts, counted from 1.
+    pub fn round_delay(&self, round: u32) -> Duration {
+        let delay = self.delay(1).saturating_mul(1 << round.saturating_sub(1).min(16));
+        delay.min(Duration::from_millis(self.max_delay_in_ms.unwrap_or(300_000)))
+    }
+}
+
+/// Warm standby replica of the database. When the primary can't be reached
+/// reads and writes move to it, as long as no other instance holds the
+/// instance lease there; there is no automatic failback. Username and
+/// password default to the primary's.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DatabaseFailover {
+    pub host: String,
+    pub port: u32,
+    pub username: Option<String>,
+    pub password: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,9 +236,404 @@ pub struct Network {
     pub name: String,
     pub network: String,
     pub monitor_address: String,
+    /// Ethereum node, `ws(s)://` or `http(s)://`. Over HTTP new heads are
+    /// polled instead of subscribed to.
     pub ws_node: String,
     pub ws_glitch_node: String,
     pub confirmations: i32,
+    /// Expected `eth_chainId` of the node. Scanning doesn't start while the
+    /// node reports another chain.
+    pub chain_id: Option<u64>,
+    /// Symbol of the bridged token in `tokens`. The token address, business
+    /// fee and Glitch asset left out here are taken from there.
+    pub token: Option<String>,
+    pub token_address: Option<String>,
+    pub custody_address: Option<String>,
+    pub eth_auth: Option<EndpointAuth>,
+    pub glitch_auth: Option<EndpointAuth>,
+    pub recent_logs_cache_size: Option<usize>,
+    /// Blocks per `eth_getLogs` call when catching up (2000 by default).
+    pub catch_up_chunk_blocks: Option<u64>,
+    /// Ranges decoded at once when catching up (2 by default).
+    pub decode_workers: Option<usize>,
+    /// Also fetch the transaction of each deposit to record its gas used,
+    /// effective gas price and value. Costs two RPC calls per deposit.
+    pub track_source_tx: Option<bool>,
+    pub event_signature: Option<String>,
+    /// Event of an upgraded contract watched next to `event_signature`
+    /// while the contract is migrated. Its deposits are stored as version 2.
+    pub event_migration: Option<EventMigration>,
+    /// Decode every log a second time with the shadow decoder and report
+    /// the differences. Payouts only use the active decoder.
+    pub shadow_decoding: Option<bool>,
+    pub indexed_topics: Option<Vec<Option<Vec<String>>>>,
+    pub token_total_supply: Option<String>,
+    pub glitch_private_key: Option<String>,
+    pub glitch_fee_address: Option<FeeDestination>,
+    pub business_fee: Option<f64>,
+    pub glitch_gas: Option<GlitchGas>,
+    /// Pay this pipeline out as this asset of the Glitch assets pallet
+    /// instead of the native balance. The business fee is settled in the same
+    /// asset; the network fee is still paid in the native token.
+    pub glitch_asset_id: Option<u32>,
+    pub interval_days_for_transfer: Option<u32>,
+    pub fee_interval: Option<String>,
+    pub proxy: Option<ProxyWatch>,
+    pub expected_contract: Option<ExpectedContract>,
+    pub reorg_quarantine: Option<ReorgQuarantine>,
+    pub substrate_source: Option<SubstrateSource>,
+    /// Scanning pauses while more deposits than this wait to be paid out.
+    pub max_backlog: Option<u64>,
+    /// Most value, in the smallest unit, that may be paid out and not final
+    /// yet (PROCESSING) at once. Above it new payouts wait for finalizations.
+    pub max_in_flight_value: Option<u128>,
+    /// How long the cap may keep new payouts waiting before it is alerted.
+    /// Defaults to 30 minutes.
+    pub max_in_flight_wait_in_minutes: Option<u64>,
+    /// Most a recipient may get from the pipeline in a UTC day, in the
+    /// smallest unit. Deposits over it are held as SUSPICIOUS.
+    pub max_daily_per_recipient: Option<u128>,
+    pub sender_filter: Option<SenderFilter>,
+    /// Also keeps the logs the scanner ignores in `skipped_log`, besides
+    /// the metric and the `skipped` log target.
+    pub record_skipped_logs: Option<bool>,
+    pub eth_fee_policy: Option<EthFeePolicy>,
+    pub eth_ack: Option<EthAck>,
+    pub glitch_withdrawals: Option<GlitchWithdrawals>,
+}
+
+/// Glitch account receiving the business fee, given in the config as SS58 or
+/// as a `0x`-prefixed hex public key and validated when the config is loaded.
+/// The SS58 network prefix it was written with is kept to be checked against
+/// `glitch_ss58_prefix`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct FeeDestination(AccountId32, Option<u16>);
+
+impl FeeDestination {
+    pub fn account_id(&self) -> AccountId32 {
+        self.0.clone()
+    }
+
+    pub fn check_ss58_prefix(&self, ss58_prefix: Option<u16>) -> Result<(), String> {
+        match self.1 {
+            Some(address_prefix) => check_ss58_prefix(&self.to_string(), address_prefix, ss58_prefix),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TryFrom<String> for FeeDestination {
+    type Error = String;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        parse_glitch_address(&address, None)
+            .map(|public| FeeDestination(AccountId32::from(public), ss58_prefix_of(&address)))
+            .map_err(|e| format!("Invalid fee destination: {e}"))
+    }
+}
+
+impl From<FeeDestination> for String {
+    fn from(destination: FeeDestination) -> Self {
+        destination.to_string()
+    }
+}
+
+impl std::fmt::Display for FeeDestination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.1 {
+            Some(prefix) => write!(f, "{}", self.0.to_ss58check_with_version(Ss58AddressFormat::custom(prefix))),
+            None => write!(f, "{}", account_id_to_ss58(&self.0)),
+        }
+    }
+}
+
+/// Upgradeable proxy in front of the monitored contract. A change of its
+/// implementation may change the event layout the decoder relies on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProxyWatch {
+    pub address: String,
+    pub expected_implementation: Option<String>,
+    pub pause_on_upgrade: bool,
+    pub interval_in_seconds: Option<u64>,
+}
+
+/// What the monitored contract must look like for the bridge to start.
+/// `code_hash` is the keccak256 of the deployed bytecode; `version` the
+/// result of its `version()` view, compared as a string.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExpectedContract {
+    pub code_hash: Option<String>,
+    pub version: Option<String>,
+}
+
+/// Deposits from blocks touched by a reorg at least `depth_threshold` deep
+/// are held for `quarantine_in_minutes`, on top of the confirmations.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReorgQuarantine {
+    pub depth_threshold: u64,
+    pub quarantine_in_minutes: u64,
+    pub history_size: Option<usize>,
+}
+
+/// Makes the network a substrate source chain: `ws_node` is its endpoint and
+/// `monitor_address` the SS58 custody account. Transfer events `(from, to,
+/// amount)` to the custody account are bridged to the sender on Glitch.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubstrateSource {
+    pub pallet: Option<String>,
+    pub event: Option<String>,
+}
+
+/// Withdrawals from Glitch back to the network: the `event` of `pallet`
+/// (`Bridge.Withdrawn` by default) with data `(who: AccountId32, to: H160,
+/// amount: u128)`, emitted when tokens are burnt or locked on Glitch. They
+/// are queued from finalized blocks only, starting at `start_block` or at
+/// the finalized head.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GlitchWithdrawals {
+    pub pallet: Option<String>,
+    pub event: Option<String>,
+    pub start_block: Option<u32>,
+    /// Without it withdrawals are only queued.
+    pub release: Option<WithdrawalRelease>,
+}
+
+/// How queued withdrawals are released on the network: by calling
+/// `function` (`release(address,uint256,bytes32)` with recipient, amount
+/// and withdrawal event id by default) on `contract_address` (the monitor
+/// address by default), either sent from the key in `private_key_env` or
+/// proposed to the `safe` for its owners to confirm and execute.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WithdrawalRelease {
+    pub contract_address: Option<String>,
+    pub function: Option<String>,
+    pub gas_limit: Option<u64>,
+    pub private_key_env: Option<String>,
+    pub safe: Option<SafeRelay>,
+}
+
+/// Gnosis Safe the releases are proposed to through its transaction
+/// service, e.g. `https://safe-transaction-mainnet.safe.global`. The
+/// proposer key belongs to an owner or delegate of the Safe and can't move
+/// funds by itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SafeRelay {
+    pub safe_address: String,
+    pub service_url: String,
+    pub proposer_key_env: String,
+}
+
+/// Deposits from senders in `exclude`, or not in `include` when it is given,
+/// are not paid out: they are dropped at scan time or, with `action` `test`,
+/// stored in the TEST state so smoke tests on mainnet stay out of the
+/// production accounting.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SenderFilter {
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub action: Option<SenderFilterAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SenderFilterAction {
+    #[default]
+    Skip,
+    Test,
+}
+
+/// Acknowledges every paid deposit on Ethereum by calling `function`, by
+/// default `acknowledgeDeposit(bytes32,bytes32)` with the deposit tx hash and
+/// the Glitch block hash, on `contract_address` (the monitored contract if
+/// not given). The signing key, hex encoded, is read from `private_key_env`.
+/// Only txs with an id from `start_at_tx_id` on are acknowledged.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EthAck {
+    pub function: Option<String>,
+    pub contract_address: Option<String>,
+    pub private_key_env: String,
+    pub gas_limit: Option<u64>,
+    pub start_at_tx_id: Option<u64>,
+}
+
+/// What to do with the business fee of a deposit whose event reports a
+/// protocol fee already deducted by the contract on Ethereum: `skip` it or
+/// `reduce` it by the ETH fee, so users are not charged twice.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EthFeePolicy {
+    Skip,
+    #[default]
+    Reduce,
+}
+
+/// The new contract event during a migration window, emitted by
+/// `monitor_address` unless the new contract has its own address.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventMigration {
+    /// Inputs may be named as in the contract ABI, e.g.
+    /// `TransferToGlitch(address,string,uint256 amount,uint256 ethFee)`;
+    /// unnamed ones are the amount, the unlock time and the fee, in order.
+    pub event_signature: String,
+    pub monitor_address: Option<String>,
+}
+
+/// Credentials for restricted RPC providers. Websocket handshakes only carry
+/// what is in the URL, so the API key goes in the path (Infura/Alchemy style)
+/// or in the query string, and basic auth in the URL user info.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EndpointAuth {
+    pub api_key: Option<String>,
+    pub api_key_param: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sent with every request, e.g. a provider's API key header. Only
+    /// `http(s)` Ethereum endpoints take them.
+    pub headers: Option<BTreeMap<String, String>>,
+}
+
+impl EndpointAuth {
+    pub fn apply(&self, endpoint: &str) -> String {
+        let mut url = match Url::parse(endpoint) {
+            Ok(url) => url,
+            Err(e) => {
+                error!("Invalid endpoint {}: {}", endpoint, e);
+                return endpoint.to_string();
+            }
+        };
+
+        if let Some(api_key) = &self.api_key {
+            match &self.api_key_param {
+                Some(param) => {
+                    url.query_pairs_mut().append_pair(param, api_key);
+                }
+                None => {
+                    if let Ok(mut segments) = url.path_segments_mut() {
+                        segments.pop_if_empty().push(api_key);
+                    }
+                }
+            }
+        }
+
+        if let Some(username) = &self.username {
+            let _ = url.set_username(username);
+            let _ = url.set_password(self.password.as_deref());
+        }
+
+        url.to_string()
+    }
+}
+
+/// Parses a fee interval such as "12h" or "3d". The fee payer looks every
+/// minute, so shorter intervals are refused.
+pub fn parse_fee_interval(value: &str) -> Result<Duration, String> {
+    let interval = humantime::parse_duration(value).map_err(|e| format!("{value:?} is not a duration: {e}"))?;
+    if interval < Duration::from_secs(60) {
+        return Err(format!("{value:?} is shorter than a minute"));
+    }
+    Ok(interval)
+}
+
+/// Parses `fee_effective_from`, a UTC date as YYYY-MM-DD or
+/// YYYY-MM-DD HH:MM:SS.
+pub fn parse_fee_effective_from(value: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_hms_opt(0, 0, 0).unwrap()))
+        .map(|date| Utc.from_utc_datetime(&date))
+        .map_err(|_| format!("{value:?} is not a date as YYYY-MM-DD or YYYY-MM-DD HH:MM:SS"))
+}
+
+/// Checks that every rule giving a label gives it the same action, or none
+/// in all of them: the payer looks actions up by label, so a label can
+/// only mean one thing.
+pub fn check_tag_rules(rules: &[TagRule]) -> Result<(), String> {
+    let describe = |action: Option<TagAction>| action.map_or("no action".to_string(), |action| format!("{action:?}"));
+    let mut actions: BTreeMap<&str, Option<TagAction>> = BTreeMap::new();
+    for rule in rules {
+        match actions.get(rule.label.as_str()) {
+            Some(action) if *action != rule.action => {
+                return Err(format!(
+                    "label {} is given both {} and {}",
+                    rule.label,
+                    describe(*action),
+                    describe(rule.action)
+                ))
+            }
+            Some(_) => {}
+            None => {
+                actions.insert(&rule.label, rule.action);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Network {
+    /// Where the business fee of this pipeline goes: its own address, then
+    /// its entry in `fee_destinations`, then the global address.
+    pub fn fee_destination(&self, config: &Config) -> FeeDestination {
+        self.glitch_fee_address
+            .clone()
+            .or_else(|| {
+                config.fee_destinations
+                    .as_ref()
+                    .and_then(|destinations| destinations.get(&self.name).cloned())
+            })
+            .unwrap_or_else(|| config.glitch_fee_address.clone())
+    }
+
+    /// Time between the business fee transfers of this pipeline: its own
+    /// `fee_interval` or `interval_days_for_transfer`, then the global ones.
+    /// Validated when the config is loaded.
+    pub fn fee_interval(&self, config: &Config) -> Duration {
+        let interval = match (&self.fee_interval, self.interval_days_for_transfer, &config.fee_interval) {
+            (Some(interval), _, _) => parse_fee_interval(interval),
+            (None, Some(days), _) => Ok(Duration::from_secs(days as u64 * SECONDS_PER_DAY)),
+            (None, None, Some(interval)) => parse_fee_interval(interval),
+            (None, None, None) => Ok(Duration::from_secs(config.interval_days_for_transfer as u64 * SECONDS_PER_DAY)),
+        };
+        interval.unwrap_or_else(|e| panic!("Invalid fee interval of {}: {e}", self.name))
+    }
+
+    pub fn max_in_flight_wait(&self) -> Duration {
+        Duration::from_secs(60 * self.max_in_flight_wait_in_minutes.unwrap_or(30))
+    }
+
+    /// The registry entry of the bridged token, if the pipeline names one.
+    pub fn token<'a>(&self, config: &'a Config) -> Option<&'a Token> {
+        let symbol = self.token.as_ref()?;
+        config.tokens.iter().flatten().find(|token| &token.symbol == symbol)
+    }
+
+    /// Through the local proxy tunnel when a proxy applies.
+    pub fn eth_node_url(&self) -> String {
+        proxy::route(&match &self.eth_auth {
+            Some(auth) => auth.apply(&self.ws_node),
+            None => self.ws_node.clone(),
+        })
+    }
+
+    /// Headers of every request to the Ethereum node.
+    pub fn eth_headers(&self) -> Option<&BTreeMap<String, String>> {
+        self.eth_auth.as_ref().and_then(|auth| auth.headers.as_ref())
+    }
+
+    /// The pipeline signer, falling back to the global one.
+    pub fn glitch_private_key(&self, config: &Config) -> String {
+        self.glitch_private_key
+            .clone()
+            .or_else(|| config.glitch_private_key.clone())
+            .unwrap()
+    }
+
+    /// Through the local proxy tunnel when a proxy applies.
+    pub fn glitch_node_url(&self) -> String {
+        proxy::route(&match &self.glitch_auth {
+            Some(auth) => auth.apply(&self.ws_glitch_node),
+            None => self.ws_glitch_node.clone(),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -46,19 +647,452 @@ pub struct Notification {
     pub slack_webhook: String,
     pub delay_in_minutes: u64,
     pub low_balance: f64,
+    pub sla_in_minutes: Option<u64>,
+}
+
+/// Panics are stored in the `crash_report` table and, unless `notify` is
+/// false, sent to the operators. `sentry_dsn` is only used by builds with
+/// the `sentry` feature.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrashReporting {
+    pub notify: Option<bool>,
+    pub sentry_dsn: Option<String>,
+}
+
+/// Automatic top-up of the hot wallets from the treasury, either through a
+/// `Proxy.proxy` call signed by a delegate or by calling a ticketing webhook.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TopUp {
+    pub threshold: f64,
+    pub amount: f64,
+    pub cooldown_in_minutes: u64,
+    pub treasury_address: Option<String>,
+    pub proxy_private_key: Option<String>,
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Canary {
+    pub glitch_address: String,
+    pub amount: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpApi {
+    pub listen_address: String,
+    pub admin_token: Option<String>,
+    /// Requests per minute allowed from a single IP on the public routes.
+    pub public_requests_per_minute: Option<u32>,
+    /// Environment variable with the ed25519 seed (hex or secret phrase)
+    /// signing the deposit status responses. Its public key is served at
+    /// `/deposit-signing-key`.
+    pub status_signing_key_env: Option<String>,
+}
+
+/// Lifecycle events of the txs POSTed to `url` one by one, in `sequence`
+/// order. An event is retried until the endpoint answers 2xx, so it can
+/// arrive more than once: consumers deduplicate by `sequence`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhooks {
+    pub url: String,
+    /// Environment variable with a bearer token sent with every request.
+    pub auth_token_env: Option<String>,
+    /// How long a missing sequence number is waited for before it's taken
+    /// as a rolled back write and skipped. Defaults to 30 seconds.
+    pub gap_timeout_in_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SupplyCheck {
+    pub interval_in_minutes: u64,
+    pub tolerance: f64,
+}
+
+/// Daily consistency audit, run once a day after `hour_utc` (2 by default).
+/// Txs pending or in flight for longer than `stuck_after_in_minutes` (60 by
+/// default) are reported as stuck. The supply part uses the tolerance of
+/// `supply_check` and is skipped without it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Audit {
+    pub hour_utc: Option<u32>,
+    pub stuck_after_in_minutes: Option<u64>,
+}
+
+/// Screening of new deposits before they can be paid out: local rules on
+/// senders, recipients and amount, and an HTTP screening service at `url`
+/// (its bearer token in `api_key_env`). Deposits held, or whose screening
+/// fails or takes over `timeout_in_secs` (10 by default), wait as SUSPICIOUS.
+/// `blocked_recipients` match the account in any SS58 format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Compliance {
+    pub url: Option<String>,
+    pub api_key_env: Option<String>,
+    pub timeout_in_secs: Option<u64>,
+    pub blocked_senders: Option<Vec<String>>,
+    pub blocked_recipients: Option<Vec<String>>,
+    pub max_amount: Option<u128>,
+}
+
+/// Flags the Glitch addresses paid by many distinct Ethereum senders within
+/// a short window, a common fraud pattern, for manual review.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DuplicateRecipients {
+    /// Defaults to an hour.
+    pub window_in_minutes: Option<u64>,
+    /// Distinct senders to one address within the window that raise an
+    /// alert. Defaults to 5.
+    pub max_distinct_senders: Option<usize>,
+}
+
+impl DuplicateRecipients {
+    pub fn window_in_minutes(&self) -> u64 {
+        self.window_in_minutes.unwrap_or(60)
+    }
+
+    pub fn max_distinct_senders(&self) -> usize {
+        self.max_distinct_senders.unwrap_or(5)
+    }
+}
+
+/// Alert when the oldest deposit waiting to be paid out, or the oldest one
+/// in flight, is older than these. The age gauges are exported either way.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QueueAge {
+    pub max_to_process_age_in_minutes: Option<u64>,
+    pub max_processing_age_in_minutes: Option<u64>,
+}
+
+/// A bridged token, described once for every module: its ERC-20 contract,
+/// decimals, the deposit amounts accepted, its business fee and the Glitch
+/// asset it is paid out as. Amounts are in the smallest unit. The registry
+/// is copied to the `token` table at startup and served at `/tokens`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Token {
+    pub symbol: String,
+    pub eth_address: String,
+    pub decimals: u8,
+    /// Smaller deposits are held as SUSPICIOUS.
+    pub min_amount: Option<u128>,
+    /// Larger deposits are held as SUSPICIOUS.
+    pub max_amount: Option<u128>,
+    pub business_fee: Option<f64>,
+    /// Paid out as this asset of the assets pallet, the native balance if
+    /// not set.
+    pub glitch_asset_id: Option<u32>,
+}
+
+/// The payer of each pipeline runs once per finalized head of Glitch and
+/// submits at most `max_extrinsics_per_block` payouts each time, unlimited
+/// by default.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HeadTicks {
+    pub max_extrinsics_per_block: Option<u32>,
+}
+
+/// How long the finalized head of Glitch may stay still before the payouts
+/// and fee transfers of the pipelines using that node are paused. Mortal
+/// extrinsics submitted meanwhile would only expire. Defaults to 2 minutes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FinalityStall {
+    pub max_stall_in_secs: Option<u64>,
+}
+
+impl FinalityStall {
+    pub fn max_stall(&self) -> Duration {
+        Duration::from_secs(self.max_stall_in_secs.unwrap_or(120))
+    }
+}
+
+/// Periodic sweep of the rows left in an intermediate state. Each kind is
+/// remediated once older than its rule; kinds without a rule are not swept.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StuckSweeper {
+    /// PROCESSING txs, by the time since their payout was submitted.
+    pub processing: Option<SweepRule>,
+    /// SUSPICIOUS txs waiting for review, by the time since they were held.
+    /// Only alerted or escalated: a hold is lifted by an operator.
+    pub held: Option<SweepRule>,
+    /// Payouts decided but not taken by the submitter of the outbox.
+    pub outbox: Option<SweepRule>,
+    /// Who gets the escalations, `notifications.send_to` if not set.
+    pub escalate_to: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SweepRule {
+    pub after_in_minutes: u64,
+    pub action: SweepAction,
+}
+
+/// What the stuck sweeper does with a row over the age of its rule.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepAction {
+    /// Put the tx back in the queue: PROCESSING txs are reconciled with
+    /// Glitch and outbox payouts dropped. Rows that can't be requeued are
+    /// alerted. Refused for held txs.
+    Requeue,
+    /// Alert the operators once per row.
+    Alert,
+    /// Alert `escalate_to` once per row.
+    Escalate,
+}
+
+/// Watch of the outgoing transfers of the Glitch signers. Transfers that
+/// are neither a payout nor a fee settlement recorded by the bridge, nor the
+/// canary, are alerted: the key may be compromised.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SignerActivity {
+    /// How long a transfer may take to show up in the bridge records before
+    /// it is alerted. Defaults to 10 minutes.
+    pub grace_in_minutes: Option<u64>,
+    /// Who gets the alerts, `notifications.send_to` if not set.
+    pub escalate_to: Option<Vec<String>>,
+}
+
+impl SignerActivity {
+    pub fn grace(&self) -> Duration {
+        Duration::from_secs(60 * self.grace_in_minutes.unwrap_or(10))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcRetry {
+    pub retries_per_minute: u32,
+    pub base_delay_in_ms: u64,
+    pub max_delay_in_ms: u64,
+}
+
+impl Default for RpcRetry {
+    fn default() -> Self {
+        Self {
+            retries_per_minute: 30,
+            base_delay_in_ms: 500,
+            max_delay_in_ms: 30_000,
+        }
+    }
+}
+
+/// Latency budgets of the operations the pipeline loops wait on, so a hung
+/// websocket or a slow query fails instead of freezing the loop.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Timeouts {
+    /// Each Ethereum RPC call. Calls over it are retried like rate limited
+    /// ones, from the `rpc_retry` budget.
+    pub eth_rpc_in_ms: Option<u64>,
+    /// Wait for the next Ethereum head before the websocket is reopened.
+    pub eth_new_head_in_ms: Option<u64>,
+    /// Getting a database connection, waiting for a row lock and running a
+    /// `SELECT`. A payout whose write fails on it stays PROCESSING for
+    /// reconciliation.
+    pub db_query_in_ms: Option<u64>,
+    /// Submitting a payout and waiting for its block. Whether a payout over
+    /// it went out is unknown, so it is left PROCESSING for reconciliation.
+    pub extrinsic_submission_in_ms: Option<u64>,
+}
+
+impl Timeouts {
+    pub fn eth_rpc(&self) -> Duration {
+        Duration::from_millis(self.eth_rpc_in_ms.unwrap_or(30_000))
+    }
+
+    pub fn eth_new_head(&self) -> Duration {
+        Duration::from_millis(self.eth_new_head_in_ms.unwrap_or(120_000))
+    }
+
+    pub fn db_query(&self) -> Duration {
+        Duration::from_millis(self.db_query_in_ms.unwrap_or(10_000))
+    }
+
+    pub fn extrinsic_submission(&self) -> Duration {
+        Duration::from_millis(self.extrinsic_submission_in_ms.unwrap_or(300_000))
+    }
+}
+
+/// Proxy the node connections go through, over the `HTTPS_PROXY`,
+/// `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY` variables used without it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Proxy {
+    /// `http://[user:password@]host:port` for an HTTP `CONNECT` proxy or
+    /// `socks5://[user:password@]host:port`.
+    pub url: String,
+    /// Hosts, and their subdomains, reached directly.
+    pub no_proxy: Option<Vec<String>>,
+}
+
+/// Prefix of the environment variables overriding the configuration file.
+const ENV_PREFIX: &str = "GLITCH_BRIDGE__";
+
+/// Sets the value at the path of an override, creating the objects and
+/// array entries on the way. Values are parsed as JSON when they can be and
+/// taken as strings otherwise, unless the file already has a string there.
+fn set_env_override(config: &mut Value, path: &[String], raw: &str) {
+    if path.is_empty() {
+        return;
+    }
+
+    let mut node = config;
+    for segment in path {
+        node = match segment.parse::<usize>() {
+            Ok(index) if node.is_array() => {
+                let entries = node.as_array_mut().unwrap();
+                while entries.len() <= index {
+                    entries.push(Value::Object(Map::new()));
+                }
+                &mut entries[index]
+            }
+            _ => {
+                if !node.is_object() {
+                    *node = Value::Object(Map::new());
+                }
+                node.as_object_mut()
+                    .unwrap()
+                    .entry(segment.clone())
+                    .or_insert(Value::Null)
+            }
+        };
+    }
+
+    let keep_string = node.is_string();
+    *node = match serde_json::from_str::<Value>(raw) {
+        Ok(value) if !keep_string => value,
+        _ => Value::String(raw.to_string()),
+    };
+}
+
+/// Applies every `GLITCH_BRIDGE__<PATH>` variable on top of the file. The
+/// path is the lowercased setting with `__` between levels and array indexes
+/// as numbers, e.g. `GLITCH_BRIDGE__DB__PASSWORD` or
+/// `GLITCH_BRIDGE__NETWORKS__0__WS_NODE`. Variables win over the file. A
+/// value that must be a string but looks like JSON, such as a numeric
+/// password with no file entry to go by, is given quoted: `'"1234"'`.
+fn apply_env_overrides(config: &mut Value) -> usize {
+    let mut overrides: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    // Lower indexes first, so arrays grow in order.
+    overrides.sort();
+
+    for (name, raw) in &overrides {
+        let path: Vec<String> = name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(|segment| segment.to_lowercase())
+            .collect();
+        set_env_override(config, &path, raw);
+    }
+
+    overrides.len()
 }
 
 impl Config {
+    /// Loads the configuration file, if there is one, and applies the
+    /// `GLITCH_BRIDGE__*` environment variables on top (see
+    /// `apply_env_overrides`). Without a file every setting comes from the
+    /// environment.
     pub fn new(args: Args) -> Self {
-        let mut file = File::open(&args.config).expect("File not found!");
-
-        let mut data = String::new();
+        let mut config = match File::open(&args.config) {
+            Ok(mut file) => {
+                let mut data = String::new();
+                file.read_to_string(&mut data).expect("Error while reading file!");
+                match serde_json::from_str(&data) {
+                    Ok(config) => config,
+                    Err(e) => panic!("Error parsing json: {e}"),
+                }
+            }
+            Err(e) => {
+                info!("No configuration file at {:?} ({}), using the environment only.", args.config, e);
+                Value::Object(Map::new())
+            }
+        };
 
-        file.read_to_string(&mut data).expect("Error while reading file!");
+        let overrides = apply_env_overrides(&mut config);
+        if overrides > 0 {
+            info!("{} setting{} taken from the environment.", overrides, if overrides > 1 { "s" } else { "" });
+        }
 
-        match serde_json::from_str(&data) {
+        let mut config: Config = match serde_json::from_value(config) {
             Ok(config) => config,
-            Err(e) => panic!("Error parsing json: {e}"),
+            Err(e) => panic!("Error parsing the configuration: {e}"),
+        };
+        config.apply_token_registry();
+
+        if let Some(destinations) = &config.fee_destinations {
+            for name in destinations.keys() {
+                if !config.networks.iter().any(|network| &network.name == name) {
+                    panic!("Fee destination configured for unknown network {name}");
+                }
+            }
+        }
+
+        if let Some(SweepRule { action: SweepAction::Requeue, .. }) =
+            config.stuck_sweeper.as_ref().and_then(|stuck_sweeper| stuck_sweeper.held.as_ref())
+        {
+            panic!("stuck_sweeper.held can only alert or escalate, held txs are released by an operator");
+        }
+
+        config.fee_effective_from();
+
+        if let Err(e) = check_tag_rules(config.tag_rules.as_deref().unwrap_or_default()) {
+            panic!("Invalid tag_rules: {e}");
+        }
+
+        for network in config.networks.iter() {
+            if let Err(e) = network.fee_destination(&config).check_ss58_prefix(config.glitch_ss58_prefix) {
+                panic!("Invalid fee destination of {}: {e}", network.name);
+            }
+            network.fee_interval(&config);
+
+            let http = network.ws_node.starts_with("http://") || network.ws_node.starts_with("https://");
+            if network.eth_headers().is_some() && !http {
+                panic!("Network {} has eth_auth headers, which only an http(s) ws_node takes", network.name);
+            }
+            if network.glitch_auth.as_ref().map_or(false, |auth| auth.headers.is_some()) {
+                panic!("Network {} has glitch_auth headers, which a websocket can't carry", network.name);
+            }
+        }
+
+        config
+    }
+
+    /// When a changed business fee or fee destination applies from, if
+    /// configured. Validated when the config is loaded.
+    pub fn fee_effective_from(&self) -> Option<DateTime<Utc>> {
+        self.fee_effective_from.as_deref().map(|value| {
+            parse_fee_effective_from(value).unwrap_or_else(|e| panic!("Invalid fee_effective_from: {e}"))
+        })
+    }
+
+    /// Fills the token settings each pipeline leaves out from its registry
+    /// entry. A pipeline contradicting its entry is a configuration error.
+    fn apply_token_registry(&mut self) {
+        let tokens = self.tokens.clone().unwrap_or_default();
+        for network in self.networks.iter_mut() {
+            let symbol = match &network.token {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+            let token = tokens
+                .iter()
+                .find(|token| &token.symbol == symbol)
+                .unwrap_or_else(|| panic!("Network {} uses token {symbol}, which is not in tokens", network.name));
+
+            match &network.token_address {
+                Some(address) if !address.eq_ignore_ascii_case(&token.eth_address) => {
+                    panic!("Network {} has token address {address}, but {symbol} is at {}", network.name, token.eth_address)
+                }
+                Some(_) => {}
+                None => network.token_address = Some(token.eth_address.clone()),
+            }
+            match (network.glitch_asset_id, token.glitch_asset_id) {
+                (Some(asset_id), Some(token_asset_id)) if asset_id != token_asset_id => {
+                    panic!("Network {} pays out asset {asset_id}, but {symbol} maps to asset {token_asset_id}", network.name)
+                }
+                (None, token_asset_id) => network.glitch_asset_id = token_asset_id,
+                _ => {}
+            }
+            if network.business_fee.is_none() {
+                network.business_fee = token.business_fee;
+            }
         }
     }
 
@@ -0,0 +1,91 @@
+use log::info;
+use web3::api::{Eth, Namespace};
+use web3::signing::keccak256;
+use web3::types::{Bytes, CallRequest, H160, H256, U256};
+
+use crate::config::{ExpectedContract, Network};
+use crate::types::{eth_transport, to_hex, u256_to_usize, EthTransport};
+
+const VERSION_SIGNATURE: &str = "version()";
+
+/// Return value of `version()`, as a string if the contract returns one and
+/// in decimal otherwise.
+fn decode_version(data: &[u8]) -> Option<String> {
+    if data.len() >= 64 && U256::from_big_endian(&data[0..32]) == U256::from(32) {
+        let len = u256_to_usize(U256::from_big_endian(&data[32..64]))?;
+        let raw = data.get(64..64 + len)?;
+        return String::from_utf8(raw.to_vec()).ok();
+    }
+
+    data.get(0..32)
+        .map(|word| U256::from_big_endian(word).to_string())
+}
+
+async fn check_contract(eth: &Eth<EthTransport>, address: H160, expected: &ExpectedContract) -> Result<(), String> {
+    if let Some(expected_code_hash) = &expected.code_hash {
+        let code = eth
+            .code(address, None)
+            .await
+            .map_err(|e| format!("Error reading the contract code: {e:?}"))?;
+        let code_hash = to_hex(H256::from(keccak256(&code.0)));
+
+        if !code_hash.eq_ignore_ascii_case(expected_code_hash) {
+            return Err(format!(
+                "Code hash is {code_hash}, {expected_code_hash} expected"
+            ));
+        }
+    }
+
+    if let Some(expected_version) = &expected.version {
+        let call = CallRequest {
+            to: Some(address),
+            data: Some(Bytes(keccak256(VERSION_SIGNATURE.as_bytes())[..4].to_vec())),
+            ..Default::default()
+        };
+        let result = eth
+            .call(call, None)
+            .await
+            .map_err(|e| format!("Error calling version(): {e:?}"))?;
+        let version = decode_version(&result.0)
+            .ok_or_else(|| format!("Unexpected version() result 0x{}", hex::encode(&result.0)))?;
+
+        if &version != expected_version {
+            return Err(format!("Version is {version}, {expected_version} expected"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares the monitored contract of each network against the code hash
+/// and version expected in the config. Refuses to start on any mismatch,
+/// so production is never pointed at the wrong contract.
+pub async fn validate_contracts(networks: &[Network]) {
+    for network_config in networks {
+        let expected = match &network_config.expected_contract {
+            Some(expected) => expected,
+            None => continue,
+        };
+
+        let address: H160 = network_config
+            .monitor_address
+            .parse()
+            .expect("Invalid monitor address!");
+        let transport = eth_transport(&network_config.eth_node_url(), network_config.eth_headers())
+            .await
+            .unwrap_or_else(|e| panic!("Error connecting with {} network: {:?}", network_config.network, e));
+        let eth = Eth::new(transport);
+
+        if let Err(e) = check_contract(&eth, address, expected).await {
+            panic!(
+                "Contract {} on {} does not match the config: {}",
+                network_config.monitor_address, network_config.network, e
+            );
+        }
+
+        info!(
+            "Contract {} on {} matches the config",
+            network_config.monitor_address, network_config.network
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::panic::{self, PanicInfo};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use log::error;
+
+use crate::config::{Config, CrashReporting};
+use crate::database::DatabaseEngine;
+use crate::notifications::notify;
+use crate::version::BUILD_VERSION;
+
+/// Longest the hook waits for the report to be stored and sent.
+const RECORD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Set while a report is being recorded, so a panic of the recorder itself
+/// is not recorded again.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static CURRENT_TX: Cell<Option<u128>> = Cell::new(None);
+}
+
+/// Marks the tx processed by the current thread until dropped, so a panic
+/// on this thread is reported with its id.
+pub struct TxGuard {
+    previous: Option<u128>,
+}
+
+impl TxGuard {
+    pub fn enter(tx_id: u128) -> Self {
+        let previous = CURRENT_TX.with(|current| current.replace(Some(tx_id)));
+        TxGuard { previous }
+    }
+}
+
+impl Drop for TxGuard {
+    fn drop(&mut self) {
+        CURRENT_TX.with(|current| current.set(self.previous));
+    }
+}
+
+/// What is known about a panic when the hook runs.
+pub struct CrashReport {
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub tx_id: Option<u128>,
+    pub backtrace: String,
+}
+
+impl CrashReport {
+    fn from_panic(info: &PanicInfo) -> Self {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+
+        CrashReport {
+            message,
+            location: info.location().map(|location| location.to_string()),
+            thread: thread::current().name().map(str::to_string),
+            tx_id: CURRENT_TX.with(|current| current.get()),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "Bridge {} panicked{}{}: {}{}",
+            BUILD_VERSION,
+            self.thread
+                .as_ref()
+                .map(|thread| format!(" in thread {thread}"))
+                .unwrap_or_default(),
+            self.location
+                .as_ref()
+                .map(|location| format!(" at {location}"))
+                .unwrap_or_default(),
+            self.message,
+            self.tx_id
+                .map(|tx_id| format!(" (while processing tx {tx_id})"))
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// The hook runs on the panicking thread, which may be a runtime worker, so
+/// the report is stored and sent from a thread with its own runtime. The
+/// hook waits for it at most `RECORD_TIMEOUT`, a hung database or mail
+/// server is left behind. Panics while a report is being recorded, the
+/// recorder's own included, are only logged.
+fn record(report: CrashReport, config: &Config, crash_reporting: &CrashReporting) {
+    if RECORDING.swap(true, Ordering::SeqCst) {
+        error!("Panic while recording a crash report, not recorded.");
+        return;
+    }
+
+    let database_config = config.db.clone();
+    let smtp_config = config.notifications.clone();
+    let alert = crash_reporting.notify.unwrap_or(true);
+
+    let (done, recorded) = mpsc::channel();
+    let recorder = thread::Builder::new().name("crash_recorder".to_string()).spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Error building the crash report runtime!");
+
+        runtime.block_on(async {
+            DatabaseEngine::new(database_config)
+                .insert_crash_report(&report)
+                .await;
+            if alert {
+                let message = format!("{}\n\n{}", report.summary(), report.backtrace);
+                notify(&smtp_config, "Bridge panic!", &message).await;
+            }
+        });
+        let _ = done.send(());
+    });
+
+    match recorder {
+        Ok(_) => match recorded.recv_timeout(RECORD_TIMEOUT) {
+            Ok(()) => RECORDING.store(false, Ordering::SeqCst),
+            // The recorder panicked and is done.
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                error!("Error recording the crash report.");
+                RECORDING.store(false, Ordering::SeqCst);
+            }
+            // Still running: the guard stays set until the process ends.
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                error!("The crash report was not recorded within {:?}, giving up on it.", RECORD_TIMEOUT)
+            }
+        },
+        Err(e) => {
+            error!("Error starting the crash report recorder: {}", e);
+            RECORDING.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Installs a panic hook that logs every panic with its backtrace and, when
+/// `crash_reporting` is configured, stores it in `crash_report` and alerts
+/// the operators. The default hook still runs afterwards.
+pub fn install_panic_hook(config: &Config) {
+    let config = config.clone();
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let report = CrashReport::from_panic(info);
+        error!("{}\n{}", report.summary(), report.backtrace);
+
+        if let Some(crash_reporting) = &config.crash_reporting {
+            record(report, &config, crash_reporting);
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// Reports panics to Sentry as well. The returned guard flushes pending
+/// events when dropped, so it must live as long as the process.
+#[cfg(feature = "sentry")]
+pub fn init_sentry(config: &Config) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.crash_reporting.as_ref()?.sentry_dsn.clone()?;
+
+    Some(sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: Some(BUILD_VERSION.into()),
+            ..Default::default()
+        },
+    )))
+}
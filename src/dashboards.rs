@@ -0,0 +1,266 @@
+//! Grafana dashboards over the metrics of `metrics`. The metric names are
+//! read from the registered collectors, so the panels can't drift from them.
+
+use std::fs;
+use std::path::Path;
+
+use log::info;
+use prometheus::core::Collector;
+use serde_json::{json, Value};
+
+use crate::metrics;
+
+const PANEL_WIDTH: u32 = 12;
+const PANEL_HEIGHT: u32 = 8;
+
+fn name(collector: &dyn Collector) -> String {
+    collector.desc()[0].fq_name.clone()
+}
+
+/// A PromQL query with the legend of its series.
+struct Query {
+    expr: String,
+    legend: &'static str,
+}
+
+fn query(expr: String, legend: &'static str) -> Query {
+    Query { expr, legend }
+}
+
+struct Panel {
+    title: &'static str,
+    unit: &'static str,
+    queries: Vec<Query>,
+}
+
+fn panel(title: &'static str, unit: &'static str, queries: Vec<Query>) -> Panel {
+    Panel { title, unit, queries }
+}
+
+/// Rate over five minutes of a counter, per the given labels.
+fn rate(collector: &dyn Collector, by: &str) -> String {
+    format!("sum by ({by}) (rate({}[5m]))", name(collector))
+}
+
+fn dashboard(uid: &str, title: &str, panels: Vec<Panel>) -> Value {
+    let panels: Vec<Value> = panels
+        .into_iter()
+        .enumerate()
+        .map(|(index, panel)| {
+            let index = index as u32;
+            let targets: Vec<Value> = panel
+                .queries
+                .into_iter()
+                .enumerate()
+                .map(|(ref_index, query)| {
+                    json!({
+                        "refId": ((b'A' + ref_index as u8) as char).to_string(),
+                        "datasource": { "type": "prometheus", "uid": "${datasource}" },
+                        "expr": query.expr,
+                        "legendFormat": query.legend,
+                    })
+                })
+                .collect();
+            json!({
+                "id": index + 1,
+                "type": "timeseries",
+                "title": panel.title,
+                "datasource": { "type": "prometheus", "uid": "${datasource}" },
+                "gridPos": {
+                    "h": PANEL_HEIGHT,
+                    "w": PANEL_WIDTH,
+                    "x": (index % 2) * PANEL_WIDTH,
+                    "y": (index / 2) * PANEL_HEIGHT,
+                },
+                "fieldConfig": { "defaults": { "unit": panel.unit }, "overrides": [] },
+                "options": { "legend": { "displayMode": "list", "placement": "bottom" } },
+                "targets": targets,
+            })
+        })
+        .collect();
+
+    json!({
+        "uid": uid,
+        "title": title,
+        "tags": ["glitch-bridge"],
+        "timezone": "utc",
+        "schemaVersion": 36,
+        "refresh": "30s",
+        "time": { "from": "now-6h", "to": "now" },
+        "templating": {
+            "list": [{
+                "name": "datasource",
+                "label": "Data source",
+                "type": "datasource",
+                "query": "prometheus",
+            }]
+        },
+        "panels": panels,
+    })
+}
+
+/// Queue depth, lag and balances of the pipelines.
+fn overview() -> Value {
+    let latency = name(&*metrics::TRANSFER_LATENCY);
+    dashboard(
+        "glitch-bridge-overview",
+        "Glitch Bridge - Overview",
+        vec![
+            panel(
+                "Queue depth",
+                "short",
+                vec![query(format!("sum by (network) ({})", name(&*metrics::QUEUE_DEPTH)), "{{network}}")],
+            ),
+            panel(
+                "Oldest pending deposit",
+                "s",
+                vec![query(
+                    format!("max by (network, state) ({})", name(&*metrics::OLDEST_PENDING_AGE)),
+                    "{{network}} {{state}}",
+                )],
+            ),
+            panel(
+                "Transfer latency (p50 / p95)",
+                "s",
+                vec![
+                    query(
+                        format!("histogram_quantile(0.5, sum by (le, network, stage) (rate({latency}_bucket[5m])))"),
+                        "p50 {{network}} {{stage}}",
+                    ),
+                    query(
+                        format!("histogram_quantile(0.95, sum by (le, network, stage) (rate({latency}_bucket[5m])))"),
+                        "p95 {{network}} {{stage}}",
+                    ),
+                ],
+            ),
+            panel(
+                "Pending finalizations",
+                "short",
+                vec![query(format!("sum by (network) ({})", name(&*metrics::PENDING_FINALIZATIONS)), "{{network}}")],
+            ),
+            panel(
+                "Signer balances",
+                "short",
+                vec![query(format!("max by (signer) ({})", name(&*metrics::SIGNER_BALANCE)), "{{signer}}")],
+            ),
+            panel(
+                "Supply delta (locked - minted)",
+                "short",
+                vec![query(name(&*metrics::SUPPLY_DELTA), "delta")],
+            ),
+            panel(
+                "Average network fee",
+                "short",
+                vec![query(format!("max by (network) ({})", name(&*metrics::NETWORK_FEE_AVERAGE)), "{{network}}")],
+            ),
+            panel(
+                "Canary transfers",
+                "ops",
+                vec![query(rate(&*metrics::CANARY_TRANSFERS, "network, result"), "{{network}} {{result}}")],
+            ),
+        ],
+    )
+}
+
+/// Failure rates of the RPCs, the database and the tasks.
+fn health() -> Value {
+    dashboard(
+        "glitch-bridge-health",
+        "Glitch Bridge - Health",
+        vec![
+            panel(
+                "RPC rate limits and timeouts",
+                "ops",
+                vec![
+                    query(rate(&*metrics::RPC_RATE_LIMITED, "network"), "rate limited {{network}}"),
+                    query(rate(&*metrics::RPC_TIMEOUTS, "network"), "timed out {{network}}"),
+                ],
+            ),
+            panel(
+                "RPC retry budget left",
+                "short",
+                vec![query(format!("min by (network) ({})", name(&*metrics::RPC_RETRY_BUDGET)), "{{network}}")],
+            ),
+            panel(
+                "Database",
+                "short",
+                vec![
+                    query(format!("max by (database) ({})", name(&*metrics::DB_UP)), "up {{database}}"),
+                    query(
+                        rate(&*metrics::DB_CONNECTION_FAILURES, "database, source"),
+                        "failures {{database}} {{source}}",
+                    ),
+                    query(rate(&*metrics::DB_POOL_RECREATIONS, "database"), "pool recreated {{database}}"),
+                ],
+            ),
+            panel(
+                "Tasks down",
+                "short",
+                vec![query(format!("{} == 0", name(&*metrics::TASK_UP)), "{{task}}")],
+            ),
+            panel(
+                "Task restarts",
+                "ops",
+                vec![query(rate(&*metrics::TASK_RESTARTS, "task"), "{{task}}")],
+            ),
+            panel(
+                "Suspicious deposits and decoder divergences",
+                "ops",
+                vec![
+                    query(rate(&*metrics::SUSPICIOUS_DEPOSITS, "network"), "suspicious {{network}}"),
+                    query(rate(&*metrics::DECODER_DIVERGENCES, "network"), "divergences {{network}}"),
+                ],
+            ),
+            panel(
+                "Deposit size (p95, whole tokens)",
+                "short",
+                vec![query(
+                    format!(
+                        "histogram_quantile(0.95, sum by (le, network) (rate({}_bucket[1h])))",
+                        name(&*metrics::DEPOSIT_AMOUNT)
+                    ),
+                    "{{network}}",
+                )],
+            ),
+            panel(
+                "Reorgs and contract upgrades",
+                "ops",
+                vec![
+                    query(rate(&*metrics::REORGS, "network"), "reorgs {{network}}"),
+                    query(rate(&*metrics::CONTRACT_UPGRADES, "network"), "upgrades {{network}}"),
+                ],
+            ),
+            panel(
+                "Network fee fallbacks",
+                "ops",
+                vec![query(rate(&*metrics::NETWORK_FEE_FALLBACKS, "network"), "{{network}}")],
+            ),
+            panel(
+                "Scheduled jobs",
+                "s",
+                vec![
+                    query(
+                        format!(
+                            "histogram_quantile(0.95, sum by (le, job) (rate({}_bucket[15m])))",
+                            name(&*metrics::JOB_DURATION)
+                        ),
+                        "p95 {{job}}",
+                    ),
+                    query(format!("{} == 1", name(&*metrics::JOB_PAUSED)), "paused {{job}}"),
+                ],
+            ),
+        ],
+    )
+}
+
+/// Writes every dashboard as `<uid>.json` into `output`, ready to be
+/// imported into Grafana or provisioned from a directory.
+pub fn write_dashboards(output: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(output)?;
+    for dashboard in [overview(), health()] {
+        let path = output.join(format!("{}.json", dashboard["uid"].as_str().unwrap_or("dashboard")));
+        fs::write(&path, serde_json::to_string_pretty(&dashboard)?)?;
+        info!("Dashboard written to {:?}", path);
+    }
+    Ok(())
+}
@@ -1,32 +1,161 @@
-use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex as StdMutex;
 
-use log::{debug, error, info};
+use chrono::{DateTime, TimeZone, Utc};
+use log::{debug, error, info, warn};
 use mysql_async::prelude::{BatchQuery, Queryable, WithParams};
-use mysql_async::{params, Conn, Pool, Row, TxOpts, Params, OptsBuilder};
-use sp_core::U256;
-use web3::types::{Log, H160, H256};
-use tokio::time::{Duration, sleep};
+use mysql_async::{params, Conn, Pool, Row, Transaction, TxOpts, Params, OptsBuilder};
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, sleep, timeout};
 
-use crate::config::{self, Database};
+use crate::bulk::TxFilter;
+use crate::config::{self, Database, DatabaseFailover, Notification, Token};
+use crate::crash::CrashReport;
+use crate::decoder::{max_stored_amount, Deposit};
+use crate::encryption::ColumnCipher;
+use crate::glitch_scanner::Withdrawal;
+use crate::metrics;
+use crate::tagging::{join_labels, split_labels};
+use crate::notifications::notify;
+use crate::payout::Receipt;
+use crate::skipped_logs::SkippedLog;
+use crate::version::BUILD_VERSION;
 
 const SELECT_TRANSACTIONS_TO_PROCESS: &str =
-    r"SELECT id, to_glitch_address, amount FROM tx WHERE state = 'TO_PROCESS'";
+    r"SELECT id, to_glitch_address, amount, UNIX_TIMESTAMP(time), version, eth_fee, labels FROM tx WHERE state = 'TO_PROCESS' AND scanner_name = :name AND (unlock_at IS NULL OR unlock_at <= UNIX_TIMESTAMP())";
 const SELECT_NETWORK_STATE: &str =
     r"SELECT id, network, monitor_address, last_block FROM scanner_state WHERE name = :name ";
 const INSERT_NETWORK_STATE: &str = r"INSERT INTO scanner_state (name, network, monitor_address) VALUES (:name, :network, :monitor_address)";
 const INSERT_TX_FEE: &str =
-    r"INSERT INTO fee_transaction (hash, amount) values (:tx_glitch_hash, :amount)";
+    r"INSERT INTO fee_transaction (hash, amount, scanner_name) values (:tx_glitch_hash, :amount, :name)";
+const UPDATE_FEE_CONFIRMATION: &str =
+    r"UPDATE fee_transaction SET confirmation = :confirmation, discrepancy = :discrepancy WHERE id = :id";
 const SELECT_LAST_BLOCK: &str = r"SELECT last_block FROM scanner_state WHERE name = :name";
 const SELECT_FEE_ACCUMULATED: &str =
-    r"SELECT accumulated_fees FROM scanner_state WHERE name = :name";
+    r"SELECT accumulated_fees, CAST(COALESCE((SELECT MAX(id) FROM fee_adjustment WHERE scanner_name = :name), 0) AS UNSIGNED) FROM scanner_state WHERE name = :name";
 const UPDATE_LAST_BLOCK: &str = r"UPDATE scanner_state SET last_block = :block WHERE name = :name";
-const UPDATE_FEE: &str =
-    r"UPDATE scanner_state SET accumulated_fees = :accumulated_fees WHERE name = :name";
-const UPDATE_TX_GLITCH: &str = r"UPDATE tx SET tx_glitch_hash = :glitch_tx_hash, state = 'PROCESSED', business_fee_amount = :business_fee_amount, business_fee_percentage = :business_fee_percentage WHERE id = :id";
-const INSERT_TXS: &str = r"INSERT INTO tx (tx_eth_hash, from_eth_address, amount, to_glitch_address) VALUES (:tx_eth_hash, :from_eth_address, :amount, :to_glitch_address)";
-const SAVE_ERROR: &str = r"UPDATE tx SET error = :error WHERE id = :id";
-const GET_LAST_FEE_TIME: &str = r"SELECT time FROM fee_transaction ft ORDER BY time DESC LIMIT 1";
-const UPDATE_TX_WITH_TRANSACTION_FEE_ID: &str = r"UPDATE tx t SET t.wich_transaction_fee = :transaction_fee_id WHERE t.wich_transaction_fee is NULL  AND t.state = 'PROCESSED';";
+const ADD_TO_FEE: &str =
+    r"UPDATE scanner_state SET accumulated_fees = CAST(CAST(accumulated_fees AS DECIMAL(65, 0)) + CAST(:amount AS DECIMAL(65, 0)) AS CHAR) WHERE name = :name";
+const DEDUCT_FROM_FEE: &str =
+    r"UPDATE scanner_state SET accumulated_fees = CAST(GREATEST(CAST(accumulated_fees AS DECIMAL(65, 0)) - CAST(:amount AS DECIMAL(65, 0)), 0) AS CHAR) WHERE name = :name";
+const UPDATE_TX_GLITCH: &str = r"UPDATE tx SET tx_glitch_hash = :glitch_tx_hash, state = 'PROCESSED', finalized_at = CURRENT_TIMESTAMP(), business_fee_amount = :business_fee_amount, business_fee_percentage = :business_fee_percentage, network_fee = :network_fee, network_fee_estimated = :network_fee_estimated, payout_version = :payout_version, amount_breakdown = :amount_breakdown, version = version + 1 WHERE id = :id AND version = :version";
+const INSERT_TXS: &str = r"INSERT INTO tx (tx_eth_hash, from_eth_address, amount, to_glitch_address, state, error, scanner_name, eth_block_number, decoder_version, unlock_at, event_version, eth_fee, labels, eth_block_time) VALUES (:tx_eth_hash, :from_eth_address, :amount, :to_glitch_address, :state, :error, :name, :eth_block_number, :decoder_version, :unlock_at, :event_version, :eth_fee, :labels, :eth_block_time)";
+const UPDATE_TX_SUBMITTED: &str = r"UPDATE tx SET state = 'PROCESSING', failure_kind = NULL, submitted_at = CURRENT_TIMESTAMP(), version = version + 1 WHERE id = :id AND version = :version AND state = 'TO_PROCESS'";
+const UPDATE_TX_INCLUDED: &str = r"UPDATE tx SET extrinsic_hash = :extrinsic_hash, version = version + 1 WHERE id = :id AND version = :version AND state = 'PROCESSING'";
+const SELECT_PROCESSING_TXS: &str = r"SELECT tx.id, tx.version, tx.to_glitch_address, (SELECT glitch_outbox.amount FROM glitch_outbox WHERE glitch_outbox.tx_id = tx.id ORDER BY glitch_outbox.id DESC LIMIT 1), UNIX_TIMESTAMP(COALESCE(tx.submitted_at, tx.time)), COALESCE(tx.extrinsic_hash, (SELECT glitch_outbox.extrinsic_hash FROM glitch_outbox WHERE glitch_outbox.tx_id = tx.id AND glitch_outbox.state IN ('SUBMITTING', 'UNKNOWN') ORDER BY glitch_outbox.id DESC LIMIT 1)) FROM tx WHERE tx.state = 'PROCESSING' AND tx.scanner_name = :name ORDER BY tx.id";
+const UPDATE_TX_RECONCILED: &str = r"UPDATE tx SET state = 'PROCESSED', tx_glitch_hash = :glitch_tx_hash, finalized_at = CURRENT_TIMESTAMP(), amount_breakdown = :amount_breakdown, version = version + 1 WHERE id = :id AND version = :version AND state = 'PROCESSING'";
+const SELECT_TXS_TO_ACK: &str = r"SELECT id, tx_eth_hash, tx_glitch_hash FROM tx WHERE state = 'PROCESSED' AND scanner_name = :name AND ack_tx_hash IS NULL AND tx_glitch_hash IS NOT NULL AND id >= :start_at ORDER BY id LIMIT :limit";
+const UPDATE_TX_ACK: &str = r"UPDATE tx SET ack_tx_hash = :ack_tx_hash WHERE id = :id AND ack_tx_hash IS NULL";
+const RELEASE_TX_CLAIM: &str = r"UPDATE tx SET state = 'TO_PROCESS', version = version + 1 WHERE id = :id AND version = :version AND state = 'PROCESSING'";
+const SELECT_TXS_BREACHING_SLA: &str = r"SELECT id FROM tx WHERE sla_alerted = FALSE AND COALESCE(UNIX_TIMESTAMP(finalized_at), UNIX_TIMESTAMP()) - GREATEST(UNIX_TIMESTAMP(time), COALESCE(unlock_at, 0)) > :sla_in_secs";
+const UPDATE_TX_SLA_ALERTED: &str = r"UPDATE tx SET sla_alerted = TRUE WHERE id = :id";
+const SELECT_TOTAL_BRIDGED: &str = r"SELECT CAST(GREATEST(COALESCE((SELECT SUM(CAST(amount AS DECIMAL(65,0))) FROM tx WHERE state IN ('TO_PROCESS', 'PROCESSING', 'PROCESSED') AND FIND_IN_SET(scanner_name, :names)), 0) - COALESCE((SELECT SUM(CAST(accumulated_fees AS DECIMAL(65,0))) FROM scanner_state WHERE FIND_IN_SET(name, :names)), 0), 0) AS CHAR)";
+const SELECT_IN_FLIGHT_VALUE: &str = r"SELECT CAST(COALESCE(SUM(CAST(amount AS DECIMAL(65,0))), 0) AS CHAR) FROM tx WHERE state = 'PROCESSING' AND scanner_name = :name";
+/// Deposits still backed by the contract: everything but test and
+/// cancelled ones.
+const SELECT_DEPOSITED_VALUE: &str = r"SELECT CAST(COALESCE(SUM(CAST(amount AS DECIMAL(65,0))), 0) AS CHAR) FROM tx WHERE state NOT IN ('TEST', 'CANCELLED') AND scanner_name = :name";
+const SELECT_RELEASED_VALUE: &str = r"SELECT CAST(COALESCE(SUM(amount), 0) AS CHAR) FROM withdrawal WHERE state = 'PROCESSED' AND scanner_name = :name";
+const SELECT_DEPOSIT_SCREENING_VERDICTS: &str =
+    r"SELECT verdict FROM deposit_screening WHERE scanner_name = :name AND tx_eth_hash = :tx_eth_hash";
+const INSERT_DEPOSIT_SCREENING: &str = r"INSERT INTO deposit_screening (scanner_name, tx_eth_hash, screener, verdict, risk, detail) VALUES (:name, :tx_eth_hash, :screener, :verdict, :risk, :detail)";
+const UPDATE_TX_SOURCE: &str = r"UPDATE tx SET source_gas_used = :gas_used, source_gas_price = :gas_price, source_value = :value WHERE tx_eth_hash = :tx_eth_hash";
+const INSERT_SUPPLY_CHECK: &str = r"INSERT INTO supply_check (locked, minted, delta, within_tolerance) VALUES (:locked, :minted, :delta, :within_tolerance)";
+const SELECT_ALL_NETWORK_STATES: &str =
+    r"SELECT name, network, monitor_address, accumulated_fees, last_block FROM scanner_state";
+const SELECT_PENDING_TXS: &str = r"SELECT tx_eth_hash, from_eth_address, to_glitch_address, amount, state, error, scanner_name, unlock_at FROM tx WHERE state IN ('TO_PROCESS', 'PROCESSING')";
+const DELETE_NETWORK_STATE: &str = r"DELETE FROM scanner_state WHERE name = :name";
+const RESTORE_NETWORK_STATE: &str = r"INSERT INTO scanner_state (name, network, monitor_address, accumulated_fees, last_block) VALUES (:name, :network, :monitor_address, :accumulated_fees, :last_block)";
+const RESTORE_PENDING_TX: &str = r"INSERT INTO tx (tx_eth_hash, from_eth_address, to_glitch_address, amount, state, error, scanner_name, unlock_at) SELECT :tx_eth_hash, :from_eth_address, :to_glitch_address, :amount, :state, :error, :scanner_name, :unlock_at FROM DUAL WHERE NOT EXISTS (SELECT 1 FROM tx WHERE tx_eth_hash = :tx_eth_hash)";
+const SELECT_SUSPICIOUS_TXS: &str = r"SELECT id, tx_eth_hash, from_eth_address, to_glitch_address, amount, error FROM tx WHERE state = 'SUSPICIOUS'";
+const RELEASE_SUSPICIOUS_TX: &str =
+    r"UPDATE tx SET state = 'TO_PROCESS', error = NULL, failure_kind = NULL, version = version + 1 WHERE id = :id AND state = 'SUSPICIOUS'";
+/// Only payouts not claimed yet can be cancelled; the version bump makes a
+/// payer that read the tx before the cancel fail its claim.
+const CANCEL_TX: &str = r"UPDATE tx SET state = 'CANCELLED', error = :note, version = version + 1 WHERE id = :id AND state IN ('TO_PROCESS', 'SUSPICIOUS', 'QUARANTINED')";
+const SELECT_TX_STATE: &str = r"SELECT state FROM tx WHERE id = :id";
+const SELECT_TXS_BY_FILTER: &str = r"SELECT id FROM tx WHERE FIND_IN_SET(state, :states) AND (:state IS NULL OR state = :state) AND (:name IS NULL OR scanner_name = :name) AND (:pipelines IS NULL OR FIND_IN_SET(scanner_name, :pipelines)) AND (:from IS NULL OR time >= :from) AND (:to IS NULL OR time < :to) AND (:error_pattern IS NULL OR error LIKE :error_pattern) ORDER BY id";
+const BULK_SET_TX_STATE: &str = r"UPDATE tx SET state = :to_state, error = IF(:clear_error, NULL, COALESCE(:note, error)), version = version + 1 WHERE id = :id AND FIND_IN_SET(state, :states)";
+const SELECT_DEPOSIT_STATUS: &str = r"SELECT state, tx_glitch_hash, UNIX_TIMESTAMP(time), UNIX_TIMESTAMP(finalized_at), unlock_at, glitch_block_number, extrinsic_index, transfer_event FROM tx WHERE tx_eth_hash = :tx_eth_hash ORDER BY id DESC LIMIT 1";
+const INSERT_REORG_QUARANTINE: &str = r"INSERT INTO reorg_quarantine (scanner_name, from_block, to_block, until) VALUES (:name, :from_block, :to_block, DATE_ADD(CURRENT_TIMESTAMP(), INTERVAL :quarantine_in_secs SECOND))";
+const QUARANTINE_TXS_IN_RANGE: &str = r"UPDATE tx SET state = 'QUARANTINED', version = version + 1 WHERE scanner_name = :name AND state = 'TO_PROCESS' AND eth_block_number BETWEEN :from_block AND :to_block";
+const SELECT_ACTIVE_QUARANTINES: &str = r"SELECT from_block, to_block FROM reorg_quarantine WHERE scanner_name = :name AND until > CURRENT_TIMESTAMP()";
+const END_QUARANTINE: &str =
+    r"UPDATE reorg_quarantine SET until = CURRENT_TIMESTAMP() WHERE id = :id AND until > CURRENT_TIMESTAMP()";
+const RELEASE_EXPIRED_QUARANTINES: &str = r"UPDATE tx SET state = 'TO_PROCESS', version = version + 1 WHERE state = 'QUARANTINED' AND NOT EXISTS (SELECT 1 FROM reorg_quarantine q WHERE q.scanner_name = tx.scanner_name AND tx.eth_block_number BETWEEN q.from_block AND q.to_block AND q.until > CURRENT_TIMESTAMP())";
+const SELECT_RECENT_TOP_UP_REQUEST: &str = r"SELECT COUNT(*) FROM top_up_request WHERE hot_wallet = :hot_wallet AND time > DATE_SUB(CURRENT_TIMESTAMP(), INTERVAL :cooldown_in_secs SECOND)";
+const INSERT_TOP_UP_REQUEST: &str = r"INSERT INTO top_up_request (hot_wallet, amount, method, reference, error) VALUES (:hot_wallet, :amount, :method, :reference, :error)";
+const INSERT_SCANNER_ERROR: &str = r"INSERT INTO scanner_error (scanner_name, kind, block_number, message) VALUES (:name, :kind, :block_number, :message)";
+const SELECT_SCANNER_ERRORS: &str = r"SELECT id, scanner_name, kind, block_number, message, DATE_FORMAT(time, '%Y-%m-%dT%H:%i:%sZ') FROM scanner_error WHERE (:name IS NULL OR scanner_name = :name) ORDER BY id DESC LIMIT :limit";
+const INSERT_FEE_POLICY: &str = r"INSERT INTO fee_policy (scanner_name, business_fee_percentage, fee_destination, effective_from) VALUES (:name, :business_fee_percentage, :fee_destination, COALESCE(FROM_UNIXTIME(:effective_from), CURRENT_TIMESTAMP()))";
+const SELECT_FEE_POLICIES: &str = r"SELECT business_fee_percentage, fee_destination, UNIX_TIMESTAMP(effective_from) FROM fee_policy WHERE scanner_name = :name ORDER BY effective_from, id";
+const COUNT_OTHER_CONNECTIONS: &str = r"SELECT COUNT(*) FROM information_schema.processlist WHERE db = DATABASE() AND id <> CONNECTION_ID()";
+const COUNT_HELD_LEASES: &str = r"SELECT COUNT(*) FROM instance_lease WHERE expires_at > CURRENT_TIMESTAMP()";
+const SELECT_TABLE_ROWS: &str = r"SELECT table_rows FROM information_schema.tables WHERE table_schema = DATABASE() AND table_name = :table_name";
+const INSERT_WITHDRAWALS: &str = r"INSERT IGNORE INTO withdrawal (event_id, scanner_name, from_glitch_address, to_eth_address, amount, glitch_block_number) VALUES (:event_id, :name, :from_glitch_address, :to_eth_address, :amount, :glitch_block_number)";
+const SELECT_WITHDRAWALS_TO_PROCESS: &str = r"SELECT id, version, event_id, to_eth_address, CAST(amount AS CHAR) FROM withdrawal WHERE state = 'TO_PROCESS' AND scanner_name = :name ORDER BY id LIMIT :limit";
+const CLAIM_WITHDRAWAL: &str = r"UPDATE withdrawal SET state = 'PROCESSING', version = version + 1 WHERE id = :id AND version = :version AND state = 'TO_PROCESS'";
+const UPDATE_WITHDRAWAL_RELEASE: &str = r"UPDATE withdrawal SET state = :state, eth_tx_hash = :eth_tx_hash, safe_tx_hash = :safe_tx_hash, error = NULL, version = version + 1 WHERE id = :id AND version = :version";
+const RELEASE_WITHDRAWAL_CLAIM: &str = r"UPDATE withdrawal SET state = 'TO_PROCESS', eth_tx_hash = NULL, error = :error, version = version + 1 WHERE id = :id AND version = :version";
+const RECORD_WITHDRAWAL_SENT: &str = r"UPDATE withdrawal SET eth_tx_hash = :eth_tx_hash, version = version + 1 WHERE id = :id AND version = :version AND state = 'PROCESSING'";
+const FAIL_WITHDRAWAL: &str = r"UPDATE withdrawal SET state = 'FAILED', error = :error, version = version + 1 WHERE id = :id AND version = :version AND state IN ('TO_PROCESS', 'PROCESSING')";
+const SELECT_PROCESSING_WITHDRAWALS: &str = r"SELECT id, version, eth_tx_hash FROM withdrawal WHERE state = 'PROCESSING' AND scanner_name = :name ORDER BY id";
+const SELECT_STORED_DEPOSITS: &str = r"SELECT id, scanner_name, from_eth_address, to_glitch_address, CAST(amount AS CHAR), CAST(state AS CHAR), eth_block_number, unlock_at, event_version, CAST(eth_fee AS CHAR), decoder_version FROM tx WHERE tx_eth_hash = :tx_eth_hash ORDER BY id";
+const SELECT_RECIPIENT_DEPOSITS_ON: &str = r"SELECT tx_eth_hash, to_glitch_address, CAST(amount AS CHAR) FROM tx WHERE scanner_name = :name AND COALESCE(eth_block_time, UNIX_TIMESTAMP(time)) >= :day AND COALESCE(eth_block_time, UNIX_TIMESTAMP(time)) < :day + 86400 AND state IN ('TO_PROCESS', 'PROCESSING', 'PROCESSED', 'QUARANTINED') AND to_glitch_address IS NOT NULL AND amount IS NOT NULL";
+const SELECT_RECENT_DEPOSITS: &str = r"SELECT scanner_name, from_eth_address, to_glitch_address FROM tx WHERE time >= CURRENT_TIMESTAMP() - INTERVAL :window_in_minutes MINUTE AND state NOT IN ('TEST', 'CANCELLED') AND scanner_name IS NOT NULL AND to_glitch_address IS NOT NULL";
+const COUNT_TXS_FROM: &str = r"SELECT COUNT(*), CAST(COALESCE(SUM(state = 'PROCESSED'), 0) AS UNSIGNED) FROM tx WHERE from_eth_address = :from_eth_address";
+const COUNT_TXS_TO_PROCESS: &str =
+    r"SELECT COUNT(*) FROM tx WHERE state = 'TO_PROCESS' AND scanner_name = :name AND (unlock_at IS NULL OR unlock_at <= UNIX_TIMESTAMP())";
+const SAVE_ERROR: &str = r"UPDATE tx SET error = :error, version = version + 1 WHERE id = :id";
+const HOLD_TX_TO_PROCESS: &str = r"UPDATE tx SET state = 'SUSPICIOUS', error = :error, version = version + 1 WHERE id = :id AND version = :version AND state = 'TO_PROCESS'";
+const GET_LAST_FEE_TIME: &str = r"SELECT UNIX_TIMESTAMP(time) FROM fee_transaction ft WHERE ft.scanner_name = :name ORDER BY time DESC LIMIT 1";
+// The period starts at the previous fee payment of the pipeline or, for the
+// first one, at the oldest tx it covers.
+const INSERT_FEE_INVOICE: &str = r"INSERT INTO fee_invoice (fee_transaction_id, scanner_name, period_start, period_end, tx_count, total_volume, fee_amount, glitch_tx_hash) SELECT :fee_transaction_id, :name, COALESCE((SELECT MAX(ft.time) FROM fee_transaction ft WHERE ft.scanner_name = :name AND ft.id < :fee_transaction_id), MIN(t.time), CURRENT_TIMESTAMP()), CURRENT_TIMESTAMP(), COUNT(t.id), CAST(COALESCE(SUM(CAST(t.amount AS DECIMAL(65, 0))), 0) AS CHAR), :amount, :glitch_tx_hash FROM tx t WHERE t.wich_transaction_fee = :fee_transaction_id";
+const SELECT_FEE_INVOICES: &str = r"SELECT id, scanner_name, DATE_FORMAT(period_start, '%Y-%m-%dT%H:%i:%sZ'), DATE_FORMAT(period_end, '%Y-%m-%dT%H:%i:%sZ'), tx_count, total_volume, fee_amount, glitch_tx_hash FROM fee_invoice WHERE (:name IS NULL OR scanner_name = :name) ORDER BY id";
+const UPDATE_TX_WITH_TRANSACTION_FEE_ID: &str = r"UPDATE tx t SET t.wich_transaction_fee = :transaction_fee_id WHERE t.wich_transaction_fee is NULL  AND t.state = 'PROCESSED' AND t.scanner_name = :name;";
+
+const SELECT_FEE_DRIFT: &str = r"SELECT s.name, s.accumulated_fees, CAST(COALESCE((SELECT SUM(CAST(t.business_fee_amount AS DECIMAL(65, 0))) FROM tx t WHERE t.scanner_name = s.name AND t.state = 'PROCESSED' AND t.wich_transaction_fee IS NULL), 0) + COALESCE((SELECT SUM(CAST(a.amount AS DECIMAL(65, 0))) FROM fee_adjustment a WHERE a.scanner_name = s.name AND a.fee_transaction_id IS NULL), 0) AS CHAR) FROM scanner_state s";
+const SELECT_TX_EVENTS_AFTER: &str = r"SELECT sequence, tx_id, scanner_name, tx_eth_hash, previous_state, state, tx_glitch_hash, DATE_FORMAT(time, '%Y-%m-%dT%H:%i:%sZ'), CAST(TIMESTAMPDIFF(SECOND, time, CURRENT_TIMESTAMP()) AS SIGNED) FROM tx_event WHERE sequence > :after ORDER BY sequence LIMIT :limit";
+const SELECT_EVENT_CURSOR: &str = r"SELECT last_sequence FROM event_cursor WHERE name = :name";
+const UPSERT_EVENT_CURSOR: &str = r"INSERT INTO event_cursor (name, last_sequence) VALUES (:name, :last_sequence) ON DUPLICATE KEY UPDATE last_sequence = GREATEST(last_sequence, :last_sequence)";
+const INSERT_OUTBOX_PAYOUT: &str = r"INSERT INTO glitch_outbox (tx_id, tx_version, scanner_name, recipient, amount, business_fee_amount, business_fee_percentage, estimated_fee, amount_breakdown, tx_detected_at) VALUES (:tx_id, :tx_version, :name, :recipient, :amount, :business_fee_amount, :business_fee_percentage, :estimated_fee, :amount_breakdown, :tx_detected_at)";
+const SELECT_PENDING_OUTBOX: &str = r"SELECT id, tx_id, tx_version, scanner_name, recipient, CAST(amount AS CHAR), CAST(business_fee_amount AS CHAR), business_fee_percentage, CAST(estimated_fee AS CHAR), amount_breakdown, tx_detected_at FROM glitch_outbox WHERE scanner_name = :name AND state = 'PENDING' ORDER BY id";
+const SELECT_OUTBOX_PENDING_VALUE: &str = r"SELECT CAST(COALESCE(SUM(amount), 0) AS CHAR) FROM glitch_outbox WHERE scanner_name = :name AND state IN ('PENDING', 'SUBMITTING')";
+const START_OUTBOX_SUBMISSION: &str = r"UPDATE glitch_outbox SET state = 'SUBMITTING' WHERE id = :id AND state = 'PENDING'";
+const SELECT_BRIDGE_TRANSFER: &str = r"SELECT EXISTS(SELECT 1 FROM glitch_outbox WHERE extrinsic_hash = :extrinsic_hash) OR EXISTS(SELECT 1 FROM tx WHERE extrinsic_hash = :extrinsic_hash) OR EXISTS(SELECT 1 FROM fee_transaction WHERE hash = :block_hash AND amount = :amount)";
+const RECORD_OUTBOX_EXTRINSIC: &str = r"UPDATE glitch_outbox SET extrinsic_hash = :extrinsic_hash WHERE id = :id";
+const FINISH_OUTBOX_PAYOUT: &str = r"UPDATE glitch_outbox SET state = :state, block_hash = :block_hash, error = :error WHERE id = :id";
+const INTERRUPT_OUTBOX_SUBMISSIONS: &str = r"UPDATE glitch_outbox SET state = 'UNKNOWN', error = :error WHERE scanner_name = :name AND state = 'SUBMITTING'";
+const CONFIRM_TX_CLAIM: &str = r"UPDATE tx SET version = version + 1 WHERE id = :id AND version = :version AND state = 'PROCESSING'";
+const SELECT_STUCK_PROCESSING_TXS: &str = r"SELECT t.id, t.id, t.version, t.scanner_name, CAST(TIMESTAMPDIFF(SECOND, COALESCE((SELECT MAX(COALESCE(o.updated_at, o.time)) FROM glitch_outbox o WHERE o.tx_id = t.id), t.submitted_at, t.time), CURRENT_TIMESTAMP()) AS SIGNED) AS age FROM tx t WHERE t.state = 'PROCESSING' AND NOT EXISTS (SELECT 1 FROM glitch_outbox o WHERE o.tx_id = t.id AND o.state IN ('PENDING', 'SUBMITTING')) HAVING age > :after_in_secs ORDER BY t.id";
+const SELECT_HELD_TXS: &str = r"SELECT t.id, t.id, t.version, t.scanner_name, CAST(TIMESTAMPDIFF(SECOND, COALESCE((SELECT MAX(e.time) FROM tx_event e WHERE e.tx_id = t.id AND e.state = 'SUSPICIOUS'), t.time), CURRENT_TIMESTAMP()) AS SIGNED) AS age FROM tx t WHERE t.state = 'SUSPICIOUS' HAVING age > :after_in_secs ORDER BY t.id";
+const SELECT_STUCK_OUTBOX: &str = r"SELECT id, tx_id, tx_version, scanner_name, CAST(TIMESTAMPDIFF(SECOND, time, CURRENT_TIMESTAMP()) AS SIGNED) AS age FROM glitch_outbox WHERE state = 'PENDING' HAVING age > :after_in_secs ORDER BY id";
+const DROP_OUTBOX_PAYOUT: &str = r"UPDATE glitch_outbox SET state = 'FAILED', error = :error WHERE id = :id AND state = 'PENDING'";
+const UPDATE_TX_DRY_RUN_FAILED: &str = r"UPDATE tx SET state = IF(:hold, 'SUSPICIOUS', 'TO_PROCESS'), failure_kind = :failure_kind, error = :error, version = version + 1 WHERE id = :id AND version = :version AND state = 'PROCESSING'";
+const UPSERT_TOKEN: &str = r"INSERT INTO token (symbol, eth_address, decimals, min_amount, max_amount, business_fee, glitch_asset_id) VALUES (:symbol, :eth_address, :decimals, :min_amount, :max_amount, :business_fee, :glitch_asset_id) ON DUPLICATE KEY UPDATE eth_address = VALUES(eth_address), decimals = VALUES(decimals), min_amount = VALUES(min_amount), max_amount = VALUES(max_amount), business_fee = VALUES(business_fee), glitch_asset_id = VALUES(glitch_asset_id)";
+const SELECT_FEE_ACCUMULATED_FOR_UPDATE: &str = r"SELECT accumulated_fees FROM scanner_state WHERE name = :name FOR UPDATE";
+const SELECT_FEE_ADJUSTMENT_BY_REFERENCE: &str = r"SELECT id FROM fee_adjustment WHERE scanner_name = :name AND reference = :reference";
+const INSERT_FEE_ADJUSTMENT: &str = r"INSERT INTO fee_adjustment (scanner_name, amount, reason, requested_by, reference, accumulated_before, accumulated_after) VALUES (:name, :amount, :reason, :requested_by, :reference, :accumulated_before, :accumulated_after)";
+const UPDATE_FEE_ADJUSTMENTS_WITH_TRANSACTION_FEE_ID: &str = r"UPDATE fee_adjustment SET fee_transaction_id = :transaction_fee_id WHERE fee_transaction_id IS NULL AND scanner_name = :name AND id <= :last_adjustment_id";
+const SELECT_STUCK_TXS: &str = r"SELECT id, CAST(state AS CHAR) FROM tx WHERE (state = 'PROCESSING' AND TIMESTAMPDIFF(SECOND, COALESCE(submitted_at, time), CURRENT_TIMESTAMP()) > :stuck_after_in_secs) OR (state = 'TO_PROCESS' AND UNIX_TIMESTAMP() - COALESCE(unlock_at, UNIX_TIMESTAMP(time)) > :stuck_after_in_secs) ORDER BY id";
+const SELECT_AVERAGE_PAYOUT_TIME: &str = r"SELECT CAST(ROUND(AVG(UNIX_TIMESTAMP(finalized_at) - COALESCE(unlock_at, UNIX_TIMESTAMP(time)))) AS SIGNED) FROM tx WHERE state = 'PROCESSED' AND scanner_name = :name AND finalized_at >= CURRENT_TIMESTAMP() - INTERVAL :hours HOUR";
+const SELECT_NETWORK_FEE_STATS: &str = r"SELECT scanner_name, COUNT(*), CAST(ROUND(AVG(network_fee)) AS CHAR), CAST(MIN(network_fee) AS CHAR), CAST(MAX(network_fee) AS CHAR) FROM tx WHERE state = 'PROCESSED' AND network_fee > 0 AND scanner_name IS NOT NULL AND (:name IS NULL OR scanner_name = :name) AND finalized_at >= CURRENT_TIMESTAMP() - INTERVAL :hours HOUR GROUP BY scanner_name";
+const SELECT_OLDEST_PENDING_AGES: &str = r"SELECT scanner_name, CAST(state AS CHAR), CAST(UNIX_TIMESTAMP() - MIN(IF(state = 'PROCESSING', UNIX_TIMESTAMP(COALESCE(submitted_at, time)), COALESCE(unlock_at, UNIX_TIMESTAMP(time)))) AS SIGNED) FROM tx WHERE scanner_name IS NOT NULL AND (state = 'PROCESSING' OR (state = 'TO_PROCESS' AND (unlock_at IS NULL OR unlock_at <= UNIX_TIMESTAMP()))) GROUP BY scanner_name, state";
+const INSERT_AUDIT_REPORT: &str = r"INSERT INTO audit_report (supply_ok, fee_drift_ok, stuck_txs, passed, report) VALUES (:supply_ok, :fee_drift_ok, :stuck_txs, :passed, :report)";
+const SELECT_LAST_AUDIT_TIME: &str = r"SELECT UNIX_TIMESTAMP(time) FROM audit_report ORDER BY id DESC LIMIT 1";
+const INSERT_SKIPPED_LOG: &str = r"INSERT INTO skipped_log (scanner_name, reason, tx_eth_hash, log_index, block_number, detail) VALUES (:name, :reason, :tx_eth_hash, :log_index, :block_number, :detail)";
+const INSERT_DECODER_DIVERGENCE: &str = r"INSERT INTO decoder_divergence (scanner_name, tx_eth_hash, log_index, block_number, difference) VALUES (:name, :tx_eth_hash, :log_index, :block_number, :difference)";
+const INSERT_CRASH_REPORT: &str = r"INSERT INTO crash_report (message, location, thread, tx_id, backtrace, build_version) VALUES (:message, :location, :thread, :tx_id, :backtrace, :build_version)";
+const SELECT_SCHEMA_COLUMNS: &str = r"SELECT TABLE_NAME, COLUMN_NAME, COLUMN_TYPE FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE() ORDER BY TABLE_NAME, ORDINAL_POSITION";
+const SELECT_SCHEMA_INDEXES: &str = r"SELECT DISTINCT TABLE_NAME, INDEX_NAME FROM information_schema.STATISTICS WHERE TABLE_SCHEMA = DATABASE() ORDER BY TABLE_NAME, INDEX_NAME";
+// MySQL applies the assignments in order, so `expires_at` sees the new holder.
+const ACQUIRE_INSTANCE_LEASE: &str = r"INSERT INTO instance_lease (name, holder, expires_at) VALUES ('writer', :holder, CURRENT_TIMESTAMP() + INTERVAL :ttl SECOND) ON DUPLICATE KEY UPDATE holder = IF(holder = VALUES(holder) OR expires_at < CURRENT_TIMESTAMP(), VALUES(holder), holder), expires_at = IF(holder = VALUES(holder), VALUES(expires_at), expires_at)";
+const SELECT_INSTANCE_LEASE_HOLDER: &str = r"SELECT holder FROM instance_lease WHERE name = 'writer'";
+
+/// Seconds the instance lease is valid without being renewed.
+pub const INSTANCE_LEASE_TTL_IN_SECONDS: u64 = 30;
 
 #[derive(Clone)]
 pub struct ScannerState {
@@ -41,7 +170,346 @@ pub struct ScannerState {
 pub struct TxToProcess {
     pub id: u128,
     pub glitch_address: String,
-    pub amount: String,
+    /// None when the stored amount is missing or out of range.
+    pub amount: Option<u128>,
+    pub detected_at: i64,
+    /// Row version read with the tx; state changes only apply if it still matches.
+    pub version: u32,
+    /// Protocol fee already deducted on Ethereum, if the event reported it.
+    pub eth_fee: Option<u128>,
+    pub labels: Vec<String>,
+}
+
+/// A tx claimed for payout whose outcome was not recorded.
+#[derive(Debug)]
+pub struct ProcessingTx {
+    pub id: u128,
+    pub version: u32,
+    pub glitch_address: String,
+    /// Amount of the latest payout decided for the tx, what the recipient
+    /// was sent if it went out.
+    pub payout_amount: Option<u128>,
+    /// When the payout was submitted, or the tx detected if that is unknown.
+    pub submitted_at: i64,
+    /// Hash of the included payout, or of the extrinsic of the latest
+    /// submission left SUBMITTING or UNKNOWN in the outbox.
+    pub extrinsic_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NetworkStateRow {
+    pub name: String,
+    pub network: String,
+    pub monitor_address: String,
+    pub accumulated_fees: String,
+    pub last_block: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PendingTxRow {
+    pub tx_eth_hash: String,
+    pub from_eth_address: String,
+    pub to_glitch_address: Option<String>,
+    pub amount: Option<String>,
+    pub state: String,
+    pub error: Option<String>,
+    pub scanner_name: Option<String>,
+    #[serde(default)]
+    pub unlock_at: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SuspiciousTx {
+    pub id: u128,
+    pub tx_eth_hash: String,
+    pub from_eth_address: String,
+    pub to_glitch_address: Option<String>,
+    pub amount: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ScannerErrorKind {
+    Rpc,
+    Decode,
+    SkippedBlock,
+}
+
+impl ScannerErrorKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ScannerErrorKind::Rpc => "RPC",
+            ScannerErrorKind::Decode => "DECODE",
+            ScannerErrorKind::SkippedBlock => "SKIPPED_BLOCK",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScannerError {
+    pub id: u128,
+    pub scanner_name: String,
+    pub kind: String,
+    pub block_number: Option<u64>,
+    pub message: String,
+    pub time: String,
+}
+
+/// Glitch network fees actually paid by the payouts of a pipeline, in plancks.
+#[derive(Serialize, Debug)]
+pub struct NetworkFeeStats {
+    pub scanner_name: String,
+    pub payouts: u64,
+    pub average: String,
+    pub min: String,
+    pub max: String,
+}
+
+/// Summary of the txs covered by one business fee payment.
+#[derive(Serialize, Debug)]
+pub struct FeeInvoice {
+    pub id: u128,
+    pub scanner_name: String,
+    pub period_start: String,
+    pub period_end: String,
+    pub tx_count: u64,
+    pub total_volume: String,
+    pub fee_amount: String,
+    pub glitch_tx_hash: String,
+    /// Symbol and decimals of the pipeline's token in the registry.
+    pub token: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+/// A payout decided by the payer and waiting in `glitch_outbox` for the
+/// submitter. `amount` is what the recipient gets.
+#[derive(Debug, Clone)]
+pub struct OutboxPayout {
+    /// 0 until the decision is committed.
+    pub id: u64,
+    pub tx_id: u128,
+    /// Version of the tx once claimed for this payout.
+    pub tx_version: u32,
+    pub scanner_name: String,
+    pub recipient: String,
+    pub amount: u128,
+    pub business_fee_amount: u128,
+    pub business_fee_percentage: f64,
+    pub estimated_fee: u128,
+    pub amount_breakdown: String,
+    pub tx_detected_at: i64,
+}
+
+/// What a finalized payout is recorded with on its tx by `update_tx`.
+#[derive(Debug)]
+pub struct SettledPayout<'a> {
+    pub glitch_hash: String,
+    pub business_fee_amount: u128,
+    pub business_fee_percentage: String,
+    pub network_fee: u128,
+    pub network_fee_estimated: u128,
+    pub amount_breakdown: String,
+    pub receipt: Option<&'a Receipt>,
+}
+
+/// The state writes decided in one pass of the payer, persisted together by
+/// `apply_transitions` instead of one connection per tx.
+#[derive(Debug, Default)]
+pub struct TxTransitions {
+    errors: Vec<(u128, String)>,
+    holds: Vec<(u128, u32, String)>,
+    payouts: Vec<(OutboxPayout, u32)>,
+}
+
+impl TxTransitions {
+    /// Records an error on the tx, leaving it in the queue.
+    pub fn error(&mut self, id: u128, error_message: String) {
+        self.errors.push((id, error_message));
+    }
+
+    /// Takes the tx, read at `version`, out of the queue as SUSPICIOUS with
+    /// the reason as error, for a problem retrying can't fix.
+    pub fn hold(&mut self, id: u128, version: u32, reason: String) {
+        self.holds.push((id, version, reason));
+    }
+
+    /// Claims the tx, read at `version`, and queues its payout.
+    pub fn payout(&mut self, payout: OutboxPayout, version: u32) {
+        self.payouts.push((payout, version));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.holds.is_empty() && self.payouts.is_empty()
+    }
+}
+
+type OutboxPayoutRow = (u64, u128, u32, String, Option<String>, String, String, String, String, String, i64);
+
+/// A tx, or a payout of the outbox, left in an intermediate state. For txs
+/// `id` is `tx_id`.
+#[derive(Debug, Clone)]
+pub struct StuckRow {
+    pub id: u128,
+    pub tx_id: u128,
+    pub tx_version: u32,
+    pub scanner_name: String,
+    pub age_in_secs: i64,
+}
+
+/// Why the dry run of a payout failed, as kept in `tx.failure_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    InsufficientFunds,
+    BadDestination,
+    WouldReapSigner,
+    Rejected,
+}
+
+impl FailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            FailureKind::BadDestination => "BAD_DESTINATION",
+            FailureKind::WouldReapSigner => "WOULD_REAP_SIGNER",
+            FailureKind::Rejected => "REJECTED",
+        }
+    }
+}
+
+/// State of a payout of the outbox once the submitter is done with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxState {
+    Included,
+    NotIncluded,
+    Unknown,
+    Failed,
+}
+
+impl OutboxState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OutboxState::Included => "INCLUDED",
+            OutboxState::NotIncluded => "NOT_INCLUDED",
+            OutboxState::Unknown => "UNKNOWN",
+            OutboxState::Failed => "FAILED",
+        }
+    }
+}
+
+/// A state change of a tx. `sequence` grows with every event, so consumers
+/// can order them and detect the ones they missed.
+#[derive(Serialize, Debug, Clone)]
+pub struct TxEvent {
+    pub sequence: u64,
+    pub tx_id: u128,
+    pub scanner_name: Option<String>,
+    pub tx_eth_hash: String,
+    pub previous_state: Option<String>,
+    pub state: String,
+    pub tx_glitch_hash: Option<String>,
+    pub time: String,
+    #[serde(skip)]
+    pub age_in_secs: i64,
+}
+
+type TxEventRow = (
+    u64,
+    u128,
+    Option<String>,
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    String,
+    i64,
+);
+
+/// A manual change of the fee counter of a pipeline, e.g. a refund or a
+/// correction. `amount` is signed, in the smallest unit of the token.
+#[derive(Debug, Clone)]
+pub struct FeeAdjustment {
+    pub scanner_name: String,
+    pub amount: i128,
+    pub reason: String,
+    pub requested_by: String,
+    /// Identifies the adjustment so importing it again is a no-op.
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FeeAdjustmentOutcome {
+    /// Applied, with the fee counter after it.
+    Applied(u128),
+    /// The reference was already imported as this adjustment id.
+    AlreadyApplied(u64),
+}
+
+type StoredDepositRow = (
+    u128,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    String,
+    Option<u64>,
+    Option<i64>,
+    Option<u8>,
+    Option<String>,
+    Option<String>,
+);
+
+/// A deposit as stored, to be checked against the chain.
+#[derive(Debug)]
+pub struct StoredDeposit {
+    pub id: u128,
+    pub scanner_name: Option<String>,
+    pub from_eth_address: Option<String>,
+    pub glitch_address: Option<String>,
+    pub amount: Option<String>,
+    pub state: String,
+    pub block_number: Option<u64>,
+    pub unlock_at: Option<i64>,
+    pub event_version: Option<u8>,
+    pub eth_fee: Option<String>,
+    /// Build of the bridge that decoded it.
+    pub decoder_version: Option<String>,
+}
+
+/// A claimed withdrawal, with the hash of its release call once signed.
+pub struct ProcessingWithdrawal {
+    pub id: u128,
+    pub version: u32,
+    pub eth_tx_hash: Option<String>,
+}
+
+/// A queued withdrawal, `amount` None if it doesn't fit.
+pub struct WithdrawalToProcess {
+    pub id: u128,
+    pub version: u32,
+    pub event_id: String,
+    pub to_eth_address: String,
+    pub amount: Option<u128>,
+}
+
+/// Business fee and destination of a pipeline from `effective_from` on.
+#[derive(Debug, Clone)]
+pub struct FeePolicy {
+    pub business_fee: f64,
+    pub fee_destination: String,
+    pub effective_from: i64,
+}
+
+/// Raw status of a deposit, before it is mapped to what the public API shows.
+pub struct DepositStatus {
+    pub state: String,
+    pub tx_glitch_hash: Option<String>,
+    pub detected_at: i64,
+    pub paid_at: Option<i64>,
+    pub unlock_at: Option<i64>,
+    pub glitch_block_number: Option<u32>,
+    pub extrinsic_index: Option<u32>,
+    /// JSON of the transfer event, see `payout::Receipt`.
+    pub transfer_event: Option<String>,
 }
 
 pub struct DatabaseEngine {
@@ -50,258 +518,2215 @@ pub struct DatabaseEngine {
     pub password: String,
     pub port: u32,
     pub database: String,
+    /// Encrypts `tx.to_glitch_address` and `tx.error` at rest when configured.
+    pub cipher: Option<ColumnCipher>,
+    /// Warm standby taken over when the primary stays unreachable.
+    pub failover: Option<DatabaseFailover>,
+    /// Set once reads and writes moved to the failover.
+    on_failover: AtomicBool,
+    /// Serializes the takeover between the tasks that lost the primary.
+    failing_over: Mutex<()>,
+    /// Identifies this process in the instance lease.
+    pub instance_id: String,
+    /// Nothing is written, so the failover is followed without taking the
+    /// instance lease.
+    read_only: bool,
+    /// Where failover alerts are sent, if anywhere.
+    pub notifications: Option<Notification>,
+    /// Connection pools of the primary and of the failover, built on first
+    /// use and dropped by the health watchdog when they stop answering.
+    primary_pool: StdMutex<Option<Pool>>,
+    failover_pool: StdMutex<Option<Pool>>,
+    /// Budget to get a connection, and to wait for a row lock or run a read
+    /// server side.
+    query_timeout: Duration,
+    retry: config::DatabaseRetry,
 }
 
 impl DatabaseEngine {
-    pub async fn establish_connection(&self) -> Conn {
-        const MAX_RETRIES: u8 = 5;
-        for i in 1..=MAX_RETRIES {
-            let database_url = format!(
+    fn database_url(&self, failover: bool) -> String {
+        match (&self.failover, failover) {
+            (Some(standby), true) => format!(
+                "mysql://{}:{}@{}:{}/{}",
+                standby.username.as_deref().unwrap_or(&self.user),
+                standby.password.as_deref().unwrap_or(&self.password),
+                standby.host,
+                standby.port,
+                self.database
+            ),
+            _ => format!(
                 "mysql://{}:{}@{}:{}/{}",
                 self.user,
                 self.password,
                 self.host,
                 self.port,
                 self.database
-            );
-            let opts = OptsBuilder::from_opts(database_url.as_str());
-            match mysql_async::Conn::new(opts).await {
-                Ok(conn) => return conn,
+            ),
+        }
+    }
+
+    fn pool_slot(&self, failover: bool) -> &StdMutex<Option<Pool>> {
+        if failover {
+            &self.failover_pool
+        } else {
+            &self.primary_pool
+        }
+    }
+
+    fn pool(&self, failover: bool) -> Pool {
+        self.pool_slot(failover)
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                // TIMESTAMP columns are stored in UTC; keep them in UTC when they
+                // are read or written as text too, whatever the server timezone.
+                // A statement stuck behind a lock, or a read running long,
+                // fails within the budget, and keepalives find sockets that
+                // died silently.
+                let opts = OptsBuilder::from_opts(self.database_url(failover).as_str())
+                    .init(vec![
+                        "SET time_zone = '+00:00'".to_string(),
+                        format!("SET SESSION innodb_lock_wait_timeout = {}", self.query_timeout.as_secs().max(1)),
+                        format!("SET SESSION max_execution_time = {}", self.query_timeout.as_millis()),
+                    ])
+                    .tcp_keepalive(Some(10_000_u32));
+                Pool::new(opts)
+            })
+            .clone()
+    }
+
+    fn target(failover: bool) -> &'static str {
+        if failover { "failover" } else { "primary" }
+    }
+
+    async fn connect(&self, failover: bool) -> Option<Conn> {
+        let max_attempts = self.retry.max_attempts();
+        let target = Self::target(failover);
+        for attempt in 1..=max_attempts {
+            let conn = timeout(self.query_timeout, self.pool(failover).get_conn())
+                .await
+                .unwrap_or_else(|_| Err(mysql_async::Error::Other(format!("no connection within {:?}", self.query_timeout).into())));
+            match conn {
+                Ok(conn) => return Some(conn),
                 Err(e) => {
-                    error!("Error establishing connection (attempt {} of {}): {}", i, MAX_RETRIES, e);
-                    if i < MAX_RETRIES {
-                        sleep(Duration::from_secs(5)).await;
-                    } else {
-                        error!("The connection could not be established after {} attempts, terminating the program.", MAX_RETRIES);
-                        process::exit(1);
+                    metrics::DB_CONNECTION_FAILURES.with_label_values(&[target, "query"]).inc();
+                    error!("Error establishing connection to the {} (attempt {} of {}): {}", target, attempt, max_attempts, e);
+                    if attempt < max_attempts {
+                        sleep(self.retry.delay(attempt)).await;
                     }
                 }
             }
         }
-        unreachable!()
+        None
+    }
+
+    /// Runs `SELECT 1` on a pooled connection of the database in use.
+    pub async fn ping(&self, ping_timeout: Duration) -> Result<(), String> {
+        let failover = self.on_failover();
+        let ping = async {
+            let mut conn = self.pool(failover).get_conn().await?;
+            conn.query_drop("SELECT 1").await
+        };
+        let result = match timeout(ping_timeout, ping).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("no answer within {:?}", ping_timeout)),
+        };
+
+        let target = Self::target(failover);
+        metrics::DB_UP.with_label_values(&[target]).set(result.is_ok() as i64);
+        if result.is_err() {
+            metrics::DB_CONNECTION_FAILURES.with_label_values(&[target, "ping"]).inc();
+        }
+        result
+    }
+
+    /// Drops the pool of the database in use, with its connections, so the
+    /// next query connects from scratch.
+    pub fn recreate_pool(&self) {
+        let failover = self.on_failover();
+        if let Some(pool) = self.pool_slot(failover).lock().unwrap().take() {
+            metrics::DB_POOL_RECREATIONS.with_label_values(&[Self::target(failover)]).inc();
+            // Waits for the connections in use to come back, so it is left
+            // running on its own.
+            tokio::spawn(async move {
+                if let Err(e) = pool.disconnect().await {
+                    warn!("Error disconnecting the old connection pool: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn alert(&self, subject: &str, message: &str) {
+        error!("{}", message);
+        if let Some(notifications) = &self.notifications {
+            notify(notifications, subject, message).await;
+        }
+    }
+
+    /// Without a failover, the attempts go on while the primary can't be
+    /// reached, rounds of the retry policy apart with a growing delay, and
+    /// the operators are alerted once per outage; the storage layer never
+    /// fails the calling task. With one, the failover is taken over instead,
+    /// and while neither can be used the attempts go on with an alert each
+    /// round.
+    pub async fn establish_connection(&self) -> Conn {
+        let mut failed_rounds = 0;
+        loop {
+            let failover = self.on_failover.load(Ordering::SeqCst);
+            if let Some(conn) = self.connect(failover).await {
+                if failed_rounds > 0 && self.failover.is_none() {
+                    info!("The database can be reached again after {} failed rounds.", failed_rounds);
+                }
+                return conn;
+            }
+
+            if self.failover.is_none() {
+                failed_rounds += 1;
+                let message = format!(
+                    "The database can't be reached after {} attempts. Retrying.",
+                    self.retry.max_attempts() * failed_rounds
+                );
+                if failed_rounds == 1 {
+                    self.alert("Bridge database unreachable!", &message).await;
+                } else {
+                    error!("{}", message);
+                }
+                sleep(self.retry.round_delay(failed_rounds)).await;
+                continue;
+            }
+
+            if failover {
+                self.alert(
+                    "Bridge database failover unreachable!",
+                    "The failover database can't be reached either. Retrying.",
+                )
+                .await;
+                continue;
+            }
+
+            match self.take_over_failover().await {
+                Ok(conn) => return conn,
+                Err(reason) => {
+                    self.alert(
+                        "Bridge database failover refused!",
+                        &format!("The primary database is unreachable and the failover was not taken over: {reason}. Retrying the primary."),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    /// Moves reads and writes to the failover once this instance holds the
+    /// instance lease there. The lease is replicated from the primary, so
+    /// another instance that still renews it may still be writing to the
+    /// primary and taking over would split the data. A read-only engine
+    /// can't split it and follows the failover without the lease.
+    async fn take_over_failover(&self) -> Result<Conn, String> {
+        let _guard = self.failing_over.lock().await;
+        if self.on_failover.load(Ordering::SeqCst) {
+            return self.connect(true).await.ok_or_else(|| "the failover is unreachable".to_string());
+        }
+
+        let mut conn = self.connect(true).await.ok_or_else(|| "the failover is unreachable".to_string())?;
+        if !self.read_only {
+            let holder = Self::acquire_lease(&mut conn, &self.instance_id)
+                .await
+                .map_err(|e| format!("the instance lease could not be read: {e}"))?;
+            if holder != self.instance_id {
+                return Err(format!("the instance lease is held by {holder}"));
+            }
+        }
+
+        self.on_failover.store(true, Ordering::SeqCst);
+        self.alert(
+            "Bridge switched to the failover database!",
+            &format!(
+                "The primary database is unreachable, reads and writes moved to the failover at {}. Switch back by restarting once the primary is restored and in sync.",
+                self.failover.as_ref().map(|standby| standby.host.as_str()).unwrap_or_default()
+            ),
+        )
+        .await;
+        Ok(conn)
+    }
+
+    /// Takes the lease if it is free or expired, renews it if it is ours,
+    /// and returns its holder.
+    async fn acquire_lease(conn: &mut Conn, instance_id: &str) -> Result<String, mysql_async::Error> {
+        conn.exec_drop(
+            ACQUIRE_INSTANCE_LEASE,
+            params! { "holder" => instance_id, "ttl" => INSTANCE_LEASE_TTL_IN_SECONDS },
+        )
+        .await?;
+        let holder: Option<String> = conn.query_first(SELECT_INSTANCE_LEASE_HOLDER).await?;
+        Ok(holder.unwrap_or_default())
+    }
+
+    /// Renews the instance lease on the database in use. Returns whether
+    /// this instance holds it.
+    pub async fn renew_instance_lease(&self) -> bool {
+        let mut conn = self.establish_connection().await;
+        match Self::acquire_lease(&mut conn, &self.instance_id).await {
+            Ok(holder) => holder == self.instance_id,
+            Err(e) => {
+                error!("Error renewing the instance lease: {}", e);
+                false
+            }
+        }
+    }
+
+    pub fn on_failover(&self) -> bool {
+        self.on_failover.load(Ordering::SeqCst)
+    }
+}
+
+impl DatabaseEngine {
+    pub fn new(db_config: config::Database) -> Self {
+        Self {
+            host: db_config.host,
+            user: db_config.username,
+            password: db_config.password,
+            port: db_config.port,
+            database: db_config.database,
+            cipher: db_config.encryption_key_env.as_deref().map(ColumnCipher::from_env),
+            failover: db_config.failover,
+            retry: db_config.retry.unwrap_or_default(),
+            on_failover: AtomicBool::new(false),
+            failing_over: Mutex::new(()),
+            instance_id: hex::encode(rand::thread_rng().gen::<[u8; 16]>()),
+            read_only: false,
+            notifications: None,
+            primary_pool: StdMutex::new(None),
+            failover_pool: StdMutex::new(None),
+            query_timeout: config::Timeouts::default().db_query(),
+        }
+    }
+
+    /// Budget of `timeouts.db_query_in_ms`, applied to the pools built after.
+    pub fn with_query_timeout(mut self, query_timeout: Duration) -> Self {
+        self.query_timeout = query_timeout;
+        self
+    }
+
+    /// For bridges that only read: the instance lease is never written.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sends the failover and unreachable database alerts through `notifications`.
+    pub fn with_notifications(mut self, notifications: Notification) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
+    fn seal(&self, value: Option<&str>) -> Option<String> {
+        value.map(|value| match &self.cipher {
+            Some(cipher) => cipher.encrypt(value),
+            None => value.to_string(),
+        })
+    }
+
+    fn open(&self, value: Option<String>) -> Option<String> {
+        value.map(|value| match &self.cipher {
+            Some(cipher) => cipher.decrypt(value),
+            None => value,
+        })
+    }
+
+    fn deposit_params(&self, deposit: &Deposit, scanner_name: &str) -> Params {
+        params! {
+            "name" => scanner_name,
+            "tx_eth_hash" => &deposit.tx_eth_hash,
+            "from_eth_address" => &deposit.from_eth_address,
+            "amount" => (deposit.amount <= max_stored_amount()).then(|| deposit.amount.to_string()),
+            "to_glitch_address" => self.seal(Some(&deposit.glitch_address)),
+            "state" => deposit.state,
+            "error" => self.seal(deposit.note.as_deref()),
+            "eth_block_number" => deposit.block_number,
+            "decoder_version" => BUILD_VERSION,
+            "unlock_at" => deposit.unlock_at,
+            "event_version" => deposit.event_version,
+            "eth_fee" => deposit.eth_fee.filter(|fee| *fee <= max_stored_amount()).map(|fee| fee.to_string()),
+            "labels" => join_labels(&deposit.labels),
+            "eth_block_time" => deposit.block_time
+        }
+    }
+
+    pub async fn get_fee_last_time(&self, scanner_name: &str) -> Option<DateTime<Utc>> {
+        let mut conn = self.establish_connection().await;
+        let result: Option<i64> = conn
+            .exec_first(GET_LAST_FEE_TIME, params! { "name" => scanner_name })
+            .await
+            .unwrap();
+        drop(conn);
+        result.and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+    }
+
+    pub async fn txs_to_process(&self, scanner_name: &str) -> Vec<TxToProcess> {
+        let mut conn = self.establish_connection().await;
+
+        let txs_to_process = conn
+            .exec_map(
+                SELECT_TRANSACTIONS_TO_PROCESS,
+                params! { "name" => scanner_name },
+                |(id, glitch_address, amount, detected_at, version, eth_fee, labels): (u128, String, Option<String>, i64, u32, Option<String>, Option<String>)| TxToProcess {
+                    id,
+                    glitch_address: self.open(Some(glitch_address)).unwrap(),
+                    amount: amount.and_then(|amount| amount.parse().ok()),
+                    detected_at,
+                    version,
+                    eth_fee: eth_fee.and_then(|eth_fee| eth_fee.parse().ok()),
+                    labels: split_labels(labels),
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        txs_to_process
+    }
+
+    pub async fn count_txs_to_process(&self, scanner_name: &str) -> u64 {
+        let mut conn = self.establish_connection().await;
+
+        let result: Option<u64> = conn
+            .exec_first(COUNT_TXS_TO_PROCESS, params! { "name" => scanner_name })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result.unwrap_or(0)
+    }
+
+    pub async fn update_tx_with_error(&self, id: u128, error_message: String) {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "id" => id,
+            "error" => self.seal(Some(&error_message)),
+        };
+
+        let result = conn.exec_drop(SAVE_ERROR, params).await;
+
+        match result {
+            Ok(_) => debug!("Glitch tx updated!"),
+            Err(e) => error!("Error in the glitch tx updated: {}", e),
+        }
+        drop(conn);
+    }
+
+
+    /// Gives a claimed tx back to the queue after a failed submission.
+    pub async fn release_tx_claim(&self, id: u128, version: u32) -> Option<u32> {
+        self.compare_and_swap(RELEASE_TX_CLAIM, id, version, Params::Empty).await
+    }
+
+    pub async fn processing_txs(&self, scanner_name: &str) -> Vec<ProcessingTx> {
+        let mut conn = self.establish_connection().await;
+        let result = conn
+            .exec_map(
+                SELECT_PROCESSING_TXS,
+                params! { "name" => scanner_name },
+                |(id, version, glitch_address, payout_amount, submitted_at, extrinsic_hash): (u128, u32, String, Option<String>, i64, Option<String>)| ProcessingTx {
+                    id,
+                    version,
+                    glitch_address: self.open(Some(glitch_address)).unwrap(),
+                    payout_amount: payout_amount.and_then(|amount| amount.parse().ok()),
+                    submitted_at,
+                    extrinsic_hash,
+                },
+            )
+            .await
+            .unwrap();
+        drop(conn);
+        result
+    }
+
+    /// Paid txs not acknowledged on Ethereum yet, as `(id, tx_eth_hash,
+    /// tx_glitch_hash)`.
+    pub async fn txs_to_ack(&self, scanner_name: &str, start_at: u64, limit: u32) -> Vec<(u128, String, String)> {
+        let mut conn = self.establish_connection().await;
+        let result = conn
+            .exec(
+                SELECT_TXS_TO_ACK,
+                params! { "name" => scanner_name, "start_at" => start_at, "limit" => limit },
+            )
+            .await
+            .unwrap();
+        drop(conn);
+        result
+    }
+
+    pub async fn record_tx_ack(&self, id: u128, ack_tx_hash: String) {
+        let mut conn = self.establish_connection().await;
+        let params = params! { "id" => id, "ack_tx_hash" => ack_tx_hash };
+        if let Err(e) = conn.exec_drop(UPDATE_TX_ACK, params).await {
+            error!("Error recording the acknowledgment of tx {}: {}", id, e);
+        }
+        drop(conn);
+    }
+
+    /// Marks a PROCESSING tx as paid by a transfer found on chain, with a
+    /// note on how it was found in place of the amount breakdown.
+    pub async fn reconcile_tx_paid(&self, id: u128, version: u32, glitch_tx_hash: String, note: String) -> Option<u32> {
+        let params = params! { "glitch_tx_hash" => glitch_tx_hash, "amount_breakdown" => note };
+        self.compare_and_swap(UPDATE_TX_RECONCILED, id, version, params).await
+    }
+
+    /// Records the hash of the extrinsic paying a claimed tx once it is in a
+    /// block, so it can be found if the tx is never confirmed as finalized.
+    pub async fn record_tx_inclusion(&self, id: u128, version: u32, extrinsic_hash: String) -> Option<u32> {
+        let params = params! { "extrinsic_hash" => extrinsic_hash };
+        self.compare_and_swap(UPDATE_TX_INCLUDED, id, version, params).await
+    }
+
+    /// Runs a state-changing update guarded by `id` and `version`, which
+    /// must bump the version. Returns the new version if the row matched.
+    async fn compare_and_swap(&self, query: &str, id: u128, version: u32, params: Params) -> Option<u32> {
+        let mut conn = self.establish_connection().await;
+
+        let mut named = match params {
+            Params::Named(named) => named,
+            _ => Default::default(),
+        };
+        named.insert(b"id".to_vec(), id.into());
+        named.insert(b"version".to_vec(), version.into());
+
+        let result = conn.exec_drop(query, Params::Named(named)).await;
+
+        let swapped = match result {
+            Ok(_) if conn.affected_rows() == 1 => Some(version + 1),
+            Ok(_) => {
+                warn!("Tx {} was changed concurrently (expected version {}).", id, version);
+                None
+            }
+            Err(e) => {
+                error!("Error updating tx {}: {}", id, e);
+                None
+            }
+        };
+        drop(conn);
+        swapped
+    }
+
+    pub async fn txs_breaching_sla(&self, sla_in_secs: u64) -> Vec<u128> {
+        let mut conn = self.establish_connection().await;
+
+        let result: Vec<u128> = conn
+            .exec(SELECT_TXS_BREACHING_SLA, params! { "sla_in_secs" => sla_in_secs })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    pub async fn mark_sla_alerted(&self, ids: &[u128]) {
+        let mut conn = self.establish_connection().await;
+
+        let result = UPDATE_TX_SLA_ALERTED
+            .with(ids.iter().map(|id| params! { "id" => *id }))
+            .batch(&mut conn)
+            .await;
+
+        match result {
+            Ok(_) => debug!("Txs marked as SLA alerted!"),
+            Err(e) => error!("Error marking txs as SLA alerted: {}", e),
+        }
+        drop(conn);
+    }
+
+    /// What the deposits of the pipelines put on Glitch, paid out or owed:
+    /// the txs queued, in flight or paid, less the business fees kept and not
+    /// paid out yet.
+    pub async fn get_total_bridged(&self, scanner_names: &[&str]) -> u128 {
+        let mut conn = self.establish_connection().await;
+
+        let result: String = conn
+            .exec_first(SELECT_TOTAL_BRIDGED, params! { "names" => scanner_names.join(",") })
+            .await
+            .unwrap()
+            .unwrap();
+
+        drop(conn);
+        result.parse().unwrap()
+    }
+
+    /// Value of the txs of a pipeline paid out and not final yet.
+    pub async fn in_flight_value(&self, scanner_name: &str) -> u128 {
+        let mut conn = self.establish_connection().await;
+
+        let result: String = conn
+            .exec_first(SELECT_IN_FLIGHT_VALUE, params! { "name" => scanner_name })
+            .await
+            .unwrap()
+            .unwrap();
+
+        drop(conn);
+        result.parse().unwrap()
+    }
+
+    /// Value deposited through a pipeline and value released back to the
+    /// network by its withdrawals, `withdrawals_name` being the scanner of
+    /// those.
+    pub async fn ledger_totals(&self, scanner_name: &str, withdrawals_name: &str) -> (u128, u128) {
+        let mut conn = self.establish_connection().await;
+
+        let deposited: String = conn
+            .exec_first(SELECT_DEPOSITED_VALUE, params! { "name" => scanner_name })
+            .await
+            .unwrap()
+            .unwrap();
+        let released: String = conn
+            .exec_first(SELECT_RELEASED_VALUE, params! { "name" => withdrawals_name })
+            .await
+            .unwrap()
+            .unwrap();
+
+        drop(conn);
+        (deposited.parse().unwrap(), released.parse().unwrap())
+    }
+
+    /// Verdicts recorded on a deposit by earlier screenings, None if they
+    /// could not be read.
+    pub async fn deposit_screening_verdicts(&self, scanner_name: &str, tx_eth_hash: &str) -> Option<Vec<String>> {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "name" => scanner_name,
+            "tx_eth_hash" => tx_eth_hash,
+        };
+        match conn.exec(SELECT_DEPOSIT_SCREENING_VERDICTS, params).await {
+            Ok(verdicts) => Some(verdicts),
+            Err(e) => {
+                error!("Error reading the screenings of deposit {tx_eth_hash}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Records the verdict of a compliance screener on a deposit. The
+    /// answer of the screener is sealed like the other free text.
+    pub async fn insert_deposit_screening(
+        &self,
+        scanner_name: &str,
+        tx_eth_hash: &str,
+        screener: &str,
+        verdict: &str,
+        risk: Option<String>,
+        detail: String,
+    ) {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "name" => scanner_name,
+            "tx_eth_hash" => tx_eth_hash,
+            "screener" => screener,
+            "verdict" => verdict,
+            "risk" => risk,
+            "detail" => self.seal(Some(&detail)),
+        };
+        if let Err(e) = conn.exec_drop(INSERT_DEPOSIT_SCREENING, params).await {
+            error!("Error recording the screening of deposit {tx_eth_hash}: {e}");
+        }
+    }
+
+    /// Records the gas used, effective gas price and value of the source
+    /// transaction of a deposit.
+    pub async fn record_tx_source(
+        &self,
+        tx_eth_hash: &str,
+        gas_used: Option<u64>,
+        gas_price: Option<String>,
+        value: Option<String>,
+    ) {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "tx_eth_hash" => tx_eth_hash,
+            "gas_used" => gas_used,
+            "gas_price" => gas_price,
+            "value" => value,
+        };
+        if let Err(e) = conn.exec_drop(UPDATE_TX_SOURCE, params).await {
+            error!("Error recording the source transaction of {tx_eth_hash}: {e}");
+        }
+    }
+
+    pub async fn insert_supply_check(
+        &self,
+        locked: u128,
+        minted: u128,
+        delta: i128,
+        within_tolerance: bool,
+    ) {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "locked" => locked.to_string(),
+            "minted" => minted.to_string(),
+            "delta" => delta.to_string(),
+            "within_tolerance" => within_tolerance,
+        };
+
+        let result = conn.exec_drop(INSERT_SUPPLY_CHECK, params).await;
+
+        match result {
+            Ok(_) => debug!("Supply check saved!"),
+            Err(e) => error!("Error saving the supply check: {}", e),
+        }
+        drop(conn);
+    }
+
+    pub async fn suspicious_txs(&self) -> Vec<SuspiciousTx> {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn
+            .query_map(
+                SELECT_SUSPICIOUS_TXS,
+                |(id, tx_eth_hash, from_eth_address, to_glitch_address, amount, reason)| {
+                    SuspiciousTx {
+                        id,
+                        tx_eth_hash,
+                        from_eth_address,
+                        to_glitch_address: self.open(to_glitch_address),
+                        amount,
+                        reason: self.open(reason),
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    /// Status of the latest tx of the Ethereum transaction, for the public API.
+    pub async fn deposit_status(&self, tx_eth_hash: &str) -> Option<DepositStatus> {
+        let mut conn = self.establish_connection().await;
+
+        #[allow(clippy::type_complexity)]
+        let result: Option<(
+            String,
+            Option<String>,
+            i64,
+            Option<i64>,
+            Option<i64>,
+            Option<u32>,
+            Option<u32>,
+            Option<String>,
+        )> = conn
+            .exec_first(SELECT_DEPOSIT_STATUS, params! { "tx_eth_hash" => tx_eth_hash })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result.map(
+            |(state, tx_glitch_hash, detected_at, paid_at, unlock_at, glitch_block_number, extrinsic_index, transfer_event)| {
+                DepositStatus {
+                    state,
+                    tx_glitch_hash,
+                    detected_at,
+                    paid_at,
+                    unlock_at,
+                    glitch_block_number,
+                    extrinsic_index,
+                    transfer_event,
+                }
+            },
+        )
+    }
+
+    /// Records the quarantine window and holds the deposits of the range that
+    /// were already recorded but not paid yet.
+    pub async fn insert_reorg_quarantine(
+        &self,
+        scanner_name: &str,
+        from_block: u64,
+        to_block: u64,
+        quarantine_in_secs: u64,
+    ) -> u64 {
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await.unwrap();
+
+        let range = params! {
+            "name" => scanner_name,
+            "from_block" => from_block,
+            "to_block" => to_block,
+            "quarantine_in_secs" => quarantine_in_secs
+        };
+
+        tx.exec_drop(INSERT_REORG_QUARANTINE, range.clone()).await.unwrap();
+        tx.exec_drop(QUARANTINE_TXS_IN_RANGE, range).await.unwrap();
+        let held = tx.affected_rows();
+
+        tx.commit().await.unwrap();
+        held
+    }
+
+    pub async fn active_quarantines(&self, scanner_name: &str) -> Vec<(u64, u64)> {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn
+            .exec(SELECT_ACTIVE_QUARANTINES, params! { "name" => scanner_name })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    /// Ends a quarantine window before its period is over.
+    pub async fn end_quarantine(&self, id: u128) -> bool {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn.exec_drop(END_QUARANTINE, params! { "id" => id }).await;
+
+        let ended = match result {
+            Ok(_) => conn.affected_rows() > 0,
+            Err(e) => {
+                error!("Error ending the quarantine: {}", e);
+                false
+            }
+        };
+
+        drop(conn);
+        ended
+    }
+
+    /// Moves back to TO_PROCESS the deposits no active quarantine covers.
+    pub async fn release_expired_quarantines(&self) -> u64 {
+        let mut conn = self.establish_connection().await;
+
+        let released = match conn.query_drop(RELEASE_EXPIRED_QUARANTINES).await {
+            Ok(_) => conn.affected_rows(),
+            Err(e) => {
+                error!("Error releasing quarantined txs: {}", e);
+                0
+            }
+        };
+
+        drop(conn);
+        released
+    }
+
+    pub async fn recent_top_up_request(&self, hot_wallet: &str, cooldown_in_secs: u64) -> bool {
+        let mut conn = self.establish_connection().await;
+
+        let count: Option<u64> = conn
+            .exec_first(
+                SELECT_RECENT_TOP_UP_REQUEST,
+                params! {
+                    "hot_wallet" => hot_wallet,
+                    "cooldown_in_secs" => cooldown_in_secs
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        count.unwrap_or(0) > 0
+    }
+
+    pub async fn insert_top_up_request(
+        &self,
+        hot_wallet: &str,
+        amount: u128,
+        method: &str,
+        reference: Option<&str>,
+        error: Option<&str>,
+    ) {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "hot_wallet" => hot_wallet,
+            "amount" => amount.to_string(),
+            "method" => method,
+            "reference" => reference,
+            "error" => error
+        };
+
+        match conn.exec_drop(INSERT_TOP_UP_REQUEST, params).await {
+            Ok(_) => debug!("Top-up request recorded!"),
+            Err(e) => error!("Error recording the top-up request: {}", e),
+        }
+        drop(conn);
+    }
+
+    /// Keeps a record of a scanner failure for later diagnosis. Failing to
+    /// record it is only logged, the scanner must go on.
+    pub async fn insert_scanner_error(
+        &self,
+        scanner_name: &str,
+        kind: ScannerErrorKind,
+        block_number: Option<u64>,
+        message: &str,
+    ) {
+        let mut conn = self.establish_connection().await;
+        let params = params! {
+            "name" => scanner_name,
+            "kind" => kind.as_str(),
+            "block_number" => block_number,
+            "message" => message
+        };
+
+        if let Err(e) = conn.exec_drop(INSERT_SCANNER_ERROR, params).await {
+            error!("Error recording the scanner error: {}", e);
+        }
+        drop(conn);
+    }
+
+    pub async fn scanner_errors(&self, scanner_name: Option<String>, limit: u32) -> Vec<ScannerError> {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn
+            .exec_map(
+                SELECT_SCANNER_ERRORS,
+                params! { "name" => scanner_name, "limit" => limit },
+                |(id, scanner_name, kind, block_number, message, time)| ScannerError {
+                    id,
+                    scanner_name,
+                    kind,
+                    block_number,
+                    message,
+                    time,
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    pub async fn fee_invoices(&self, scanner_name: Option<String>) -> Vec<FeeInvoice> {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn
+            .exec_map(
+                SELECT_FEE_INVOICES,
+                params! { "name" => scanner_name },
+                |(id, scanner_name, period_start, period_end, tx_count, total_volume, fee_amount, glitch_tx_hash)| {
+                    FeeInvoice {
+                        id,
+                        scanner_name,
+                        period_start,
+                        period_end,
+                        tx_count,
+                        total_volume,
+                        fee_amount,
+                        glitch_tx_hash,
+                        token: None,
+                        decimals: None,
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    /// Fee policies of the pipeline, oldest effective first.
+    pub async fn fee_policies(&self, scanner_name: &str) -> Vec<FeePolicy> {
+        let mut conn = self.establish_connection().await;
+
+        let policies = conn
+            .exec_map(
+                SELECT_FEE_POLICIES,
+                params! { "name" => scanner_name },
+                |(business_fee_percentage, fee_destination, effective_from): (String, String, i64)| FeePolicy {
+                    business_fee: business_fee_percentage.parse().unwrap_or_else(|_| {
+                        panic!("Invalid business fee {business_fee_percentage} in the fee policies of {scanner_name}!")
+                    }),
+                    fee_destination,
+                    effective_from,
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        policies
+    }
+
+    /// Records a fee policy effective from `effective_from`, a unix
+    /// timestamp, or from now.
+    pub async fn insert_fee_policy(
+        &self,
+        scanner_name: &str,
+        business_fee: f64,
+        fee_destination: String,
+        effective_from: Option<i64>,
+    ) {
+        let mut conn = self.establish_connection().await;
+
+        conn.exec_drop(
+            INSERT_FEE_POLICY,
+            params! {
+                "name" => scanner_name,
+                "business_fee_percentage" => business_fee.to_string(),
+                "fee_destination" => fee_destination,
+                "effective_from" => effective_from,
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    /// `(scanner_name, from_eth_address, to_glitch_address)` of the deposits
+    /// detected in the last `window_in_minutes`, test and cancelled ones
+    /// left out. Recipients are grouped here
+    /// and not in SQL since they may be encrypted.
+    pub async fn recent_deposits(&self, window_in_minutes: u64) -> Vec<(String, String, String)> {
+        let mut conn = self.establish_connection().await;
+
+        let deposits: Vec<(String, String, Option<String>)> = conn
+            .exec(SELECT_RECENT_DEPOSITS, params! { "window_in_minutes" => window_in_minutes })
+            .await
+            .unwrap();
+
+        drop(conn);
+        deposits
+            .into_iter()
+            .filter_map(|(scanner_name, from_eth_address, to_glitch_address)| {
+                Some((scanner_name, from_eth_address, self.open(to_glitch_address)?))
+            })
+            .collect()
+    }
+
+    /// `(tx_eth_hash, to_glitch_address, amount)` of the deposits of the
+    /// pipeline whose block is on the UTC day starting at the unix time
+    /// `day` (the detection time for rows without it) and that are, or will
+    /// be, paid out. Held and cancelled deposits are left out.
+    pub async fn recipient_deposits_on(&self, scanner_name: &str, day: i64) -> Vec<(String, String, u128)> {
+        let mut conn = self.establish_connection().await;
+
+        let deposits: Vec<(String, Option<String>, String)> = conn
+            .exec(SELECT_RECIPIENT_DEPOSITS_ON, params! { "name" => scanner_name, "day" => day })
+            .await
+            .unwrap();
+
+        drop(conn);
+        deposits
+            .into_iter()
+            .filter_map(|(tx_eth_hash, to_glitch_address, amount)| {
+                Some((tx_eth_hash, self.open(to_glitch_address)?, amount.parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// Rows stored for a deposit transaction, oldest first.
+    pub async fn stored_deposits(&self, tx_eth_hash: &str) -> Vec<StoredDeposit> {
+        let mut conn = self.establish_connection().await;
+
+        let rows: Vec<StoredDepositRow> = conn
+            .exec(SELECT_STORED_DEPOSITS, params! { "tx_eth_hash" => tx_eth_hash })
+            .await
+            .unwrap();
+
+        drop(conn);
+        rows.into_iter()
+            .map(
+                |(id, scanner_name, from_eth_address, glitch_address, amount, state, block_number, unlock_at, event_version, eth_fee, decoder_version)| {
+                    StoredDeposit {
+                        id,
+                        scanner_name,
+                        from_eth_address,
+                        glitch_address: self.open(glitch_address),
+                        amount,
+                        state,
+                        block_number,
+                        unlock_at,
+                        event_version,
+                        eth_fee,
+                        decoder_version,
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// Total and processed txs sent from an address.
+    pub async fn count_txs_from(&self, from_eth_address: &str) -> (u64, u64) {
+        let mut conn = self.establish_connection().await;
+
+        let result: Option<(u64, u64)> = conn
+            .exec_first(COUNT_TXS_FROM, params! { "from_eth_address" => from_eth_address })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result.unwrap_or((0, 0))
+    }
+
+    /// Cancels a tx whose payout was not submitted. On failure returns the
+    /// state it is in, or None when there is no such tx.
+    pub async fn cancel_tx(&self, id: u128, note: &str) -> Result<(), Option<String>> {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn
+            .exec_drop(CANCEL_TX, params! { "id" => id, "note" => self.seal(Some(note)) })
+            .await;
+        match result {
+            Ok(_) if conn.affected_rows() > 0 => return Ok(()),
+            Ok(_) => {}
+            Err(e) => error!("Error cancelling tx {}: {}", id, e),
+        }
+
+        let state: Option<String> = conn
+            .exec_first(SELECT_TX_STATE, params! { "id" => id })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Error reading the state of tx {}: {}", id, e);
+                None
+            });
+        drop(conn);
+        Err(state)
+    }
+
+    /// Returns whether a SUSPICIOUS tx with that id was moved back to TO_PROCESS.
+    pub async fn release_suspicious_tx(&self, id: u128) -> bool {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn.exec_drop(RELEASE_SUSPICIOUS_TX, params! { "id" => id }).await;
+
+        let released = match result {
+            Ok(_) => conn.affected_rows() > 0,
+            Err(e) => {
+                error!("Error releasing the suspicious tx: {}", e);
+                false
+            }
+        };
+
+        drop(conn);
+        released
+    }
+
+    /// Ids of the txs in one of `states` matching the filter.
+    pub async fn txs_by_filter(&self, states: &[&str], filter: &TxFilter, pipelines: Option<Vec<String>>) -> Vec<u128> {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "states" => states.join(","),
+            "state" => &filter.state,
+            "name" => &filter.scanner,
+            "pipelines" => pipelines.map(|pipelines| pipelines.join(",")),
+            "from" => &filter.from,
+            "to" => &filter.to,
+            "error_pattern" => &filter.error_pattern,
+        };
+        let result = conn.exec(SELECT_TXS_BY_FILTER, params).await.unwrap();
+
+        drop(conn);
+        result
+    }
+
+    /// Moves the txs still in one of `states` to `to_state` in a single
+    /// transaction and returns the ids actually changed. `note` replaces the
+    /// error of the tx; txs going back to the queue have it cleared.
+    pub async fn bulk_set_state(
+        &self,
+        ids: &[u128],
+        states: &[&str],
+        to_state: &str,
+        note: Option<String>,
+    ) -> Vec<u128> {
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await.unwrap();
+
+        let states = states.join(",");
+        let clear_error = to_state == "TO_PROCESS" && note.is_none();
+        let mut changed = Vec::new();
+
+        for id in ids {
+            let params = params! {
+                "id" => *id,
+                "states" => &states,
+                "to_state" => to_state,
+                "clear_error" => clear_error,
+                "note" => self.seal(note.as_deref()),
+            };
+
+            match tx.exec_drop(BULK_SET_TX_STATE, params).await {
+                Ok(_) if tx.affected_rows() > 0 => changed.push(*id),
+                Ok(_) => debug!("Tx {} changed state meanwhile, skipped", id),
+                Err(e) => {
+                    error!("Error changing the state of tx {}, rolling back: {}", id, e);
+                    tx.rollback().await.unwrap();
+                    return Vec::new();
+                }
+            }
+        }
+
+        tx.commit().await.unwrap();
+        changed
+    }
+
+    /// `(scanner, accumulated fee counter, business fees of the txs not yet
+    /// settled plus the fee adjustments not yet settled)` of every pipeline.
+    /// Both amounts should be equal.
+    pub async fn fee_drift(&self) -> Vec<(String, String, String)> {
+        let mut conn = self.establish_connection().await;
+        let result = conn.query(SELECT_FEE_DRIFT).await.unwrap();
+        drop(conn);
+        result
+    }
+
+    /// `(id, state)` of the txs waiting to be paid, or in flight, for longer
+    /// than `stuck_after_in_secs`.
+    pub async fn stuck_txs(&self, stuck_after_in_secs: u64) -> Vec<(u128, String)> {
+        let mut conn = self.establish_connection().await;
+
+        let result = conn
+            .exec(SELECT_STUCK_TXS, params! { "stuck_after_in_secs" => stuck_after_in_secs })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    /// Network fee statistics of the payouts finalized in the last `hours`,
+    /// for one pipeline or all of them.
+    pub async fn network_fee_stats(&self, scanner_name: Option<&str>, hours: u32) -> Vec<NetworkFeeStats> {
+        let mut conn = self.establish_connection().await;
+        let result = conn
+            .exec_map(
+                SELECT_NETWORK_FEE_STATS,
+                params! { "name" => scanner_name, "hours" => hours },
+                |(scanner_name, payouts, average, min, max)| NetworkFeeStats {
+                    scanner_name,
+                    payouts,
+                    average,
+                    min,
+                    max,
+                },
+            )
+            .await
+            .unwrap();
+        drop(conn);
+        result
+    }
+
+    /// Average seconds from detection to finalized payout of the deposits of
+    /// the pipeline paid in the last `hours`, None without any.
+    pub async fn average_payout_time(&self, scanner_name: &str, hours: u32) -> Option<i64> {
+        let mut conn = self.establish_connection().await;
+        let result: Option<Option<i64>> = conn
+            .exec_first(SELECT_AVERAGE_PAYOUT_TIME, params! { "name" => scanner_name, "hours" => hours })
+            .await
+            .unwrap();
+        drop(conn);
+        result.flatten()
+    }
+
+    /// `(scanner, state, seconds)` age of the oldest TO_PROCESS tx that can
+    /// be paid (unlocked) and of the oldest PROCESSING tx of each pipeline.
+    pub async fn oldest_pending_ages(&self) -> Vec<(String, String, i64)> {
+        let mut conn = self.establish_connection().await;
+        let result = conn.query(SELECT_OLDEST_PENDING_AGES).await.unwrap();
+        drop(conn);
+        result
+    }
+
+    pub async fn insert_audit_report(
+        &self,
+        supply_ok: Option<bool>,
+        fee_drift_ok: bool,
+        stuck_txs: usize,
+        passed: bool,
+        report: &str,
+    ) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "supply_ok" => supply_ok,
+            "fee_drift_ok" => fee_drift_ok,
+            "stuck_txs" => stuck_txs,
+            "passed" => passed,
+            "report" => report,
+        };
+
+        if let Err(e) = conn.exec_drop(INSERT_AUDIT_REPORT, params).await {
+            error!("Error recording the audit report: {}", e);
+        }
+        drop(conn);
+    }
+
+    pub async fn last_audit_time(&self) -> Option<DateTime<Utc>> {
+        let mut conn = self.establish_connection().await;
+        let result: Option<i64> = conn.query_first(SELECT_LAST_AUDIT_TIME).await.unwrap();
+        drop(conn);
+        result.and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+    }
+
+    pub async fn insert_decoder_divergence(
+        &self,
+        scanner_name: &str,
+        tx_eth_hash: Option<String>,
+        log_index: Option<u64>,
+        block_number: Option<u64>,
+        difference: &str,
+    ) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "name" => scanner_name,
+            "tx_eth_hash" => tx_eth_hash,
+            "log_index" => log_index,
+            "block_number" => block_number,
+            "difference" => difference,
+        };
+
+        if let Err(e) = conn.exec_drop(INSERT_DECODER_DIVERGENCE, params).await {
+            error!("Error recording the decoder divergence: {}", e);
+        }
+        drop(conn);
+    }
+
+    pub async fn insert_skipped_log(&self, skipped: &SkippedLog) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "name" => &skipped.scanner_name,
+            "reason" => skipped.reason.as_str(),
+            "tx_eth_hash" => &skipped.tx_eth_hash,
+            "log_index" => skipped.log_index,
+            "block_number" => skipped.block_number,
+            "detail" => &skipped.detail,
+        };
+
+        if let Err(e) = conn.exec_drop(INSERT_SKIPPED_LOG, params).await {
+            error!("Error recording the skipped log: {}", e);
+        }
+        drop(conn);
+    }
+
+    pub async fn insert_crash_report(&self, report: &CrashReport) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "message" => &report.message,
+            "location" => &report.location,
+            "thread" => &report.thread,
+            "tx_id" => report.tx_id,
+            "backtrace" => &report.backtrace,
+            "build_version" => BUILD_VERSION,
+        };
+
+        if let Err(e) = conn.exec_drop(INSERT_CRASH_REPORT, params).await {
+            error!("Error recording the crash report: {}", e);
+        }
+        drop(conn);
+    }
+
+    /// `(table, column, column type)` of every column of the live database.
+    pub async fn schema_columns(&self) -> Vec<(String, String, String)> {
+        let mut conn = self.establish_connection().await;
+        let columns = conn.query(SELECT_SCHEMA_COLUMNS).await.unwrap();
+        drop(conn);
+        columns
+    }
+
+    /// `(table, index)` of every index of the live database.
+    pub async fn schema_indexes(&self) -> Vec<(String, String)> {
+        let mut conn = self.establish_connection().await;
+        let indexes = conn.query(SELECT_SCHEMA_INDEXES).await.unwrap();
+        drop(conn);
+        indexes
+    }
+
+    /// Whether a bridge seems to be using the database. A held instance
+    /// lease tells, as every bridge that writes keeps one renewed. Other
+    /// open connections are checked too, but without the PROCESS privilege
+    /// the processlist only shows this one's.
+    pub async fn database_in_use(&self) -> bool {
+        let mut conn = self.establish_connection().await;
+        let leases: Option<u64> = conn.query_first(COUNT_HELD_LEASES).await.unwrap_or(None);
+        if leases.unwrap_or(0) > 0 {
+            return true;
+        }
+        let connections: Option<u64> = conn.query_first(COUNT_OTHER_CONNECTIONS).await.unwrap_or(None);
+        connections.unwrap_or(0) > 0
+    }
+
+    /// Estimated rows of a table, None if it doesn't exist.
+    pub async fn table_rows(&self, table_name: &str) -> Option<u64> {
+        let mut conn = self.establish_connection().await;
+        let rows: Option<Option<u64>> = conn
+            .exec_first(SELECT_TABLE_ROWS, params! { "table_name" => table_name })
+            .await
+            .unwrap();
+        drop(conn);
+        rows.map(|rows| rows.unwrap_or(0))
+    }
+
+    /// Runs a schema change, waiting at most `lock_wait_in_secs` for the
+    /// metadata lock so queries don't pile up behind it.
+    pub async fn run_ddl(&self, statement: &str, lock_wait_in_secs: u64) -> Result<(), mysql_async::Error> {
+        let mut conn = self.establish_connection().await;
+        conn.query_drop(format!("SET SESSION lock_wait_timeout = {}", lock_wait_in_secs.max(1)))
+            .await?;
+        conn.query_drop(statement).await
+    }
+
+    pub async fn export_state(&self) -> (Vec<NetworkStateRow>, Vec<PendingTxRow>) {
+        let mut conn = self.establish_connection().await;
+
+        let network_states = conn
+            .query_map(
+                SELECT_ALL_NETWORK_STATES,
+                |(name, network, monitor_address, accumulated_fees, last_block)| NetworkStateRow {
+                    name,
+                    network,
+                    monitor_address,
+                    accumulated_fees,
+                    last_block,
+                },
+            )
+            .await
+            .unwrap();
+
+        let pending_txs = conn
+            .query_map(
+                SELECT_PENDING_TXS,
+                |(tx_eth_hash, from_eth_address, to_glitch_address, amount, state, error, scanner_name, unlock_at)| {
+                    PendingTxRow {
+                        tx_eth_hash,
+                        from_eth_address,
+                        to_glitch_address: self.open(to_glitch_address),
+                        amount,
+                        state,
+                        error: self.open(error),
+                        scanner_name,
+                        unlock_at,
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
+        drop(conn);
+        (network_states, pending_txs)
+    }
+
+    /// Replaces the scanner states and adds the pending txs not already
+    /// present, all in a single transaction.
+    pub async fn import_state(
+        &self,
+        network_states: &[NetworkStateRow],
+        pending_txs: &[PendingTxRow],
+    ) -> Result<(), mysql_async::Error> {
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await?;
+
+        for state in network_states {
+            tx.exec_drop(DELETE_NETWORK_STATE, params! { "name" => &state.name })
+                .await?;
+            tx.exec_drop(
+                RESTORE_NETWORK_STATE,
+                params! {
+                    "name" => &state.name,
+                    "network" => &state.network,
+                    "monitor_address" => &state.monitor_address,
+                    "accumulated_fees" => &state.accumulated_fees,
+                    "last_block" => state.last_block,
+                },
+            )
+            .await?;
+        }
+
+        tx.exec_batch(
+            RESTORE_PENDING_TX,
+            pending_txs.iter().map(|pending| {
+                params! {
+                    "tx_eth_hash" => &pending.tx_eth_hash,
+                    "from_eth_address" => &pending.from_eth_address,
+                    "to_glitch_address" => self.seal(pending.to_glitch_address.as_deref()),
+                    "amount" => &pending.amount,
+                    "state" => &pending.state,
+                    "error" => self.seal(pending.error.as_deref()),
+                    "scanner_name" => &pending.scanner_name,
+                    "unlock_at" => pending.unlock_at,
+                }
+            }),
+        )
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// Adds to the fee counter in place, so concurrent adjustments and
+    /// settlements are never overwritten.
+    pub async fn increment_fee_counter(&self, scanner_name: String, amount: u128) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "name" => scanner_name,
+            "amount" => amount.to_string()
+        };
+
+        let result = conn.exec_drop(ADD_TO_FEE, params).await;
+
+        match result {
+            Ok(_) => debug!("Fee increased successful!"),
+            Err(e) => error!("Error in the fee increased: {}", e),
+        }
+    }
+
+    /// Records the payout of a claimed tx. Returns false if the tx was
+    /// changed by someone else since it was claimed.
+    pub async fn update_tx(&self, id: u128, version: u32, payout: SettledPayout<'_>) -> bool {
+        let SettledPayout {
+            glitch_hash,
+            business_fee_amount,
+            business_fee_percentage,
+            network_fee,
+            network_fee_estimated,
+            amount_breakdown,
+            receipt,
+        } = payout;
+        let params = params! {
+            "glitch_tx_hash" => glitch_hash,
+            "business_fee_amount" => business_fee_amount,
+            "business_fee_percentage" => business_fee_percentage,
+            "network_fee" => network_fee.to_string(),
+            "network_fee_estimated" => network_fee_estimated.to_string(),
+            "payout_version" => BUILD_VERSION,
+            "amount_breakdown" => amount_breakdown,
+            "glitch_block_number" => receipt.and_then(|receipt| receipt.block_number),
+            "extrinsic_index" => receipt.map(|receipt| receipt.extrinsic_index),
+            "transfer_event" => receipt.map(|receipt| receipt.event.to_string())
+        };
+
+        let updated = self.compare_and_swap(UPDATE_TX_GLITCH, id, version, params).await;
+        if updated.is_some() {
+            debug!("Glitch tx updated!");
+        }
+        updated.is_some()
+    }
+
+    pub async fn get_last_block(&self, scanner_name: &str) -> u32 {
+        let mut conn = self.establish_connection().await;
+
+        let result: u32 = conn
+            .exec_first(
+                SELECT_LAST_BLOCK,
+                params! {
+                    "name" => scanner_name
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        drop(conn);
+        result
+    }
+
+    pub async fn set_last_block(&self, scanner_name: &str, block: u32) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! { "block" => block, "name" => scanner_name };
+        if let Err(e) = conn.exec_drop(UPDATE_LAST_BLOCK, params).await {
+            error!("Error in the block update of {}: {}", scanner_name, e);
+        }
+        drop(conn);
+    }
+
+    /// Whether a transfer of a signer is one the bridge recorded: a payout,
+    /// by its extrinsic hash, or a fee settlement, by its block and amount.
+    /// None if it could not be looked up.
+    pub async fn is_bridge_transfer(&self, extrinsic_hash: Option<&str>, block_hash: &str, amount: u128) -> Option<bool> {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "extrinsic_hash" => extrinsic_hash,
+            "block_hash" => block_hash,
+            "amount" => amount.to_string(),
+        };
+        let known: Result<Option<bool>, mysql_async::Error> = conn.exec_first(SELECT_BRIDGE_TRANSFER, params).await;
+
+        drop(conn);
+        match known {
+            Ok(known) => Some(known.unwrap_or(false)),
+            Err(e) => {
+                error!("Error matching a signer transfer: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Moves the scanner to `block` and stores its deposits, all or nothing.
+    /// Returns false if nothing was stored.
+    pub async fn update_block_and_insert_txs(
+        &self,
+        scanner_name: String,
+        block: u32,
+        deposits: Vec<Deposit>,
+    ) -> bool {
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await.unwrap();
+
+        let params = params! {
+            "block" => block,
+            "name" => &scanner_name
+        };
+
+        let update_block_result = tx.exec_drop(UPDATE_LAST_BLOCK, params).await;
+        match update_block_result {
+            Ok(_) => debug!("Block update successful!"),
+            Err(e) => {
+                error!("Error in the block update: {}", e);
+                tx.rollback().await.unwrap();
+                return false;
+            }
+        }
+
+        if !deposits.is_empty() {
+            let insert_logs_result = tx.exec_batch(
+                INSERT_TXS,
+                deposits.iter().map(|deposit| self.deposit_params(deposit, &scanner_name)),
+            )
+            .await;
+
+            match insert_logs_result {
+                Ok(_) => debug!("Inserts successful!"),
+                Err(e) => {
+                    error!("Inserts with error: {}", e);
+                    tx.rollback().await.unwrap();
+                    return false;
+                }
+            }
+        }
+
+        if tx.affected_rows() > 0 {
+            tx.commit().await.is_ok()
+        } else {
+            tx.rollback().await.unwrap();
+            true
+        }
     }
-}
 
-impl DatabaseEngine {
-    pub fn new(db_config: config::Database) -> Self {
-        Self {
-            host: db_config.host,
-            user: db_config.username,
-            password: db_config.password,
-            port: db_config.port,
-            database: db_config.database,
+    /// Like `update_block_and_insert_txs`, for the withdrawal queue. Events
+    /// already queued are skipped, so a block can be scanned again. Returns
+    /// whether the block was recorded.
+    pub async fn update_block_and_insert_withdrawals(
+        &self,
+        scanner_name: String,
+        block: u32,
+        withdrawals: Vec<Withdrawal>,
+    ) -> bool {
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await.unwrap();
+
+        let params = params! {
+            "block" => block,
+            "name" => &scanner_name
+        };
+        if let Err(e) = tx.exec_drop(UPDATE_LAST_BLOCK, params).await {
+            error!("Error in the block update: {}", e);
+            tx.rollback().await.unwrap();
+            return false;
+        }
+
+        if !withdrawals.is_empty() {
+            let result = tx
+                .exec_batch(
+                    INSERT_WITHDRAWALS,
+                    withdrawals.iter().map(|withdrawal| {
+                        params! {
+                            "event_id" => &withdrawal.event_id,
+                            "name" => &scanner_name,
+                            "from_glitch_address" => &withdrawal.from_glitch_address,
+                            "to_eth_address" => &withdrawal.to_eth_address,
+                            "amount" => withdrawal.amount.to_string(),
+                            "glitch_block_number" => withdrawal.block_number,
+                        }
+                    }),
+                )
+                .await;
+            if let Err(e) = result {
+                error!("Error queueing the withdrawals of block {}: {}", block, e);
+                tx.rollback().await.unwrap();
+                return false;
+            }
         }
+
+        tx.commit().await.unwrap();
+        true
     }
 
-    pub async fn get_fee_last_time(&self) -> Option<String> {
+    /// Withdrawals waiting to be released, oldest first.
+    pub async fn withdrawals_to_process(&self, scanner_name: &str, limit: u32) -> Vec<WithdrawalToProcess> {
         let mut conn = self.establish_connection().await;
-        let result: Option<String> = conn.query_first(GET_LAST_FEE_TIME).await.unwrap();
+
+        let withdrawals = conn
+            .exec_map(
+                SELECT_WITHDRAWALS_TO_PROCESS,
+                params! { "name" => scanner_name, "limit" => limit },
+                |(id, version, event_id, to_eth_address, amount): (u128, u32, String, String, String)| {
+                    WithdrawalToProcess {
+                        id,
+                        version,
+                        event_id,
+                        to_eth_address,
+                        amount: amount.parse().ok(),
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
         drop(conn);
-        result
+        withdrawals
+    }
+
+    /// Moves a withdrawal to PROCESSING. Returns the new version if it was
+    /// still waiting.
+    pub async fn claim_withdrawal(&self, id: u128, version: u32) -> Option<u32> {
+        self.compare_and_swap(CLAIM_WITHDRAWAL, id, version, Params::Empty).await
+    }
+
+    /// Records the hash of the release call of a claimed withdrawal before
+    /// it is sent; it stays PROCESSING until the call is mined.
+    pub async fn record_withdrawal_sent(&self, id: u128, version: u32, eth_tx_hash: String) -> Option<u32> {
+        self.compare_and_swap(RECORD_WITHDRAWAL_SENT, id, version, params! { "eth_tx_hash" => eth_tx_hash })
+            .await
     }
 
-    pub async fn txs_to_process(&self) -> Vec<TxToProcess> {
+    /// Claimed withdrawals of the pipeline, left by a previous tick or run.
+    pub async fn processing_withdrawals(&self, scanner_name: &str) -> Vec<ProcessingWithdrawal> {
         let mut conn = self.establish_connection().await;
 
-        let txs_to_process = conn
-            .query_map(
-                SELECT_TRANSACTIONS_TO_PROCESS,
-                |(id, glitch_address, amount)| TxToProcess {
-                    id,
-                    glitch_address,
-                    amount,
-                },
+        let withdrawals = conn
+            .exec_map(
+                SELECT_PROCESSING_WITHDRAWALS,
+                params! { "name" => scanner_name },
+                |(id, version, eth_tx_hash)| ProcessingWithdrawal { id, version, eth_tx_hash },
             )
             .await
             .unwrap();
 
         drop(conn);
-        txs_to_process
+        withdrawals
     }
 
-    pub async fn update_tx_with_error(&self, id: u128, error_message: String) {
-        let mut conn = self.establish_connection().await;
+    /// Takes a withdrawal that can't be released out of the queue for good,
+    /// with the reason.
+    pub async fn fail_withdrawal(&self, id: u128, version: u32, error: String) -> Option<u32> {
+        let params = params! { "error" => self.seal(Some(&error)) };
+        self.compare_and_swap(FAIL_WITHDRAWAL, id, version, params).await
+    }
+
+    /// Records the release of a claimed withdrawal: mined as `eth_tx_hash`
+    /// (PROCESSED) or proposed as `safe_tx_hash` (PROPOSED).
+    pub async fn record_withdrawal_release(
+        &self,
+        id: u128,
+        version: u32,
+        eth_tx_hash: Option<String>,
+        safe_tx_hash: Option<String>,
+    ) -> Option<u32> {
+        let state = if eth_tx_hash.is_some() { "PROCESSED" } else { "PROPOSED" };
         let params = params! {
-            "id" => id,
-            "error" => error_message,
+            "state" => state,
+            "eth_tx_hash" => eth_tx_hash,
+            "safe_tx_hash" => safe_tx_hash,
         };
+        self.compare_and_swap(UPDATE_WITHDRAWAL_RELEASE, id, version, params).await
+    }
 
-        let result = conn.exec_drop(SAVE_ERROR, params).await;
-
-        match result {
-            Ok(_) => debug!("Glitch tx updated!"),
-            Err(e) => error!("Error in the glitch tx updated: {}", e),
-        }
-        drop(conn);
+    /// Puts a claimed withdrawal back in the queue with the reason.
+    pub async fn release_withdrawal_claim(&self, id: u128, version: u32, error: String) -> Option<u32> {
+        let params = params! { "error" => self.seal(Some(&error)) };
+        self.compare_and_swap(RELEASE_WITHDRAWAL_CLAIM, id, version, params).await
     }
 
-    pub async fn increment_fee_counter(&self, scanner_name: String, amount: u128) {
+    /// The fee counter of the pipeline and the id of its latest adjustment,
+    /// read together: the adjustments up to that id are in the counter.
+    pub async fn get_fee_counter(&self, scanner_name: &str) -> (u128, u64) {
         let mut conn = self.establish_connection().await;
 
-        let current_fee_counter: u128 = conn
+        let (accumulated_fees, last_adjustment_id): (u128, u64) = conn
             .exec_first(
                 SELECT_FEE_ACCUMULATED,
                 params! {
-                    "name" => &scanner_name
+                    "name" => scanner_name
                 },
             )
             .await
             .unwrap()
             .unwrap();
 
+        drop(conn);
+        (accumulated_fees, last_adjustment_id)
+    }
+
+    /// Takes the settled `fee_amount` off the fee counter, keeping what was
+    /// collected or adjusted since it was read.
+    pub async fn deduct_fee_counter(&self, fee_amount: u128, scanner_name: &str) {
+        let mut conn = self.establish_connection().await;
         let params = params! {
             "name" => scanner_name,
-            "accumulated_fees" => current_fee_counter + amount
+            "amount" => fee_amount.to_string()
         };
 
-        let result = conn.exec_drop(UPDATE_FEE, params).await;
+        let result = conn.exec_drop(DEDUCT_FROM_FEE, params).await;
 
         match result {
-            Ok(_) => debug!("Fee increased successful!"),
-            Err(e) => error!("Error in the fee increased: {}", e),
+            Ok(_) => debug!("Fee counter settled!"),
+            Err(e) => error!("Error settling the fee counter: {}", e),
         }
+
+        drop(conn);
     }
 
-    pub async fn update_tx(
+    /// Claims the tx and inserts its outbox row within `tx`. Ok(None) if the
+    /// tx changed since it was read, in which case nothing was written.
+    async fn claim_and_queue(
         &self,
-        id: u128,
-        glitch_hash: String,
-        business_fee_amount: u128,
-        business_fee_percentage: String,
-    ) {
-        let mut conn = self.establish_connection().await;
+        tx: &mut Transaction<'_>,
+        payout: &OutboxPayout,
+        version: u32,
+    ) -> Result<Option<u64>, mysql_async::Error> {
+        tx.exec_drop(UPDATE_TX_SUBMITTED, params! { "id" => payout.tx_id, "version" => version })
+            .await?;
+        if tx.affected_rows() != 1 {
+            warn!("Tx {} was changed concurrently (expected version {}).", payout.tx_id, version);
+            return Ok(None);
+        }
+
         let params = params! {
-            "id" => id,
-            "glitch_tx_hash" => glitch_hash,
-            "business_fee_amount" => business_fee_amount,
-            "business_fee_percentage" => business_fee_percentage
+            "tx_id" => payout.tx_id,
+            "tx_version" => version + 1,
+            "name" => &payout.scanner_name,
+            "recipient" => self.seal(Some(&payout.recipient)),
+            "amount" => payout.amount.to_string(),
+            "business_fee_amount" => payout.business_fee_amount.to_string(),
+            "business_fee_percentage" => payout.business_fee_percentage.to_string(),
+            "estimated_fee" => payout.estimated_fee.to_string(),
+            "amount_breakdown" => &payout.amount_breakdown,
+            "tx_detected_at" => payout.tx_detected_at,
         };
+        tx.exec_drop(INSERT_OUTBOX_PAYOUT, params).await?;
+        Ok(tx.last_insert_id())
+    }
+
+    /// Persists the transitions of a payer pass in one transaction: the
+    /// errors and the holds in a batch each, then each claim with its outbox
+    /// row. A hold or a claim that lost a race is skipped without affecting
+    /// the others; on a database error nothing is written and the txs are
+    /// read again on the next pass. Returns the ids of the queued payouts by tx id.
+    pub async fn apply_transitions(&self, transitions: TxTransitions) -> Vec<(u128, u64)> {
+        if transitions.is_empty() {
+            return Vec::new();
+        }
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await.unwrap();
 
-        let result = conn.exec_drop(UPDATE_TX_GLITCH, params).await;
+        if !transitions.errors.is_empty() {
+            let params = transitions.errors.iter().map(|(id, error_message)| {
+                params! {
+                    "id" => *id,
+                    "error" => self.seal(Some(error_message)),
+                }
+            });
+            if let Err(e) = SAVE_ERROR.with(params).batch(&mut tx).await {
+                error!("Error saving the errors of {} txs: {}", transitions.errors.len(), e);
+                tx.rollback().await.unwrap();
+                return Vec::new();
+            }
+        }
 
-        match result {
-            Ok(_) => debug!("Glitch tx updated!"),
-            Err(e) => error!("Error in the glitch tx updated: {}", e),
+        if !transitions.holds.is_empty() {
+            let params = transitions.holds.iter().map(|(id, version, reason)| {
+                params! {
+                    "id" => *id,
+                    "version" => *version,
+                    "error" => self.seal(Some(reason)),
+                }
+            });
+            if let Err(e) = HOLD_TX_TO_PROCESS.with(params).batch(&mut tx).await {
+                error!("Error holding {} txs: {}", transitions.holds.len(), e);
+                tx.rollback().await.unwrap();
+                return Vec::new();
+            }
+        }
+
+        let mut queued = Vec::with_capacity(transitions.payouts.len());
+        for (payout, version) in transitions.payouts.iter() {
+            match self.claim_and_queue(&mut tx, payout, *version).await {
+                Ok(Some(id)) => queued.push((payout.tx_id, id)),
+                Ok(None) => info!("Tx {} was not queued, it will be read again.", payout.tx_id),
+                Err(e) => {
+                    error!("Error queueing the payout of tx {}, the whole batch is retried: {}", payout.tx_id, e);
+                    tx.rollback().await.unwrap();
+                    return Vec::new();
+                }
+            }
         }
+
+        tx.commit().await.unwrap();
         drop(conn);
+        debug!(
+            "{} tx error(s), {} hold(s) and {} payout(s) persisted in one transaction.",
+            transitions.errors.len(),
+            transitions.holds.len(),
+            queued.len()
+        );
+        queued
     }
 
-    pub async fn get_last_block(&self, scanner_name: &str) -> u32 {
+    /// Payouts of the pipeline waiting to be submitted, oldest first.
+    pub async fn pending_outbox(&self, scanner_name: &str) -> Vec<OutboxPayout> {
         let mut conn = self.establish_connection().await;
 
-        let result: u32 = conn
-            .exec_first(
-                SELECT_LAST_BLOCK,
-                params! {
-                    "name" => scanner_name
+        let result: Vec<OutboxPayoutRow> = conn
+            .exec(SELECT_PENDING_OUTBOX, params! { "name" => scanner_name })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+            .into_iter()
+            .map(
+                |(
+                    id,
+                    tx_id,
+                    tx_version,
+                    scanner_name,
+                    recipient,
+                    amount,
+                    business_fee_amount,
+                    business_fee_percentage,
+                    estimated_fee,
+                    amount_breakdown,
+                    tx_detected_at,
+                )| OutboxPayout {
+                    id,
+                    tx_id,
+                    tx_version,
+                    scanner_name,
+                    recipient: self.open(recipient).unwrap_or_default(),
+                    amount: amount.parse().unwrap_or_default(),
+                    business_fee_amount: business_fee_amount.parse().unwrap_or_default(),
+                    business_fee_percentage: business_fee_percentage.parse().unwrap_or_default(),
+                    estimated_fee: estimated_fee.parse().unwrap_or_default(),
+                    amount_breakdown,
+                    tx_detected_at,
                 },
             )
+            .collect()
+    }
+
+    /// Amount the queued payouts of the pipeline are about to send.
+    pub async fn outbox_pending_value(&self, scanner_name: &str) -> u128 {
+        let mut conn = self.establish_connection().await;
+
+        let result: Option<String> = conn
+            .exec_first(SELECT_OUTBOX_PENDING_VALUE, params! { "name" => scanner_name })
             .await
-            .unwrap()
             .unwrap();
 
         drop(conn);
-        result
+        result.and_then(|value| value.parse().ok()).unwrap_or(0)
     }
 
-    pub async fn update_block_and_insert_txs(
-        &self,
-        scanner_name: String,
-        block: u32,
-        logs: Vec<Log>,
-    ) {
+    /// Takes a queued payout for submission. False if it was already taken.
+    pub async fn start_outbox_submission(&self, id: u64) -> bool {
+        let mut conn = self.establish_connection().await;
+
+        match conn.exec_drop(START_OUTBOX_SUBMISSION, params! { "id" => id }).await {
+            Ok(_) => conn.affected_rows() == 1,
+            Err(e) => {
+                error!("Error taking outbox payout {id}: {e}");
+                false
+            }
+        }
+    }
+
+    /// Records the hash of the extrinsic about to be sent for the payout,
+    /// which reconciliation looks for if the submission is interrupted.
+    /// False if it could not be recorded.
+    pub async fn record_outbox_extrinsic(&self, id: u64, extrinsic_hash: &str) -> bool {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! { "id" => id, "extrinsic_hash" => extrinsic_hash };
+        match conn.exec_drop(RECORD_OUTBOX_EXTRINSIC, params).await {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Error recording the extrinsic of outbox payout {id}: {e}");
+                false
+            }
+        }
+    }
+
+    /// Records how the submission of the payout ended.
+    pub async fn finish_outbox_payout(&self, id: u64, state: OutboxState, block_hash: Option<&str>, error: Option<String>) {
         let mut conn = self.establish_connection().await;
-        let mut tx = conn.start_transaction(TxOpts::new()).await.unwrap();
 
         let params = params! {
-            "block" => block,
-            "name" => scanner_name
+            "id" => id,
+            "state" => state.as_str(),
+            "block_hash" => block_hash,
+            "error" => self.seal(error.as_deref()),
         };
-
-        let update_block_result = tx.exec_drop(UPDATE_LAST_BLOCK, params).await;
-        match update_block_result {
-            Ok(_) => debug!("Block update successful!"),
-            Err(e) => error!("Error in the block update: {}", e),
+        if let Err(e) = conn.exec_drop(FINISH_OUTBOX_PAYOUT, params).await {
+            error!("Error recording the outcome of outbox payout {id}: {e}");
         }
+    }
 
-        if !logs.is_empty() {
-            let insert_logs_result = tx.exec_batch(
-                INSERT_TXS,
-                logs.iter().map(|tx| {
-                    let data: Vec<u8> = tx.data.0.clone();
-                    let data_chunks: Vec<&[u8]> = data.chunks(32).collect();
-                    let string_len = U256::from_big_endian(data_chunks[2]).as_usize();
-                    let glitch_address: Vec<u8> = [data_chunks[3], data_chunks[4]]
-                        .concat()
-                        .iter()
-                        .copied()
-                        .take(string_len)
-                        .collect();
-    
-                    params! {
-                        "tx_eth_hash" => format!("{:#x}",tx.transaction_hash.unwrap()),
-                        "from_eth_address" => h256_to_address(*tx.topics.get(1).unwrap()),
-                        "amount" => U256::from_big_endian(data_chunks[1]).to_string(),
-                        "to_glitch_address" => std::str::from_utf8(glitch_address.as_slice()).unwrap()
-                    }
-                }),
-            )
-            .await;
+    /// Marks the submissions cut short by a restart as UNKNOWN; their txs
+    /// are still PROCESSING and reconciled at startup.
+    pub async fn interrupt_outbox_submissions(&self, scanner_name: &str) -> u64 {
+        let mut conn = self.establish_connection().await;
 
-            match insert_logs_result {
-                Ok(_) => debug!("Inserts successful!"),
-                Err(e) => error!("Inserts with error: {}", e),
+        let params = params! {
+            "name" => scanner_name,
+            "error" => self.seal(Some("Interrupted by a restart, the tx is reconciled")),
+        };
+        match conn.exec_drop(INTERRUPT_OUTBOX_SUBMISSIONS, params).await {
+            Ok(_) => conn.affected_rows(),
+            Err(e) => {
+                error!("Error closing the interrupted outbox payouts of {scanner_name}: {e}");
+                0
             }
         }
+    }
 
-        if tx.affected_rows() > 0 {
-            tx.commit().await.unwrap()
-        } else {
-            tx.rollback().await.unwrap()
-        }
+    /// Takes a PROCESSING tx whose payout failed its dry run out of flight,
+    /// with the reason: held as SUSPICIOUS when `hold`, back to TO_PROCESS
+    /// otherwise. Returns the new version.
+    pub async fn record_dry_run_failure(
+        &self,
+        id: u128,
+        version: u32,
+        failure_kind: FailureKind,
+        error: String,
+        hold: bool,
+    ) -> Option<u32> {
+        let params = params! {
+            "hold" => hold,
+            "failure_kind" => failure_kind.as_str(),
+            "error" => self.seal(Some(&error)),
+        };
+        self.compare_and_swap(UPDATE_TX_DRY_RUN_FAILED, id, version, params).await
+    }
+
+    /// Checks the tx is still PROCESSING at the version it was claimed with
+    /// and bumps it. Returns the new version.
+    pub async fn confirm_tx_claim(&self, id: u128, version: u32) -> Option<u32> {
+        self.compare_and_swap(CONFIRM_TX_CLAIM, id, version, Params::Empty).await
     }
 
-    pub async fn get_fee_counter(&self, scanner_name: &str) -> u128 {
+    async fn stuck_rows(&self, query: &str, after_in_secs: u64) -> Vec<StuckRow> {
         let mut conn = self.establish_connection().await;
 
-        let result: u128 = conn
-            .exec_first(
-                SELECT_FEE_ACCUMULATED,
-                params! {
-                    "name" => scanner_name
+        let result = conn
+            .exec_map(
+                query,
+                params! { "after_in_secs" => after_in_secs },
+                |(id, tx_id, tx_version, scanner_name, age_in_secs)| StuckRow {
+                    id,
+                    tx_id,
+                    tx_version,
+                    scanner_name,
+                    age_in_secs,
                 },
             )
             .await
-            .unwrap()
             .unwrap();
 
         drop(conn);
         result
     }
 
-    pub async fn modify_fee_counter(&self, fee_amount: u128, scanner_name: &str) {
+    /// PROCESSING txs whose last submission ended more than `after_in_secs`
+    /// ago, leaving out those with a payout still queued or being sent.
+    pub async fn stuck_processing_txs(&self, after_in_secs: u64) -> Vec<StuckRow> {
+        self.stuck_rows(SELECT_STUCK_PROCESSING_TXS, after_in_secs).await
+    }
+
+    /// SUSPICIOUS txs held for more than `after_in_secs`.
+    pub async fn held_txs(&self, after_in_secs: u64) -> Vec<StuckRow> {
+        self.stuck_rows(SELECT_HELD_TXS, after_in_secs).await
+    }
+
+    /// Outbox payouts queued more than `after_in_secs` ago and not taken by
+    /// the submitter.
+    pub async fn stuck_outbox(&self, after_in_secs: u64) -> Vec<StuckRow> {
+        self.stuck_rows(SELECT_STUCK_OUTBOX, after_in_secs).await
+    }
+
+    /// Drops a payout the submitter has not taken and puts its tx back in
+    /// the queue. False if the submitter took it first or the tx changed.
+    pub async fn drop_outbox_payout(&self, id: u64, tx_id: u128, tx_version: u32, reason: &str) -> bool {
         let mut conn = self.establish_connection().await;
-        let params = params! {
-            "name" => scanner_name,
-            "accumulated_fees" => fee_amount
+
+        let params = params! { "id" => id, "error" => self.seal(Some(reason)) };
+        let dropped = match conn.exec_drop(DROP_OUTBOX_PAYOUT, params).await {
+            Ok(_) => conn.affected_rows() == 1,
+            Err(e) => {
+                error!("Error dropping outbox payout {id}: {e}");
+                false
+            }
         };
+        drop(conn);
 
-        let result = conn.exec_drop(UPDATE_FEE, params).await;
+        dropped && self.release_tx_claim(tx_id, tx_version).await.is_some()
+    }
 
-        match result {
-            Ok(_) => debug!("Fee increased successful!"),
-            Err(e) => error!("Error in the fee increased: {}", e),
+    /// Writes the token registry of the configuration to the `token` table.
+    /// Tokens no longer configured are kept, old txs may refer to them.
+    pub async fn record_tokens(&self, tokens: &[Token]) {
+        let mut conn = self.establish_connection().await;
+
+        let params = tokens.iter().map(|token| {
+            params! {
+                "symbol" => &token.symbol,
+                "eth_address" => &token.eth_address,
+                "decimals" => token.decimals,
+                "min_amount" => token.min_amount.map(|amount| amount.to_string()),
+                "max_amount" => token.max_amount.map(|amount| amount.to_string()),
+                "business_fee" => token.business_fee,
+                "glitch_asset_id" => token.glitch_asset_id,
+            }
+        });
+        match UPSERT_TOKEN.with(params).batch(&mut conn).await {
+            Ok(_) => info!("{} token(s) recorded", tokens.len()),
+            Err(e) => error!("Error recording the token registry: {e}"),
         }
+    }
+
+    /// Up to `limit` tx events after the `after` sequence number, in order.
+    pub async fn tx_events(&self, after: u64, limit: u32) -> Vec<TxEvent> {
+        let mut conn = self.establish_connection().await;
+
+        let result: Vec<TxEventRow> = conn
+            .exec(SELECT_TX_EVENTS_AFTER, params! { "after" => after, "limit" => limit })
+            .await
+            .unwrap();
+
+        drop(conn);
+        result
+            .into_iter()
+            .map(
+                |(sequence, tx_id, scanner_name, tx_eth_hash, previous_state, state, tx_glitch_hash, time, age_in_secs)| {
+                    TxEvent {
+                        sequence,
+                        tx_id,
+                        scanner_name,
+                        tx_eth_hash,
+                        previous_state,
+                        state,
+                        tx_glitch_hash,
+                        time,
+                        age_in_secs,
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// Sequence number of the last event the consumer `name` got, 0 if none.
+    pub async fn event_cursor(&self, name: &str) -> u64 {
+        let mut conn = self.establish_connection().await;
+
+        let result: Option<u64> = conn
+            .exec_first(SELECT_EVENT_CURSOR, params! { "name" => name })
+            .await
+            .unwrap();
 
         drop(conn);
+        result.unwrap_or(0)
+    }
+
+    /// Moves the cursor of the consumer `name` forward to `last_sequence`.
+    pub async fn advance_event_cursor(&self, name: &str, last_sequence: u64) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! { "name" => name, "last_sequence" => last_sequence };
+        if let Err(e) = conn.exec_drop(UPSERT_EVENT_CURSOR, params).await {
+            error!("Error advancing the event cursor of {name}: {e}");
+        }
+    }
+
+    /// Adds the signed `adjustment.amount` to the fee counter of the pipeline
+    /// and records it, in one transaction. Adjustments whose reference was
+    /// already imported are skipped, and one that would leave the counter
+    /// negative is refused. Returns the counter after it.
+    pub async fn apply_fee_adjustment(&self, adjustment: &FeeAdjustment) -> Result<FeeAdjustmentOutcome, String> {
+        let mut conn = self.establish_connection().await;
+        let mut tx = conn.start_transaction(TxOpts::new()).await.map_err(|e| e.to_string())?;
+
+        if let Some(reference) = &adjustment.reference {
+            let existing: Option<u64> = tx
+                .exec_first(
+                    SELECT_FEE_ADJUSTMENT_BY_REFERENCE,
+                    params! { "name" => &adjustment.scanner_name, "reference" => reference },
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            if let Some(id) = existing {
+                tx.rollback().await.map_err(|e| e.to_string())?;
+                return Ok(FeeAdjustmentOutcome::AlreadyApplied(id));
+            }
+        }
+
+        let accumulated: u128 = tx
+            .exec_first(SELECT_FEE_ACCUMULATED_FOR_UPDATE, params! { "name" => &adjustment.scanner_name })
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("No pipeline {}", adjustment.scanner_name))?;
+        let after = accumulated as i128 + adjustment.amount;
+        if after < 0 {
+            tx.rollback().await.map_err(|e| e.to_string())?;
+            return Err(format!(
+                "{} would leave the fees of {} negative ({} accumulated)",
+                adjustment.amount, adjustment.scanner_name, accumulated
+            ));
+        }
+
+        tx.exec_drop(
+            ADD_TO_FEE,
+            params! { "name" => &adjustment.scanner_name, "amount" => adjustment.amount.to_string() },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.exec_drop(
+            INSERT_FEE_ADJUSTMENT,
+            params! {
+                "name" => &adjustment.scanner_name,
+                "amount" => adjustment.amount.to_string(),
+                "reason" => &adjustment.reason,
+                "requested_by" => &adjustment.requested_by,
+                "reference" => &adjustment.reference,
+                "accumulated_before" => accumulated.to_string(),
+                "accumulated_after" => after.to_string(),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(FeeAdjustmentOutcome::Applied(after as u128))
     }
 
-    pub async fn insert_tx_fee(&self, glitch_hash: String, amount: String) {
+    /// Records a fee transaction and its invoice, settling the adjustments
+    /// up to `last_adjustment_id`. Returns its id.
+    pub async fn insert_tx_fee(
+        &self,
+        scanner_name: &str,
+        glitch_hash: String,
+        amount: String,
+        last_adjustment_id: u64,
+    ) -> Option<u64> {
         let mut conn = self.establish_connection().await;
 
         let params = params! {
-            "tx_glitch_hash" => glitch_hash,
-            "amount" => amount,
+            "tx_glitch_hash" => &glitch_hash,
+            "amount" => &amount,
+            "name" => scanner_name,
         };
         let result = INSERT_TX_FEE.with(vec![params]).batch(&mut conn).await;
 
@@ -310,14 +2735,50 @@ impl DatabaseEngine {
                 debug!("New tx fee created!");
                 let last_id: u64 = conn.exec_first("SELECT LAST_INSERT_ID()", Params::Empty).await.unwrap().unwrap();
 
-                let result = conn.exec_drop(UPDATE_TX_WITH_TRANSACTION_FEE_ID, params!{"transaction_fee_id" => last_id}).await;
+                let result = conn.exec_drop(UPDATE_TX_WITH_TRANSACTION_FEE_ID, params!{"transaction_fee_id" => last_id, "name" => scanner_name}).await;
 
                 match result {
                     Ok(_) => info!("Tx updated with transaction fee id!"),
                     Err(e) => error!("Error when updating the transaction fee id of the tx {e}")
                 }
+
+                let result = conn.exec_drop(UPDATE_FEE_ADJUSTMENTS_WITH_TRANSACTION_FEE_ID, params!{"transaction_fee_id" => last_id, "name" => scanner_name, "last_adjustment_id" => last_adjustment_id}).await;
+
+                if let Err(e) = result {
+                    error!("Error when updating the transaction fee id of the fee adjustments {e}")
+                }
+
+                let params = params! {
+                    "fee_transaction_id" => last_id,
+                    "name" => scanner_name,
+                    "amount" => amount,
+                    "glitch_tx_hash" => glitch_hash,
+                };
+                match conn.exec_drop(INSERT_FEE_INVOICE, params).await {
+                    Ok(_) => info!("Fee invoice created for fee transaction {last_id}"),
+                    Err(e) => error!("Error creating the fee invoice: {e}"),
+                }
+                Some(last_id)
             },
-            Err(e) => error!("Fee tx could not be created in the database.: {e}"),
+            Err(e) => {
+                error!("Fee tx could not be created in the database.: {e}");
+                None
+            }
+        }
+    }
+
+    /// Records what the finalized events of a fee transaction showed, with
+    /// the mismatch if they don't match the fee sent.
+    pub async fn record_fee_confirmation(&self, id: u64, discrepancy: Option<String>) {
+        let mut conn = self.establish_connection().await;
+
+        let params = params! {
+            "id" => id,
+            "confirmation" => if discrepancy.is_some() { "DISCREPANCY" } else { "CONFIRMED" },
+            "discrepancy" => discrepancy,
+        };
+        if let Err(e) = conn.exec_drop(UPDATE_FEE_CONFIRMATION, params).await {
+            error!("Error recording the confirmation of fee transaction {id}: {e}");
         }
     }
 
@@ -359,39 +2820,32 @@ impl DatabaseEngine {
         ret
     }
 
-    pub async fn insert_txs(&self, logs: Vec<Log>) {
+    /// Stores the deposits, all or nothing. Returns false if they were not.
+    pub async fn insert_txs(&self, scanner_name: &str, deposits: Vec<Deposit>) -> bool {
+        if deposits.is_empty() {
+            return true;
+        }
         let mut conn = self.establish_connection().await;
-        let result = INSERT_TXS
-            .with(logs.iter().map(|tx| {
-                let data: Vec<u8> = tx.data.0.clone();
-                let data_chunks: Vec<&[u8]> = data.chunks(32).collect();
-                let string_len = U256::from_big_endian(data_chunks[2]).as_usize();
-                let glitch_address: Vec<u8> = [data_chunks[3], data_chunks[4]]
-                    .concat()
-                    .iter()
-                    .copied()
-                    .take(string_len)
-                    .collect();
-
-                params! {
-                    "tx_eth_hash" => format!("{:#x}",tx.transaction_hash.unwrap()),
-                    "from_eth_address" => h256_to_address(*tx.topics.get(1).unwrap()),
-                    "amount" => U256::from_big_endian(data_chunks[1]).to_string(),
-                    "to_glitch_address" => std::str::from_utf8(glitch_address.as_slice()).unwrap()
-                }
-            }))
-            .batch(&mut conn)
-            .await;
+        let result = async {
+            let mut tx = conn.start_transaction(TxOpts::new()).await?;
+            tx.exec_batch(
+                INSERT_TXS,
+                deposits.iter().map(|deposit| self.deposit_params(deposit, scanner_name)),
+            )
+            .await?;
+            tx.commit().await
+        }
+        .await;
 
         match result {
-            Ok(_) => debug!("Inserts successful!"),
-            Err(e) => error!("Inserts with error: {}", e),
+            Ok(_) => {
+                debug!("Inserts successful!");
+                true
+            }
+            Err(e) => {
+                error!("Inserts with error: {}", e);
+                false
+            }
         }
-
-        drop(conn);
     }
 }
-
-fn h256_to_address(h: H256) -> String {
-    format!("{:#x}", H160::from(h))
-}
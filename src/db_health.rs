@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use tokio::time::Duration;
+
+use crate::database::DatabaseEngine;
+use crate::scheduler::Ticker;
+
+/// How long a ping may take before it counts as failed.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Consecutive failed pings after which the pool is built again.
+const MAX_FAILED_PINGS: u32 = 3;
+
+/// Pings the database in use on every tick, reporting the result in
+/// `glitch_bridge_db_up`, and drops the connection pool after
+/// `MAX_FAILED_PINGS` failures in a row so payouts don't run into the dead
+/// connections it holds.
+pub async fn monitor_database(database_engine: Arc<DatabaseEngine>, mut ticker: Ticker) {
+    let mut failures = 0;
+
+    loop {
+        ticker.tick().await;
+
+        match database_engine.ping(PING_TIMEOUT).await {
+            Ok(()) => {
+                if failures > 0 {
+                    info!("Database answering again after {} failed ping(s).", failures);
+                }
+                failures = 0;
+            }
+            Err(e) => {
+                failures += 1;
+                warn!("Database health ping failed ({} in a row): {}", failures, e);
+                if failures >= MAX_FAILED_PINGS {
+                    error!("Database failed {} pings in a row, recreating the connection pool.", failures);
+                    database_engine.recreate_pool();
+                    failures = 0;
+                }
+            }
+        }
+    }
+}
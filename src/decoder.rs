@@ -0,0 +1,576 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use log::info;
+use sp_core::crypto::Pair;
+use sp_core::sr25519::{self, Public};
+use web3::signing::keccak256;
+use web3::types::{Log, H160, H256, U256};
+
+use crate::config::{Config, Network, SenderFilter, SenderFilterAction, SECONDS_PER_DAY};
+use crate::database::DatabaseEngine;
+use crate::metrics;
+use crate::skipped_logs::{SkipReason, SkippedLog};
+use crate::tagging::DepositTagger;
+use crate::types::{h256_to_address, normalize_glitch_address, parse_glitch_address, to_hex, u256_to_usize};
+
+pub const STATE_TO_PROCESS: &str = "TO_PROCESS";
+pub const STATE_SUSPICIOUS: &str = "SUSPICIOUS";
+pub const STATE_QUARANTINED: &str = "QUARANTINED";
+/// Deposits of filtered senders kept for the record, never paid out.
+pub const STATE_TEST: &str = "TEST";
+/// Deposits cancelled by an operator before their payout was submitted.
+pub const STATE_CANCELLED: &str = "CANCELLED";
+
+pub const DEPOSIT_EVENT_SIGNATURE: &str = "TransferToGlitch(address,string,uint256)";
+/// Deposits of the event in `event_signature`.
+pub const EVENT_VERSION_V1: u8 = 1;
+/// Deposits of the event in `event_migration`.
+pub const EVENT_VERSION_V2: u8 = 2;
+
+/// A `TransferToGlitch` event decoded into the values stored in the `tx` table.
+#[derive(Debug, Clone)]
+pub struct Deposit {
+    pub tx_eth_hash: String,
+    pub from_eth_address: String,
+    pub amount: U256,
+    pub glitch_address: String,
+    pub state: &'static str,
+    pub note: Option<String>,
+    pub block_number: Option<u64>,
+    /// Unix time of the block, filled in by the scanner when the daily cap
+    /// needs it.
+    pub block_time: Option<i64>,
+    /// Unix time before which the deposit must not be paid out, if the
+    /// event carried one.
+    pub unlock_at: Option<u64>,
+    /// Protocol fee the contract already deducted from `amount` on Ethereum,
+    /// if the event reports it.
+    pub eth_fee: Option<U256>,
+    /// Version of the contract event the deposit was decoded from.
+    pub event_version: u8,
+    /// Labels of the `tag_rules` the deposit matched.
+    pub labels: Vec<String>,
+}
+
+/// Largest amount the `DECIMAL(38, 0)` amount columns hold.
+pub fn max_stored_amount() -> U256 {
+    U256::exp10(38) - 1
+}
+
+/// Latest unlock time accepted, the last second of year 9999. Later ones are
+/// typos or garbage, held for review instead of locking the deposit forever.
+const MAX_UNLOCK_AT: u64 = 253_402_300_799;
+
+/// The unlock time of an event as Unix time, or why it can't be honored.
+pub(crate) fn unlock_time(unlock_at: U256) -> Result<u64, String> {
+    if unlock_at > U256::from(MAX_UNLOCK_AT) {
+        return Err(format!("Unlock time {unlock_at} is out of range"));
+    }
+    Ok(unlock_at.low_u64())
+}
+
+/// Longest recipient accepted: a `0x`-prefixed 32 byte hex public key.
+pub(crate) const MAX_GLITCH_ADDRESS_LEN: usize = 66;
+/// Size of the `to_glitch_address` column, for recipients kept for review.
+const GLITCH_ADDRESS_COLUMN_LEN: usize = 48;
+
+/// Validates the raw recipient bytes of the event before they reach the store.
+fn decode_glitch_address(raw: &[u8]) -> Result<String, String> {
+    if raw.len() > MAX_GLITCH_ADDRESS_LEN {
+        return Err(format!(
+            "Recipient is {} bytes long, the maximum is {}",
+            raw.len(),
+            MAX_GLITCH_ADDRESS_LEN
+        ));
+    }
+
+    let glitch_address =
+        std::str::from_utf8(raw).map_err(|e| format!("Recipient is not valid UTF-8: {e}"))?;
+
+    if glitch_address.chars().any(char::is_control) {
+        return Err("Recipient contains control characters".to_string());
+    }
+
+    Ok(glitch_address.to_string())
+}
+
+/// Where the fields of a deposit event are in the log data, as indexes of
+/// its non-indexed inputs: each has a word in the data head, the recipient
+/// string the offset of its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLayout {
+    pub head_words: usize,
+    pub recipient: usize,
+    pub amount: usize,
+    pub unlock_at: Option<usize>,
+    pub eth_fee: Option<usize>,
+}
+
+impl EventLayout {
+    /// The layout of the contract events known without names: the recipient
+    /// and then the amount, the unlock time and the protocol fee already
+    /// deducted on Ethereum, as many as the event has.
+    pub fn positional(head_words: usize) -> Self {
+        Self {
+            head_words,
+            recipient: 0,
+            amount: 1,
+            unlock_at: (head_words > 2).then_some(2),
+            eth_fee: (head_words > 3).then_some(3),
+        }
+    }
+
+    /// The canonical signature and the layout of an event signature whose
+    /// first input is the indexed sender. Inputs may be named as in the
+    /// contract ABI, `uint256 unlockAt` or `uint256 ethFee`, which tells an
+    /// event reporting only the fee from one with only an unlock time;
+    /// unnamed `uint256` inputs are the amount, the unlock time and the fee,
+    /// in that order.
+    pub fn parse(signature: &str) -> Result<(String, Self), String> {
+        let (name, inputs) = signature
+            .split_once('(')
+            .and_then(|(name, rest)| Some((name.trim(), rest.strip_suffix(')')?)))
+            .ok_or_else(|| format!("{signature:?} is not an event signature"))?;
+
+        let mut types = Vec::new();
+        let mut recipient = None;
+        let mut named: Vec<(usize, &str)> = Vec::new();
+        let mut unnamed = Vec::new();
+        for (position, input) in inputs.split(',').enumerate() {
+            let mut words = input.split_whitespace().filter(|word| *word != "indexed");
+            let kind = words.next().ok_or_else(|| format!("{signature:?} has an empty input"))?;
+            types.push(kind);
+            // The sender, in a topic.
+            if position == 0 {
+                continue;
+            }
+            let index = position - 1;
+            match (kind, words.next()) {
+                ("string", _) if recipient.is_none() => recipient = Some(index),
+                ("uint256", Some(field)) => named.push((index, field)),
+                ("uint256", None) => unnamed.push(index),
+                _ => return Err(format!("{signature:?} has an unexpected input {input:?}")),
+            }
+        }
+
+        let mut fields: [Option<usize>; 3] = [None; 3];
+        for (index, field) in named {
+            let slot = match field.replace('_', "").to_lowercase().as_str() {
+                "amount" | "value" => 0,
+                "unlock" | "unlockat" => 1,
+                "fee" | "ethfee" => 2,
+                _ => return Err(format!("{signature:?} has an unknown input {field:?}")),
+            };
+            if fields[slot].replace(index).is_some() {
+                return Err(format!("{signature:?} has the input {field:?} twice"));
+            }
+        }
+        let mut unnamed = unnamed.into_iter();
+        for field in fields.iter_mut().filter(|field| field.is_none()) {
+            *field = unnamed.next();
+        }
+        if unnamed.next().is_some() {
+            return Err(format!("{signature:?} has more inputs than a deposit event"));
+        }
+
+        let layout = Self {
+            head_words: types.len() - 1,
+            recipient: recipient.ok_or_else(|| format!("{signature:?} has no recipient string"))?,
+            amount: fields[0].ok_or_else(|| format!("{signature:?} has no amount"))?,
+            unlock_at: fields[1],
+            eth_fee: fields[2],
+        };
+        Ok((format!("{}({})", name, types.join(",")), layout))
+    }
+}
+
+/// Decodes the event, with the layout its recipient offset implies (see
+/// `EventLayout::positional`). Logs that don't have the event layout are an
+/// error; recipients failing the sanitation are kept as SUSPICIOUS, escaped
+/// and truncated, with the raw bytes in the note.
+pub fn decode_deposit(log: &Log) -> Result<Deposit, String> {
+    // `TransferToGlitch(address,string,uint256)`, with an unlock time
+    // `TransferToGlitch(address,string,uint256,uint256)` and also reporting
+    // the protocol fee `TransferToGlitch(address,string,uint256,uint256,uint256)`.
+    let string_offset = log.data.0.get(0..32).and_then(|word| u256_to_usize(U256::from_big_endian(word)));
+    match string_offset {
+        Some(offset @ (64 | 96 | 128)) => decode_with_layout(log, &EventLayout::positional(offset / 32)),
+        _ => Err(format!(
+            "Log of {:?} has the recipient at offset {:?}, 64, 96 or 128 expected",
+            log.transaction_hash, string_offset
+        )),
+    }
+}
+
+/// Decodes the event with the given layout, see `decode_deposit`.
+pub fn decode_with_layout(log: &Log, layout: &EventLayout) -> Result<Deposit, String> {
+    let tx_eth_hash = log
+        .transaction_hash
+        .ok_or_else(|| "Log without transaction hash".to_string())?;
+    let from = log
+        .topics
+        .get(1)
+        .ok_or_else(|| format!("Log of {} without sender topic", to_hex(tx_eth_hash)))?;
+
+    let data = &log.data.0;
+    let head_len = 32 * layout.head_words;
+    if data.len() < head_len + 32 {
+        return Err(format!(
+            "Log of {} has {} bytes of data, at least {} expected",
+            to_hex(tx_eth_hash),
+            data.len(),
+            head_len + 32
+        ));
+    }
+    let word = |index: usize| U256::from_big_endian(&data[32 * index..32 * (index + 1)]);
+
+    // The recipient is the only dynamic input, its content follows the head.
+    let string_offset = u256_to_usize(word(layout.recipient)).unwrap_or(usize::MAX);
+    if string_offset != head_len {
+        return Err(format!(
+            "Log of {} has the recipient at offset {}, {} expected",
+            to_hex(tx_eth_hash),
+            string_offset,
+            head_len
+        ));
+    }
+
+    let amount = word(layout.amount);
+    let unlock_at = layout.unlock_at.map(word).filter(|unlock_at| !unlock_at.is_zero());
+    let eth_fee = layout.eth_fee.map(word);
+    let string_len = u256_to_usize(U256::from_big_endian(&data[string_offset..string_offset + 32]))
+        .unwrap_or(usize::MAX);
+    let raw_glitch_address = &data[string_offset + 32..];
+
+    let decoded = match raw_glitch_address.get(..string_len) {
+        Some(raw) => decode_glitch_address(raw),
+        None => Err(format!("Recipient length {string_len} exceeds the log data")),
+    };
+
+    let (glitch_address, mut state, mut note) = match decoded {
+        Ok(glitch_address) => (glitch_address, STATE_TO_PROCESS, None),
+        Err(reason) => {
+            let raw = &raw_glitch_address[..string_len.min(raw_glitch_address.len())];
+            let escaped: String = String::from_utf8_lossy(raw)
+                .escape_default()
+                .take(GLITCH_ADDRESS_COLUMN_LEN)
+                .collect();
+            let note = format!("{reason} (raw recipient 0x{})", hex::encode(raw));
+            (escaped, STATE_SUSPICIOUS, Some(note))
+        }
+    };
+    let unlock_at = match unlock_at.map(unlock_time) {
+        Some(Ok(unlock_at)) => Some(unlock_at),
+        Some(Err(reason)) => {
+            if state == STATE_TO_PROCESS {
+                state = STATE_SUSPICIOUS;
+                note = Some(reason);
+            }
+            None
+        }
+        None => None,
+    };
+    if eth_fee.map_or(false, |eth_fee| eth_fee > max_stored_amount()) && state == STATE_TO_PROCESS {
+        state = STATE_SUSPICIOUS;
+        note = Some(format!("ETH fee {} is out of range", eth_fee.unwrap_or_default()));
+    }
+
+    Ok(Deposit {
+        tx_eth_hash: to_hex(tx_eth_hash),
+        from_eth_address: h256_to_address(*from),
+        amount,
+        glitch_address,
+        state,
+        note,
+        block_number: log.block_number.map(|number| number.as_u64()),
+        block_time: None,
+        unlock_at,
+        eth_fee,
+        event_version: EVENT_VERSION_V1,
+        labels: Vec::new(),
+    })
+}
+
+/// One deposit event watched on a pipeline: the contract emitting it, its
+/// topic and the layout of its inputs, so each version is decoded by its own
+/// ABI.
+#[derive(Debug, Clone)]
+pub struct DepositEvent {
+    pub version: u8,
+    /// Canonical signature, without input names.
+    pub signature: String,
+    pub address: H160,
+    pub topic: H256,
+    pub layout: EventLayout,
+}
+
+impl DepositEvent {
+    pub fn new(version: u8, address: H160, signature: &str) -> Self {
+        let (signature, layout) =
+            EventLayout::parse(signature).unwrap_or_else(|e| panic!("Invalid event signature: {e}"));
+
+        Self {
+            version,
+            topic: H256::from(keccak256(signature.as_bytes())),
+            signature,
+            address,
+            layout,
+        }
+    }
+
+    /// The configured event of the pipeline plus, during a contract
+    /// migration, the event of the new contract.
+    pub fn for_network(network_config: &Network) -> Vec<Self> {
+        let address: H160 = network_config.monitor_address.parse().unwrap();
+        let mut events = vec![Self::new(
+            EVENT_VERSION_V1,
+            address,
+            network_config
+                .event_signature
+                .as_deref()
+                .unwrap_or(DEPOSIT_EVENT_SIGNATURE),
+        )];
+
+        if let Some(migration) = &network_config.event_migration {
+            let address = match &migration.monitor_address {
+                Some(address) => address.parse().expect("Invalid migration monitor address!"),
+                None => address,
+            };
+            events.push(Self::new(EVENT_VERSION_V2, address, &migration.event_signature));
+        }
+
+        events
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        log.address == self.address && log.topics.first() == Some(&self.topic)
+    }
+
+    /// Decodes the log, rejecting it unless it has this event's layout.
+    pub fn decode(&self, log: &Log) -> Result<Deposit, String> {
+        let mut deposit = decode_with_layout(log, &self.layout)?;
+        deposit.event_version = self.version;
+        Ok(deposit)
+    }
+}
+
+/// Heuristics flagging deposits that decode to values no legitimate user
+/// would produce, so they wait for an operator instead of being paid.
+#[derive(Debug, Clone)]
+pub struct SanityChecks {
+    network: String,
+    /// Decimals of the token, to observe deposit sizes in whole tokens.
+    decimals: u8,
+    max_amount: Option<U256>,
+    /// Deposit limits of the token registry.
+    token_limits: (Option<U256>, Option<U256>),
+    max_daily_per_recipient: Option<U256>,
+    signer: Option<Public>,
+    ss58_prefix: Option<u16>,
+    sender_filter: Option<SenderFilter>,
+    tagger: DepositTagger,
+}
+
+impl SanityChecks {
+    pub fn new(config: &Config, network_config: &Network) -> Self {
+        let max_amount = network_config
+            .token_total_supply
+            .as_ref()
+            .map(|supply| U256::from_dec_str(supply).expect("Invalid token total supply!"));
+        let signer = network_config
+            .glitch_private_key
+            .as_ref()
+            .or(config.glitch_private_key.as_ref())
+            .and_then(|pk| sr25519::Pair::from_string(pk, None).ok())
+            .map(|pair| pair.public());
+        let token_limits = network_config.token(config).map_or((None, None), |token| {
+            (token.min_amount.map(U256::from), token.max_amount.map(U256::from))
+        });
+
+        Self {
+            network: network_config.name.clone(),
+            decimals: network_config.token(config).map_or(18, |token| token.decimals),
+            max_amount,
+            token_limits,
+            max_daily_per_recipient: network_config.max_daily_per_recipient.map(U256::from),
+            signer,
+            ss58_prefix: config.glitch_ss58_prefix,
+            sender_filter: network_config.sender_filter.clone(),
+            tagger: DepositTagger::new(config, network_config),
+        }
+    }
+
+    /// Whether the sender filter lets deposits of this sender be paid out.
+    fn sender_allowed(&self, sender: &str) -> bool {
+        let filter = match &self.sender_filter {
+            Some(filter) => filter,
+            None => return true,
+        };
+        let listed = |senders: &Option<Vec<String>>| {
+            senders
+                .as_ref()
+                .map(|senders| senders.iter().any(|listed| listed.eq_ignore_ascii_case(sender)))
+        };
+
+        listed(&filter.include).unwrap_or(true) && !listed(&filter.exclude).unwrap_or(false)
+    }
+
+    /// Applies the sender filter, the sanity checks and the tag rules.
+    /// Deposits of filtered senders are skipped, or moved to TEST if so
+    /// configured.
+    pub fn screen(&self, mut deposit: Deposit) -> Result<Deposit, SkippedLog> {
+        if let Ok(amount) = deposit.amount.to_string().parse::<f64>() {
+            metrics::DEPOSIT_AMOUNT
+                .with_label_values(&[&self.network])
+                .observe(amount / 10f64.powi(self.decimals as i32));
+        }
+
+        if self.sender_allowed(&deposit.from_eth_address) {
+            let mut deposit = self.apply(deposit);
+            self.tagger.tag(&mut deposit);
+            return Ok(deposit);
+        }
+
+        let action = self
+            .sender_filter
+            .as_ref()
+            .and_then(|filter| filter.action)
+            .unwrap_or_default();
+        info!(
+            "Deposit {} from filtered sender {}: {:?}",
+            deposit.tx_eth_hash, deposit.from_eth_address, action
+        );
+        match action {
+            SenderFilterAction::Skip => Err(SkippedLog::of_deposit(
+                &self.network,
+                &deposit,
+                SkipReason::FilteredSender,
+                format!("Sender {} is filtered", deposit.from_eth_address),
+            )),
+            SenderFilterAction::Test => {
+                deposit.state = STATE_TEST;
+                deposit.note = Some("Sender filtered, not paid out".to_string());
+                Ok(deposit)
+            }
+        }
+    }
+
+    pub fn reason(&self, deposit: &Deposit) -> Option<String> {
+        if deposit.amount.is_zero() {
+            return Some("Zero amount".to_string());
+        }
+
+        if let Some(max_amount) = self.max_amount {
+            if deposit.amount > max_amount {
+                return Some(format!(
+                    "Amount {} exceeds the token total supply",
+                    deposit.amount
+                ));
+            }
+        }
+
+        let (min_deposit, max_deposit) = self.token_limits;
+        if let Some(min_deposit) = min_deposit.filter(|min_deposit| deposit.amount < *min_deposit) {
+            return Some(format!("Amount {} is below the token minimum of {}", deposit.amount, min_deposit));
+        }
+        if let Some(max_deposit) = max_deposit.filter(|max_deposit| deposit.amount > *max_deposit) {
+            return Some(format!("Amount {} is over the token maximum of {}", deposit.amount, max_deposit));
+        }
+
+        if let Some(signer) = self.signer {
+            if parse_glitch_address(&deposit.glitch_address, None).ok() == Some(signer) {
+                return Some("Recipient is the bridge signer".to_string());
+            }
+        }
+
+        None
+    }
+
+    pub fn has_daily_cap(&self) -> bool {
+        self.max_daily_per_recipient.is_some()
+    }
+
+    /// Holds the deposits that take their recipient over the daily cap,
+    /// counting what the recipient got from the pipeline on the UTC day of
+    /// the deposit's block, or of now without its time. Deposits already
+    /// stored were counted when first seen and are skipped. Runs right
+    /// before the deposits are inserted, with the inserts of the pipeline
+    /// serialized, so the totals read include every earlier deposit.
+    /// Returns the indexes of the deposits it held.
+    pub async fn apply_daily_cap(&self, database_engine: &DatabaseEngine, deposits: &mut [Deposit]) -> Vec<usize> {
+        let max_daily_per_recipient = match self.max_daily_per_recipient {
+            Some(max_daily_per_recipient) => max_daily_per_recipient,
+            None => return vec![],
+        };
+        let now = Utc::now().timestamp();
+        let day = SECONDS_PER_DAY as i64;
+        let day_of = |deposit: &Deposit| deposit.block_time.unwrap_or(now) / day * day;
+
+        let days: HashSet<i64> = deposits
+            .iter()
+            .filter(|deposit| deposit.state == STATE_TO_PROCESS)
+            .map(day_of)
+            .collect();
+        let mut known: HashSet<String> = HashSet::new();
+        let mut totals: HashMap<(i64, String), U256> = HashMap::new();
+        for day in days {
+            for (tx_eth_hash, recipient, amount) in database_engine.recipient_deposits_on(&self.network, day).await {
+                let total = totals.entry((day, recipient)).or_default();
+                *total = total.saturating_add(U256::from(amount));
+                known.insert(tx_eth_hash);
+            }
+        }
+
+        let mut held = Vec::new();
+        for (index, deposit) in deposits.iter_mut().enumerate() {
+            if deposit.state != STATE_TO_PROCESS || known.contains(&deposit.tx_eth_hash) {
+                continue;
+            }
+            let key = (day_of(deposit), deposit.glitch_address.clone());
+            let total = totals.get(&key).copied().unwrap_or_default();
+            let new_total = total.saturating_add(deposit.amount);
+            if new_total > max_daily_per_recipient {
+                deposit.state = STATE_SUSPICIOUS;
+                deposit.note = Some(format!(
+                    "Recipient already got {} that day, {} more is over the daily cap of {}",
+                    total, deposit.amount, max_daily_per_recipient
+                ));
+                held.push(index);
+                continue;
+            }
+            totals.insert(key, new_total);
+        }
+        held
+    }
+
+    /// Normalizes the recipient to SS58 and marks the deposit as SUSPICIOUS
+    /// when any heuristic matches. Deposits already held are left as they are. Unparseable recipients are kept as sent,
+    /// the payer records the error.
+    pub fn apply(&self, mut deposit: Deposit) -> Deposit {
+        // Stored without amount, so it is kept here whatever the state.
+        if deposit.amount > max_stored_amount() {
+            let reason = format!("Amount {} does not fit the amount column", deposit.amount);
+            deposit.note = Some(match deposit.note.take() {
+                Some(note) => format!("{note}; {reason}"),
+                None => reason,
+            });
+            deposit.state = STATE_SUSPICIOUS;
+            return deposit;
+        }
+
+        if deposit.state != STATE_TO_PROCESS {
+            return deposit;
+        }
+
+        if let Ok(address) = normalize_glitch_address(&deposit.glitch_address, self.ss58_prefix) {
+            deposit.glitch_address = address;
+        }
+
+        if let Some(reason) = self.reason(&deposit) {
+            deposit.state = STATE_SUSPICIOUS;
+            deposit.note = Some(reason);
+        }
+        deposit
+    }
+}
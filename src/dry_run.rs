@@ -0,0 +1,141 @@
+use codec::Decode;
+use serde_json::{json, Value};
+
+use crate::payout::Simulation;
+use crate::types::GlitchApi;
+
+/// `sp_runtime::InvalidTransaction`.
+#[derive(Decode, Debug)]
+enum InvalidTransaction {
+    Call,
+    Payment,
+    Future,
+    Stale,
+    BadProof,
+    AncientBirthBlock,
+    ExhaustsResources,
+    Custom(#[allow(dead_code)] u8),
+    BadMandatory,
+    MandatoryValidation,
+    BadSigner,
+}
+
+/// `sp_runtime::UnknownTransaction`.
+#[derive(Decode, Debug)]
+enum UnknownTransaction {
+    CannotLookup,
+    NoUnsignedValidator,
+    Custom(#[allow(dead_code)] u8),
+}
+
+/// `sp_runtime::TransactionValidityError`.
+#[derive(Decode, Debug)]
+enum TransactionValidityError {
+    Invalid(InvalidTransaction),
+    Unknown(UnknownTransaction),
+}
+
+/// `sp_runtime::ModuleError`, the message is not encoded.
+#[derive(Decode, Debug)]
+struct ModuleError {
+    index: u8,
+    error: [u8; 4],
+}
+
+/// `sp_runtime::TokenError`.
+#[derive(Decode, Debug)]
+enum TokenError {
+    NoFunds,
+    WouldDie,
+    BelowMinimum,
+    CannotCreate,
+    UnknownAsset,
+    Frozen,
+    Unsupported,
+}
+
+/// `sp_runtime::DispatchError`.
+#[derive(Decode, Debug)]
+enum DispatchError {
+    Other,
+    CannotLookup,
+    BadOrigin,
+    Module(ModuleError),
+    ConsumerRemaining,
+    NoProviders,
+    TooManyConsumers,
+    Token(TokenError),
+    Arithmetic(#[allow(dead_code)] u8),
+    Transactional(#[allow(dead_code)] u8),
+}
+
+/// `sp_runtime::ApplyExtrinsicResult`.
+type ApplyExtrinsicResult = Result<Result<(), DispatchError>, TransactionValidityError>;
+
+/// `Pallet.Error` of a module error, from the runtime metadata.
+fn module_error_name(api: &GlitchApi, error: &ModuleError) -> String {
+    match api.metadata.error(error.index, error.error[0]) {
+        Ok(metadata) => format!("{}.{}", metadata.pallet(), metadata.error()),
+        Err(_) => format!("module {} error {}", error.index, error.error[0]),
+    }
+}
+
+fn classify_dispatch_error(api: &GlitchApi, error: DispatchError) -> Simulation {
+    if let DispatchError::Module(module_error) = &error {
+        let name = module_error_name(api, module_error);
+        let reason = format!("the dry run failed with {name}");
+        return match name.as_str() {
+            // The signer holding none of the asset is out of funds too.
+            "Balances.InsufficientBalance" | "Assets.BalanceLow" | "Assets.NoAccount" => {
+                Simulation::InsufficientFunds(reason)
+            }
+            "Balances.KeepAlive" | "Assets.WouldDie" => Simulation::WouldReapSigner(reason),
+            "Balances.ExistentialDeposit" | "Assets.BelowMinimum" => Simulation::BadDestination(reason),
+            // Frozen may be the signer's account or the whole asset.
+            _ => Simulation::Rejected(reason),
+        };
+    }
+
+    let reason = format!("the dry run failed with {error:?}");
+    match error {
+        DispatchError::Token(TokenError::NoFunds) => Simulation::InsufficientFunds(reason),
+        DispatchError::Token(TokenError::WouldDie) => Simulation::WouldReapSigner(reason),
+        DispatchError::Token(TokenError::BelowMinimum | TokenError::CannotCreate) | DispatchError::CannotLookup => {
+            Simulation::BadDestination(reason)
+        }
+        _ => Simulation::Rejected(reason),
+    }
+}
+
+/// Runs the hex encoded extrinsic through `system_dryRun` on the best block
+/// and classifies the failures a payout can run into.
+pub fn dry_run(api: &GlitchApi, xt_hex: &str) -> Simulation {
+    let request = json!({ "jsonrpc": "2.0", "id": "1", "method": "system_dryRun", "params": [xt_hex] });
+    let encoded = match api.get_request(request) {
+        Ok(Some(response)) => match serde_json::from_str::<Value>(&response) {
+            Ok(Value::String(encoded)) => encoded,
+            _ => return Simulation::Unavailable(format!("unexpected dry run response {response}")),
+        },
+        Ok(None) => return Simulation::Unavailable("empty dry run response".to_string()),
+        Err(e) => return Simulation::Unavailable(format!("system_dryRun failed: {e:?}")),
+    };
+
+    let bytes = match hex::decode(encoded.trim_start_matches("0x")) {
+        Ok(bytes) => bytes,
+        Err(e) => return Simulation::Unavailable(format!("dry run result is not hex: {e}")),
+    };
+    match ApplyExtrinsicResult::decode(&mut bytes.as_slice()) {
+        Ok(Ok(Ok(()))) => Simulation::Passes,
+        Ok(Ok(Err(error))) => classify_dispatch_error(api, error),
+        Ok(Err(TransactionValidityError::Invalid(InvalidTransaction::Payment))) => {
+            Simulation::InsufficientFunds("the signer can't pay the network fee".to_string())
+        }
+        Ok(Err(TransactionValidityError::Invalid(error))) => {
+            Simulation::Rejected(format!("the extrinsic is not valid: {error:?}"))
+        }
+        Ok(Err(TransactionValidityError::Unknown(error))) => {
+            Simulation::Rejected(format!("the validity of the extrinsic is unknown: {error:?}"))
+        }
+        Err(e) => Simulation::Unavailable(format!("dry run result could not be decoded: {e}")),
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::config::{DuplicateRecipients, Notification};
+use crate::database::DatabaseEngine;
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+
+/// Deposits of a pipeline to one Glitch address within the window.
+pub struct RecipientCluster {
+    pub scanner_name: String,
+    pub recipient: String,
+    pub senders: BTreeSet<String>,
+    pub deposits: u64,
+}
+
+/// Recipients of the last `window_in_minutes` paid by at least `min_senders`
+/// distinct senders, the most paid first.
+pub async fn duplicate_recipients(
+    database_engine: &DatabaseEngine,
+    window_in_minutes: u64,
+    min_senders: usize,
+) -> Vec<RecipientCluster> {
+    let mut clusters: BTreeMap<(String, String), RecipientCluster> = BTreeMap::new();
+    for (scanner_name, sender, recipient) in database_engine.recent_deposits(window_in_minutes).await {
+        let cluster = clusters
+            .entry((scanner_name.clone(), recipient.clone()))
+            .or_insert_with(|| RecipientCluster {
+                scanner_name,
+                recipient,
+                senders: BTreeSet::new(),
+                deposits: 0,
+            });
+        cluster.senders.insert(sender.to_lowercase());
+        cluster.deposits += 1;
+    }
+
+    let mut clusters: Vec<RecipientCluster> = clusters
+        .into_values()
+        .filter(|cluster| cluster.senders.len() >= min_senders)
+        .collect();
+    clusters.sort_by(|a, b| b.senders.len().cmp(&a.senders.len()));
+    clusters
+}
+
+/// Logs the recipients paid by at least `min_senders` distinct senders.
+pub async fn report_duplicate_recipients(database_engine: &DatabaseEngine, window_in_minutes: u64, min_senders: usize) {
+    let clusters = duplicate_recipients(database_engine, window_in_minutes, min_senders).await;
+    info!(
+        "{} recipient(s) paid by {} or more distinct senders in the last {} minutes",
+        clusters.len(),
+        min_senders,
+        window_in_minutes
+    );
+    for cluster in clusters {
+        info!(
+            "{} {}: {} senders, {} deposits ({})",
+            cluster.scanner_name,
+            cluster.recipient,
+            cluster.senders.len(),
+            cluster.deposits,
+            cluster.senders.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+}
+
+/// Exports, by pipeline, how many recipients reach the threshold and the
+/// most senders any recipient has, and alerts once per recipient when it
+/// reaches it, as `report_duplicate_recipients` lists them. Nothing is held:
+/// the deposits are left for manual review.
+pub async fn monitor_duplicate_recipients(
+    settings: DuplicateRecipients,
+    networks: Vec<String>,
+    database_engine: Arc<DatabaseEngine>,
+    smtp_config: Notification,
+    mut ticker: Ticker,
+) {
+    info!("Duplicate recipients monitor running!");
+    let window_in_minutes = settings.window_in_minutes();
+    let max_senders = settings.max_distinct_senders();
+    let mut alerted: HashSet<(String, String)> = HashSet::new();
+
+    loop {
+        ticker.tick().await;
+
+        // Every recipient with two senders or more, to export the maximum.
+        let clusters = duplicate_recipients(&database_engine, window_in_minutes, 2).await;
+
+        for network in networks.iter() {
+            let of_network = clusters.iter().filter(|cluster| &cluster.scanner_name == network);
+            let over = of_network.clone().filter(|cluster| cluster.senders.len() >= max_senders).count();
+            let most = of_network.map(|cluster| cluster.senders.len()).max().unwrap_or(0);
+            metrics::DUPLICATE_RECIPIENTS.with_label_values(&[network]).set(over as i64);
+            metrics::MAX_SENDERS_PER_RECIPIENT.with_label_values(&[network]).set(most as i64);
+        }
+
+        let flagged: HashSet<(String, String)> = clusters
+            .iter()
+            .filter(|cluster| cluster.senders.len() >= max_senders)
+            .map(|cluster| (cluster.scanner_name.clone(), cluster.recipient.clone()))
+            .collect();
+        alerted.retain(|key| flagged.contains(key));
+
+        for cluster in clusters.iter().filter(|cluster| cluster.senders.len() >= max_senders) {
+            if !alerted.insert((cluster.scanner_name.clone(), cluster.recipient.clone())) {
+                continue;
+            }
+
+            let message = format!(
+                "{} distinct senders paid {} deposits to {} on {} in the last {} minutes, reaching the limit of {}. Review them manually: {}",
+                cluster.senders.len(),
+                cluster.deposits,
+                cluster.recipient,
+                cluster.scanner_name,
+                window_in_minutes,
+                max_senders,
+                cluster.senders.iter().cloned().collect::<Vec<_>>().join(", ")
+            );
+            warn!("{}", message);
+            notify(&smtp_config, "Bridge deposits to a shared recipient!", &message).await;
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::Rng;
+
+/// Marks values written encrypted, so rows stored before encryption was
+/// enabled are still read as they are.
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM encryption of sensitive tx columns (recipient and error).
+/// Values are stored as `enc:v1:` followed by the hex of nonce and ciphertext.
+pub struct ColumnCipher {
+    cipher: Aes256Gcm,
+}
+
+impl ColumnCipher {
+    /// Reads the 32 byte key, hex encoded, from the environment variable.
+    /// A KMS can provide it by injecting the variable at startup.
+    pub fn from_env(key_env: &str) -> Self {
+        let key = std::env::var(key_env)
+            .unwrap_or_else(|_| panic!("The column encryption key {key_env} is not set!"));
+        let key = hex::decode(key.trim().trim_start_matches("0x"))
+            .expect("The column encryption key must be hex encoded!");
+        let cipher = Aes256Gcm::new_from_slice(&key).expect("The column encryption key must be 32 bytes long!");
+
+        Self { cipher }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = rand::thread_rng().gen::<[u8; NONCE_LEN]>();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .expect("Column encryption failed!");
+
+        format!("{PREFIX}{}{}", hex::encode(nonce), hex::encode(ciphertext))
+    }
+
+    /// Panics on values that can't be decrypted: the key is wrong or the row
+    /// was tampered with, and neither should be paid out.
+    pub fn decrypt(&self, value: String) -> String {
+        let encoded = match value.strip_prefix(PREFIX) {
+            Some(encoded) => encoded,
+            None => return value,
+        };
+
+        let bytes = hex::decode(encoded).expect("Malformed encrypted column!");
+        if bytes.len() < NONCE_LEN {
+            panic!("Malformed encrypted column!");
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .expect("Column decryption failed, is the key right?");
+
+        String::from_utf8(plaintext).expect("Decrypted column is not UTF-8!")
+    }
+}
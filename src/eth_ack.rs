@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use secp256k1::SecretKey;
+use web3::api::{Accounts, Eth, Namespace};
+use web3::signing::{keccak256, Key, SecretKeyRef};
+use web3::types::{BlockNumber, Bytes, TransactionParameters, H160, H256, U256};
+
+use crate::config::{EthAck, Network};
+use crate::database::DatabaseEngine;
+use crate::scheduler::Ticker;
+use crate::types::{eth_transport, to_hex};
+
+const DEFAULT_ACK_FUNCTION: &str = "acknowledgeDeposit(bytes32,bytes32)";
+const DEFAULT_GAS_LIMIT: u64 = 100_000;
+/// Acknowledgments sent per tick, so a backlog doesn't flood the mempool.
+const ACKS_PER_TICK: u32 = 20;
+
+fn parse_hash(hash: &str) -> Option<H256> {
+    hash.trim_start_matches("0x").parse().ok()
+}
+
+/// Calldata of `function(bytes32,bytes32)` with both hashes.
+fn ack_calldata(function: &str, tx_eth_hash: H256, tx_glitch_hash: H256) -> Bytes {
+    let mut data = keccak256(function.as_bytes())[..4].to_vec();
+    data.extend_from_slice(tx_eth_hash.as_bytes());
+    data.extend_from_slice(tx_glitch_hash.as_bytes());
+    Bytes(data)
+}
+
+fn secret_key(key_env: &str) -> SecretKey {
+    let key = std::env::var(key_env).unwrap_or_else(|_| panic!("The acknowledgment key {key_env} is not set!"));
+    let key = hex::decode(key.trim().trim_start_matches("0x")).expect("The acknowledgment key must be hex encoded!");
+    SecretKey::from_slice(&key).expect("Invalid acknowledgment key!")
+}
+
+/// Calls the acknowledgment function on Ethereum for every deposit the
+/// pipeline paid, recording the hash of the call with the tx. Calls that are
+/// dropped from the mempool are not retried; clear `ack_tx_hash` to resend.
+pub async fn acknowledge_deposits(
+    eth_ack: EthAck,
+    network_config: Network,
+    database_engine: Arc<DatabaseEngine>,
+    mut ticker: Ticker,
+) {
+    let key = secret_key(&eth_ack.private_key_env);
+    let from = SecretKeyRef::new(&key).address();
+    let contract: H160 = eth_ack
+        .contract_address
+        .as_ref()
+        .unwrap_or(&network_config.monitor_address)
+        .parse()
+        .expect("Invalid acknowledgment contract address!");
+    let function = eth_ack.function.clone().unwrap_or_else(|| DEFAULT_ACK_FUNCTION.to_string());
+    let gas = U256::from(eth_ack.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT));
+
+    let transport = eth_transport(&network_config.eth_node_url(), network_config.eth_headers())
+        .await
+        .unwrap_or_else(|e| panic!("Error connecting with {} network: {:?}", network_config.network, e));
+    let eth = Eth::new(transport.clone());
+    let accounts = Accounts::new(transport);
+    info!("Acknowledging the deposits of {} from {:?}", network_config.name, from);
+
+    loop {
+        ticker.tick().await;
+
+        let txs = database_engine
+            .txs_to_ack(&network_config.name, eth_ack.start_at_tx_id.unwrap_or(0), ACKS_PER_TICK)
+            .await;
+        if txs.is_empty() {
+            continue;
+        }
+
+        // The nonce is tracked here, the node's would repeat within a tick.
+        let mut nonce = match eth.transaction_count(from, Some(BlockNumber::Pending)).await {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                error!("Error reading the nonce of the acknowledgment account: {:?}", e);
+                continue;
+            }
+        };
+
+        for (id, tx_eth_hash, tx_glitch_hash) in txs {
+            let (eth_hash, glitch_hash) = match (parse_hash(&tx_eth_hash), parse_hash(&tx_glitch_hash)) {
+                (Some(eth_hash), Some(glitch_hash)) => (eth_hash, glitch_hash),
+                _ => {
+                    warn!("Tx {} has hashes that can't be acknowledged, skipping it.", id);
+                    database_engine.record_tx_ack(id, "unacknowledgeable".to_string()).await;
+                    continue;
+                }
+            };
+
+            let transaction = TransactionParameters {
+                nonce: Some(nonce),
+                to: Some(contract),
+                gas,
+                data: ack_calldata(&function, eth_hash, glitch_hash),
+                chain_id: network_config.chain_id,
+                ..Default::default()
+            };
+            let signed = match accounts.sign_transaction(transaction, &key).await {
+                Ok(signed) => signed,
+                Err(e) => {
+                    error!("Error signing the acknowledgment of tx {}: {:?}", id, e);
+                    break;
+                }
+            };
+
+            match eth.send_raw_transaction(signed.raw_transaction).await {
+                Ok(hash) => {
+                    info!("Tx {} acknowledged on Ethereum in {}", id, to_hex(hash));
+                    database_engine.record_tx_ack(id, to_hex(hash)).await;
+                    nonce += U256::one();
+                }
+                Err(e) => {
+                    error!("Error sending the acknowledgment of tx {}: {:?}", id, e);
+                    break;
+                }
+            }
+        }
+    }
+}
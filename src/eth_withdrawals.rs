@@ -0,0 +1,390 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use secp256k1::SecretKey;
+use serde_json::{json, Value};
+use web3::api::{Accounts, Eth, Namespace};
+use web3::signing::{keccak256, Key, SecretKeyRef};
+use web3::types::{BlockNumber, Bytes, TransactionId, TransactionParameters, H160, H256, U256};
+
+use crate::config::{Network, SafeRelay, WithdrawalRelease};
+use crate::database::{DatabaseEngine, ProcessingWithdrawal, WithdrawalToProcess};
+use crate::glitch_scanner::withdrawals_scanner_name;
+use crate::scheduler::Ticker;
+use crate::types::{eth_transport, to_hex, EthTransport};
+
+const DEFAULT_RELEASE_FUNCTION: &str = "release(address,uint256,bytes32)";
+const DEFAULT_GAS_LIMIT: u64 = 150_000;
+/// Releases sent or proposed per tick.
+const RELEASES_PER_TICK: u32 = 20;
+const DOMAIN_TYPE: &str = "EIP712Domain(uint256 chainId,address verifyingContract)";
+const SAFE_TX_TYPE: &str = "SafeTx(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,uint256 nonce)";
+
+/// Calldata of `function(address,uint256,bytes32)` with the recipient, the
+/// amount and the withdrawal event id, which the contract uses to refuse a
+/// release twice.
+fn release_calldata(function: &str, to: H160, amount: U256, event_id: H256) -> Bytes {
+    let mut data = keccak256(function.as_bytes())[..4].to_vec();
+    data.extend_from_slice(H256::from(to).as_bytes());
+    let mut amount_word = [0u8; 32];
+    amount.to_big_endian(&mut amount_word);
+    data.extend_from_slice(&amount_word);
+    data.extend_from_slice(event_id.as_bytes());
+    Bytes(data)
+}
+
+fn secret_key(key_env: &str) -> SecretKey {
+    let key = std::env::var(key_env).unwrap_or_else(|_| panic!("The release key {key_env} is not set!"));
+    let key = hex::decode(key.trim().trim_start_matches("0x")).expect("The release key must be hex encoded!");
+    SecretKey::from_slice(&key).expect("Invalid release key!")
+}
+
+/// EIP-55 mixed case form, which the Safe transaction service requires.
+fn checksummed(address: H160) -> String {
+    let lower = hex::encode(address.as_bytes());
+    let hash = keccak256(lower.as_bytes());
+    let mixed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{mixed}")
+}
+
+fn word(value: U256) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    word
+}
+
+/// EIP-712 hash of a call from the Safe, `safeTxHash` in the Safe contracts.
+/// No refund, gas paid by the executor.
+fn safe_tx_hash(chain_id: u64, safe: H160, to: H160, data: &[u8], nonce: U256) -> H256 {
+    let mut domain = keccak256(DOMAIN_TYPE.as_bytes()).to_vec();
+    domain.extend_from_slice(&word(U256::from(chain_id)));
+    domain.extend_from_slice(H256::from(safe).as_bytes());
+
+    let mut safe_tx = keccak256(SAFE_TX_TYPE.as_bytes()).to_vec();
+    safe_tx.extend_from_slice(H256::from(to).as_bytes());
+    safe_tx.extend_from_slice(&[0u8; 32]); // value
+    safe_tx.extend_from_slice(&keccak256(data));
+    safe_tx.extend_from_slice(&[0u8; 32 * 5]); // operation, safeTxGas, baseGas, gasPrice, gasToken
+    safe_tx.extend_from_slice(&[0u8; 32]); // refundReceiver
+    safe_tx.extend_from_slice(&word(nonce));
+
+    let mut message = vec![0x19, 0x01];
+    message.extend_from_slice(&keccak256(&domain));
+    message.extend_from_slice(&keccak256(&safe_tx));
+    H256(keccak256(&message))
+}
+
+async fn get_json(url: &str) -> Result<Value, String> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Safe transaction service error: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!("Safe transaction service answered {}", response.status()));
+    }
+    let body = response.text().await.map_err(|e| e.to_string())?;
+    serde_json::from_str(&body).map_err(|e| format!("Unreadable answer of the Safe transaction service: {e}"))
+}
+
+/// Next nonce of the Safe, after the ones already proposed and not executed.
+async fn next_safe_nonce(service: &str, safe: &str) -> Result<U256, String> {
+    let info = get_json(&format!("{service}/api/v1/safes/{safe}/")).await?;
+    let nonce = info["nonce"]
+        .as_u64()
+        .or_else(|| info["nonce"].as_str().and_then(|nonce| nonce.parse().ok()))
+        .ok_or("the Safe has no nonce")?;
+
+    let pending = get_json(&format!(
+        "{service}/api/v1/safes/{safe}/multisig-transactions/?executed=false&nonce__gte={nonce}&ordering=-nonce&limit=1"
+    ))
+    .await?;
+    let last_pending = pending["results"][0]["nonce"].as_u64();
+
+    Ok(U256::from(match last_pending {
+        Some(last) if last >= nonce => last + 1,
+        _ => nonce,
+    }))
+}
+
+/// Proposes a call to the Safe; the owners confirm and execute it from the
+/// Safe app. Returns the `safeTxHash` of the proposal.
+async fn propose(
+    safe_relay: &SafeRelay,
+    proposer: &SecretKey,
+    chain_id: u64,
+    safe: H160,
+    to: H160,
+    data: &Bytes,
+    nonce: U256,
+) -> Result<H256, String> {
+    let hash = safe_tx_hash(chain_id, safe, to, &data.0, nonce);
+    let signature = SecretKeyRef::new(proposer)
+        .sign(hash.as_bytes(), None)
+        .map_err(|e| format!("Error signing the proposal: {e:?}"))?;
+    let mut signature_bytes = signature.r.as_bytes().to_vec();
+    signature_bytes.extend_from_slice(signature.s.as_bytes());
+    signature_bytes.push(signature.v as u8);
+
+    let zero = checksummed(H160::zero());
+    let body = json!({
+        "to": checksummed(to),
+        "value": "0",
+        "data": format!("0x{}", hex::encode(&data.0)),
+        "operation": 0,
+        "safeTxGas": "0",
+        "baseGas": "0",
+        "gasPrice": "0",
+        "gasToken": zero,
+        "refundReceiver": zero,
+        "nonce": nonce.as_u64(),
+        "contractTransactionHash": to_hex(hash),
+        "sender": checksummed(SecretKeyRef::new(proposer).address()),
+        "signature": format!("0x{}", hex::encode(signature_bytes)),
+        "origin": "glitch-bridge",
+    });
+
+    let service = safe_relay.service_url.trim_end_matches('/');
+    let response = reqwest::Client::new()
+        .post(format!("{service}/api/v1/safes/{}/multisig-transactions/", checksummed(safe)))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Safe transaction service error: {e}"))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(format!("Safe transaction service answered {}: {}", status, response.text().await.unwrap_or_default()));
+    }
+
+    Ok(hash)
+}
+
+/// Settles the withdrawals left PROCESSING by an earlier tick or run. One
+/// whose release call was mined is PROCESSED, or FAILED if the call
+/// reverted; one whose call the node doesn't know, or that was claimed but
+/// never sent, goes back to the queue, as the contract refuses to release
+/// the same event twice. Calls still pending are left for the next tick.
+async fn settle_withdrawals(eth: &Eth<EthTransport>, name: &str, database_engine: &DatabaseEngine) {
+    for withdrawal in database_engine.processing_withdrawals(name).await {
+        let ProcessingWithdrawal { id, version, eth_tx_hash } = withdrawal;
+        let hash = match eth_tx_hash.as_deref().map(|hash| hash.trim_start_matches("0x").parse::<H256>()) {
+            Some(Ok(hash)) => hash,
+            Some(Err(_)) => {
+                database_engine
+                    .fail_withdrawal(id, version, format!("Unreadable release call hash {eth_tx_hash:?}"))
+                    .await;
+                continue;
+            }
+            None => {
+                warn!("Withdrawal {} was claimed but its release not sent, back to the queue.", id);
+                database_engine
+                    .release_withdrawal_claim(id, version, "Interrupted before the release was sent".to_string())
+                    .await;
+                continue;
+            }
+        };
+
+        let receipt = match eth.transaction_receipt(hash).await {
+            Ok(receipt) => receipt.filter(|receipt| receipt.block_number.is_some()),
+            Err(e) => {
+                error!("Error reading the receipt of the release of withdrawal {}: {:?}", id, e);
+                continue;
+            }
+        };
+        match receipt {
+            Some(receipt) if receipt.status == Some(1.into()) => {
+                info!("Withdrawal {} released in {}", id, to_hex(hash));
+                database_engine
+                    .record_withdrawal_release(id, version, Some(to_hex(hash)), None)
+                    .await;
+            }
+            Some(_) => {
+                let message = format!("The release call {} reverted", to_hex(hash));
+                error!("Withdrawal {}: {}", id, message);
+                database_engine.fail_withdrawal(id, version, message).await;
+            }
+            None => match eth.transaction(TransactionId::Hash(hash)).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    warn!("The release {} of withdrawal {} was dropped, back to the queue.", to_hex(hash), id);
+                    database_engine
+                        .release_withdrawal_claim(id, version, format!("The release call {} was dropped", to_hex(hash)))
+                        .await;
+                }
+                Err(e) => error!("Error reading the release of withdrawal {}: {:?}", id, e),
+            },
+        }
+    }
+}
+
+/// Releases on the network the withdrawals queued by the Glitch scanner of
+/// the pipeline: each one is claimed, then either sent from the release key,
+/// staying PROCESSING with the hash of the call until its receipt settles
+/// it, or proposed to the Safe, marking it PROPOSED with the `safeTxHash`
+/// until its owners execute it. A call that fails to be sent or proposed
+/// puts the withdrawal back in the queue with the error; a withdrawal that
+/// can't be released at all is FAILED.
+pub async fn release_withdrawals(
+    release: WithdrawalRelease,
+    network_config: Network,
+    database_engine: Arc<DatabaseEngine>,
+    mut ticker: Ticker,
+) {
+    let name = withdrawals_scanner_name(&network_config.name);
+    let contract: H160 = release
+        .contract_address
+        .as_ref()
+        .unwrap_or(&network_config.monitor_address)
+        .parse()
+        .expect("Invalid release contract address!");
+    let function = release.function.clone().unwrap_or_else(|| DEFAULT_RELEASE_FUNCTION.to_string());
+    let gas = U256::from(release.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT));
+
+    let transport = eth_transport(&network_config.eth_node_url(), network_config.eth_headers())
+        .await
+        .unwrap_or_else(|e| panic!("Error connecting with {} network: {:?}", network_config.network, e));
+    let eth = Eth::new(transport.clone());
+    let accounts = Accounts::new(transport);
+
+    let (key, safe) = match (&release.safe, &release.private_key_env) {
+        (Some(safe_relay), _) => {
+            let safe: H160 = safe_relay.safe_address.parse().expect("Invalid Safe address!");
+            (secret_key(&safe_relay.proposer_key_env), Some((safe_relay, safe)))
+        }
+        (None, Some(key_env)) => (secret_key(key_env), None),
+        (None, None) => panic!("The withdrawal release of {} needs a private_key_env or a safe!", network_config.name),
+    };
+    let from = SecretKeyRef::new(&key).address();
+    let chain_id = match network_config.chain_id {
+        Some(chain_id) => chain_id,
+        None => eth.chain_id().await.expect("Error reading the chain id").as_u64(),
+    };
+    match safe {
+        Some((_, safe)) => info!("Proposing the withdrawals of {} to Safe {:?} from {:?}", network_config.name, safe, from),
+        None => info!("Releasing the withdrawals of {} from {:?}", network_config.name, from),
+    }
+
+    loop {
+        ticker.tick().await;
+
+        settle_withdrawals(&eth, &name, &database_engine).await;
+        let withdrawals = database_engine.withdrawals_to_process(&name, RELEASES_PER_TICK).await;
+        if withdrawals.is_empty() {
+            continue;
+        }
+
+        // Nonces are tracked here, the node's and the service's would repeat
+        // within a tick.
+        let nonce = match safe {
+            Some((safe_relay, safe)) => {
+                next_safe_nonce(safe_relay.service_url.trim_end_matches('/'), &checksummed(safe)).await
+            }
+            None => eth
+                .transaction_count(from, Some(BlockNumber::Pending))
+                .await
+                .map_err(|e| format!("{e:?}")),
+        };
+        let mut nonce = match nonce {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                error!("Error reading the nonce for the withdrawals of {}: {}", network_config.name, e);
+                continue;
+            }
+        };
+
+        for withdrawal in withdrawals {
+            let WithdrawalToProcess { id, version, .. } = withdrawal;
+            let (to, amount, event_id) = match (
+                withdrawal.to_eth_address.parse::<H160>(),
+                withdrawal.amount,
+                withdrawal.event_id.trim_start_matches("0x").parse::<H256>(),
+            ) {
+                (Ok(to), Some(amount), Ok(event_id)) => (to, U256::from(amount), event_id),
+                _ => {
+                    let message = "Unreadable recipient, amount or event id".to_string();
+                    warn!("Withdrawal {} can't be released: {}.", id, message);
+                    database_engine.fail_withdrawal(id, version, message).await;
+                    continue;
+                }
+            };
+
+            let version = match database_engine.claim_withdrawal(id, version).await {
+                Some(version) => version,
+                None => continue,
+            };
+            let data = release_calldata(&function, to, amount, event_id);
+
+            let result = match safe {
+                Some((safe_relay, safe)) => propose(safe_relay, &key, chain_id, safe, contract, &data, nonce)
+                    .await
+                    .map(|hash| (None, Some(to_hex(hash)))),
+                None => {
+                    let transaction = TransactionParameters {
+                        nonce: Some(nonce),
+                        to: Some(contract),
+                        gas,
+                        data,
+                        chain_id: Some(chain_id),
+                        ..Default::default()
+                    };
+                    match accounts.sign_transaction(transaction, &key).await {
+                        // Recorded first, so a call sent but not confirmed
+                        // is settled by its receipt after a restart.
+                        Ok(signed) => match database_engine
+                            .record_withdrawal_sent(id, version, to_hex(signed.transaction_hash))
+                            .await
+                        {
+                            Some(_) => match eth.send_raw_transaction(signed.raw_transaction).await {
+                                Ok(hash) => Ok((Some(to_hex(hash)), None)),
+                                // It may have reached the node anyway, its
+                                // receipt or absence settles it.
+                                Err(e) => {
+                                    error!("Withdrawal {}: error sending the release: {:?}", id, e);
+                                    break;
+                                }
+                            },
+                            None => Err("The withdrawal changed while being released".to_string()),
+                        },
+                        Err(e) => Err(format!("Error signing the release: {e:?}")),
+                    }
+                }
+            };
+
+            match result {
+                Ok((Some(eth_tx_hash), _)) => {
+                    info!("Withdrawal {} of {} to {:?} sent in {}, waiting for its receipt", id, amount, to, eth_tx_hash);
+                    nonce += U256::one();
+                }
+                Ok((None, safe_tx_hash)) => {
+                    info!(
+                        "Withdrawal {} of {} to {:?} proposed to the Safe as {}",
+                        id,
+                        amount,
+                        to,
+                        safe_tx_hash.as_deref().unwrap_or_default()
+                    );
+                    database_engine
+                        .record_withdrawal_release(id, version, None, safe_tx_hash)
+                        .await;
+                    nonce += U256::one();
+                }
+                Err(e) => {
+                    error!("Withdrawal {}: {}", id, e);
+                    database_engine.release_withdrawal_claim(id, version, e).await;
+                    break;
+                }
+            }
+        }
+    }
+}
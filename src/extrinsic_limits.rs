@@ -0,0 +1,99 @@
+use codec::Decode;
+
+use crate::payout::Simulation;
+use crate::types::GlitchApi;
+
+/// A value per dispatch class, as in `frame_support::weights::PerDispatchClass`.
+#[derive(Decode, Debug)]
+struct PerDispatchClass<T> {
+    normal: T,
+    #[allow(dead_code)]
+    operational: T,
+    #[allow(dead_code)]
+    mandatory: T,
+}
+
+/// `frame_system::limits::BlockLength`.
+#[derive(Decode, Debug)]
+struct BlockLength {
+    max: PerDispatchClass<u32>,
+}
+
+/// `frame_system::limits::WeightsPerClass`.
+#[derive(Decode, Debug)]
+struct WeightsPerClass {
+    #[allow(dead_code)]
+    base_extrinsic: u64,
+    max_extrinsic: Option<u64>,
+    max_total: Option<u64>,
+    #[allow(dead_code)]
+    reserved: Option<u64>,
+}
+
+/// `frame_system::limits::BlockWeights`.
+#[derive(Decode, Debug)]
+struct BlockWeights {
+    #[allow(dead_code)]
+    base_block: u64,
+    max_block: u64,
+    per_class: PerDispatchClass<WeightsPerClass>,
+}
+
+/// Limits of a normal class extrinsic, read from the `System` constants of
+/// the runtime metadata.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtrinsicLimits {
+    pub max_length: u32,
+    pub max_weight: u64,
+}
+
+impl ExtrinsicLimits {
+    pub fn of(api: &GlitchApi) -> Result<Self, String> {
+        let length: BlockLength = api
+            .get_constant("System", "BlockLength")
+            .map_err(|e| format!("Error reading System.BlockLength: {e:?}"))?;
+        let weights: BlockWeights = api
+            .get_constant("System", "BlockWeights")
+            .map_err(|e| format!("Error reading System.BlockWeights: {e:?}"))?;
+
+        let normal = weights.per_class.normal;
+        let max_weight = normal
+            .max_extrinsic
+            .or(normal.max_total)
+            .unwrap_or(weights.max_block);
+
+        Ok(Self {
+            max_length: length.max.normal,
+            max_weight,
+        })
+    }
+}
+
+/// Checks the hex encoded extrinsic against the block limits before it is
+/// submitted, so one that can never be included is not sent (and retried)
+/// only to fail with `ExhaustsResources`. A payout is a single transfer that
+/// can't be split, so one over the limits is rejected and held. The limits
+/// are runtime constants, read once per connection with `ExtrinsicLimits::of`.
+pub fn validate_extrinsic(api: &GlitchApi, limits: &ExtrinsicLimits, xt_hex: &str) -> Simulation {
+    let length = xt_hex.trim_start_matches("0x").len() / 2;
+    if length > limits.max_length as usize {
+        return Simulation::Rejected(format!(
+            "the extrinsic of {} bytes exceeds the block length limit of {}",
+            length, limits.max_length
+        ));
+    }
+
+    let weight = match api.get_payment_info(xt_hex, None) {
+        Ok(Some(info)) => info.weight,
+        Ok(None) => return Simulation::Unavailable("no weight reported for the extrinsic".to_string()),
+        Err(e) => return Simulation::Unavailable(format!("Error querying the extrinsic weight: {e:?}")),
+    };
+    if weight > limits.max_weight {
+        return Simulation::Rejected(format!(
+            "the extrinsic weight {} exceeds the per extrinsic limit of {}",
+            weight, limits.max_weight
+        ));
+    }
+
+    Simulation::Passes
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::config::Notification;
+use crate::database::DatabaseEngine;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+
+/// Keeps the instance lease of the database in use renewed, so the failover
+/// (which receives it through replication) knows this instance is the
+/// writer and migrations know a bridge is running. With a failover, alerts
+/// once when another instance holds it.
+pub async fn hold_instance_lease(
+    database_engine: Arc<DatabaseEngine>,
+    smtp_config: Notification,
+    mut ticker: Ticker,
+) {
+    info!("Holding the instance lease as {}", database_engine.instance_id);
+    let mut held = true;
+
+    loop {
+        ticker.tick().await;
+
+        let renewed = database_engine.renew_instance_lease().await;
+        if renewed == held {
+            continue;
+        }
+        held = renewed;
+
+        if held {
+            info!("Instance lease acquired again.");
+            continue;
+        }
+
+        if database_engine.failover.is_none() {
+            info!("Another instance holds the instance lease.");
+            continue;
+        }
+        let message = format!(
+            "Instance {} could not renew the database instance lease, another instance holds it. This instance will not take over the failover database.",
+            database_engine.instance_id
+        );
+        warn!("{}", message);
+        notify(&smtp_config, "Bridge instance lease lost!", &message).await;
+    }
+}
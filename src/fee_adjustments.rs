@@ -0,0 +1,129 @@
+//! Manual adjustments of the accumulated business fees (refunds,
+//! corrections), imported from a CSV finance signs off. Each one goes
+//! through the fee counter in a transaction and is kept in the
+//! `fee_adjustment` table with who asked for it and why, and it is settled
+//! by the next fee transfer of the pipeline like the fees of the txs.
+
+use std::fs;
+use std::path::Path;
+
+use log::{error, info, warn};
+
+use crate::database::{DatabaseEngine, FeeAdjustment, FeeAdjustmentOutcome};
+
+const FEE_ADJUSTMENT_HEADER: &str = "network,amount,requested_by,reason,reference";
+
+/// Splits a CSV line into fields. Fields may be quoted, with `""` for a
+/// quote inside them.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|field| field.trim().to_string()).collect()
+}
+
+fn parse_adjustment(line: &str) -> Result<FeeAdjustment, String> {
+    let fields = csv_fields(line);
+    if fields.len() < 4 || fields.len() > 5 {
+        return Err(format!("expected {FEE_ADJUSTMENT_HEADER}, got {} fields", fields.len()));
+    }
+
+    let amount: i128 = fields[1]
+        .trim_start_matches('+')
+        .parse()
+        .map_err(|_| format!("{} is not a signed integer amount", fields[1]))?;
+    if amount == 0 {
+        return Err("the amount is 0".to_string());
+    }
+    if fields[2].is_empty() || fields[3].is_empty() {
+        return Err("requested_by and reason are required".to_string());
+    }
+
+    Ok(FeeAdjustment {
+        scanner_name: fields[0].clone(),
+        amount,
+        requested_by: fields[2].clone(),
+        reason: fields[3].clone(),
+        reference: fields.get(4).filter(|reference| !reference.is_empty()).cloned(),
+    })
+}
+
+/// Reads the adjustments of `input`, a CSV with the header
+/// `network,amount,requested_by,reason,reference`, and applies them when
+/// `apply` is set. Nothing is applied if any line is invalid. Returns false
+/// if any adjustment could not be read or applied.
+pub async fn import_fee_adjustments(database_engine: &DatabaseEngine, input: &Path, apply: bool) -> bool {
+    let contents = fs::read_to_string(input).expect("Error while reading the fee adjustments file!");
+    let mut lines = contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+    match lines.next() {
+        Some((_, header)) if csv_fields(header).join(",") == FEE_ADJUSTMENT_HEADER => {}
+        _ => {
+            error!("The first line of {} must be {}", input.display(), FEE_ADJUSTMENT_HEADER);
+            return false;
+        }
+    }
+
+    let mut adjustments = Vec::new();
+    let mut valid = true;
+    for (index, line) in lines {
+        match parse_adjustment(line) {
+            Ok(adjustment) => adjustments.push(adjustment),
+            Err(e) => {
+                error!("Line {}: {}", index + 1, e);
+                valid = false;
+            }
+        }
+    }
+    if !valid {
+        error!("No adjustment applied, fix the file and import it again.");
+        return false;
+    }
+
+    let mut ok = true;
+    for adjustment in adjustments.iter() {
+        info!(
+            "{} {} to the fees of {} by {}: {}",
+            if adjustment.amount > 0 { "Adding" } else { "Subtracting" },
+            adjustment.amount.unsigned_abs(),
+            adjustment.scanner_name,
+            adjustment.requested_by,
+            adjustment.reason
+        );
+        if !apply {
+            continue;
+        }
+
+        match database_engine.apply_fee_adjustment(adjustment).await {
+            Ok(FeeAdjustmentOutcome::Applied(accumulated)) => {
+                info!("  applied, {} now has {} in accumulated fees", adjustment.scanner_name, accumulated)
+            }
+            Ok(FeeAdjustmentOutcome::AlreadyApplied(id)) => {
+                warn!("  skipped, its reference was already imported as adjustment {}", id)
+            }
+            Err(e) => {
+                error!("  not applied: {}", e);
+                ok = false;
+            }
+        }
+    }
+
+    if !apply {
+        info!("Dry run, nothing changed. Run again with --apply to apply these {} adjustment(s).", adjustments.len());
+    }
+    ok
+}
@@ -0,0 +1,77 @@
+//! History of the business fee and fee destination of each pipeline. A
+//! deposit is charged the fee in effect when it was detected, and the fee of
+//! a period goes to the destination in effect when the period started, so a
+//! change made mid-period never alters what was already accounted for.
+
+use chrono::Utc;
+use log::{info, warn};
+
+use crate::config::{Config, FeeDestination, Network};
+use crate::database::{DatabaseEngine, FeePolicy};
+
+/// The policy in effect at `at`, a unix timestamp. Deposits older than the
+/// history get its first policy.
+pub fn policy_at(policies: &[FeePolicy], at: i64) -> Option<&FeePolicy> {
+    policies
+        .iter()
+        .rev()
+        .find(|policy| policy.effective_from <= at)
+        .or_else(|| policies.first())
+}
+
+/// Destination of a policy, None if it is no longer a valid address.
+pub fn destination_of(policy: &FeePolicy) -> Option<FeeDestination> {
+    FeeDestination::try_from(policy.fee_destination.clone())
+        .map_err(|e| warn!("Fee policy from {} has an invalid destination: {}", policy.effective_from, e))
+        .ok()
+}
+
+/// Records the configured fee and destination of the pipeline when they
+/// differ from the latest recorded policy, effective from
+/// `fee_effective_from` or from now.
+pub async fn record_configured_policy(config: &Config, network_config: &Network, database_engine: &DatabaseEngine) {
+    let name = &network_config.name;
+    let business_fee = network_config.business_fee.unwrap_or(config.business_fee);
+    let fee_destination = network_config.fee_destination(config).to_string();
+
+    let policies = database_engine.fee_policies(name).await;
+    let unchanged = policies
+        .last()
+        .map_or(false, |latest| latest.business_fee == business_fee && latest.fee_destination == fee_destination);
+    if unchanged {
+        return;
+    }
+
+    // A policy can't reach back: deposits already detected were charged the
+    // recorded one, and the history must stay in order.
+    let effective_from = config.fee_effective_from();
+    let starts_at = effective_from.unwrap_or_else(Utc::now);
+    if effective_from.map_or(false, |effective_from| effective_from < Utc::now()) {
+        panic!(
+            "The fee policy of {} changed effective from {}, which is past. Set fee_effective_from to a later date.",
+            name, starts_at
+        );
+    }
+    if let Some(latest) = policies.last().filter(|latest| starts_at.timestamp() < latest.effective_from) {
+        panic!(
+            "The fee policy of {} changed effective from {}, before the latest recorded one from {}.",
+            name, starts_at, latest.effective_from
+        );
+    }
+
+    info!(
+        "Fee policy of {} changed to {}% paid to {}, effective from {}",
+        name,
+        business_fee,
+        fee_destination,
+        effective_from.map_or_else(|| "now".to_string(), |effective_from| effective_from.to_string())
+    );
+    database_engine
+        .insert_fee_policy(
+            name,
+            business_fee,
+            fee_destination,
+            effective_from.map(|effective_from| effective_from.timestamp()),
+        )
+        .await;
+}
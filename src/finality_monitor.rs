@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::{error, info, warn};
+use sp_core::sr25519;
+use substrate_api_client::{rpc::WsRpcClient, Api, PlainTipExtrinsicParams};
+
+use crate::config::{FinalityStall, Network, Notification};
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::{Scheduler, Ticker};
+use crate::types::GlitchApi;
+
+fn finalized_number(api: &GlitchApi) -> Option<u32> {
+    let hash = api.get_finalized_head().ok()??;
+    api.get_storage_value("System", "Number", Some(hash)).ok()?
+}
+
+/// Watches the finalized head of the Glitch node of a pipeline. When it
+/// doesn't advance for longer than the configured stall, the payer and fee
+/// payer jobs of the pipeline are paused and an alert goes out; once it
+/// moves again the jobs this monitor paused are resumed. Jobs already
+/// paused by an operator or another monitor are left alone. A node that
+/// can't be read counts as stalled. The jobs it paused are kept in `paused`,
+/// so a restarted monitor still resumes them, once it sees the head move.
+pub async fn monitor_finality(
+    finality_stall: FinalityStall,
+    network_config: Network,
+    smtp_config: Notification,
+    scheduler: Arc<Scheduler>,
+    paused: Arc<Mutex<Vec<String>>>,
+    mut ticker: Ticker,
+) {
+    let max_stall = finality_stall.max_stall();
+    info!("Finality monitor of {} running!", network_config.name);
+
+    let mut api: Option<GlitchApi> = None;
+    let mut last_finalized: Option<u32> = None;
+    let mut stalled = !paused.lock().unwrap().is_empty();
+    let mut advanced_at = if stalled {
+        Instant::now().checked_sub(max_stall).unwrap_or_else(Instant::now)
+    } else {
+        Instant::now()
+    };
+
+    loop {
+        ticker.tick().await;
+
+        if api.is_none() {
+            let client = WsRpcClient::new(&network_config.glitch_node_url());
+            match Api::<sr25519::Pair, _, PlainTipExtrinsicParams>::new(client) {
+                Ok(connected) => api = Some(connected),
+                Err(e) => error!("Error connecting with the Glitch node of {}: {:?}", network_config.name, e),
+            }
+        }
+
+        // A stalled monitor only counts the head as moving once it saw it move.
+        match api.as_ref().and_then(finalized_number) {
+            Some(number) if last_finalized.map_or(!stalled, |last| number > last) => {
+                last_finalized = Some(number);
+                advanced_at = Instant::now();
+            }
+            Some(number) => {
+                last_finalized.get_or_insert(number);
+            }
+            None => {
+                error!("Error reading the finalized head of Glitch for {}", network_config.name);
+                api = None;
+            }
+        }
+
+        let stall = advanced_at.elapsed();
+        metrics::FINALITY_STALL
+            .with_label_values(&[&network_config.name])
+            .set(stall.as_secs() as i64);
+
+        if !stalled && stall > max_stall {
+            stalled = true;
+            for job_name in [format!("payer:{}", network_config.name), format!("fee_payer:{}", network_config.name)] {
+                if let Some(job) = scheduler.job(&job_name).filter(|job| !job.is_paused()) {
+                    job.pause();
+                    paused.lock().unwrap().push(job_name);
+                }
+            }
+
+            let message = format!(
+                "The finalized head of Glitch has not advanced for {} seconds (last finalized block {}). New payouts and fee transfers of {} are paused until it does.",
+                stall.as_secs(),
+                last_finalized.map_or("unknown".to_string(), |number| number.to_string()),
+                network_config.name
+            );
+            warn!("{}", message);
+            notify(&smtp_config, "Glitch finality stalled!", &message).await;
+        } else if stalled && stall <= max_stall {
+            stalled = false;
+            let job_names: Vec<String> = paused.lock().unwrap().drain(..).collect();
+            for job in job_names.iter().filter_map(|job_name| scheduler.job(job_name)) {
+                job.resume();
+            }
+
+            let message = format!(
+                "The finalized head of Glitch advanced again to block {}. Payouts and fee transfers of {} resumed.",
+                last_finalized.unwrap_or_default(),
+                network_config.name
+            );
+            info!("{}", message);
+            notify(&smtp_config, "Glitch finality recovered", &message).await;
+        }
+    }
+}
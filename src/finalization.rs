@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use sp_core::{sr25519, H256};
+use substrate_api_client::{rpc::WsRpcClient, Api, PlainTipExtrinsicParams};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::database::DatabaseEngine;
+use crate::glitch::{settle_transfer, Settlement};
+use crate::heads::forward_finalized_heads;
+use crate::metrics;
+use crate::payout::{PayoutExecutor, SignedPayout};
+use crate::types::{to_hex, GlitchApi};
+
+/// A payout included in `block`, not final yet.
+pub struct PendingFinalization {
+    pub settlement: Settlement,
+    pub signed: SignedPayout,
+    pub block: String,
+}
+
+/// Hands included payouts over to `track_finalization`.
+#[derive(Clone)]
+pub struct FinalizationTracker {
+    sender: UnboundedSender<PendingFinalization>,
+}
+
+impl FinalizationTracker {
+    pub fn new() -> (Self, UnboundedReceiver<PendingFinalization>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender }, receiver)
+    }
+
+    pub fn track(&self, pending: PendingFinalization) {
+        if self.sender.send(pending).is_err() {
+            error!("The finalization tracker is gone, the tx stays PROCESSING.");
+        }
+    }
+}
+
+struct Waiting {
+    pending: PendingFinalization,
+    block_number: Option<u32>,
+}
+
+fn finalized_number(api: &GlitchApi) -> Option<u32> {
+    let hash = api.get_finalized_head().ok()??;
+    api.get_storage_value("System", "Number", Some(hash)).ok()?
+}
+
+fn block_number(api: &GlitchApi, block_hash: H256) -> Option<u32> {
+    api.get_storage_value("System", "Number", Some(block_hash)).ok()?
+}
+
+/// Settles the payouts whose inclusion block is now final. Payouts whose
+/// block was retracted are left PROCESSING with an error for manual review,
+/// since the extrinsic may still land in another block.
+async fn settle_finalized(
+    api: &GlitchApi,
+    executor: &dyn PayoutExecutor,
+    waiting: Vec<Waiting>,
+    database_engine: &DatabaseEngine,
+) -> Vec<Waiting> {
+    let finalized = match finalized_number(api) {
+        Some(number) => number,
+        None => {
+            warn!("Could not read the finalized block number.");
+            return waiting;
+        }
+    };
+
+    let mut still_waiting = Vec::new();
+    for mut entry in waiting {
+        let block_hash = match entry.pending.block.trim_start_matches("0x").parse::<H256>() {
+            Ok(block_hash) => block_hash,
+            Err(_) => {
+                error!("Tx {}: unreadable block hash {}", entry.pending.settlement.tx_id, entry.pending.block);
+                continue;
+            }
+        };
+        if entry.block_number.is_none() {
+            entry.block_number = block_number(api, block_hash);
+        }
+        let number = match entry.block_number {
+            Some(number) if number <= finalized => number,
+            _ => {
+                still_waiting.push(entry);
+                continue;
+            }
+        };
+
+        let PendingFinalization {
+            settlement,
+            signed,
+            block,
+        } = entry.pending;
+
+        match api.get_block_hash(Some(number)) {
+            Ok(Some(canonical)) if canonical == block_hash => {
+                settle_transfer(executor, settlement, &signed, &block, database_engine).await;
+            }
+            Ok(Some(canonical)) => {
+                let message = format!(
+                    "Payout extrinsic {} was in block {} but block {} was finalized instead; check where it landed before releasing the tx",
+                    signed.hash,
+                    block,
+                    to_hex(canonical)
+                );
+                error!("Tx {}: {}", settlement.tx_id, message);
+                database_engine.update_tx_with_error(settlement.tx_id, message).await;
+            }
+            _ => {
+                still_waiting.push(Waiting {
+                    pending: PendingFinalization {
+                        settlement,
+                        signed,
+                        block,
+                    },
+                    block_number: Some(number),
+                });
+            }
+        }
+    }
+
+    still_waiting
+}
+
+/// Completes the payouts of a pipeline submitted with `async_finalization`
+/// as the finalized heads of Glitch come in. Payouts still waiting when the
+/// bridge stops stay PROCESSING with their `extrinsic_hash` recorded.
+pub async fn track_finalization(
+    name: String,
+    glitch_node: String,
+    executor: Arc<dyn PayoutExecutor>,
+    database_engine: Arc<DatabaseEngine>,
+    mut pending: UnboundedReceiver<PendingFinalization>,
+) {
+    let client = WsRpcClient::new(&glitch_node);
+    let api: GlitchApi = Api::<sr25519::Pair, _, PlainTipExtrinsicParams>::new(client).unwrap();
+
+    let (heads_sender, mut heads) = mpsc::unbounded_channel();
+    let node = glitch_node.clone();
+    std::thread::spawn(move || forward_finalized_heads(node, heads_sender));
+    info!("Tracking finalization of the payouts of {}", name);
+
+    let mut waiting = Vec::new();
+    loop {
+        tokio::select! {
+            Some(included) = pending.recv() => waiting.push(Waiting {
+                pending: included,
+                block_number: None,
+            }),
+            Some(()) = heads.recv() => {
+                if !waiting.is_empty() {
+                    waiting = settle_finalized(&api, executor.as_ref(), waiting, &database_engine).await;
+                }
+            }
+            else => break,
+        }
+
+        metrics::PENDING_FINALIZATIONS
+            .with_label_values(&[&name])
+            .set(waiting.len() as i64);
+    }
+}
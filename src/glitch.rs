@@ -1,36 +1,242 @@
-use chrono::{Days, NaiveDateTime, Utc};
-use log::{error, info, warn};
-use sp_core::{crypto::Pair, sr25519, sr25519::Public};
-use std::{str::FromStr, sync::Arc};
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use log::{debug, error, info, warn};
+use serde_json::json;
+use codec::{Compact, Decode, Encode};
+use sp_core::{crypto::Pair, hashing::blake2_256, sr25519, sr25519::Public, H256};
+use std::sync::{Arc, Mutex};
 use substrate_api_client::{
-    rpc::WsRpcClient, AccountId, Api, BaseExtrinsicParams, GenericAddress, MultiAddress, PlainTip,
+    compose_extrinsic, rpc::WsRpcClient, AccountId, Api, ApiResult, GenericAddress, MultiAddress,
     PlainTipExtrinsicParams, XtStatus,
 };
-use tokio::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio::time::{sleep, Duration, Instant};
+use tracing::Span;
+use web3::types::U256;
 
-use crate::database::DatabaseEngine;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{
+    Canary, Config, EthFeePolicy, FeeDestination, GlitchGas, GlitchGasMode, HeadTicks, Network, Notification,
+    Rounding, TagAction, SECONDS_PER_DAY,
+};
+use crate::crash::TxGuard;
+use crate::dry_run::dry_run;
+use crate::database::{
+    DatabaseEngine, FailureKind, OutboxPayout, OutboxState, SettledPayout, TxTransitions,
+};
+use crate::extrinsic_limits::{validate_extrinsic, ExtrinsicLimits};
+use crate::fee_policy::{destination_of, policy_at};
+use crate::finalization::{track_finalization, FinalizationTracker, PendingFinalization};
+use crate::glitch_events;
+use crate::heads::BlockTicker;
+use crate::logger::FEE_LOG_TARGET;
+use crate::metrics;
+use crate::notifications::notify;
+use crate::outbox::{submit_outbox, OutboxSubmitter};
+use crate::payout::{Confirmation, Payout, PayoutExecutor, Receipt, SignedPayout, Simulation, Submission};
+use crate::recipient_locks::RecipientLocks;
+use crate::scheduler::{Scheduler, Ticker};
+use crate::supervisor::stopped;
+use crate::tagging::LabelActions;
+use crate::types::{account_id_to_ss58, check_genesis_hash, parse_glitch_address, public_to_ss58, to_hex, GlitchApi};
 
-async fn calculate_amount_to_transfer_and_business_fee_v2(
-    api: &Api<sr25519::Pair, WsRpcClient, BaseExtrinsicParams<PlainTip>>,
-    glitch_gas: bool,
+/// Precision of the business fee percentage: 6 decimals.
+const BUSINESS_FEE_SCALE: u128 = 1_000_000;
+
+/// `business_fee` percent of `amount`, computed in integers and rounded
+/// according to the policy, plus a description of the step.
+pub fn business_fee_of(amount: u128, business_fee: f64, rounding: Rounding) -> (u128, String) {
+    let scaled_fee = (business_fee * BUSINESS_FEE_SCALE as f64).round() as u128;
+    let denominator = U256::from(100 * BUSINESS_FEE_SCALE);
+    let (quotient, remainder) = (U256::from(amount) * U256::from(scaled_fee)).div_mod(denominator);
+
+    let round_up = match rounding {
+        Rounding::BridgeFavor => !remainder.is_zero(),
+        Rounding::UserFavor => false,
+        Rounding::Nearest => remainder * 2 >= denominator,
+    };
+    let fee = quotient.low_u128() + round_up as u128;
+
+    let step = format!(
+        "business fee {}% of {} = {} + {}/{} rounded {} ({:?}) to {}",
+        business_fee,
+        amount,
+        quotient,
+        remainder,
+        denominator,
+        if round_up { "up" } else { "down" },
+        rounding,
+        fee
+    );
+    (fee, step)
+}
+
+/// Lowers the business fee by the protocol fee the contract already took on
+/// Ethereum, according to the policy, plus a description of the step.
+fn apply_eth_fee(business_fee_amount: u128, eth_fee: u128, policy: EthFeePolicy) -> (u128, String) {
+    match policy {
+        EthFeePolicy::Skip => (0, format!("business fee skipped, ETH fee {} already charged", eth_fee)),
+        EthFeePolicy::Reduce => {
+            let reduced = business_fee_amount.saturating_sub(eth_fee);
+            (
+                reduced,
+                format!("business fee reduced by ETH fee {} to {}", eth_fee, reduced),
+            )
+        }
+    }
+}
+
+/// How long an exact fee estimate may take in `auto` mode before the
+/// average fee is used instead.
+const FEE_ESTIMATE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Wait between attempts to connect the payout executor to the node.
+const EXECUTOR_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// How long the dry run of a payout may take before it is sent unchecked.
+const DRY_RUN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Hours of finalized payouts the `auto` mode average is taken over.
+const FEE_AVERAGE_WINDOW_IN_HOURS: u32 = 24;
+
+/// Estimates the fee off the async workers, giving up after
+/// `FEE_ESTIMATE_TIMEOUT`.
+async fn estimate_fee(api: &GlitchApi, xt_hex: String) -> Result<u128, String> {
+    let api = api.clone();
+    let estimate = tokio::task::spawn_blocking(move || {
+        api.get_fee_details(xt_hex.as_str(), None)
+            .map_err(|e| format!("{e:?}"))?
+            .map(|details| details.final_fee())
+            .ok_or_else(|| "no fee details".to_string())
+    });
+
+    match tokio::time::timeout(FEE_ESTIMATE_TIMEOUT, estimate).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("{e}")),
+        Err(_) => Err(format!("timed out after {:?}", FEE_ESTIMATE_TIMEOUT)),
+    }
+}
+
+/// Average network fee the pipeline paid over the last day, 0 if it has no
+/// payouts to go by.
+async fn average_network_fee(name: &str, database_engine: &DatabaseEngine) -> u128 {
+    let average = database_engine
+        .network_fee_stats(Some(name), FEE_AVERAGE_WINDOW_IN_HOURS)
+        .await
+        .into_iter()
+        .next()
+        .and_then(|stats| stats.average.parse::<u128>().ok())
+        .unwrap_or(0);
+    metrics::NETWORK_FEE_AVERAGE
+        .with_label_values(&[name])
+        .set(average as f64);
+    average
+}
+
+/// Network fee deducted from a payout of `amount` to `public`, as set by
+/// `glitch_gas`.
+pub(crate) async fn network_fee_of(
+    name: &str,
+    api: &GlitchApi,
+    database_engine: &DatabaseEngine,
+    glitch_gas: GlitchGas,
     amount: u128,
-    business_fee: f64,
     public: Public,
-) -> (u128, u128) {
+) -> u128 {
     let xt_to_send = api
         .balance_transfer(MultiAddress::Id(AccountId::from(public)), amount)
         .hex_encode();
-    let fee = if glitch_gas {
-        api.get_fee_details(xt_to_send.as_str(), None)
+    match glitch_gas {
+        GlitchGas::Flag(true) => api
+            .get_fee_details(xt_to_send.as_str(), None)
             .unwrap()
             .unwrap()
-            .final_fee()
-    } else {
-        0_u128
-    };
+            .final_fee(),
+        GlitchGas::Flag(false) => 0_u128,
+        GlitchGas::Mode(GlitchGasMode::Auto) => match estimate_fee(api, xt_to_send).await {
+            Ok(fee) => fee,
+            Err(e) => {
+                let average = average_network_fee(name, database_engine).await;
+                warn!("Fee estimation failed ({}), deducting the average fee {}", e, average);
+                metrics::NETWORK_FEE_FALLBACKS.with_label_values(&[name]).inc();
+                average
+            }
+        },
+    }
+}
+
+/// `network_fee_of` for callers that must neither block nor panic, like the
+/// quotes: the node is queried off the runtime within `FEE_ESTIMATE_TIMEOUT`,
+/// and a fee it can't estimate is an error rather than the average.
+pub(crate) async fn try_network_fee_of(
+    api: &GlitchApi,
+    glitch_gas: GlitchGas,
+    amount: u128,
+    public: Public,
+) -> Result<u128, String> {
+    if let GlitchGas::Flag(false) = glitch_gas {
+        return Ok(0);
+    }
+
+    let api = api.clone();
+    let estimate = tokio::task::spawn_blocking(move || {
+        let xt_to_send = api
+            .balance_transfer(MultiAddress::Id(AccountId::from(public)), amount)
+            .hex_encode();
+        api.get_fee_details(xt_to_send.as_str(), None)
+            .map_err(|e| format!("{e:?}"))?
+            .map(|details| details.final_fee())
+            .ok_or_else(|| "no fee details".to_string())
+    });
+
+    match tokio::time::timeout(FEE_ESTIMATE_TIMEOUT, estimate).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("{e}")),
+        Err(_) => Err(format!("timed out after {:?}", FEE_ESTIMATE_TIMEOUT)),
+    }
+}
 
+/// Returns `(amount to transfer, business fee, breakdown)` of a deposit of
+/// `amount` once the network `fee` is deducted. `amount` must cover `fee`.
+pub(crate) fn payout_of(
+    amount: u128,
+    fee: u128,
+    business_fee: f64,
+    rounding: Rounding,
+    eth_fee: Option<u128>,
+    eth_fee_policy: EthFeePolicy,
+) -> (u128, u128, String) {
     let amount_to_transfer = amount - fee;
-    let business_fee_amount = ((amount_to_transfer as f64 * business_fee) / 100.0) as u128;
+    let (mut business_fee_amount, mut business_fee_step) =
+        business_fee_of(amount_to_transfer, business_fee, rounding);
+    // A zero fee was not charged, the business fee applies in full.
+    if let Some(eth_fee) = eth_fee.filter(|eth_fee| *eth_fee > 0) {
+        let (reduced, eth_fee_step) = apply_eth_fee(business_fee_amount, eth_fee, eth_fee_policy);
+        business_fee_amount = reduced;
+        business_fee_step = format!("{}; {}", business_fee_step, eth_fee_step);
+    }
+    let breakdown = format!(
+        "received {}; estimated network fee {}; {}; payout {}",
+        amount,
+        fee,
+        business_fee_step,
+        amount_to_transfer - business_fee_amount
+    );
+    (amount_to_transfer, business_fee_amount, breakdown)
+}
+
+/// Returns `(amount to transfer, business fee, estimated network fee,
+/// breakdown)`, the breakdown being what is stored with the tx to explain
+/// each step.
+async fn calculate_amount_to_transfer_and_business_fee_v2(
+    settings: &PayoutSettings,
+    api: &GlitchApi,
+    database_engine: &DatabaseEngine,
+    amount: u128,
+    business_fee: f64,
+    eth_fee: Option<u128>,
+    public: Public,
+) -> (u128, u128, u128, String) {
+    let fee = network_fee_of(&settings.name, api, database_engine, settings.glitch_gas, amount, public).await;
+    let (amount_to_transfer, business_fee_amount, breakdown) =
+        payout_of(amount, fee, business_fee, settings.rounding, eth_fee, settings.eth_fee_policy);
 
     info!("Business fee amount is: {}", business_fee_amount);
 
@@ -41,235 +247,1236 @@ async fn calculate_amount_to_transfer_and_business_fee_v2(
     );
     info!("Amount to be transferred {}", amount_to_transfer);
 
-    return (amount_to_transfer, business_fee_amount);
+    return (amount_to_transfer, business_fee_amount, fee, breakdown);
 }
 
-pub async fn make_transfer(
-    scanner_name: String,
-    tx_ix: u128,
-    tx_glitch_address: String,
-    node: &str,
-    glitch_pk: String,
-    public: Public,
-    amount_to_transfer: u128,
-    amount_business_fee: u128,
-    database_engine: Arc<DatabaseEngine>,
-    business_fee_percentage: f64,
+/// Prefix of `Assets.Account`, whose first field is the balance.
+#[derive(Decode)]
+struct AssetAccount {
+    balance: u128,
+}
+
+/// Free balance of the account in what the pipeline pays out: the native
+/// balance, or the asset of the assets pallet if `asset_id` is set.
+fn payout_balance(api: &GlitchApi, account: &AccountId, asset_id: Option<u32>) -> ApiResult<u128> {
+    match asset_id {
+        None => Ok(api.get_account_data(account)?.map(|data| data.free).unwrap_or(0)),
+        Some(asset_id) => Ok(api
+            .get_storage_double_map::<_, _, AssetAccount>("Assets", "Account", asset_id, account.clone(), None)?
+            .map(|asset_account| asset_account.balance)
+            .unwrap_or(0)),
+    }
+}
+
+/// Signed transfer of `amount` to `to` as native balance, or with
+/// `Assets.transfer` if `asset_id` is set. Returns the hex encoded extrinsic
+/// and its hash.
+fn payout_extrinsic(api: &GlitchApi, to: AccountId, amount: u128, asset_id: Option<u32>) -> (String, H256) {
+    match asset_id {
+        None => {
+            let xt = api.balance_transfer(GenericAddress::Id(to), amount);
+            (xt.hex_encode(), H256::from(blake2_256(&xt.encode())))
+        }
+        Some(asset_id) => {
+            let xt = compose_extrinsic!(
+                api,
+                "Assets",
+                "transfer",
+                Compact(asset_id),
+                GenericAddress::Id(to),
+                Compact(amount)
+            );
+            (xt.hex_encode(), H256::from(blake2_256(&xt.encode())))
+        }
+    }
+}
+
+/// What is needed to record a transfer as paid once its block is final.
+pub struct Settlement {
+    pub scanner_name: String,
+    pub tx_id: u128,
+    pub tx_version: u32,
+    pub tx_glitch_address: String,
+    pub tx_detected_at: i64,
+    pub submitted_at: i64,
+    pub amount_business_fee: u128,
+    pub estimated_fee: u128,
+    pub amount_breakdown: String,
+    pub business_fee_percentage: f64,
+}
+
+/// Records the transfer finalized in `block`: actual network fee, receipt,
+/// tx PROCESSED and business fee counted.
+pub async fn settle_transfer(
+    executor: &dyn PayoutExecutor,
+    settlement: Settlement,
+    signed: &SignedPayout,
+    block: &str,
+    database_engine: &DatabaseEngine,
 ) {
-    let client = WsRpcClient::new(node);
-    let signer: sr25519::Pair = Pair::from_string(&glitch_pk, None).unwrap();
-    let api = Api::<_, _, PlainTipExtrinsicParams>::new(client)
-        .map(|api| api.set_signer(signer))
-        .unwrap();
-    let xt_to_send = api.balance_transfer(
-        MultiAddress::Id(AccountId::from(public)),
-        amount_to_transfer - amount_business_fee,
+    let Settlement {
+        scanner_name,
+        tx_id,
+        tx_version,
+        tx_glitch_address,
+        tx_detected_at,
+        submitted_at,
+        amount_business_fee,
+        estimated_fee,
+        amount_breakdown,
+        business_fee_percentage,
+    } = settlement;
+
+    let finalized_at = Utc::now().timestamp();
+    metrics::TRANSFER_LATENCY
+        .with_label_values(&[&scanner_name, "submitted_to_finalized"])
+        .observe((finalized_at - submitted_at) as f64);
+    metrics::TRANSFER_LATENCY
+        .with_label_values(&[&scanner_name, "end_to_end"])
+        .observe((finalized_at - tx_detected_at) as f64);
+
+    // The user was charged the estimated fee up front. If the runtime
+    // refunded part of it, the difference stays in the bridge account
+    // and is accounted for as business fee.
+    let confirmation = executor.confirm(signed, block).await;
+    let network_fee = if estimated_fee == 0 {
+        0
+    } else {
+        confirmation.network_fee.unwrap_or_else(|| {
+            warn!(
+                "Actual fee of the transfer for tx {} not found, keeping the estimate.",
+                tx_id
+            );
+            estimated_fee
+        })
+    };
+    if confirmation.receipt.is_none() {
+        warn!("Transfer of tx {} not found among the events of {}, no receipt recorded.", tx_id, block);
+    }
+    let fee_rebate = estimated_fee.saturating_sub(network_fee);
+    if fee_rebate > 0 {
+        info!(
+            "Fee rebate of {} on tx {} (estimated {}, charged {})",
+            fee_rebate, tx_id, estimated_fee, network_fee
+        );
+    }
+
+    let amount_breakdown = format!(
+        "{}; actual network fee {}; fee rebate {} kept as business fee",
+        amount_breakdown, network_fee, fee_rebate
     );
 
-    let xt_result = match api.send_extrinsic(xt_to_send.hex_encode(), XtStatus::Finalized) {
-        Ok(r) => r,
+    let updated = database_engine
+        .update_tx(
+            tx_id,
+            tx_version,
+            SettledPayout {
+                glitch_hash: block.to_string(),
+                business_fee_amount: amount_business_fee + fee_rebate,
+                business_fee_percentage: business_fee_percentage.to_string(),
+                network_fee,
+                network_fee_estimated: estimated_fee,
+                amount_breakdown,
+                receipt: confirmation.receipt.as_ref(),
+            },
+        )
+        .await;
+    if !updated {
+        error!(
+            "Transfer {} for tx {} was finalized but the tx was changed concurrently; it needs manual review.",
+            block,
+            tx_id
+        );
+        return;
+    }
+    database_engine
+        .increment_fee_counter(scanner_name, amount_business_fee + fee_rebate)
+        .await;
+    info!("Trasfer to address {} completed!", tx_glitch_address);
+}
+
+/// Sends a payout taken from the outbox, then settles it or puts its tx
+/// back in the queue. Every outcome is recorded on the outbox row. Returns
+/// why the dry run failed, if the payout was stopped by it.
+#[tracing::instrument(name = "transfer", skip_all, fields(scanner = %payout.scanner_name, tx_id = payout.tx_id))]
+pub async fn make_transfer(
+    payout: OutboxPayout,
+    executor: &dyn PayoutExecutor,
+    database_engine: Arc<DatabaseEngine>,
+    finalization: Option<&FinalizationTracker>,
+) -> Option<FailureKind> {
+    let tx_ix = payout.tx_id;
+
+    // The tx may have been reconciled or changed by an operator since the
+    // payout was decided; then the decision no longer holds.
+    let tx_version = match database_engine.confirm_tx_claim(tx_ix, payout.tx_version).await {
+        Some(version) => version,
+        None => {
+            warn!("Tx {} changed since its payout was decided, dropping outbox payout {}.", tx_ix, payout.id);
+            database_engine
+                .finish_outbox_payout(payout.id, OutboxState::Failed, None, Some("The tx changed after the decision".to_string()))
+                .await;
+            return None;
+        }
+    };
+
+    let signed = match executor
+        .build(tx_ix, &payout.recipient, payout.amount)
+        .and_then(|built| executor.sign(built))
+    {
+        Ok(signed) => signed,
         Err(e) => {
-            error!("Transfer error: {:?}", e);
+            error!("Payout of tx {} not submitted: {}", tx_ix, e);
+            database_engine
+                .finish_outbox_payout(payout.id, OutboxState::Failed, None, Some(e.clone()))
+                .await;
+            database_engine.release_tx_claim(tx_ix, tx_version).await;
+            database_engine.update_tx_with_error(tx_ix, e).await;
+            return None;
+        }
+    };
+
+    let failure = match executor.simulate(&signed).await {
+        Simulation::Passes => None,
+        Simulation::Unavailable(reason) => {
+            debug!("Payout of tx {} not simulated, submitting it anyway: {}", tx_ix, reason);
             None
         }
+        Simulation::InsufficientFunds(reason) => Some((FailureKind::InsufficientFunds, reason)),
+        Simulation::BadDestination(reason) => Some((FailureKind::BadDestination, reason)),
+        Simulation::WouldReapSigner(reason) => Some((FailureKind::WouldReapSigner, reason)),
+        Simulation::Rejected(reason) => Some((FailureKind::Rejected, reason)),
     };
+    if let Some((failure_kind, reason)) = failure {
+        warn!("Payout of tx {} not submitted, {}.", tx_ix, reason);
+        metrics::DRY_RUN_FAILURES
+            .with_label_values(&[&payout.scanner_name, failure_kind.as_str()])
+            .inc();
+        database_engine
+            .finish_outbox_payout(payout.id, OutboxState::Failed, None, Some(reason.clone()))
+            .await;
+        // A recipient that can't be paid, or a payout the runtime rejects,
+        // needs someone to look at it; a signer short of funds is retried
+        // with the queue once the submitter backs off.
+        let hold = matches!(failure_kind, FailureKind::BadDestination | FailureKind::Rejected);
+        database_engine
+            .record_dry_run_failure(tx_ix, tx_version, failure_kind, format!("Dry run: {reason}"), hold)
+            .await;
+        return Some(failure_kind);
+    }
 
-    match xt_result {
-        Some(hash) => {
+    // Known before sending, so an interrupted submission can be traced.
+    if !database_engine.record_outbox_extrinsic(payout.id, &signed.hash).await {
+        let reason = "The extrinsic hash could not be recorded".to_string();
+        database_engine
+            .finish_outbox_payout(payout.id, OutboxState::Failed, None, Some(reason.clone()))
+            .await;
+        database_engine.release_tx_claim(tx_ix, tx_version).await;
+        database_engine.update_tx_with_error(tx_ix, reason).await;
+        return None;
+    }
+    let submitted_at = Utc::now().timestamp();
+    metrics::TRANSFER_LATENCY
+        .with_label_values(&[&payout.scanner_name, "detected_to_submitted"])
+        .observe((submitted_at - payout.tx_detected_at) as f64);
+
+    let block = match executor.submit(&signed).await {
+        Submission::Included { block } => {
+            database_engine
+                .finish_outbox_payout(payout.id, OutboxState::Included, Some(block.as_str()), None)
+                .await;
+            block
+        }
+        Submission::NotIncluded(reason) => {
+            error!("Transfer error: {}", reason);
+            info!(
+                "Transfer to address {} not completed. It will be tried again.",
+                payout.recipient
+            );
             database_engine
-                .update_tx(
-                    tx_ix,
-                    format!("{:#x}", hash),
-                    amount_business_fee,
-                    business_fee_percentage.to_string(),
-                )
+                .finish_outbox_payout(payout.id, OutboxState::NotIncluded, None, Some(reason))
                 .await;
+            database_engine.release_tx_claim(tx_ix, tx_version).await;
+            return None;
+        }
+        Submission::Unknown(reason) => {
+            // Whether the payout went out is unknown, so the claim is kept
+            // and the tx stays PROCESSING for reconciliation.
+            error!("Tx {}: {}", tx_ix, reason);
             database_engine
-                .increment_fee_counter(scanner_name, amount_business_fee)
+                .finish_outbox_payout(payout.id, OutboxState::Unknown, None, Some(reason.clone()))
                 .await;
-            info!("Trasfer to address {} completed!", tx_glitch_address);
+            database_engine.update_tx_with_error(tx_ix, reason).await;
+            return None;
         }
-        None => info!(
-            "Transfer to address {} not completed. It will be tried again.",
-            tx_glitch_address
-        ),
     };
+
+    let OutboxPayout {
+        scanner_name,
+        recipient: tx_glitch_address,
+        business_fee_amount: amount_business_fee,
+        business_fee_percentage,
+        estimated_fee,
+        amount_breakdown,
+        tx_detected_at,
+        ..
+    } = payout;
+
+    let mut settlement = Settlement {
+        scanner_name,
+        tx_id: tx_ix,
+        tx_version,
+        tx_glitch_address,
+        tx_detected_at,
+        submitted_at,
+        amount_business_fee,
+        estimated_fee,
+        amount_breakdown,
+        business_fee_percentage,
+    };
+
+    match finalization {
+        Some(tracker) => {
+            match database_engine
+                .record_tx_inclusion(tx_ix, tx_version, signed.hash.clone())
+                .await
+            {
+                Some(version) => settlement.tx_version = version,
+                None => {
+                    error!(
+                        "Transfer {} for tx {} was included but the tx was changed concurrently; it needs manual review.",
+                        signed.hash,
+                        tx_ix
+                    );
+                    return None;
+                }
+            }
+            info!(
+                "Transfer {} for tx {} included in block {}, waiting for finalization.",
+                signed.hash,
+                tx_ix,
+                block
+            );
+            tracker.track(PendingFinalization {
+                settlement,
+                signed,
+                block,
+            });
+        }
+        None => settle_transfer(executor, settlement, &signed, &block, &database_engine).await,
+    }
+    None
 }
 
-pub async fn run_network_listener(
-    name: String,
-    glitch_pk: String,
+/// A native transfer that leaves the signer below the existential deposit
+/// goes through and reaps its account, the dry run doesn't catch it.
+fn check_signer_kept_alive(api: &GlitchApi, signer: &AccountId, xt_hex: &str, amount: u128) -> Simulation {
+    let existential_deposit: u128 = match api.get_constant("Balances", "ExistentialDeposit") {
+        Ok(existential_deposit) => existential_deposit,
+        Err(e) => return Simulation::Unavailable(format!("Error reading Balances.ExistentialDeposit: {e:?}")),
+    };
+    let free = match payout_balance(api, signer, None) {
+        Ok(free) => free,
+        Err(e) => return Simulation::Unavailable(format!("Error obtaining the signer balance: {e:?}")),
+    };
+    let fee = api
+        .get_fee_details(xt_hex, None)
+        .ok()
+        .flatten()
+        .map(|details| details.final_fee())
+        .unwrap_or(0);
+
+    let left = free.saturating_sub(amount).saturating_sub(fee);
+    if left < existential_deposit {
+        return Simulation::WouldReapSigner(format!(
+            "the signer would be left with {left}, below the existential deposit of {existential_deposit}"
+        ));
+    }
+    Simulation::Passes
+}
+
+/// Pays out on Glitch, in the native token or in the asset of `asset_id`.
+/// The connection is dropped when the node fails a call and opened again
+/// for the next one.
+pub struct GlitchExecutor {
     glitch_node: String,
-    business_fee: f64,
-    glitch_gas: bool,
+    glitch_pk: String,
+    glitch_genesis_hash: Option<String>,
+    connection: Mutex<Option<(GlitchApi, ExtrinsicLimits)>>,
+    signer_account: AccountId,
+    asset_id: Option<u32>,
+    ss58_prefix: Option<u16>,
+    /// Only inclusion is waited for when finalization is tracked apart.
+    wait_for: XtStatus,
+    submission_timeout: Duration,
+}
+
+impl GlitchExecutor {
+    /// Connects right away, so a wrong node or genesis hash is found before
+    /// any payout.
+    pub fn new(
+        glitch_node: &str,
+        glitch_pk: &str,
+        glitch_genesis_hash: Option<&str>,
+        asset_id: Option<u32>,
+        ss58_prefix: Option<u16>,
+        async_finalization: bool,
+        submission_timeout: Duration,
+    ) -> Result<Self, String> {
+        let signer: sr25519::Pair = Pair::from_string(glitch_pk, None).unwrap();
+        let executor = Self {
+            glitch_node: glitch_node.to_string(),
+            glitch_pk: glitch_pk.to_string(),
+            glitch_genesis_hash: glitch_genesis_hash.map(str::to_string),
+            connection: Mutex::new(None),
+            signer_account: AccountId::from(signer.public()),
+            asset_id,
+            ss58_prefix,
+            wait_for: if async_finalization { XtStatus::InBlock } else { XtStatus::Finalized },
+            submission_timeout,
+        };
+        executor.connection()?;
+        Ok(executor)
+    }
+
+    /// The open connection and the block limits read with it, connecting
+    /// if there is none.
+    fn connection(&self) -> Result<(GlitchApi, ExtrinsicLimits), String> {
+        let mut connection = self.connection.lock().unwrap();
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+
+        let signer: sr25519::Pair = Pair::from_string(&self.glitch_pk, None).unwrap();
+        let api: GlitchApi = Api::<_, _, PlainTipExtrinsicParams>::new(WsRpcClient::new(&self.glitch_node))
+            .map(|api| api.set_signer(signer))
+            .map_err(|e| format!("Error connecting to {}: {e:?}", self.glitch_node))?;
+        check_genesis_hash(&api, self.glitch_genesis_hash.as_deref())?;
+        let limits = ExtrinsicLimits::of(&api)?;
+
+        *connection = Some((api.clone(), limits));
+        Ok((api, limits))
+    }
+
+    fn disconnect(&self) {
+        *self.connection.lock().unwrap() = None;
+    }
+}
+
+impl PayoutExecutor for GlitchExecutor {
+    fn build(&self, tx_id: u128, recipient: &str, amount: u128) -> Result<Payout, String> {
+        parse_glitch_address(recipient, self.ss58_prefix).map_err(|e| format!("Error with address: {e}"))?;
+        Ok(Payout {
+            tx_id,
+            recipient: recipient.to_string(),
+            amount,
+        })
+    }
+
+    fn sign(&self, payout: Payout) -> Result<SignedPayout, String> {
+        let public = parse_glitch_address(&payout.recipient, self.ss58_prefix)?;
+        let (api, _) = self.connection()?;
+        let (encoded, hash) = payout_extrinsic(&api, AccountId::from(public), payout.amount, self.asset_id);
+        Ok(SignedPayout {
+            payout,
+            encoded,
+            hash: to_hex(hash),
+        })
+    }
+
+    fn simulate<'a>(&'a self, signed: &'a SignedPayout) -> BoxFuture<'a, Simulation> {
+        async move {
+            let (api, limits) = match self.connection() {
+                Ok(connection) => connection,
+                Err(e) => return Simulation::Unavailable(e),
+            };
+            let (xt_hex, signer) = (signed.encoded.clone(), self.signer_account.clone());
+            let (amount, asset_id) = (signed.payout.amount, self.asset_id);
+
+            let simulation = tokio::task::spawn_blocking(move || {
+                if let rejected @ Simulation::Rejected(_) = validate_extrinsic(&api, &limits, &xt_hex) {
+                    return rejected;
+                }
+                match dry_run(&api, &xt_hex) {
+                    Simulation::Passes if asset_id.is_none() => check_signer_kept_alive(&api, &signer, &xt_hex, amount),
+                    simulation => simulation,
+                }
+            });
+            let simulation = match tokio::time::timeout(DRY_RUN_TIMEOUT, simulation).await {
+                Ok(Ok(simulation)) => simulation,
+                Ok(Err(e)) => Simulation::Unavailable(format!("Dry run task failed: {e}")),
+                Err(_) => Simulation::Unavailable(format!("Dry run timed out after {:?}", DRY_RUN_TIMEOUT)),
+            };
+            if let Simulation::Unavailable(_) = simulation {
+                self.disconnect();
+            }
+            simulation
+        }
+        .boxed()
+    }
+
+    fn submit<'a>(&'a self, signed: &'a SignedPayout) -> BoxFuture<'a, Submission> {
+        async move {
+            let api = match self.connection() {
+                Ok((api, _)) => api,
+                Err(e) => return Submission::NotIncluded(e),
+            };
+            let (xt_hex, wait_for) = (signed.encoded.clone(), self.wait_for);
+            let tx_id = signed.payout.tx_id;
+
+            // Waiting for the block blocks, so it runs off the async workers.
+            // The span goes along so its logs can be attributed to the tx.
+            let span = Span::current();
+            let submission = tokio::task::spawn_blocking(move || {
+                let _entered = span.enter();
+                let _tx = TxGuard::enter(tx_id);
+                api.send_extrinsic(xt_hex, wait_for).map_err(|e| format!("{e:?}"))
+            });
+
+            // The blocking call can't be interrupted, only stopped being waited on.
+            let submission = match tokio::time::timeout(self.submission_timeout, submission).await {
+                Ok(Ok(Ok(Some(block_hash)))) => Submission::Included {
+                    block: to_hex(block_hash),
+                },
+                Ok(Ok(Ok(None))) => Submission::NotIncluded("no block reported for the extrinsic".to_string()),
+                Ok(Ok(Err(e))) => Submission::NotIncluded(e),
+                Ok(Err(e)) => Submission::Unknown(format!("Transfer task failed: {e}")),
+                Err(_) => Submission::Unknown(format!(
+                    "Payout submission timed out after {:?}; it may still be included, left PROCESSING for reconciliation",
+                    self.submission_timeout
+                )),
+            };
+            if !matches!(submission, Submission::Included { .. }) {
+                self.disconnect();
+            }
+            submission
+        }
+        .boxed()
+    }
+
+    fn confirm<'a>(&'a self, signed: &'a SignedPayout, block: &'a str) -> BoxFuture<'a, Confirmation> {
+        async move {
+            let recipient = match parse_glitch_address(&signed.payout.recipient, self.ss58_prefix) {
+                Ok(public) => AccountId::from(public),
+                Err(_) => return Confirmation::default(),
+            };
+            let block_hash: H256 = match block.trim_start_matches("0x").parse() {
+                Ok(hash) => hash,
+                Err(_) => return Confirmation::default(),
+            };
+
+            let api = match self.connection() {
+                Ok((api, _)) => api,
+                Err(_) => return Confirmation::default(),
+            };
+            let events = glitch_events::block_events(&api, block_hash);
+            let position = match glitch_events::find_transfer(
+                &events,
+                &self.signer_account,
+                &recipient,
+                signed.payout.amount,
+                self.asset_id,
+            ) {
+                Some(position) => position,
+                None => return Confirmation::default(),
+            };
+            let (phase, event) = &events[position];
+
+            let mut transfer = json!({
+                "index": position,
+                "pallet": event.pallet,
+                "name": event.variant,
+                "from": public_to_ss58(&Public::from_raw(self.signer_account.clone().into()), self.ss58_prefix),
+                "to": public_to_ss58(&Public::from_raw(recipient.into()), self.ss58_prefix),
+                "amount": signed.payout.amount.to_string(),
+            });
+            if let Some(asset_id) = self.asset_id {
+                transfer["asset_id"] = json!(asset_id);
+            }
+
+            Confirmation {
+                network_fee: glitch_events::extrinsic_fee(&events, phase, &self.signer_account),
+                receipt: glitch_events::extrinsic_index(phase).map(|extrinsic_index| Receipt {
+                    block_number: api.get_storage_value("System", "Number", Some(block_hash)).ok().flatten(),
+                    extrinsic_index,
+                    event: transfer,
+                }),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Sends a small transfer to a self-owned address to prove that signing and
+/// submission work before any user deposit is paid out.
+fn send_canary_transfer(
+    api: &GlitchApi,
+    name: &str,
+    canary: &Canary,
+) -> bool {
+    let public = match parse_glitch_address(&canary.glitch_address, None) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Invalid canary address {}: {}", canary.glitch_address, e);
+            metrics::CANARY_TRANSFERS
+                .with_label_values(&[name, "error"])
+                .inc();
+            return false;
+        }
+    };
+
+    info!(
+        "Sending canary transfer of {} to {}",
+        canary.amount, canary.glitch_address
+    );
+
+    let xt = api.balance_transfer(MultiAddress::Id(AccountId::from(public)), canary.amount);
+
+    match api.send_extrinsic(xt.hex_encode(), XtStatus::Finalized) {
+        Ok(Some(hash)) => {
+            info!("Canary transfer finalized: {}", to_hex(hash));
+            metrics::CANARY_TRANSFERS
+                .with_label_values(&[name, "success"])
+                .inc();
+            metrics::CANARY_LAST_SUCCESS
+                .with_label_values(&[name])
+                .set(Utc::now().timestamp());
+            true
+        }
+        Ok(None) => {
+            error!("Canary transfer was not finalized.");
+            metrics::CANARY_TRANSFERS
+                .with_label_values(&[name, "error"])
+                .inc();
+            false
+        }
+        Err(e) => {
+            error!("Canary transfer error: {:?}", e);
+            metrics::CANARY_TRANSFERS
+                .with_label_values(&[name, "error"])
+                .inc();
+            false
+        }
+    }
+}
+
+/// Decides the payouts of the deposits waiting in the store and queues them
+/// in the outbox for `submit_outbox` to send.
+pub async fn run_network_listener(
+    settings: PayoutSettings,
+    outbox: Arc<Notify>,
     database_engine: Arc<DatabaseEngine>,
+    mut ticker: Ticker,
+    mut stop: watch::Receiver<bool>,
 ) {
-    let client = WsRpcClient::new(&glitch_node);
-    let signer: sr25519::Pair = Pair::from_string(&glitch_pk, None).unwrap();
+    let client = WsRpcClient::new(&settings.glitch_node);
+    let signer: sr25519::Pair = Pair::from_string(&settings.glitch_pk, None).unwrap();
     let signer_account_id = AccountId::from(signer.public());
-    let api: Api<sr25519::Pair, WsRpcClient, BaseExtrinsicParams<_>> =
+    let api: GlitchApi =
         Api::<_, _, PlainTipExtrinsicParams>::new(client)
             .map(|api| api.set_signer(signer))
             .unwrap();
+    check_genesis_hash(&api, settings.glitch_genesis_hash.as_deref())
+        .unwrap_or_else(|e| panic!("{e}. Refusing to pay out {}.", settings.name));
 
-    let mut interval = tokio::time::interval(Duration::from_millis(5000));
+    let mut canary_verified = settings.canary.is_none();
+    let mut block_ticker = settings.head_ticks.as_ref().map(|_| BlockTicker::subscribe(settings.glitch_node.clone()));
+    let max_per_block = settings
+        .head_ticks
+        .as_ref()
+        .and_then(|head_ticks| head_ticks.max_extrinsics_per_block)
+        .map(|max| max as usize);
+    // Since when the in-flight cap has kept payouts waiting, and whether it was alerted.
+    let mut capped_since: Option<Instant> = None;
+    let mut cap_alerted = false;
 
     loop {
+        // Decisions are only cut short between passes.
+        let tick = async {
+            match block_ticker.as_mut() {
+                Some(block_ticker) => block_ticker.tick(&mut ticker).await,
+                None => ticker.tick().await,
+            }
+        };
         tokio::select! {
-            _ = interval.tick() => {
-
-                let mut txs = database_engine.txs_to_process().await;
-
-                txs.sort_by(|a, b| {
-                    a.amount
-                        .parse::<u128>()
-                        .unwrap()
-                        .cmp(&b.amount.parse::<u128>().unwrap())
-                });
-
-                for tx in txs {
-                    let signer_free_balance = match api.get_account_data(&signer_account_id).unwrap() {
-                        Some(data) => data.free,
-                        None => 0_u128,
-                    };
-
-                    if tx.amount.as_str().parse::<u128>().unwrap() > signer_free_balance {
-                        warn!("There is not enough balance to continue processing transactions. To continue reload the account used as a signer.");
-                        break;
-                    }
+            _ = tick => {}
+            _ = stopped(&mut stop) => return,
+        }
 
-                    let public = match Public::from_str(&tx.glitch_address) {
-                        Ok(p) => p,
-                        Err(error) => {
-                            database_engine.update_tx_with_error(tx.id, format!("Error with address: {error:?}"))
-                                .await;
-                            continue;
-                        }
-                    };
-
-                    let amount = match tx.amount.clone().parse::<u128>() {
-                        Ok(a) => a,
-                        Err(error) => {
-                            database_engine
-                                .update_tx_with_error(tx.id, format!("Error with amount: {error:?}"))
-                                .await;
-                            continue;
-                        }
-                    };
-                    let (amount_to_transfer, business_fee_amount) = calculate_amount_to_transfer_and_business_fee_v2(&api, glitch_gas, amount, business_fee, public).await;
-
-                    make_transfer(name.clone(),tx.id, tx.glitch_address, glitch_node.as_str(), glitch_pk.clone(), public, amount_to_transfer, business_fee_amount, database_engine.clone(), business_fee).await;
+        if let (false, Some(canary)) = (canary_verified, &settings.canary) {
+            canary_verified = send_canary_transfer(&api, &settings.name, canary);
+            if !canary_verified {
+                warn!("Canary transfer failed, deposits will not be processed until it succeeds.");
+                continue;
+            }
+        }
+
+        let mut txs = database_engine.txs_to_process(&settings.name).await;
+        let fee_policies = database_engine.fee_policies(&settings.name).await;
+        metrics::QUEUE_DEPTH
+            .with_label_values(&[&settings.name])
+            .set(txs.len() as i64);
 
+        // Priority deposits first, then the smallest.
+        txs.sort_by_key(|tx| (!settings.label_actions.any(&tx.labels, TagAction::Priority), tx.amount));
+
+        // Decisions of this pass are persisted together once it ends, the
+        // values already committed are kept up to date in between.
+        let mut transitions = TxTransitions::default();
+        let mut queued = database_engine.outbox_pending_value(&settings.name).await;
+        let mut in_flight = match settings.max_in_flight_value {
+            Some(_) => database_engine.in_flight_value(&settings.name).await,
+            None => 0,
+        };
+        let mut submitted = 0;
+        let mut capped = false;
+        for tx in txs {
+            if max_per_block.map_or(false, |max| submitted >= max) {
+                debug!("{} payouts submitted for {} in this block, the rest wait for the next one.", submitted, settings.name);
+                break;
+            }
+
+            let amount = match tx.amount {
+                Some(amount) => amount,
+                None => {
+                    transitions.hold(tx.id, tx.version, "Amount missing or out of range".to_string());
+                    continue;
+                }
+            };
+
+            let signer_free_balance = match payout_balance(&api, &signer_account_id, settings.asset_id) {
+                Ok(balance) => balance,
+                Err(e) => {
+                    error!("Error obtaining the signer balance: {:?}", e);
+                    // The node connection is suspect, prove the path again once it is back.
+                    canary_verified = settings.canary.is_none();
+                    break;
+                }
+            };
+
+            // Payouts already decided will take their share of the balance.
+            if queued.saturating_add(amount) > signer_free_balance {
+                warn!("There is not enough balance to continue processing transactions. To continue reload the account used as a signer.");
+                break;
+            }
+
+            // Nothing in flight lets a single payout over the cap through.
+            if let Some(max_in_flight_value) = settings.max_in_flight_value {
+                if in_flight > 0 && in_flight.saturating_add(amount) > max_in_flight_value {
+                    info!(
+                        "{} in flight for {}, waiting for finalizations before paying out tx {}.",
+                        in_flight,
+                        settings.name,
+                        tx.id
+                    );
+                    capped = true;
+                    break;
                 }
             }
+
+            let public = match parse_glitch_address(&tx.glitch_address, settings.ss58_prefix) {
+                Ok(p) => p,
+                Err(error) => {
+                    transitions.error(tx.id, format!("Error with address: {error}"));
+                    continue;
+                }
+            };
+
+            let tx_business_fee = if settings.label_actions.any(&tx.labels, TagAction::FeeExempt) {
+                0.0
+            } else {
+                policy_at(&fee_policies, tx.detected_at).map_or(settings.business_fee, |policy| policy.business_fee)
+            };
+
+            let (amount_to_transfer, business_fee_amount, estimated_fee, amount_breakdown) = calculate_amount_to_transfer_and_business_fee_v2(&settings, &api, &database_engine, amount, tx_business_fee, tx.eth_fee, public).await;
+
+            let payout = OutboxPayout {
+                id: 0,
+                tx_id: tx.id,
+                tx_version: tx.version,
+                scanner_name: settings.name.clone(),
+                recipient: tx.glitch_address,
+                amount: amount_to_transfer - business_fee_amount,
+                business_fee_amount,
+                business_fee_percentage: tx_business_fee,
+                estimated_fee,
+                amount_breakdown,
+                tx_detected_at: tx.detected_at,
+            };
+            queued = queued.saturating_add(payout.amount);
+            in_flight = in_flight.saturating_add(amount);
+            submitted += 1;
+            transitions.payout(payout, tx.version);
+        }
+
+        if !capped {
+            capped_since = None;
+            cap_alerted = false;
+        } else if capped_since.get_or_insert_with(Instant::now).elapsed() >= settings.max_in_flight_wait && !cap_alerted {
+            cap_alerted = true;
+            let message = format!(
+                "Payouts of {} have waited {} minutes for finalizations: {} is in flight, the cap is {}. Check its PROCESSING txs.",
+                settings.name,
+                settings.max_in_flight_wait.as_secs() / 60,
+                in_flight,
+                settings.max_in_flight_value.unwrap_or_default()
+            );
+            warn!("{}", message);
+            notify(&settings.smtp_config, "Bridge payouts held by the in-flight cap!", &message).await;
+        }
+
+        let queued_payouts = database_engine.apply_transitions(transitions).await;
+        for (tx_id, id) in queued_payouts.iter() {
+            debug!("Payout of tx {} queued as outbox payout {}.", tx_id, id);
+        }
+        if !queued_payouts.is_empty() {
+            outbox.notify_one();
         }
     }
 }
 
-pub async fn fee_payer_v2(
-    database_engine: Arc<DatabaseEngine>,
-    interval_in_days: u32,
-    glitch_node: String,
-    scanner_name: String,
-    glitch_pk: String,
-    fee_address: String,
-) {
-    let mut interval = tokio::time::interval(Duration::from_secs(60));
-    let signer: sr25519::Pair = Pair::from_string(&glitch_pk, None).unwrap();
-    let signer_account_id = AccountId::from(signer.public());
-    let client = WsRpcClient::new(&glitch_node); // Before "ws://13.212.108.116:9944"
-    let api = Api::<_, _, PlainTipExtrinsicParams>::new(client)
-        .map(|api| api.set_signer(signer))
-        .unwrap();
+/// Whether `interval_in_days` have passed since the last fee payment. With
+/// no previous payment the fee is due right away. Both instants are UTC, so
+/// a day is always 24 hours regardless of the host timezone or DST.
+pub fn is_time_to_pay_fee_v2(clock: &dyn Clock, last_time_fee: Option<DateTime<Utc>>, interval_in_days: u32) -> bool {
+    is_fee_due(clock, last_time_fee, Duration::from_secs(interval_in_days as u64 * SECONDS_PER_DAY))
+}
 
-    loop {
-        interval.tick().await;
-        make_fee_transfer(
-            database_engine.clone(),
-            interval_in_days,
-            &scanner_name,
-            &api,
-            &signer_account_id,
-            &fee_address,
-        )
-        .await;
+/// Whether `fee_interval` has passed since the last fee payment, or there
+/// was none.
+pub fn is_fee_due(clock: &dyn Clock, last_time_fee: Option<DateTime<Utc>>, fee_interval: Duration) -> bool {
+    let last_payment = match last_time_fee {
+        Some(time) => time,
+        None => return true,
+    };
+
+    clock.now().timestamp() - last_payment.timestamp() >= fee_interval.as_secs() as i64
+}
+
+/// Checks the finalized block of a business fee transfer for the transfer
+/// event of `amount` to the destination. Returns what was found instead when
+/// it isn't there, e.g. because the extrinsic failed.
+fn fee_discrepancy(
+    api: &GlitchApi,
+    block_hash: H256,
+    signer_account_id: &AccountId,
+    fee_address: &FeeDestination,
+    amount: u128,
+    asset_id: Option<u32>,
+) -> Option<String> {
+    let transfers: Vec<_> = glitch_events::block_events(api, block_hash)
+        .into_iter()
+        .filter_map(|(_, event)| {
+            glitch_events::transfer_from(&event.data, &event.pallet, &event.variant, signer_account_id, asset_id)
+        })
+        .collect();
+
+    let destination = fee_address.account_id();
+    if transfers.iter().any(|(to, value)| *to == destination && *value == amount) {
+        return None;
     }
+
+    let found = transfers
+        .iter()
+        .map(|(to, value)| format!("{} to {}", value, account_id_to_ss58(to)))
+        .collect::<Vec<_>>();
+    Some(format!(
+        "expected a transfer of {} to {}, found {}",
+        amount,
+        fee_address,
+        if found.is_empty() { "none".to_string() } else { found.join(", ") }
+    ))
 }
 
-async fn is_time_to_pay_fee_v2(last_time_fee: Option<String>, interval_in_secs: i64) -> bool {
-    let last_day_payment = match last_time_fee {
-        Some(time) => NaiveDateTime::parse_from_str(&time, "%Y-%m-%d %H:%M:%S").unwrap(),
-        None => NaiveDateTime::from_timestamp_millis(
-            Utc::now()
-                .checked_sub_days(Days::new(2))
-                .unwrap()
-                .timestamp(),
-        )
-        .unwrap(),
-    };
+/// How a pipeline decides its payouts, resolved from the config once.
+pub struct PayoutSettings {
+    name: String,
+    glitch_pk: String,
+    glitch_node: String,
+    glitch_genesis_hash: Option<String>,
+    business_fee: f64,
+    rounding: Rounding,
+    eth_fee_policy: EthFeePolicy,
+    glitch_gas: GlitchGas,
+    asset_id: Option<u32>,
+    ss58_prefix: Option<u16>,
+    canary: Option<Canary>,
+    label_actions: LabelActions,
+    max_in_flight_value: Option<u128>,
+    max_in_flight_wait: Duration,
+    smtp_config: Notification,
+    head_ticks: Option<HeadTicks>,
+}
 
-    Utc::now().timestamp() - last_day_payment.timestamp() >= interval_in_secs
+impl PayoutSettings {
+    pub fn new(config: &Config, network_config: &Network) -> Self {
+        Self {
+            name: network_config.name.clone(),
+            glitch_pk: network_config.glitch_private_key(config),
+            glitch_node: network_config.glitch_node_url(),
+            glitch_genesis_hash: config.glitch_genesis_hash.clone(),
+            business_fee: network_config.business_fee.unwrap_or(config.business_fee),
+            rounding: config.rounding.unwrap_or_default(),
+            eth_fee_policy: network_config.eth_fee_policy.unwrap_or_default(),
+            // The network fee is paid in the native token, it can't be taken
+            // out of an asset payout.
+            glitch_gas: match network_config.glitch_asset_id {
+                Some(_) => GlitchGas::Flag(false),
+                None => network_config.glitch_gas.unwrap_or(config.glitch_gas),
+            },
+            asset_id: network_config.glitch_asset_id,
+            ss58_prefix: config.glitch_ss58_prefix,
+            canary: config.canary.clone(),
+            label_actions: LabelActions::new(config),
+            max_in_flight_value: network_config.max_in_flight_value,
+            max_in_flight_wait: network_config.max_in_flight_wait(),
+            smtp_config: config.notifications.clone(),
+            head_ticks: config.head_ticks.clone(),
+        }
+    }
 }
 
-async fn make_fee_transfer(
+/// Pays out the deposits recorded in the store on the Glitch network.
+pub struct Payer {
+    settings: PayoutSettings,
+    recipient_locks: Option<Arc<RecipientLocks>>,
+    async_finalization: bool,
+    submission_timeout: Duration,
     database_engine: Arc<DatabaseEngine>,
-    interval_in_secs: u32,
-    scanner_name: &str,
-    api: &Api<sr25519::Pair, WsRpcClient, BaseExtrinsicParams<PlainTip>>,
-    signer_account_id: &AccountId,
-    fee_address: &str,
-) {
-    let fee_last_time = database_engine.get_fee_last_time().await;
-    info!("Fee last time: {:?}", fee_last_time);
-    if !is_time_to_pay_fee_v2(fee_last_time, interval_in_secs as i64).await {
-        return;
+    ticker: Ticker,
+}
+
+impl Payer {
+    pub fn new(
+        config: &Config,
+        network_config: &Network,
+        database_engine: Arc<DatabaseEngine>,
+        scheduler: &Scheduler,
+        recipient_locks: Arc<RecipientLocks>,
+    ) -> Self {
+        Self {
+            settings: PayoutSettings::new(config, network_config),
+            recipient_locks: config
+                .serialize_payouts_per_recipient
+                .unwrap_or(false)
+                .then(|| recipient_locks),
+            async_finalization: config.async_finalization.unwrap_or(false),
+            submission_timeout: config.timeouts.clone().unwrap_or_default().extrinsic_submission(),
+            database_engine,
+            ticker: scheduler.ticker(
+                format!("payer:{}", network_config.name),
+                Duration::from_millis(5000),
+                Duration::from_millis(500),
+            ),
+        }
     }
-    let fee_to_send = database_engine.get_fee_counter(scanner_name).await;
-    if fee_to_send == 0 {
-        return;
+
+    /// Pays out until told to stop, letting the payout being sent finish.
+    pub async fn run(self, mut stop: watch::Receiver<bool>) {
+        let (finalization, pending) = if self.async_finalization {
+            let (tracker, pending) = FinalizationTracker::new();
+            (Some(tracker), Some(pending))
+        } else {
+            (None, None)
+        };
+
+        let executor: Arc<dyn PayoutExecutor> = loop {
+            match GlitchExecutor::new(
+                &self.settings.glitch_node,
+                &self.settings.glitch_pk,
+                self.settings.glitch_genesis_hash.as_deref(),
+                self.settings.asset_id,
+                self.settings.ss58_prefix,
+                self.async_finalization,
+                self.submission_timeout,
+            ) {
+                Ok(executor) => break Arc::new(executor),
+                Err(e) => {
+                    error!("{e}. Not paying out {} until it can connect.", self.settings.name);
+                    tokio::select! {
+                        _ = sleep(EXECUTOR_RETRY_DELAY) => {}
+                        _ = stopped(&mut stop) => return,
+                    }
+                }
+            }
+        };
+
+        // The listener decides the payouts, the submitter sends them.
+        let wake = Arc::new(Notify::new());
+        let payer_job = self.ticker.handle();
+
+        let (name, ss58_prefix, glitch_node) =
+            (self.settings.name.clone(), self.settings.ss58_prefix, self.settings.glitch_node.clone());
+        let listener = run_network_listener(
+            self.settings,
+            wake.clone(),
+            self.database_engine.clone(),
+            self.ticker,
+            stop.clone(),
+        );
+
+        let submitter = submit_outbox(
+            OutboxSubmitter {
+                name: name.clone(),
+                ss58_prefix,
+                recipient_locks: self.recipient_locks,
+                finalization,
+                executor: executor.clone(),
+                database_engine: self.database_engine.clone(),
+                payer_job,
+            },
+            wake,
+            stop,
+        );
+
+        match pending {
+            Some(pending) => {
+                let tracker = track_finalization(
+                    name,
+                    glitch_node,
+                    executor,
+                    self.database_engine,
+                    pending,
+                );
+                // The tracker only follows what was sent, it is dropped once
+                // the others stopped.
+                let payouts = async { tokio::join!(listener, submitter) };
+                tokio::pin!(payouts, tracker);
+                tokio::select! {
+                    _ = &mut payouts => {}
+                    _ = &mut tracker => {
+                        payouts.await;
+                    }
+                }
+            }
+            None => {
+                tokio::join!(listener, submitter);
+            }
+        }
     }
+}
 
-    info!("It's time to pay business fee!");
-    info!("Executing transfer of {} as business fee.", fee_to_send);
+/// Periodically settles the accumulated business fee.
+pub struct FeePayer {
+    name: String,
+    glitch_pk: String,
+    glitch_node: String,
+    glitch_genesis_hash: Option<String>,
+    fee_interval: Duration,
+    fee_address: FeeDestination,
+    asset_id: Option<u32>,
+    min_reserve: Option<u128>,
+    database_engine: Arc<DatabaseEngine>,
+    clock: Arc<dyn Clock>,
+    ticker: Ticker,
+}
 
-    let signer_free_balance = match api.get_account_data(&signer_account_id).unwrap() {
-        Some(data) => {
-            warn!("Signer balance is: {:?}", data);
-            data.free
+impl FeePayer {
+    pub fn new(
+        config: &Config,
+        network_config: &Network,
+        database_engine: Arc<DatabaseEngine>,
+        scheduler: &Scheduler,
+    ) -> Self {
+        Self {
+            name: network_config.name.clone(),
+            glitch_pk: network_config.glitch_private_key(config),
+            glitch_node: network_config.glitch_node_url(),
+            glitch_genesis_hash: config.glitch_genesis_hash.clone(),
+            fee_interval: network_config.fee_interval(config),
+            fee_address: network_config.fee_destination(config),
+            asset_id: network_config.glitch_asset_id,
+            min_reserve: config.fee_payer_min_reserve,
+            database_engine,
+            clock: Arc::new(SystemClock),
+            ticker: scheduler.ticker(
+                format!("fee_payer:{}", network_config.name),
+                Duration::from_secs(60),
+                Duration::from_secs(5),
+            ),
         }
-        None => 0_u128,
-    };
+    }
 
-    if fee_to_send > signer_free_balance {
-        warn!("There are not enough funds to send the business fee.");
-        return;
+    /// Replaces the wall clock used to decide when the fee is due.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    let account_id = AccountId::from(Public::from_str(fee_address).unwrap());
+    pub async fn run(mut self) {
+        let signer: sr25519::Pair = Pair::from_string(&self.glitch_pk, None).unwrap();
+        let signer_account_id = AccountId::from(signer.public());
+        let client = WsRpcClient::new(&self.glitch_node); // Before "ws://13.212.108.116:9944"
+        let api: GlitchApi = Api::<_, _, PlainTipExtrinsicParams>::new(client)
+            .map(|api| api.set_signer(signer))
+            .unwrap();
+        let scanner_name = self.name.clone();
+        check_genesis_hash(&api, self.glitch_genesis_hash.as_deref())
+            .unwrap_or_else(|e| panic!("{e}. Refusing to pay the business fee of {scanner_name}."));
+        let min_reserve = self.min_reserve.unwrap_or_else(|| match api.get_existential_deposit() {
+            Ok(existential_deposit) => existential_deposit,
+            Err(e) => panic!("Error reading the existential deposit: {e:?}. Refusing to pay the business fee of {scanner_name}."),
+        });
+        let limits = ExtrinsicLimits::of(&api)
+            .unwrap_or_else(|e| panic!("{e}. Refusing to pay the business fee of {scanner_name}."));
 
-    let xt = api.balance_transfer(GenericAddress::Id(account_id), fee_to_send);
+        // A transfer the runtime would reject is not sent again until an
+        // operator looks at it and restarts the bridge; the fee accumulates.
+        let mut held = false;
+        loop {
+            self.ticker.tick().await;
+            if held {
+                continue;
+            }
+            if let Some(reason) = self.make_fee_transfer(&api, &limits, &signer_account_id, min_reserve).await {
+                error!(
+                    target: FEE_LOG_TARGET,
+                    "Business fee transfer of {} held, it is not retried until the bridge restarts: {}",
+                    scanner_name,
+                    reason
+                );
+                held = true;
+            }
+        }
+    }
 
-    let xt_result = match api.send_extrinsic(xt.hex_encode(), XtStatus::Finalized) {
-        Ok(r) => r,
-        Err(e) => {
-            error!("Transfer error: {:?}", e);
-            None
+    /// Transfers the business fee if it is due. Returns why the transfer was
+    /// held when the runtime would reject it, so it is not sent again.
+    async fn make_fee_transfer(
+        &self,
+        api: &GlitchApi,
+        limits: &ExtrinsicLimits,
+        signer_account_id: &AccountId,
+        min_reserve: u128,
+    ) -> Option<String> {
+        let (database_engine, scanner_name, clock) = (&self.database_engine, self.name.as_str(), self.clock.as_ref());
+        let (fee_address, asset_id) = (&self.fee_address, self.asset_id);
+        let fee_last_time = database_engine.get_fee_last_time(scanner_name).await;
+        info!(target: FEE_LOG_TARGET, "Fee last time: {:?}", fee_last_time);
+        if !is_fee_due(clock, fee_last_time, self.fee_interval) {
+            return None;
+        }
+        let (fee_to_send, last_adjustment_id) = database_engine.get_fee_counter(scanner_name).await;
+        if fee_to_send == 0 {
+            return None;
         }
-    };
 
-    match xt_result {
-        Some(hash) => {
-            database_engine.modify_fee_counter(0, scanner_name).await;
-            database_engine
-                .insert_tx_fee(format!("{:#x}", hash), fee_to_send.to_string())
-                .await;
-            info!(
-                "The transfer of the business fee ({}) has been completed",
-                fee_to_send
+        // The fee of the period goes where it was due when the period started.
+        let period_start = fee_last_time.map_or(clock.now().timestamp(), |time| time.timestamp());
+        let policies = database_engine.fee_policies(scanner_name).await;
+        let fee_address = policy_at(&policies, period_start)
+            .and_then(destination_of)
+            .unwrap_or_else(|| fee_address.clone());
+
+        info!(target: FEE_LOG_TARGET, "It's time to pay business fee!");
+        info!(target: FEE_LOG_TARGET, "Executing transfer of {} as business fee.", fee_to_send);
+
+        info!(target: FEE_LOG_TARGET, "Business fee destination: {}", fee_address);
+        let (xt_hex, _) = payout_extrinsic(api, fee_address.account_id(), fee_to_send, asset_id);
+        let network_fee = match estimate_fee(api, xt_hex).await {
+            Ok(network_fee) => network_fee,
+            Err(e) => {
+                warn!(target: FEE_LOG_TARGET, "Business fee transfer not submitted, its network fee can't be estimated: {}", e);
+                return None;
+            }
+        };
+
+        // The network fee of a native transfer comes out of the business fee, so
+        // the signer gives away exactly what it collected. An asset transfer
+        // pays it from the native balance.
+        let native_balance = match payout_balance(api, signer_account_id, None) {
+            Ok(balance) => balance,
+            Err(e) => {
+                error!(target: FEE_LOG_TARGET, "Error obtaining the signer balance, the business fee will be tried again: {:?}", e);
+                return None;
+            }
+        };
+        let (amount_to_send, native_out) = match asset_id {
+            None if network_fee >= fee_to_send => {
+                warn!(
+                    target: FEE_LOG_TARGET,
+                    "The business fee {} doesn't cover its own network fee {}, it will keep accumulating.",
+                    fee_to_send,
+                    network_fee
+                );
+                return None;
+            }
+            None => (fee_to_send - network_fee, fee_to_send),
+            Some(_) => (fee_to_send, network_fee),
+        };
+        warn!(target: FEE_LOG_TARGET, "Signer native balance is: {}", native_balance);
+
+        if native_balance < native_out.saturating_add(min_reserve) {
+            warn!(
+                target: FEE_LOG_TARGET,
+                "There are not enough funds to send the business fee and keep the reserve of {} (network fee {}).",
+                min_reserve,
+                network_fee
             );
+            return None;
         }
-        None => {
-            info!("Transfer of the business fee not completed. It will be tried again.");
+        if asset_id.is_some() {
+            match payout_balance(api, signer_account_id, asset_id) {
+                Ok(balance) if balance < fee_to_send => {
+                    warn!(target: FEE_LOG_TARGET, "There are not enough funds to send the business fee.");
+                    return None;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!(target: FEE_LOG_TARGET, "Error obtaining the signer asset balance, the business fee will be tried again: {:?}", e);
+                    return None;
+                }
+            }
+        }
+
+        info!(
+            target: FEE_LOG_TARGET,
+            "Transferring {} of business fee, network fee {}.",
+            amount_to_send,
+            network_fee
+        );
+        let (xt_hex, _) = payout_extrinsic(api, fee_address.account_id(), amount_to_send, asset_id);
+        match validate_extrinsic(api, limits, &xt_hex) {
+            Simulation::Unavailable(reason) => {
+                debug!(target: FEE_LOG_TARGET, "Business fee transfer not validated, submitting it anyway: {}", reason);
+            }
+            Simulation::Rejected(reason) => return Some(reason),
+            _ => {}
+        }
+
+        let xt_result = match api.send_extrinsic(xt_hex, XtStatus::Finalized) {
+            Ok(r) => r,
+            Err(e) => {
+                error!(target: FEE_LOG_TARGET, "Transfer error: {:?}", e);
+                None
+            }
+        };
+
+        match xt_result {
+            Some(hash) => {
+                database_engine.deduct_fee_counter(fee_to_send, scanner_name).await;
+                let fee_tx_id = database_engine
+                    .insert_tx_fee(scanner_name, to_hex(hash), amount_to_send.to_string(), last_adjustment_id)
+                    .await;
+
+                let discrepancy = fee_discrepancy(api, hash, signer_account_id, &fee_address, amount_to_send, asset_id);
+                if let Some(discrepancy) = discrepancy.as_ref() {
+                    error!(target: FEE_LOG_TARGET, "Business fee transfer in block {}: {}", to_hex(hash), discrepancy);
+                }
+                if let Some(id) = fee_tx_id {
+                    database_engine.record_fee_confirmation(id, discrepancy).await;
+                }
+                info!(
+                    target: FEE_LOG_TARGET,
+                    "The transfer of the business fee ({}) has been completed",
+                    amount_to_send
+                );
+            }
+            None => {
+                info!(target: FEE_LOG_TARGET, "Transfer of the business fee not completed. It will be tried again.");
+            }
         }
+
+        None
     }
 }
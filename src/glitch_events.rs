@@ -0,0 +1,154 @@
+use log::warn;
+use serde_json::{json, Value};
+use sp_core::{crypto::AccountId32, hashing::blake2_256, H256};
+use substrate_api_client::{EventsDecoder, Raw, RawEvent};
+
+use crate::types::{to_hex, GlitchApi};
+
+/// Events emitted in a block, decoded against the runtime metadata. The phase
+/// is kept so events can be grouped by the extrinsic that emitted them.
+pub fn block_events(api: &GlitchApi, block_hash: H256) -> Vec<(String, RawEvent)> {
+    let key = match api.metadata.storage_value_key("System", "Events") {
+        Ok(key) => key,
+        Err(e) => {
+            warn!("Events storage key not found in the metadata: {:?}", e);
+            return vec![];
+        }
+    };
+
+    let bytes = match api.get_opaque_storage_by_key_hash(key, Some(block_hash)) {
+        Ok(Some(bytes)) => bytes,
+        Ok(None) => return vec![],
+        Err(e) => {
+            warn!("Error reading events of block {:?}: {:?}", block_hash, e);
+            return vec![];
+        }
+    };
+
+    match EventsDecoder::new(api.metadata.clone()).decode_events(&mut bytes.as_slice()) {
+        Ok(events) => events
+            .into_iter()
+            .filter_map(|(phase, raw)| match raw {
+                Raw::Event(event) => Some((format!("{:?}", phase), event)),
+                Raw::Error(_) => None,
+            })
+            .collect(),
+        Err(e) => {
+            warn!("Error decoding events of block {:?}: {:?}", block_hash, e);
+            vec![]
+        }
+    }
+}
+
+/// Number of the finalized head.
+pub fn finalized_number(api: &GlitchApi) -> Option<u32> {
+    let hash = api.get_finalized_head().ok()??;
+    api.get_storage_value("System", "Number", Some(hash)).ok()?
+}
+
+/// `0x`-prefixed hashes of the extrinsics of a block, in order.
+pub fn block_extrinsic_hashes(api: &GlitchApi, block_hash: H256) -> Option<Vec<String>> {
+    let request = json!({ "jsonrpc": "2.0", "id": "1", "method": "chain_getBlock", "params": [to_hex(block_hash)] });
+    let response = match api.get_request(request) {
+        Ok(Some(response)) => response,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!("Error reading block {:?}: {:?}", block_hash, e);
+            return None;
+        }
+    };
+
+    let block: Value = serde_json::from_str(&response).ok()?;
+    block["block"]["extrinsics"]
+        .as_array()?
+        .iter()
+        .map(|extrinsic| {
+            let bytes = hex::decode(extrinsic.as_str()?.trim_start_matches("0x")).ok()?;
+            Some(to_hex(H256(blake2_256(&bytes))))
+        })
+        .collect()
+}
+
+pub(crate) fn read_account(data: &[u8], offset: usize) -> Option<AccountId32> {
+    let bytes: [u8; 32] = data.get(offset..offset + 32)?.try_into().ok()?;
+    Some(AccountId32::from(bytes))
+}
+
+pub(crate) fn read_u128(data: &[u8], offset: usize) -> Option<u128> {
+    let bytes: [u8; 16] = data.get(offset..offset + 16)?.try_into().ok()?;
+    Some(u128::from_le_bytes(bytes))
+}
+
+/// Index of the extrinsic whose events are in `phase`, e.g. `ApplyExtrinsic(2)`.
+pub fn extrinsic_index(phase: &str) -> Option<u32> {
+    phase.strip_prefix("ApplyExtrinsic(")?.strip_suffix(')')?.parse().ok()
+}
+
+/// Position among `events` of the transfer `from -> to` of `amount`, the
+/// `Balances.Transfer` event or, with `asset_id`, the `Assets.Transferred` one.
+pub fn find_transfer(
+    events: &[(String, RawEvent)],
+    from: &AccountId32,
+    to: &AccountId32,
+    amount: u128,
+    asset_id: Option<u32>,
+) -> Option<usize> {
+    events.iter().position(|(_, event)| match (asset_id, event.pallet.as_str(), event.variant.as_str()) {
+        // Balances.Transfer { from, to, amount }
+        (None, "Balances", "Transfer") => {
+            read_account(&event.data, 0).as_ref() == Some(from)
+                && read_account(&event.data, 32).as_ref() == Some(to)
+                && read_u128(&event.data, 64) == Some(amount)
+        }
+        // Assets.Transferred { asset_id, from, to, amount }
+        (Some(asset_id), "Assets", "Transferred") => {
+            event.data.get(0..4).map(|id| id == asset_id.to_le_bytes()).unwrap_or(false)
+                && read_account(&event.data, 4).as_ref() == Some(from)
+                && read_account(&event.data, 36).as_ref() == Some(to)
+                && read_u128(&event.data, 68) == Some(amount)
+        }
+        _ => false,
+    })
+}
+
+/// Recipient and amount of a transfer from `signer`, native or of the asset.
+pub(crate) fn transfer_from(
+    data: &[u8],
+    pallet: &str,
+    variant: &str,
+    signer: &AccountId32,
+    asset_id: Option<u32>,
+) -> Option<(AccountId32, u128)> {
+    match (asset_id, pallet, variant) {
+        // Balances.Transfer { from, to, amount }
+        (None, "Balances", "Transfer") if read_account(data, 0).as_ref() == Some(signer) => {
+            Some((read_account(data, 32)?, read_u128(data, 64)?))
+        }
+        // Assets.Transferred { asset_id, from, to, amount }
+        (Some(asset_id), "Assets", "Transferred") if read_account(data, 4).as_ref() == Some(signer) => {
+            let id = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+            if id != asset_id {
+                return None;
+            }
+            Some((read_account(data, 36)?, read_u128(data, 68)?))
+        }
+        _ => None,
+    }
+}
+
+/// Fee actually charged to `who` for the extrinsic of `phase`, read from the
+/// `TransactionPayment.TransactionFeePaid` event it emitted. This already
+/// accounts for any refund made by the runtime after dispatch.
+pub fn extrinsic_fee(events: &[(String, RawEvent)], phase: &str, who: &AccountId32) -> Option<u128> {
+    // TransactionPayment.TransactionFeePaid { who, actual_fee, tip }
+    events.iter().find_map(|(event_phase, event)| {
+        let is_fee_paid = event_phase == phase
+            && event.pallet == "TransactionPayment"
+            && event.variant == "TransactionFeePaid"
+            && read_account(&event.data, 0).as_ref() == Some(who);
+        if !is_fee_paid {
+            return None;
+        }
+        Some(read_u128(&event.data, 32)? + read_u128(&event.data, 48)?)
+    })
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use sp_core::{hashing::blake2_256, H256};
+use substrate_api_client::{rpc::WsRpcClient, Api};
+use web3::types::H160;
+
+use crate::config::{GlitchWithdrawals, Network};
+use crate::database::{DatabaseEngine, ScannerErrorKind};
+use crate::glitch_events::{block_events, finalized_number, read_account, read_u128};
+use crate::scheduler::Ticker;
+use crate::types::{account_id_to_ss58, to_hex, GlitchApi};
+
+const DEFAULT_PALLET: &str = "Bridge";
+const DEFAULT_EVENT: &str = "Withdrawn";
+
+/// A withdrawal event found on Glitch, to be paid on the network.
+#[derive(Debug, Clone)]
+pub struct Withdrawal {
+    /// Hash of the block hash and the index of the event, unique per event.
+    pub event_id: String,
+    pub from_glitch_address: String,
+    pub to_eth_address: String,
+    pub amount: u128,
+    pub block_number: u32,
+}
+
+/// Scanner state row of the withdrawals of a pipeline, apart from the one of
+/// its deposits.
+pub fn withdrawals_scanner_name(network_name: &str) -> String {
+    format!("withdrawals:{network_name}")
+}
+
+/// Glitch side of a pipeline: queues the withdrawal events of finalized
+/// Glitch blocks, so no confirmations are needed, tracking the last block
+/// scanned in its own `scanner_state` row.
+pub struct GlitchScanner {
+    network_config: Network,
+    withdrawals: GlitchWithdrawals,
+    database_engine: Arc<DatabaseEngine>,
+    ticker: Ticker,
+}
+
+impl GlitchScanner {
+    pub fn new(
+        network_config: Network,
+        withdrawals: GlitchWithdrawals,
+        database_engine: Arc<DatabaseEngine>,
+        ticker: Ticker,
+    ) -> Self {
+        Self {
+            network_config,
+            withdrawals,
+            database_engine,
+            ticker,
+        }
+    }
+
+    /// Withdrawal events `(who, to, amount)` of a block.
+    fn block_withdrawals(&self, api: &GlitchApi, block_number: u32) -> Option<Vec<Withdrawal>> {
+        let block_hash: H256 = api.get_block_hash(Some(block_number)).ok()??;
+        let pallet = self.withdrawals.pallet.as_deref().unwrap_or(DEFAULT_PALLET);
+        let variant = self.withdrawals.event.as_deref().unwrap_or(DEFAULT_EVENT);
+
+        let withdrawals = block_events(api, block_hash)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, event))| event.pallet == pallet && event.variant == variant)
+            .filter_map(|(index, (_, event))| {
+                let who = read_account(&event.data, 0)?;
+                let to = H160::from_slice(event.data.get(32..52)?);
+                let amount = read_u128(&event.data, 52)?;
+
+                let mut id = block_hash.as_bytes().to_vec();
+                id.extend_from_slice(&(index as u32).to_le_bytes());
+
+                Some(Withdrawal {
+                    event_id: to_hex(H256(blake2_256(&id))),
+                    from_glitch_address: account_id_to_ss58(&who),
+                    to_eth_address: format!("{to:?}"),
+                    amount,
+                    block_number,
+                })
+            })
+            .collect();
+
+        Some(withdrawals)
+    }
+
+    pub async fn run(mut self) {
+        let name = withdrawals_scanner_name(&self.network_config.name);
+        info!("Running Glitch withdrawals scanner for {}", self.network_config.name);
+
+        let api: GlitchApi = Api::new(WsRpcClient::new(&self.network_config.glitch_node_url()))
+            .expect("Error connecting with the Glitch node!");
+
+        let known = self
+            .database_engine
+            .exists_network_state(
+                &name,
+                "glitch",
+                &format!(
+                    "{}.{}",
+                    self.withdrawals.pallet.as_deref().unwrap_or(DEFAULT_PALLET),
+                    self.withdrawals.event.as_deref().unwrap_or(DEFAULT_EVENT)
+                ),
+            )
+            .await;
+
+        let mut last_block = if known {
+            self.database_engine.get_last_block(&name).await
+        } else {
+            // New scanners start at the given block or the finalized head.
+            match self.withdrawals.start_block {
+                Some(start_block) => start_block.saturating_sub(1),
+                None => finalized_number(&api).unwrap_or(0),
+            }
+        };
+
+        loop {
+            self.ticker.tick().await;
+
+            let finalized = match finalized_number(&api) {
+                Some(number) => number,
+                None => {
+                    error!("Error reading the finalized head of Glitch");
+                    self.database_engine
+                        .insert_scanner_error(&name, ScannerErrorKind::Rpc, None, "Error reading the finalized head")
+                        .await;
+                    continue;
+                }
+            };
+
+            for block_number in last_block + 1..=finalized {
+                let withdrawals = match self.block_withdrawals(&api, block_number) {
+                    Some(withdrawals) => withdrawals,
+                    None => {
+                        error!("Error reading Glitch block {}, it will be retried.", block_number);
+                        self.database_engine
+                            .insert_scanner_error(
+                                &name,
+                                ScannerErrorKind::Rpc,
+                                Some(block_number as u64),
+                                "Error reading the block events",
+                            )
+                            .await;
+                        break;
+                    }
+                };
+
+                if !withdrawals.is_empty() {
+                    info!("{} withdrawals found in Glitch block {}", withdrawals.len(), block_number);
+                }
+
+                if !self
+                    .database_engine
+                    .update_block_and_insert_withdrawals(name.clone(), block_number, withdrawals)
+                    .await
+                {
+                    break;
+                }
+                last_block = block_number;
+            }
+        }
+    }
+}
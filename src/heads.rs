@@ -0,0 +1,60 @@
+//! Finalized heads of Glitch, as a signal to run work once per block.
+
+use log::{error, warn};
+use sp_core::sr25519;
+use substrate_api_client::{rpc::WsRpcClient, Api, PlainTipExtrinsicParams};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::Duration;
+
+use crate::scheduler::Ticker;
+
+/// Signals every new finalized head, resubscribing when the connection drops.
+pub(crate) fn forward_finalized_heads(glitch_node: String, heads: UnboundedSender<()>) {
+    loop {
+        let client = WsRpcClient::new(&glitch_node);
+        match Api::<sr25519::Pair, _, PlainTipExtrinsicParams>::new(client) {
+            Ok(api) => {
+                let (sender, receiver) = std::sync::mpsc::channel();
+                match api.subscribe_finalized_heads(sender) {
+                    Ok(()) => {
+                        for _ in receiver {
+                            if heads.send(()).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => error!("Error subscribing to finalized heads: {:?}", e),
+                }
+            }
+            Err(e) => error!("Error connecting to {} for finalized heads: {:?}", glitch_node, e),
+        }
+
+        warn!("Finalized heads subscription lost, resubscribing in 5 seconds.");
+        std::thread::sleep(Duration::from_secs(5));
+    }
+}
+
+/// Ticks a job on the finalized heads of a Glitch node instead of on its
+/// interval. Pausing the job still holds it.
+pub struct BlockTicker {
+    heads: UnboundedReceiver<()>,
+}
+
+impl BlockTicker {
+    pub fn subscribe(glitch_node: String) -> Self {
+        let (sender, heads) = mpsc::unbounded_channel();
+        std::thread::spawn(move || forward_finalized_heads(glitch_node, sender));
+        Self { heads }
+    }
+
+    /// Waits for the next finalized head. Heads that came in while the job
+    /// was busy or paused count as one, so a backlog doesn't turn into a
+    /// burst of runs.
+    pub async fn tick(&mut self, ticker: &mut Ticker) {
+        let head = ticker.tick_on(self.heads.recv()).await;
+        if head.is_none() {
+            panic!("The finalized heads subscription is gone!");
+        }
+        while self.heads.try_recv().is_ok() {}
+    }
+}
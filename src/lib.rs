@@ -0,0 +1,75 @@
+pub mod api;
+pub mod args;
+pub mod attestation;
+pub mod audit;
+pub mod balance_monitor;
+pub mod bench;
+pub mod block_listener;
+pub mod bridge;
+pub mod bulk;
+pub mod clock;
+pub mod commands;
+pub mod compliance;
+pub mod config;
+pub mod contract_check;
+pub mod crash;
+pub mod dashboards;
+pub mod database;
+pub mod db_health;
+pub mod decoder;
+pub mod dry_run;
+pub mod duplicate_recipients;
+pub mod encryption;
+pub mod eth_ack;
+pub mod eth_withdrawals;
+pub mod extrinsic_limits;
+pub mod failover;
+pub mod fee_adjustments;
+pub mod fee_policy;
+pub mod finality_monitor;
+pub mod finalization;
+pub mod glitch;
+pub mod glitch_events;
+pub mod glitch_scanner;
+pub mod heads;
+pub mod log_cache;
+pub mod logger;
+pub mod metrics;
+pub mod migrate;
+pub mod notifications;
+pub mod outbox;
+pub mod payout;
+pub mod proxy;
+pub mod queue_monitor;
+pub mod quote;
+pub mod reconcile;
+pub mod recipient_locks;
+pub mod reorg;
+pub mod reporting;
+pub mod rpc;
+pub mod scanner;
+pub mod scheduler;
+pub mod schema;
+pub mod shadow_decoder;
+pub mod signer_activity;
+pub mod skipped_logs;
+pub mod sla_monitor;
+pub mod snapshot;
+pub mod source_tx;
+pub mod stuck_sweeper;
+pub mod substrate_scanner;
+pub mod supervisor;
+pub mod supply_check;
+pub mod tagging;
+pub mod top_up;
+pub mod types;
+pub mod upgrade_monitor;
+pub mod verify_deposit;
+pub mod version;
+pub mod webhooks;
+
+pub use crate::bridge::{ Bridge, BridgeBuilder };
+pub use crate::config::Config;
+pub use crate::database::DatabaseEngine as BridgeStore;
+pub use crate::glitch::{ FeePayer, Payer };
+pub use crate::scanner::{ DepositSource, Scanner };
@@ -0,0 +1,70 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use lru::LruCache;
+use web3::types::{Log, H256, U256};
+
+use crate::metrics;
+
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Remembers the most recently seen `(tx_eth_hash, log_index)` pairs so logs
+/// re-delivered by flaky RPC nodes are dropped before reaching the database.
+pub struct RecentLogs {
+    network: String,
+    cache: Mutex<LruCache<LogKey, ()>>,
+}
+
+impl RecentLogs {
+    pub fn new(network: String, capacity: Option<usize>) -> Self {
+        let capacity = NonZeroUsize::new(capacity.unwrap_or(DEFAULT_CAPACITY))
+            .unwrap_or(NonZeroUsize::new(DEFAULT_CAPACITY).unwrap());
+
+        Self {
+            network,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Returns only the logs that were not seen recently. They are only
+    /// remembered once their deposits are stored (see `remember`), so logs
+    /// whose insert failed are decoded again on the retry.
+    pub fn retain_new(&self, logs: Vec<Log>) -> Vec<Log> {
+        let cache = self.cache.lock().unwrap();
+
+        logs.into_iter()
+            .filter(|log| match log_key(log) {
+                Some(key) => {
+                    let seen = cache.contains(&key);
+                    if seen {
+                        metrics::DUPLICATE_LOGS
+                            .with_label_values(&[&self.network])
+                            .inc();
+                    }
+                    !seen
+                }
+                None => true,
+            })
+            .collect()
+    }
+
+    /// Remembers the logs whose deposits were stored.
+    pub fn remember(&self, keys: Vec<LogKey>) {
+        let mut cache = self.cache.lock().unwrap();
+        for key in keys {
+            cache.put(key, ());
+        }
+    }
+}
+
+/// `(tx_eth_hash, log_index)` of a log.
+pub type LogKey = (H256, U256);
+
+fn log_key(log: &Log) -> Option<LogKey> {
+    Some((log.transaction_hash?, log.log_index?))
+}
+
+/// Keys of the logs, to remember them once their deposits are stored.
+pub fn log_keys(logs: &[Log]) -> Vec<LogKey> {
+    logs.iter().filter_map(log_key).collect()
+}
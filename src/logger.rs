@@ -1,37 +1,224 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
 use log::LevelFilter;
 use log4rs::{
     append::{console::ConsoleAppender, file::FileAppender},
-    config::{Appender, Config, Root},
+    config::{Appender, Config, Logger, Root},
     encode::pattern::PatternEncoder,
-    filter::threshold::ThresholdFilter,
+    Handle,
 };
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
 
-pub fn config(log_level: LevelFilter) {
-    let pattern = Box::new(PatternEncoder::new(
-        "[{d(%Y-%m-%d %H:%M:%S)} {l}] {M} — {m}{n}",
-    ));
+/// Log target of the business fee payer, which lives in `glitch` but is
+/// tuned on its own.
+pub const FEE_LOG_TARGET: &str = "glitch_bridge::fee";
 
-    let stdout = ConsoleAppender::builder().encoder(pattern.clone()).build();
+/// Log target of the access records of the HTTP API, one JSON object per
+/// request.
+pub const ACCESS_LOG_TARGET: &str = "glitch_bridge::access";
 
-    let _logfile = FileAppender::builder()
-        .encoder(pattern)
-        .build("log/output.log")
-        .unwrap();
+/// Log target of the logs the scanners ignore, one JSON object per log.
+pub const SKIPPED_LOG_TARGET: &str = "glitch_bridge::skipped";
+
+/// MDC key holding the spans the logging thread is in, see `SpanContext`.
+const SPAN_MDC_KEY: &str = "span";
+const LOG_PATTERN: &str = "[{d(%Y-%m-%d %H:%M:%S)} {l}] {M} {X(span)}— {m}{n}";
+
+struct LogLevels {
+    handle: Handle,
+    root: LevelFilter,
+    /// Overrides by module, as given by the operator.
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+lazy_static! {
+    static ref LOG_LEVELS: Mutex<Option<LogLevels>> = Mutex::new(None);
+}
+
+/// Log targets of the short module names operators usually tune. Any other
+/// name is taken as a module path, e.g. `glitch_bridge::reorg`.
+fn targets(module: &str) -> Vec<String> {
+    let targets: &[&str] = match module {
+        "scanner" => &[
+            "glitch_bridge::scanner",
+            "glitch_bridge::block_listener",
+            "glitch_bridge::substrate_scanner",
+        ],
+        "database" => &["glitch_bridge::database"],
+        "glitch" => &["glitch_bridge::glitch", "glitch_bridge::finalization"],
+        "fee" => &[FEE_LOG_TARGET],
+        "access" => &[ACCESS_LOG_TARGET],
+        "skipped" => &[SKIPPED_LOG_TARGET],
+        other => return vec![other.to_string()],
+    };
+    targets.iter().map(|target| target.to_string()).collect()
+}
+
+/// Fields of a span as `name=value` pairs.
+struct SpanFieldsVisitor(String);
+
+impl Visit for SpanFieldsVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        self.0.push_str(&format!("{}={:?}", field.name(), value));
+    }
+}
+
+/// Context of a span and its parents, e.g.
+/// `task{name=payer:eth} transfer{scanner=eth tx_id=12}`.
+struct SpanFields(String);
+
+fn context_of<S>(span: &SpanRef<'_, S>) -> Option<String>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let extensions = span.extensions();
+    extensions.get::<SpanFields>().map(|fields| fields.0.clone())
+}
+
+/// Carries the `tracing` spans into the `log` records, which log4rs writes:
+/// while a span is entered, on whatever thread polls or runs it, its context
+/// is kept in the `span` MDC key that the pattern prints before the message.
+struct SpanContext;
+
+impl SpanContext {
+    fn set_current<S>(ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        match ctx.lookup_current().and_then(|span| context_of(&span)) {
+            // Trailing space, so lines outside any span keep their format.
+            Some(context) => {
+                log_mdc::insert(SPAN_MDC_KEY, format!("{context} "));
+            }
+            None => {
+                log_mdc::remove(SPAN_MDC_KEY);
+            }
+        }
+    }
+}
+
+impl<S> Layer<S> for SpanContext
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut fields = SpanFieldsVisitor(String::new());
+        attrs.record(&mut fields);
+        let own = if fields.0.is_empty() {
+            span.name().to_string()
+        } else {
+            format!("{}{{{}}}", span.name(), fields.0)
+        };
+
+        let context = match span.parent().and_then(|parent| context_of(&parent)) {
+            Some(parent) => format!("{parent} {own}"),
+            None => own,
+        };
+        span.extensions_mut().insert(SpanFields(context));
+    }
 
-    let config = Config::builder()
+    // The registry has already moved the current span when these are called.
+    fn on_enter(&self, _id: &Id, ctx: Context<'_, S>) {
+        Self::set_current(&ctx);
+    }
+
+    fn on_exit(&self, _id: &Id, ctx: Context<'_, S>) {
+        Self::set_current(&ctx);
+    }
+}
+
+fn build(root: LevelFilter, modules: &BTreeMap<String, LevelFilter>) -> Config {
+    let pattern = Box::new(PatternEncoder::new(LOG_PATTERN));
+
+    let stdout = ConsoleAppender::builder().encoder(pattern).build();
+
+    let loggers = modules.iter().flat_map(|(module, level)| {
+        targets(module)
+            .into_iter()
+            .map(move |target| Logger::builder().build(target, *level))
+    });
+
+    Config::builder()
         //.appender(Appender::builder().build("logfile", Box::new(logfile)))
-        .appender(
-            Appender::builder()
-                .filter(Box::new(ThresholdFilter::new(log_level)))
-                .build("stdout", Box::new(stdout)),
-        )
+        .appender(Appender::builder().build("stdout", Box::new(stdout)))
+        .loggers(loggers)
         .build(
             Root::builder()
                 .appender("stdout")
                 //.appender("logfile")
-                .build(LevelFilter::Trace),
+                .build(root),
         )
+        .unwrap()
+}
+
+/// Installs log4rs as the logger, at `log_level`, and the `tracing`
+/// subscriber that adds the span context to its lines.
+pub fn config(log_level: LevelFilter) {
+    let pattern = Box::new(PatternEncoder::new(LOG_PATTERN));
+
+    let _logfile = FileAppender::builder()
+        .encoder(pattern)
+        .build("log/output.log")
         .unwrap();
 
-    log4rs::init_config(config).unwrap();
+    let modules = BTreeMap::new();
+    let handle = log4rs::init_config(build(log_level, &modules)).unwrap();
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(SpanContext))
+        .expect("Could not install the tracing subscriber!");
+
+    *LOG_LEVELS.lock().unwrap() = Some(LogLevels {
+        handle,
+        root: log_level,
+        modules,
+    });
+}
+
+/// Sets the level of a module, or goes back to the global level with `None`.
+/// Takes effect right away, without restarting.
+pub fn set_module_level(module: &str, level: Option<LevelFilter>) -> Result<(), String> {
+    if module.is_empty() {
+        return Err("Empty module name".to_string());
+    }
+
+    let mut guard = LOG_LEVELS.lock().unwrap();
+    let levels = guard.as_mut().ok_or_else(|| "The logger is not configured".to_string())?;
+    match level {
+        Some(level) => levels.modules.insert(module.to_string(), level),
+        None => levels.modules.remove(module),
+    };
+    levels.handle.set_config(build(levels.root, &levels.modules));
+
+    Ok(())
+}
+
+/// Applies the `log_levels` of the config, e.g. `{"glitch": "debug"}`.
+pub fn set_module_levels(module_levels: &BTreeMap<String, String>) {
+    for (module, level) in module_levels {
+        let level = LevelFilter::from_str(level)
+            .unwrap_or_else(|_| panic!("Invalid log level {level} for {module}!"));
+        set_module_level(module, Some(level)).unwrap();
+    }
+}
+
+/// The global level and the module overrides in effect.
+pub fn levels() -> (LevelFilter, BTreeMap<String, LevelFilter>) {
+    let guard = LOG_LEVELS.lock().unwrap();
+    match guard.as_ref() {
+        Some(levels) => (levels.root, levels.modules.clone()),
+        None => (LevelFilter::Off, BTreeMap::new()),
+    }
 }
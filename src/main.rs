@@ -1,16 +1,6 @@
-mod args;
-mod balance_monitor;
-mod block_listener;
-mod config;
-mod database;
-mod glitch;
-mod logger;
-mod scanner;
-
-use crate::args::Args;
-use crate::config::Config;
 use clap::Parser;
-use scanner::ScannerV2;
+use glitch_bridge::args::Args;
+use glitch_bridge::{ commands, crash, logger, proxy, Bridge, Config };
 
 const TITLE: &str = r#"
                                                                                                               
@@ -37,9 +27,22 @@ async fn main() -> web3::Result<()> {
 
     logger::config(args.loglevel);
 
+    let command = args.command.clone();
+    let read_only = args.read_only;
     let config: Config = Config::new(args).check_private_keys();
-
-    ScannerV2::run(config).await;
+    if let Some(log_levels) = &config.log_levels {
+        logger::set_module_levels(log_levels);
+    }
+    proxy::configure(config.proxy.clone());
+
+    crash::install_panic_hook(&config);
+    #[cfg(feature = "sentry")]
+    let _sentry = crash::init_sentry(&config);
+
+    match command {
+        Some(command) => commands::run(command, config).await,
+        None => Bridge::builder().config(config).read_only(read_only).build().run().await,
+    }
 
     Ok(())
 }
@@ -0,0 +1,238 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_gauge, register_gauge_vec, register_histogram_vec, register_int_counter_vec,
+    register_int_gauge_vec, Encoder, Gauge, GaugeVec, HistogramVec, IntCounterVec, IntGaugeVec,
+    TextEncoder,
+};
+
+lazy_static! {
+    pub static ref CANARY_TRANSFERS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_canary_transfers_total",
+        "Canary transfers sent to the self-owned address, by result",
+        &["network", "result"]
+    )
+    .unwrap();
+    pub static ref CANARY_LAST_SUCCESS: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_canary_last_success_timestamp_seconds",
+        "Unix time of the last successful canary transfer",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref TRANSFER_LATENCY: HistogramVec = register_histogram_vec!(
+        "glitch_bridge_transfer_latency_seconds",
+        "Time spent by deposits in each stage of the pipeline",
+        &["network", "stage"],
+        vec![5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 3600.0]
+    )
+    .unwrap();
+    pub static ref PENDING_FINALIZATIONS: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_pending_finalizations",
+        "Payouts included in a block and waiting for it to be finalized",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref DUPLICATE_LOGS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_duplicate_logs_total",
+        "Logs dropped because they were already seen recently",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref SUSPICIOUS_DEPOSITS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_suspicious_deposits_total",
+        "Deposits held for operator review by the decode sanity checks",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref SCREENINGS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_deposit_screenings_total",
+        "Compliance screenings of new deposits by verdict (allow, hold, error)",
+        &["network", "verdict"]
+    )
+    .unwrap();
+    pub static ref DECODER_DIVERGENCES: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_decoder_divergences_total",
+        "Logs the shadow decoder decoded differently from the active one",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref RPC_RATE_LIMITED: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_rpc_rate_limited_total",
+        "Ethereum RPC calls rejected by the provider rate limit",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref RPC_TIMEOUTS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_rpc_timeouts_total",
+        "Ethereum RPC calls abandoned for running over their timeout",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref RPC_RETRY_BUDGET: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_rpc_retry_budget_remaining",
+        "Retries left in the current one minute window",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref JOB_RUNS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_job_runs_total",
+        "Completed runs of each periodic job",
+        &["job"]
+    )
+    .unwrap();
+    pub static ref JOB_DURATION: HistogramVec = register_histogram_vec!(
+        "glitch_bridge_job_duration_seconds",
+        "Duration of each run of the periodic jobs",
+        &["job"]
+    )
+    .unwrap();
+    pub static ref JOB_PAUSED: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_job_paused",
+        "Whether the periodic job is paused by an operator",
+        &["job"]
+    )
+    .unwrap();
+    pub static ref QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_queue_depth",
+        "Deposits waiting to be paid out",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref OLDEST_PENDING_AGE: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_oldest_pending_age_seconds",
+        "Age of the oldest deposit waiting to be paid out (TO_PROCESS) or in flight (PROCESSING)",
+        &["network", "state"]
+    )
+    .unwrap();
+    pub static ref REORGS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_reorgs_total",
+        "Chain reorganizations seen on the new heads subscription",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref CONTRACT_UPGRADES: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_contract_upgrades_total",
+        "Implementation changes detected on the monitored proxy",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref NETWORK_FEE_AVERAGE: GaugeVec = register_gauge_vec!(
+        "glitch_bridge_network_fee_average_plancks",
+        "Average Glitch network fee paid per payout over the last day",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref NETWORK_FEE_FALLBACKS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_network_fee_fallbacks_total",
+        "Payouts charged the average network fee because estimating it failed",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref TASK_UP: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_task_up",
+        "Whether each supervised task is running",
+        &["task"]
+    )
+    .unwrap();
+    pub static ref TASK_RESTARTS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_task_restarts_total",
+        "Restarts of each supervised task after it ended or panicked",
+        &["task"]
+    )
+    .unwrap();
+    pub static ref DB_UP: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_db_up",
+        "Whether the last health ping of the database succeeded",
+        &["database"]
+    )
+    .unwrap();
+    pub static ref DB_CONNECTION_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_db_connection_failures_total",
+        "Failed attempts to get a database connection, by who found it failing",
+        &["database", "source"]
+    )
+    .unwrap();
+    pub static ref DB_POOL_RECREATIONS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_db_pool_recreations_total",
+        "Times the connection pool was dropped and built again after failed pings",
+        &["database"]
+    )
+    .unwrap();
+    pub static ref SIGNER_BALANCE: GaugeVec = register_gauge_vec!(
+        "glitch_bridge_signer_balance_tokens",
+        "Free balance of each payout signer on Glitch, in whole tokens (`glitch_decimals`, 18 by default)",
+        &["signer"]
+    )
+    .unwrap();
+    pub static ref DUPLICATE_RECIPIENTS: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_duplicate_recipients",
+        "Glitch addresses paid by too many distinct Ethereum senders within the review window",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref MAX_SENDERS_PER_RECIPIENT: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_max_senders_per_recipient",
+        "Most distinct Ethereum senders paying to a single Glitch address within the review window",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref SUPPLY_DELTA: Gauge = register_gauge!(
+        "glitch_bridge_supply_delta_tokens",
+        "Tokens locked on Ethereum minus tokens bridged to Glitch"
+    )
+    .unwrap();
+    pub static ref API_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "glitch_bridge_api_request_duration_seconds",
+        "Latency of the HTTP API by route, method and status",
+        &["route", "method", "status"],
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    )
+    .unwrap();
+    pub static ref FINALITY_STALL: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_finality_stall_seconds",
+        "Seconds since the finalized head of Glitch last advanced, as seen by each pipeline",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref DEPOSIT_AMOUNT: HistogramVec = register_histogram_vec!(
+        "glitch_bridge_deposit_amount_tokens",
+        "Size of the deposits detected, in whole tokens (18 decimals unless the token registry says otherwise)",
+        &["network"],
+        vec![0.01, 0.1, 1.0, 10.0, 100.0, 1_000.0, 10_000.0, 100_000.0, 1_000_000.0, 10_000_000.0]
+    )
+    .unwrap();
+    pub static ref DRY_RUN_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_dry_run_failures_total",
+        "Payouts not submitted because their dry run failed, by failure kind",
+        &["network", "kind"]
+    )
+    .unwrap();
+    pub static ref STUCK_ROWS: IntGaugeVec = register_int_gauge_vec!(
+        "glitch_bridge_stuck_rows",
+        "Rows over the age of their stuck sweeper rule, by kind (processing, held, outbox)",
+        &["kind"]
+    )
+    .unwrap();
+    pub static ref SKIPPED_LOGS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_skipped_logs_total",
+        "Logs the scanners ignored, by pipeline and reason",
+        &["network", "reason"]
+    )
+    .unwrap();
+    pub static ref UNEXPECTED_SIGNER_TRANSFERS: IntCounterVec = register_int_counter_vec!(
+        "glitch_bridge_unexpected_signer_transfers_total",
+        "Outgoing transfers of the Glitch signer matching no payout, fee settlement or canary",
+        &["network"]
+    )
+    .unwrap();
+}
+
+pub fn gather() -> (String, Vec<u8>) {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        log::error!("Error encoding metrics: {e}");
+    }
+
+    (encoder.format_type().to_string(), buffer)
+}
@@ -0,0 +1,239 @@
+//! Applies the migrations in `db/` without locking busy tables. Every
+//! `ALTER TABLE` is tried as `ALGORITHM=INSTANT`, then as `ALGORITHM=INPLACE,
+//! LOCK=NONE`; MySQL refuses either before touching the table when it can't
+//! honor it. A change that needs a table copy locks writes for as long as
+//! the copy takes, so it only runs with `--allow-locking` and no bridge
+//! connected, or through pt-online-schema-change / gh-ost by hand.
+
+use std::fs;
+use std::path::Path;
+
+use log::{error, info, warn};
+
+use crate::database::DatabaseEngine;
+
+/// Longest wait for the metadata lock of a table. An `ALTER` waiting for it
+/// blocks every query that comes after, payouts included.
+const LOCK_WAIT_IN_SECS: u64 = 5;
+/// Tables over this many rows are flagged in the plan.
+const LARGE_TABLE_ROWS: u64 = 1_000_000;
+/// `ER_ALTER_OPERATION_NOT_SUPPORTED` and `..._REASON`.
+const ALGORITHM_NOT_SUPPORTED: [u16; 2] = [1845, 1846];
+
+/// How a statement is expected to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Metadata only, e.g. adding a column or creating a table.
+    Instant,
+    /// Rebuilt in place with reads and writes allowed, e.g. adding an index.
+    Inplace,
+    /// Copies or rewrites the table, locking writes meanwhile.
+    Locking,
+}
+
+/// The statements of a migration file, without comments. They end at the
+/// delimiter outside quotes and comments; like the mysql client, a
+/// `DELIMITER` line changes it, so triggers and routines with a `BEGIN ...
+/// END` body can be written the same way.
+pub fn statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut delimiter: Vec<char> = vec![';'];
+    let mut statement = String::new();
+    let mut quote: Option<char> = None;
+    let mut in_comment = false;
+
+    let mut end_statement = |statement: &mut String| {
+        let trimmed = statement.trim();
+        if !trimmed.is_empty() {
+            statements.push(trimmed.to_string());
+        }
+        statement.clear();
+    };
+
+    for line in sql.lines() {
+        if quote.is_none() && !in_comment && statement.trim().is_empty() {
+            let mut words = line.split_whitespace();
+            if let (Some(keyword), Some(new_delimiter), None) = (words.next(), words.next(), words.next()) {
+                if keyword.eq_ignore_ascii_case("DELIMITER") {
+                    delimiter = new_delimiter.chars().collect();
+                    continue;
+                }
+            }
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let (c, next) = (chars[i], chars.get(i + 1).copied());
+            if in_comment {
+                if c == '*' && next == Some('/') {
+                    in_comment = false;
+                    i += 1;
+                }
+            } else if let Some(open) = quote {
+                statement.push(c);
+                if c == '\\' && open != '`' {
+                    if let Some(next) = next {
+                        statement.push(next);
+                        i += 1;
+                    }
+                } else if c == open && next == Some(open) {
+                    statement.push(open);
+                    i += 1;
+                } else if c == open {
+                    quote = None;
+                }
+            } else if chars[i..].starts_with(&delimiter) {
+                end_statement(&mut statement);
+                i += delimiter.len();
+                continue;
+            } else if c == '#' || (c == '-' && next == Some('-') && chars.get(i + 2).map_or(true, |c| c.is_whitespace())) {
+                break;
+            } else if c == '/' && next == Some('*') {
+                in_comment = true;
+                i += 1;
+            } else {
+                if matches!(c, '\'' | '"' | '`') {
+                    quote = Some(c);
+                }
+                statement.push(c);
+            }
+            i += 1;
+        }
+        statement.push('\n');
+    }
+    end_statement(&mut statement);
+    statements
+}
+
+/// Table changed by an `ALTER TABLE` statement.
+fn altered_table(statement: &str) -> Option<String> {
+    let mut words = statement.split_whitespace();
+    let (alter, table) = (words.next()?, words.next()?);
+    if !alter.eq_ignore_ascii_case("alter") || !table.eq_ignore_ascii_case("table") {
+        return None;
+    }
+    Some(words.next()?.trim_matches('`').to_string())
+}
+
+/// Expected algorithm, from the clauses of the statement. Only a guess for
+/// the plan: applying asks MySQL itself.
+pub fn expected_algorithm(statement: &str) -> Algorithm {
+    let upper = statement.to_uppercase();
+    if upper.starts_with("CREATE TABLE") {
+        return Algorithm::Instant;
+    }
+    if upper.starts_with("CREATE INDEX") || upper.starts_with("CREATE UNIQUE INDEX") {
+        return Algorithm::Inplace;
+    }
+    if altered_table(statement).is_none() {
+        return Algorithm::Locking;
+    }
+
+    let locking = ["MODIFY", "CHANGE", "CONVERT TO", "PRIMARY KEY", "ENGINE", "FOREIGN KEY"];
+    let inplace = ["ADD INDEX", "ADD KEY", "ADD UNIQUE", "DROP INDEX", "DROP KEY", "RENAME INDEX", "DROP COLUMN"];
+    if locking.iter().any(|clause| upper.contains(clause)) {
+        Algorithm::Locking
+    } else if inplace.iter().any(|clause| upper.contains(clause)) {
+        Algorithm::Inplace
+    } else {
+        Algorithm::Instant
+    }
+}
+
+fn algorithm_not_supported(e: &mysql_async::Error) -> bool {
+    matches!(e, mysql_async::Error::Server(e) if ALGORITHM_NOT_SUPPORTED.contains(&e.code))
+}
+
+/// Runs an `ALTER TABLE` with the least locking algorithm MySQL accepts.
+/// Returns the algorithm used, or why it didn't run.
+async fn alter_online(
+    database_engine: &DatabaseEngine,
+    statement: &str,
+    allow_locking: bool,
+) -> Result<Algorithm, String> {
+    for (algorithm, clause) in [
+        (Algorithm::Instant, "ALGORITHM=INSTANT"),
+        (Algorithm::Inplace, "ALGORITHM=INPLACE, LOCK=NONE"),
+    ] {
+        match database_engine
+            .run_ddl(&format!("{statement}, {clause}"), LOCK_WAIT_IN_SECS)
+            .await
+        {
+            Ok(()) => return Ok(algorithm),
+            Err(e) if algorithm_not_supported(&e) => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    if !allow_locking {
+        return Err("it needs a table copy that locks writes; run it with --allow-locking while the bridge is stopped, or with pt-online-schema-change or gh-ost".to_string());
+    }
+    database_engine
+        .run_ddl(statement, LOCK_WAIT_IN_SECS)
+        .await
+        .map(|_| Algorithm::Locking)
+        .map_err(|e| e.to_string())
+}
+
+/// Lists how each statement of the migration would run or, with `apply`,
+/// runs them in order, stopping at the first one that can't run safely.
+/// Returns whether every statement ran (or, without `apply`, would).
+pub async fn migrate(database_engine: &DatabaseEngine, file: &Path, apply: bool, allow_locking: bool) -> bool {
+    let sql = fs::read_to_string(file).unwrap_or_else(|e| panic!("Error reading the migration {file:?}: {e}"));
+    let statements = statements(&sql);
+    let in_use = database_engine.database_in_use().await;
+    if in_use {
+        info!("A bridge is using the database, only online changes will run.");
+    }
+    let allow_locking = allow_locking && !in_use;
+
+    let mut safe = true;
+    for statement in statements.iter() {
+        let expected = expected_algorithm(statement);
+        let rows = match altered_table(statement) {
+            Some(table) => database_engine.table_rows(&table).await,
+            None => None,
+        };
+        let large = rows.map_or(false, |rows| rows > LARGE_TABLE_ROWS);
+        info!(
+            "{:?}{}: {}",
+            expected,
+            rows.map(|rows| format!(" (~{rows} rows{})", if large { ", large" } else { "" })).unwrap_or_default(),
+            statement
+        );
+
+        if !apply {
+            if expected == Algorithm::Locking && !allow_locking {
+                warn!("The statement above is expected to lock writes and would not run.");
+                safe = false;
+            }
+            continue;
+        }
+
+        let result = if altered_table(statement).is_some() {
+            alter_online(database_engine, statement, allow_locking).await
+        } else if expected != Algorithm::Locking || allow_locking {
+            database_engine
+                .run_ddl(statement, LOCK_WAIT_IN_SECS)
+                .await
+                .map(|_| expected)
+                .map_err(|e| e.to_string())
+        } else {
+            Err("it may lock the tables it touches; run it with --allow-locking while the bridge is stopped".to_string())
+        };
+
+        match result {
+            Ok(algorithm) => info!("Applied as {:?}.", algorithm),
+            Err(e) => {
+                error!("Migration stopped, the statement was not applied: {}", e);
+                return false;
+            }
+        }
+    }
+
+    if !apply {
+        info!("Dry run, nothing changed. Run again with --apply to migrate.");
+    }
+    safe
+}
@@ -0,0 +1,84 @@
+use lettre::{
+    message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+    Message,
+    SmtpTransport,
+    Transport,
+};
+use log::info;
+use reqwest::Error;
+use serde_json::json;
+
+use crate::config::Notification;
+
+pub fn build_email(
+    emails_to: Vec<String>,
+    subject: &str,
+    message: &str,
+    from: &str,
+    env: &str
+) -> Message {
+    let mut email_builder = Message::builder();
+
+    for email_to in emails_to {
+        email_builder = email_builder.to(email_to.parse().unwrap());
+        info!("Notification will be sent to: {}", email_to);
+    }
+
+    email_builder
+        .from(from.parse().unwrap())
+        .subject(format!("[{}] {}", env, subject))
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.to_string())
+        .unwrap()
+}
+
+pub async fn send_slack_notify(msg: &str, slack_webhook_url: &str, env: &str) -> Result<(), Error> {
+    let client = reqwest::Client::new();
+    let body = json!({
+        "text": format!("[{}] {}", env, msg)
+    });
+
+    client.post(slack_webhook_url).json(&body).send().await?;
+
+    Ok(())
+}
+
+/// Sends the message through every configured channel (email and Slack).
+/// Returns whether the email was delivered.
+pub async fn notify(smtp_config: &Notification, subject: &str, message: &str) -> bool {
+    let email = build_email(
+        smtp_config.send_to.clone(),
+        subject,
+        message,
+        smtp_config.from.as_str(),
+        &smtp_config.env
+    );
+
+    let creds = Credentials::new(smtp_config.user.clone(), smtp_config.password.clone());
+
+    let mailer: SmtpTransport = SmtpTransport::relay(smtp_config.host.as_str())
+        .unwrap()
+        .credentials(creds)
+        .build();
+
+    let email_sent = match mailer.send(&email) {
+        Ok(_) => {
+            info!("Email sent successfully!");
+            true
+        }
+        Err(e) => {
+            info!("Could not send email: {e:?}");
+            false
+        }
+    };
+
+    match send_slack_notify(message, &smtp_config.slack_webhook, &smtp_config.env).await {
+        Ok(_) => {
+            info!("Slack notification sent successfully!");
+        }
+        Err(e) => info!("Could not send slack notification: {e:?}"),
+    }
+
+    email_sent
+}
@@ -0,0 +1,102 @@
+//! Submits the payouts queued in `glitch_outbox`. The payer commits the
+//! decision to pay (recipient, amount, fees) together with the claim of the
+//! tx; this task is the only one sending them, and records on each row how
+//! its submission ended, so a payout is submitted at most once.
+
+use std::sync::Arc;
+
+use log::{info, warn};
+use tokio::sync::{watch, Notify};
+use tokio::time::{sleep, timeout, Duration};
+
+use crate::database::{DatabaseEngine, FailureKind};
+use crate::finalization::FinalizationTracker;
+use crate::glitch::make_transfer;
+use crate::payout::PayoutExecutor;
+use crate::recipient_locks::RecipientLocks;
+use crate::scheduler::JobHandle;
+use crate::supervisor::stopped;
+use crate::types::parse_glitch_address;
+
+/// Longest wait for the payer before looking at the outbox again.
+const POLL_INTERVAL_IN_SECS: u64 = 5;
+/// Wait after a payout the signer couldn't fund, doubled on every one in a
+/// row up to `MAX_FUNDS_BACKOFF_IN_SECS`, instead of failing every payout.
+const FUNDS_BACKOFF_IN_SECS: u64 = 30;
+const MAX_FUNDS_BACKOFF_IN_SECS: u64 = 600;
+
+/// What the submitter of a pipeline sends the payouts with.
+pub struct OutboxSubmitter {
+    pub name: String,
+    pub ss58_prefix: Option<u16>,
+    pub recipient_locks: Option<Arc<RecipientLocks>>,
+    pub finalization: Option<FinalizationTracker>,
+    pub executor: Arc<dyn PayoutExecutor>,
+    pub database_engine: Arc<DatabaseEngine>,
+    pub payer_job: Arc<JobHandle>,
+}
+
+/// Sends the pending payouts of the pipeline in the order they were
+/// decided. Woken by the payer after each decision, and polls in case a
+/// wake-up was missed. Holds while the payer job is paused, backs off while
+/// the signer can't fund the payouts, and returns once told to stop with no
+/// payout halfway sent.
+pub async fn submit_outbox(submitter: OutboxSubmitter, wake: Arc<Notify>, mut stop: watch::Receiver<bool>) {
+    let OutboxSubmitter {
+        name,
+        ss58_prefix,
+        recipient_locks,
+        finalization,
+        executor,
+        database_engine,
+        payer_job,
+    } = submitter;
+    let interrupted = database_engine.interrupt_outbox_submissions(&name).await;
+    if interrupted > 0 {
+        warn!("{} outbox payouts of {} were interrupted while being submitted, their txs are left for reconciliation.", interrupted, name);
+    }
+    info!("Outbox submitter of {} running!", name);
+
+    let mut funds_backoff: Option<u64> = None;
+    loop {
+        match funds_backoff {
+            Some(delay_in_secs) => tokio::select! {
+                _ = sleep(Duration::from_secs(delay_in_secs)) => {}
+                _ = stopped(&mut stop) => return,
+            },
+            None => tokio::select! {
+                _ = timeout(Duration::from_secs(POLL_INTERVAL_IN_SECS), wake.notified()) => {}
+                _ = stopped(&mut stop) => return,
+            },
+        }
+        if payer_job.is_paused() {
+            continue;
+        }
+
+        for payout in database_engine.pending_outbox(&name).await {
+            if payer_job.is_paused() || *stop.borrow() {
+                break;
+            }
+            if !database_engine.start_outbox_submission(payout.id).await {
+                continue;
+            }
+
+            let _recipient_guard = match (&recipient_locks, parse_glitch_address(&payout.recipient, ss58_prefix)) {
+                (Some(locks), Ok(public)) => Some(locks.lock(public).await),
+                _ => None,
+            };
+
+            match make_transfer(payout, executor.as_ref(), database_engine.clone(), finalization.as_ref()).await {
+                Some(FailureKind::InsufficientFunds | FailureKind::WouldReapSigner) => {
+                    let delay_in_secs = funds_backoff
+                        .map_or(FUNDS_BACKOFF_IN_SECS, |delay_in_secs| delay_in_secs * 2)
+                        .min(MAX_FUNDS_BACKOFF_IN_SECS);
+                    warn!("The signer of {} can't fund the payouts, trying again in {}s.", name, delay_in_secs);
+                    funds_backoff = Some(delay_in_secs);
+                    break;
+                }
+                _ => funds_backoff = None,
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Destination side of a payout, behind `PayoutExecutor` so the claim,
+//! submission and settlement of a tx in `make_transfer` don't depend on the
+//! chain paid on. `GlitchExecutor` in `glitch` is the only implementation.
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+
+/// A transfer of `amount` to `recipient` for a tx, not signed yet.
+#[derive(Debug, Clone)]
+pub struct Payout {
+    pub tx_id: u128,
+    /// As stored with the tx, checked by `PayoutExecutor::build`.
+    pub recipient: String,
+    pub amount: u128,
+}
+
+/// A payout ready to be submitted.
+#[derive(Debug, Clone)]
+pub struct SignedPayout {
+    pub payout: Payout,
+    /// Hex encoded transaction, as sent to the node.
+    pub encoded: String,
+    /// `0x`-prefixed hash of the transaction, known before submission.
+    pub hash: String,
+}
+
+/// Outcome of a submission, which decides what happens to the claim on the tx.
+#[derive(Debug)]
+pub enum Submission {
+    /// Included in the block with this `0x`-prefixed hash.
+    Included { block: String },
+    /// Surely not included; the tx can go back to the queue.
+    NotIncluded(String),
+    /// It may still be included; the tx must stay claimed.
+    Unknown(String),
+}
+
+/// Outcome of running a signed transfer against the current state of the
+/// chain without sending it.
+#[derive(Debug)]
+pub enum Simulation {
+    /// It would go through.
+    Passes,
+    /// The signer can't cover the amount and the fee.
+    InsufficientFunds(String),
+    /// The recipient can't receive it, e.g. below the existential deposit.
+    BadDestination(String),
+    /// It would go through, but leave the signer below the existential
+    /// deposit and get its account reaped.
+    WouldReapSigner(String),
+    /// Any other failure reported by the runtime, or an extrinsic over the
+    /// block limits.
+    Rejected(String),
+    /// The node could not simulate it; it is submitted unchecked.
+    Unavailable(String),
+}
+
+/// Where a transfer landed on chain, for integrations that match payouts by
+/// block, extrinsic and event instead of by hash.
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub block_number: Option<u32>,
+    /// Index of the transfer extrinsic within its block.
+    pub extrinsic_index: u32,
+    /// The transfer event, with its index among the events of the block.
+    pub event: Value,
+}
+
+/// What is learnt about a transfer once its block is final.
+#[derive(Debug, Default)]
+pub struct Confirmation {
+    /// Network fee actually charged, None if it can't be found.
+    pub network_fee: Option<u128>,
+    /// None if the transfer can't be found among the events of the block.
+    pub receipt: Option<Receipt>,
+}
+
+/// What a destination chain has to provide to be paid out to from the queue.
+pub trait PayoutExecutor: Send + Sync {
+    /// Checks the recipient and builds the transfer.
+    fn build(&self, tx_id: u128, recipient: &str, amount: u128) -> Result<Payout, String>;
+
+    /// Signs the transfer, refusing one the chain would never include.
+    fn sign(&self, payout: Payout) -> Result<SignedPayout, String>;
+
+    /// Runs the transfer against the chain without sending it, so failures
+    /// it can predict don't have to be broadcast to be found.
+    fn simulate<'a>(&'a self, signed: &'a SignedPayout) -> BoxFuture<'a, Simulation>;
+
+    /// Sends the transfer and waits for its block.
+    fn submit<'a>(&'a self, signed: &'a SignedPayout) -> BoxFuture<'a, Submission>;
+
+    /// Network fee and receipt of the transfer included in `block`, once that
+    /// block is final.
+    fn confirm<'a>(&'a self, signed: &'a SignedPayout, block: &'a str) -> BoxFuture<'a, Confirmation>;
+}
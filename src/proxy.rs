@@ -0,0 +1,422 @@
+//! Outbound proxy for the Ethereum (websocket or HTTP) and Glitch nodes.
+//! The clients can't dial through a proxy, so a node URL that must go
+//! through one is pointed at a local tunnel instead: every connection
+//! accepted there is carried to the node over an HTTP `CONNECT` or SOCKS5
+//! tunnel, TLS included for `wss` and `https`, with the `Host` of every
+//! request put back to the node's. The tunnel only serves requests under a
+//! random path prefix, known to the routed URL alone, so other local
+//! processes can't use it to reach the node or the proxy.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use rand::Rng;
+use reqwest::Url;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+use crate::config::Proxy;
+
+/// Longest proxy reply or handshake head read before giving up on it.
+const MAX_HEAD_LEN: usize = 16 * 1024;
+
+lazy_static! {
+    static ref PROXY: Mutex<Option<Proxy>> = Mutex::new(None);
+    /// Local tunnel of each `scheme://host:port` routed so far, with the
+    /// path prefix it serves.
+    static ref TUNNELS: Mutex<HashMap<String, (SocketAddr, String)>> = Mutex::new(HashMap::new());
+    /// The tunnels run here, so URLs can be routed from blocking code too.
+    static ref RUNTIME: Runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("proxy-tunnel")
+        .enable_all()
+        .build()
+        .expect("Could not start the proxy tunnels runtime!");
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Uses `proxy` over the environment variables from now on.
+pub fn configure(proxy: Option<Proxy>) {
+    if let Some(proxy) = &proxy {
+        info!("Node connections go through the proxy at {}", redacted(&proxy.url));
+    }
+    *PROXY.lock().unwrap() = proxy;
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// The configured proxy, or the one of `HTTPS_PROXY` (for TLS), `HTTP_PROXY`
+/// or `ALL_PROXY` with the exceptions of `NO_PROXY`.
+fn proxy_for(tls: bool) -> Option<Proxy> {
+    if let Some(proxy) = PROXY.lock().unwrap().clone() {
+        return Some(proxy);
+    }
+
+    let url = if tls {
+        env_var(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"])
+    } else {
+        env_var(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"])
+    }?;
+    let no_proxy = env_var(&["NO_PROXY", "no_proxy"])
+        .map(|hosts| hosts.split(',').map(|host| host.trim().to_string()).collect());
+    Some(Proxy { url, no_proxy })
+}
+
+/// Whether `host` is one of the `no_proxy` exceptions of the proxy, or a
+/// subdomain of one.
+pub fn bypassed(proxy: &Proxy, host: &str) -> bool {
+    let host = host.to_lowercase();
+    proxy.no_proxy.iter().flatten().any(|pattern| {
+        let pattern = pattern.trim().trim_start_matches('.').to_lowercase();
+        pattern == "*" || (!pattern.is_empty() && (host == pattern || host.ends_with(&format!(".{pattern}"))))
+    })
+}
+
+fn redacted(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            if url.password().is_some() {
+                let _ = url.set_password(Some("***"));
+            }
+            url.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// The URL to open the node at `url` with: itself when no proxy applies,
+/// otherwise the local tunnel to it, opened on first use, under the path
+/// prefix of the tunnel.
+pub fn route(url: &str) -> String {
+    let mut parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_string(),
+    };
+    let tls = matches!(parsed.scheme(), "wss" | "https");
+    let (host, port) = match (parsed.host_str(), parsed.port_or_known_default()) {
+        (Some(host), Some(port)) => (host.to_string(), port),
+        _ => return url.to_string(),
+    };
+    let proxy = match proxy_for(tls) {
+        Some(proxy) if !bypassed(&proxy, &host) => proxy,
+        _ => return url.to_string(),
+    };
+
+    let key = format!("{}://{}:{}", parsed.scheme(), host, port);
+    let (local, token) = {
+        let mut tunnels = TUNNELS.lock().unwrap();
+        match tunnels.get(&key) {
+            Some(tunnel) => tunnel.clone(),
+            None => match open_tunnel(proxy, host.clone(), port, tls) {
+                Ok(tunnel) => {
+                    info!("Connections to {} go through the tunnel at {}", key, tunnel.0);
+                    tunnels.entry(key).or_insert(tunnel).clone()
+                }
+                Err(e) => {
+                    error!("Could not open the proxy tunnel to {}: {}", key, e);
+                    return url.to_string();
+                }
+            },
+        }
+    };
+
+    // The tunnel speaks plain text locally and TLS to the node.
+    let scheme = if parsed.scheme().starts_with("http") { "http" } else { "ws" };
+    let _ = parsed.set_scheme(scheme);
+    let _ = parsed.set_host(Some(&local.ip().to_string()));
+    let _ = parsed.set_port(Some(local.port()));
+    let path = format!("/{token}{}", parsed.path());
+    parsed.set_path(&path);
+    parsed.to_string()
+}
+
+/// Opens a local tunnel to `host:port`, returning its address and the path
+/// prefix it serves.
+fn open_tunnel(proxy: Proxy, host: String, port: u16, tls: bool) -> Result<(SocketAddr, String), String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let local = listener.local_addr().map_err(|e| e.to_string())?;
+    let token = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+
+    let tunnel_token = token.clone();
+    RUNTIME.spawn(async move {
+        let listener = match TcpListener::from_std(listener) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Proxy tunnel to {}:{} failed: {}", host, port, e);
+                return;
+            }
+        };
+        loop {
+            let client = match listener.accept().await {
+                Ok((client, _)) => client,
+                Err(e) => {
+                    warn!("Proxy tunnel to {}:{} could not accept a connection: {}", host, port, e);
+                    continue;
+                }
+            };
+            let (proxy, host, tunnel_token) = (proxy.clone(), host.clone(), tunnel_token.clone());
+            tokio::spawn(async move {
+                if let Err(e) = carry(client, &proxy, &host, port, tls, &tunnel_token).await {
+                    warn!("Proxied connection to {}:{} ended: {}", host, port, e);
+                }
+            });
+        }
+    });
+
+    Ok((local, token))
+}
+
+/// Carries a local connection to the node through the proxy.
+async fn carry(client: TcpStream, proxy: &Proxy, host: &str, port: u16, tls: bool, token: &str) -> Result<(), String> {
+    let tunnel = dial(proxy, host, port).await?;
+    let upstream: Box<dyn Stream> = if tls {
+        let connector = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+        let stream = tokio_native_tls::TlsConnector::from(connector)
+            .connect(host, tunnel)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {e}"))?;
+        Box::new(stream)
+    } else {
+        Box::new(tunnel)
+    };
+
+    // The requests name the tunnel as host, which virtual hosted nodes reject.
+    let default_port = if tls { 443 } else { 80 };
+    let authority = if port == default_port { host.to_string() } else { format!("{host}:{port}") };
+
+    let (mut client_reader, mut client_writer) = client.into_split();
+    let (mut upstream_reader, mut upstream_writer) = tokio::io::split(upstream);
+    let requests = async {
+        carry_requests(&mut client_reader, &mut upstream_writer, &authority, token).await?;
+        upstream_writer.shutdown().await.map_err(|e| e.to_string())
+    };
+    let responses = async {
+        tokio::io::copy(&mut upstream_reader, &mut client_writer)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    tokio::try_join!(requests, responses).map(|_| ())
+}
+
+/// Carries the requests of a local connection, rewriting every head. After
+/// a websocket handshake, or a body of unknown length, there are no more
+/// heads to tell apart and the rest is carried as it comes.
+async fn carry_requests<R, W>(client: &mut R, upstream: &mut W, authority: &str, token: &str) -> Result<(), String>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let io = |e: std::io::Error| e.to_string();
+    let mut pending = Vec::new();
+    while let Some(head) = next_head(client, &mut pending).await? {
+        let head = with_host(&without_token(&head, token)?, authority);
+        upstream.write_all(&head).await.map_err(io)?;
+
+        let body_len = match body_len(&head) {
+            Some(body_len) => body_len,
+            None => {
+                upstream.write_all(&pending).await.map_err(io)?;
+                tokio::io::copy(client, upstream).await.map_err(io)?;
+                return Ok(());
+            }
+        };
+        let buffered = body_len.min(pending.len() as u64) as usize;
+        upstream.write_all(&pending[..buffered]).await.map_err(io)?;
+        pending.drain(..buffered);
+        let rest = body_len - buffered as u64;
+        let carried = tokio::io::copy(&mut (&mut *client).take(rest), upstream).await.map_err(io)?;
+        if carried < rest {
+            return Err("connection closed during a request body".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Length of the body after a request head, None when it isn't known from
+/// the head: an upgrade to a websocket or a chunked body.
+fn body_len(head: &[u8]) -> Option<u64> {
+    let text = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let mut content_length = Some(0);
+    for line in text.split("\r\n").skip(1) {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => continue,
+        };
+        match name {
+            "upgrade" | "transfer-encoding" => return None,
+            "content-length" => content_length = value.parse().ok(),
+            _ => {}
+        }
+    }
+    content_length
+}
+
+/// Reads the next HTTP head, up to `\r\n\r\n`, keeping what came after
+/// it in `pending`. None once the connection closes between heads.
+async fn next_head<S: AsyncRead + Unpin>(stream: &mut S, pending: &mut Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+    let mut buffer = [0u8; 1024];
+    loop {
+        if let Some(end) = pending.windows(4).position(|window| window == b"\r\n\r\n") {
+            let rest = pending.split_off(end + 4);
+            return Ok(Some(std::mem::replace(pending, rest)));
+        }
+        if pending.len() > MAX_HEAD_LEN {
+            return Err("HTTP head too long".to_string());
+        }
+        let read = stream.read(&mut buffer).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            if pending.is_empty() {
+                return Ok(None);
+            }
+            return Err("connection closed during the HTTP head".to_string());
+        }
+        pending.extend_from_slice(&buffer[..read]);
+    }
+}
+
+/// The request head with the path prefix of the tunnel taken out of its
+/// target. Requests without it are refused.
+pub fn without_token(head: &[u8], token: &str) -> Result<Vec<u8>, String> {
+    let refused = || "request without the path prefix of the tunnel".to_string();
+    let end = head.iter().position(|byte| *byte == b'\r').unwrap_or(head.len());
+    let request_line = std::str::from_utf8(&head[..end]).map_err(|_| refused())?;
+    let mut parts = request_line.splitn(3, ' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => return Err(refused()),
+    };
+    let rest = target
+        .strip_prefix('/')
+        .and_then(|target| target.strip_prefix(token))
+        .filter(|rest| rest.is_empty() || rest.starts_with('/') || rest.starts_with('?'))
+        .ok_or_else(refused)?;
+    let target = if rest.starts_with('/') { rest.to_string() } else { format!("/{rest}") };
+
+    let mut rewritten = format!("{method} {target} {version}").into_bytes();
+    rewritten.extend_from_slice(&head[end..]);
+    Ok(rewritten)
+}
+
+/// The HTTP head with its `Host` set to `authority`; what follows the head
+/// is kept as is.
+pub fn with_host(head: &[u8], authority: &str) -> Vec<u8> {
+    let end = head.windows(4).position(|window| window == b"\r\n\r\n").map_or(head.len(), |end| end + 4);
+    let text = String::from_utf8_lossy(&head[..end]);
+    let rewritten: Vec<String> = text
+        .split("\r\n")
+        .map(|line| {
+            if line.to_ascii_lowercase().starts_with("host:") {
+                format!("Host: {authority}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    let mut bytes = rewritten.join("\r\n").into_bytes();
+    bytes.extend_from_slice(&head[end..]);
+    bytes
+}
+
+/// Opens a TCP tunnel to `host:port` through the proxy.
+async fn dial(proxy: &Proxy, host: &str, port: u16) -> Result<TcpStream, String> {
+    let url = Url::parse(&proxy.url).map_err(|e| format!("invalid proxy URL: {e}"))?;
+    let default_port = if url.scheme().starts_with("socks") { 1080 } else { 8080 };
+    let address = format!(
+        "{}:{}",
+        url.host_str().ok_or("the proxy URL has no host")?,
+        url.port().unwrap_or(default_port)
+    );
+    let mut stream = TcpStream::connect(&address)
+        .await
+        .map_err(|e| format!("proxy {address} unreachable: {e}"))?;
+
+    let credentials = (!url.username().is_empty()).then(|| (url.username(), url.password().unwrap_or_default()));
+    match url.scheme() {
+        "http" => http_connect(&mut stream, host, port, credentials).await?,
+        "socks5" | "socks5h" => socks5_connect(&mut stream, host, port, credentials).await?,
+        scheme => return Err(format!("unsupported proxy scheme {scheme}")),
+    }
+    Ok(stream)
+}
+
+async fn http_connect(stream: &mut TcpStream, host: &str, port: u16, credentials: Option<(&str, &str)>) -> Result<(), String> {
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if let Some((username, password)) = credentials {
+        let token = base64::encode(format!("{username}:{password}"));
+        request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let head = next_head(stream, &mut Vec::new())
+        .await?
+        .ok_or("the proxy closed the connection")?;
+    let status_line = String::from_utf8_lossy(&head);
+    let status_line = status_line.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(format!("the proxy refused the tunnel: {status_line}")),
+    }
+}
+
+/// SOCKS5 `CONNECT` by host name, so the proxy resolves it (RFC 1928, with
+/// the username and password authentication of RFC 1929).
+async fn socks5_connect(stream: &mut TcpStream, host: &str, port: u16, credentials: Option<(&str, &str)>) -> Result<(), String> {
+    let io = |e: std::io::Error| e.to_string();
+    let method = if credentials.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await.map_err(io)?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await.map_err(io)?;
+    if reply != [0x05, method] {
+        return Err("the SOCKS5 proxy refused the authentication method".to_string());
+    }
+
+    if let Some((username, password)) = credentials {
+        let mut request = vec![0x01, username.len() as u8];
+        request.extend_from_slice(username.as_bytes());
+        request.push(password.len() as u8);
+        request.extend_from_slice(password.as_bytes());
+        stream.write_all(&request).await.map_err(io)?;
+        stream.read_exact(&mut reply).await.map_err(io)?;
+        if reply[1] != 0x00 {
+            return Err("the SOCKS5 proxy refused the credentials".to_string());
+        }
+    }
+
+    if host.len() > u8::MAX as usize {
+        return Err("host name too long for SOCKS5".to_string());
+    }
+    let mut request = vec![0x05, 0x01, 0x00, 0x03, host.len() as u8];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await.map_err(io)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.map_err(io)?;
+    if reply[1] != 0x00 {
+        return Err(format!("the SOCKS5 proxy refused the tunnel (reply {})", reply[1]));
+    }
+    // Bound address, which is of no use here.
+    let address_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => stream.read_u8().await.map_err(io)? as usize,
+        kind => return Err(format!("unknown SOCKS5 address type {kind}")),
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await.map_err(io)?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::config::{Notification, QueueAge};
+use crate::database::DatabaseEngine;
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+
+const STATES: [&str; 2] = ["TO_PROCESS", "PROCESSING"];
+
+/// Exports the age of the oldest TO_PROCESS and PROCESSING tx of each
+/// pipeline and alerts once when one goes over its threshold. A stuck
+/// pipeline shows up here even when the queue depth looks normal.
+pub async fn monitor_queue_age(
+    queue_age: QueueAge,
+    networks: Vec<String>,
+    database_engine: Arc<DatabaseEngine>,
+    smtp_config: Notification,
+    mut ticker: Ticker,
+) {
+    info!("Queue age monitor running!");
+    let mut alerted: HashSet<(String, String)> = HashSet::new();
+
+    loop {
+        ticker.tick().await;
+
+        let ages = database_engine.oldest_pending_ages().await;
+
+        for network in networks.iter() {
+            for state in STATES {
+                let age = ages
+                    .iter()
+                    .find(|(scanner_name, tx_state, _)| scanner_name == network && tx_state == state)
+                    .map(|(_, _, age)| (*age).max(0))
+                    .unwrap_or(0);
+                metrics::OLDEST_PENDING_AGE
+                    .with_label_values(&[network, state])
+                    .set(age);
+
+                let max_age_in_minutes = match state {
+                    "TO_PROCESS" => queue_age.max_to_process_age_in_minutes,
+                    _ => queue_age.max_processing_age_in_minutes,
+                };
+                let over = max_age_in_minutes.map_or(false, |minutes| age as u64 > minutes * 60);
+                let key = (network.clone(), state.to_string());
+
+                if !over {
+                    alerted.remove(&key);
+                    continue;
+                }
+                if !alerted.insert(key) {
+                    continue;
+                }
+
+                let message = format!(
+                    "The oldest {} tx of {} is {} minutes old, over the limit of {} minutes. The pipeline may be stuck.",
+                    state,
+                    network,
+                    age / 60,
+                    max_age_in_minutes.unwrap_or_default()
+                );
+                warn!("{}", message);
+                notify(&smtp_config, "Bridge queue is not moving!", &message).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Quotes of what a deposit would pay out, for the frontends. The network
+//! fee and the business fee are computed with the payer's own functions and
+//! settings, so a quote matches the payout of a deposit made right away.
+//! When the node can't estimate the network fee the quote is unavailable,
+//! where the payer would deduct the recent average.
+
+use chrono::Utc;
+use serde_derive::Serialize;
+use sp_core::crypto::Pair;
+use sp_core::sr25519::{self, Public};
+use substrate_api_client::{rpc::WsRpcClient, Api, PlainTipExtrinsicParams};
+use tokio::sync::Mutex;
+
+use crate::config::{Config, EthFeePolicy, GlitchGas, Network, Rounding, Token};
+use crate::database::DatabaseEngine;
+use crate::fee_policy::policy_at;
+use crate::glitch::{payout_of, try_network_fee_of};
+use crate::types::{check_genesis_hash, parse_glitch_address, GlitchApi};
+
+/// Window of the payouts the ETA is averaged over.
+const ETA_WINDOW_IN_HOURS: u32 = 24;
+
+#[derive(Serialize, Debug)]
+pub struct Quote {
+    pub network: String,
+    pub token: Option<String>,
+    pub amount: String,
+    pub business_fee_percentage: f64,
+    pub business_fee: String,
+    pub network_fee: String,
+    /// What the recipient gets on Glitch.
+    pub payout: String,
+    /// Average time to payout of the deposits paid in the last day, None
+    /// without any.
+    pub eta_in_secs: Option<i64>,
+    /// The amount is outside the token limits, the deposit would wait for
+    /// an operator.
+    pub held_for_review: bool,
+}
+
+#[derive(Debug)]
+pub enum QuoteError {
+    BadRequest(String),
+    Unavailable(String),
+}
+
+/// Quotes the deposits of one pipeline.
+pub struct Quoter {
+    pub network: String,
+    token: Option<Token>,
+    glitch_pk: String,
+    glitch_node: String,
+    glitch_genesis_hash: Option<String>,
+    business_fee: f64,
+    rounding: Rounding,
+    eth_fee_policy: EthFeePolicy,
+    glitch_gas: GlitchGas,
+    ss58_prefix: Option<u16>,
+    /// Connected on the first quote.
+    api: Mutex<Option<GlitchApi>>,
+}
+
+impl Quoter {
+    /// Takes the settings the way `Payer::new` does.
+    pub fn new(config: &Config, network_config: &Network) -> Self {
+        Self {
+            network: network_config.name.clone(),
+            token: network_config.token(config).cloned(),
+            glitch_pk: network_config.glitch_private_key(config),
+            glitch_node: network_config.glitch_node_url(),
+            glitch_genesis_hash: config.glitch_genesis_hash.clone(),
+            business_fee: network_config.business_fee.unwrap_or(config.business_fee),
+            rounding: config.rounding.unwrap_or_default(),
+            eth_fee_policy: network_config.eth_fee_policy.unwrap_or_default(),
+            glitch_gas: match network_config.glitch_asset_id {
+                Some(_) => GlitchGas::Flag(false),
+                None => network_config.glitch_gas.unwrap_or(config.glitch_gas),
+            },
+            ss58_prefix: config.glitch_ss58_prefix,
+            api: Mutex::new(None),
+        }
+    }
+
+    pub fn token_symbol(&self) -> Option<&str> {
+        self.token.as_ref().map(|token| token.symbol.as_str())
+    }
+
+    async fn api(&self) -> Result<GlitchApi, QuoteError> {
+        let mut api = self.api.lock().await;
+        if let Some(api) = api.as_ref() {
+            return Ok(api.clone());
+        }
+
+        let (glitch_node, glitch_pk, glitch_genesis_hash) =
+            (self.glitch_node.clone(), self.glitch_pk.clone(), self.glitch_genesis_hash.clone());
+        let connected = tokio::task::spawn_blocking(move || {
+            let signer: sr25519::Pair = Pair::from_string(&glitch_pk, None).unwrap();
+            let api: GlitchApi = Api::<_, _, PlainTipExtrinsicParams>::new(WsRpcClient::new(&glitch_node))
+                .map(|api| api.set_signer(signer))
+                .map_err(|e| format!("Error connecting with the Glitch node: {e:?}"))?;
+            check_genesis_hash(&api, glitch_genesis_hash.as_deref())?;
+            Ok::<_, String>(api)
+        })
+        .await
+        .map_err(|e| QuoteError::Unavailable(format!("Connection task failed: {e}")))?
+        .map_err(QuoteError::Unavailable)?;
+
+        *api = Some(connected.clone());
+        Ok(connected)
+    }
+
+    /// What a deposit of `amount` made now would pay out, to `recipient` if
+    /// given. Deposits reporting a protocol fee already paid on Ethereum get
+    /// that much less business fee, which isn't known in advance.
+    pub async fn quote(
+        &self,
+        database_engine: &DatabaseEngine,
+        amount: u128,
+        recipient: Option<&str>,
+    ) -> Result<Quote, QuoteError> {
+        // Any account gives the same fee, the extrinsic has the same length.
+        let public = match recipient {
+            Some(recipient) => parse_glitch_address(recipient, self.ss58_prefix).map_err(QuoteError::BadRequest)?,
+            None => Public::from_raw([0; 32]),
+        };
+
+        let api = self.api().await?;
+        let network_fee = match try_network_fee_of(&api, self.glitch_gas, amount, public).await {
+            Ok(network_fee) => network_fee,
+            Err(e) => {
+                // The connection may be what failed, the next quote opens another.
+                *self.api.lock().await = None;
+                return Err(QuoteError::Unavailable(format!("Error estimating the network fee: {e}")));
+            }
+        };
+        if network_fee >= amount {
+            return Err(QuoteError::BadRequest(format!(
+                "The amount doesn't cover the network fee of {network_fee}"
+            )));
+        }
+
+        let policies = database_engine.fee_policies(&self.network).await;
+        let business_fee_percentage =
+            policy_at(&policies, Utc::now().timestamp()).map_or(self.business_fee, |policy| policy.business_fee);
+        let (amount_to_transfer, business_fee, _) = payout_of(
+            amount,
+            network_fee,
+            business_fee_percentage,
+            self.rounding,
+            None,
+            self.eth_fee_policy,
+        );
+
+        let held_for_review = self.token.as_ref().map_or(false, |token| {
+            token.min_amount.map_or(false, |min| amount < min) || token.max_amount.map_or(false, |max| amount > max)
+        });
+
+        Ok(Quote {
+            network: self.network.clone(),
+            token: self.token_symbol().map(str::to_string),
+            amount: amount.to_string(),
+            business_fee_percentage,
+            business_fee: business_fee.to_string(),
+            network_fee: network_fee.to_string(),
+            payout: (amount_to_transfer - business_fee).to_string(),
+            eta_in_secs: database_engine.average_payout_time(&self.network, ETA_WINDOW_IN_HOURS).await,
+            held_for_review,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use sp_core::sr25519::Public;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// One lock per Glitch recipient, shared by the payers of every pipeline, so
+/// transfers to the same address never interleave while transfers to
+/// different addresses keep running in parallel.
+#[derive(Default)]
+pub struct RecipientLocks {
+    locks: Mutex<HashMap<Public, Arc<AsyncMutex<()>>>>,
+}
+
+impl RecipientLocks {
+    pub async fn lock(&self, recipient: Public) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // Drop the locks nobody holds or waits for.
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(recipient).or_default().clone()
+        };
+
+        lock.lock_owned().await
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet};
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use sp_core::{crypto::AccountId32, crypto::Pair, hashing::blake2_256, sr25519, H256};
+use substrate_api_client::{rpc::WsRpcClient, Api, PlainTipExtrinsicParams};
+
+use crate::config::{Config, Network};
+use crate::database::{DatabaseEngine, ProcessingTx};
+use crate::glitch_events::{block_events, transfer_from};
+use crate::types::{parse_glitch_address, to_hex, GlitchApi};
+
+/// Blocks searched back from the head when `reconciliation_lookback_blocks`
+/// is not set, a day of 6 second blocks.
+const DEFAULT_LOOKBACK_BLOCKS: u32 = 14_400;
+/// Slack between the clocks of the bridge and of the block author.
+const CLOCK_SKEW_IN_SECONDS: i64 = 60;
+
+/// A successful transfer out of the signer.
+struct Payout {
+    block_hash: H256,
+    block_number: u32,
+    timestamp: i64,
+    extrinsic_hash: Option<H256>,
+    recipient: AccountId32,
+    amount: u128,
+}
+
+fn rpc(api: &GlitchApi, method: &str, params: Value) -> Option<Value> {
+    let request = json!({ "jsonrpc": "2.0", "id": "1", "method": method, "params": params });
+    let response = api.get_request(request).ok()??;
+    serde_json::from_str(&response).ok()
+}
+
+fn extrinsic_hash(xt_hex: &str) -> Option<H256> {
+    let bytes = hex::decode(xt_hex.trim_start_matches("0x")).ok()?;
+    Some(H256::from(blake2_256(&bytes)))
+}
+
+/// Hashes of the extrinsics of the block, in order.
+fn block_extrinsic_hashes(api: &GlitchApi, block_hash: H256) -> Vec<H256> {
+    rpc(api, "chain_getBlock", json!([to_hex(block_hash)]))
+        .and_then(|block| block["block"]["extrinsics"].as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|xt| xt.as_str().and_then(extrinsic_hash))
+        .collect()
+}
+
+/// Whether the transaction pool holds an extrinsic signed by `signer`,
+/// which could still pay a tx. None if the pool can't be read.
+fn pool_has_extrinsics_of(api: &GlitchApi, signer: &AccountId32) -> Option<bool> {
+    let pending = rpc(api, "author_pendingExtrinsics", json!([]))?;
+    let signer: &[u8] = signer.as_ref();
+    Some(pending.as_array()?.iter().filter_map(|xt| xt.as_str()).any(|xt| {
+        // Length prefix, signed version byte, `MultiAddress::Id` and the account.
+        hex::decode(xt.trim_start_matches("0x"))
+            .map(|bytes| bytes.windows(33).take(8).any(|window| window[0] == 0 && &window[1..] == signer))
+            .unwrap_or(false)
+    }))
+}
+
+fn block_number(api: &GlitchApi, block_hash: H256) -> Option<u32> {
+    api.get_storage_value("System", "Number", Some(block_hash)).ok()?
+}
+
+fn block_timestamp(api: &GlitchApi, block_hash: H256) -> Option<i64> {
+    let millis: u64 = api.get_storage_value("Timestamp", "Now", Some(block_hash)).ok()??;
+    Some((millis / 1000) as i64)
+}
+
+/// Transfers out of the signer from the best block back to the first block
+/// older than `since`, at most `lookback` blocks, and the timestamp of the
+/// oldest block read (`i64::MIN` from genesis). Also returns the hashes of
+/// every extrinsic seen, to tell a failed payout from one never included.
+fn signer_payouts(
+    api: &GlitchApi,
+    signer: &AccountId32,
+    asset_id: Option<u32>,
+    since: i64,
+    lookback: u32,
+    with_hashes: bool,
+) -> Option<(Vec<Payout>, HashSet<H256>, i64)> {
+    let head = api.get_block_hash(None).ok()??;
+    let head_number = block_number(api, head)?;
+
+    let mut payouts = Vec::new();
+    let mut included = HashSet::new();
+    let mut oldest = i64::MAX;
+    for number in (head_number.saturating_sub(lookback)..=head_number).rev() {
+        let block_hash = api.get_block_hash(Some(number)).ok()??;
+        let timestamp = block_timestamp(api, block_hash)?;
+        oldest = if number == 0 { i64::MIN } else { timestamp };
+
+        let hashes = if with_hashes {
+            block_extrinsic_hashes(api, block_hash)
+        } else {
+            Vec::new()
+        };
+        included.extend(hashes.iter().copied());
+
+        for (phase, event) in block_events(api, block_hash) {
+            if let Some((recipient, amount)) = transfer_from(&event.data, &event.pallet, &event.variant, signer, asset_id) {
+                let extrinsic_hash = phase
+                    .strip_prefix("ApplyExtrinsic(")
+                    .and_then(|index| index.strip_suffix(')'))
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| hashes.get(index).copied());
+                payouts.push(Payout {
+                    block_hash,
+                    block_number: number,
+                    timestamp,
+                    extrinsic_hash,
+                    recipient,
+                    amount,
+                });
+            }
+        }
+
+        if timestamp < since {
+            break;
+        }
+    }
+
+    Some((payouts, included, oldest))
+}
+
+enum Resolution {
+    Paid(usize),
+    Unpaid(String),
+    Unresolved(String),
+}
+
+/// Whether `tx` was paid by one of `payouts`, none of which can pay more
+/// than one tx. Without the extrinsic hash only a transfer of exactly the
+/// decided payout to the recipient counts. A tx is only found unpaid if the
+/// blocks read reach back to its submission (`oldest`).
+fn resolve(
+    tx: &ProcessingTx,
+    payouts: &[Payout],
+    claimed: &HashSet<usize>,
+    included: &HashSet<H256>,
+    oldest: i64,
+    pool_busy: Option<bool>,
+    ss58_prefix: Option<u16>,
+) -> Resolution {
+    if let Some(hash) = tx.extrinsic_hash.as_deref() {
+        let hash = match hash.trim_start_matches("0x").parse::<H256>() {
+            Ok(hash) => hash,
+            Err(_) => return Resolution::Unresolved(format!("unreadable extrinsic hash {hash}")),
+        };
+        if let Some(index) = payouts.iter().position(|payout| payout.extrinsic_hash == Some(hash)) {
+            return Resolution::Paid(index);
+        }
+        if included.contains(&hash) {
+            return Resolution::Unpaid(format!("payout extrinsic {} was included but failed", to_hex(hash)));
+        }
+    } else {
+        let recipient = match parse_glitch_address(&tx.glitch_address, ss58_prefix) {
+            Ok(public) => AccountId32::from(public),
+            Err(e) => return Resolution::Unpaid(format!("recipient can't be paid: {e}")),
+        };
+        let payout_amount = match tx.payout_amount {
+            Some(payout_amount) => payout_amount,
+            None => return Resolution::Unresolved("the amount of the payout is not recorded".to_string()),
+        };
+        // Payouts are newest first, the oldest match goes to the oldest tx.
+        let found = payouts.iter().enumerate().rev().find_map(|(index, payout)| {
+            let matches = !claimed.contains(&index)
+                && payout.recipient == recipient
+                && payout.amount == payout_amount
+                && payout.timestamp >= tx.submitted_at - CLOCK_SKEW_IN_SECONDS;
+            matches.then(|| index)
+        });
+        if let Some(index) = found {
+            return Resolution::Paid(index);
+        }
+    }
+
+    if oldest >= tx.submitted_at - CLOCK_SKEW_IN_SECONDS {
+        return Resolution::Unresolved("the blocks read don't reach back to the submission".to_string());
+    }
+    match pool_busy {
+        Some(false) => Resolution::Unpaid("no payout found on chain nor pending".to_string()),
+        Some(true) => Resolution::Unresolved("the signer has extrinsics pending in the pool".to_string()),
+        None => Resolution::Unresolved("the transaction pool could not be read".to_string()),
+    }
+}
+
+/// Resolves the PROCESSING txs of a pipeline left by a previous run. A
+/// payout found on chain, by extrinsic hash or by a transfer of the decided
+/// amount from the signer to the recipient after submission, marks the tx
+/// PROCESSED; with no payout found in blocks reaching back to the
+/// submission and nothing pending from the signer, it goes back to
+/// TO_PROCESS.
+/// Anything else stays PROCESSING with the reason as error.
+pub async fn reconcile_processing_txs(config: &Config, network_config: &Network, database_engine: &DatabaseEngine) {
+    let txs = database_engine.processing_txs(&network_config.name).await;
+    reconcile(config, network_config, database_engine, txs, "at startup").await;
+}
+
+/// Resolves the PROCESSING txs of `ids` like at startup, while the payer
+/// runs. Only for txs submitted long enough ago that their submission is
+/// over, or one still being sent could be put back in the queue.
+pub async fn reconcile_stuck_txs(config: &Config, network_config: &Network, database_engine: &DatabaseEngine, ids: &[u128]) {
+    let txs = database_engine
+        .processing_txs(&network_config.name)
+        .await
+        .into_iter()
+        .filter(|tx| ids.contains(&tx.id))
+        .collect();
+    reconcile(config, network_config, database_engine, txs, "by the stuck sweeper").await;
+}
+
+async fn reconcile(
+    config: &Config,
+    network_config: &Network,
+    database_engine: &DatabaseEngine,
+    txs: Vec<ProcessingTx>,
+    context: &str,
+) {
+    let name = &network_config.name;
+    if txs.is_empty() {
+        return;
+    }
+    info!("Reconciling {} PROCESSING tx(s) of {} with Glitch", txs.len(), name);
+
+    let client = WsRpcClient::new(&network_config.glitch_node_url());
+    let api: GlitchApi = Api::<sr25519::Pair, _, PlainTipExtrinsicParams>::new(client).unwrap();
+    let signer: sr25519::Pair = Pair::from_string(&network_config.glitch_private_key(config), None).unwrap();
+    let signer = AccountId32::from(signer.public());
+
+    let since = txs.iter().map(|tx| tx.submitted_at).min().unwrap_or_default() - CLOCK_SKEW_IN_SECONDS;
+    let lookback = config.reconciliation_lookback_blocks.unwrap_or(DEFAULT_LOOKBACK_BLOCKS);
+    let with_hashes = txs.iter().any(|tx| tx.extrinsic_hash.is_some());
+    let (payouts, included, oldest) = match signer_payouts(&api, &signer, network_config.glitch_asset_id, since, lookback, with_hashes) {
+        Some(found) => found,
+        None => {
+            error!("Could not read the recent blocks of Glitch, the PROCESSING txs of {} are left as they are.", name);
+            return;
+        }
+    };
+    let pool_busy = pool_has_extrinsics_of(&api, &signer);
+    let finalized = api
+        .get_finalized_head()
+        .ok()
+        .flatten()
+        .and_then(|hash| block_number(&api, hash))
+        .unwrap_or_default();
+
+    let mut claimed = HashSet::new();
+    let mut outcomes: HashMap<&str, usize> = HashMap::new();
+    for tx in txs.iter() {
+        let resolution = resolve(tx, &payouts, &claimed, &included, oldest, pool_busy, config.glitch_ss58_prefix);
+        if let Resolution::Paid(index) = resolution {
+            claimed.insert(index);
+        }
+        let outcome = match resolution {
+            Resolution::Paid(index) if payouts[index].block_number > finalized => {
+                let message = format!(
+                    "Reconciliation: paid in block {} which is not final yet",
+                    to_hex(payouts[index].block_hash)
+                );
+                warn!("Tx {}: {}", tx.id, message);
+                database_engine.update_tx_with_error(tx.id, message).await;
+                "unresolved"
+            }
+            Resolution::Paid(index) => {
+                let payout = &payouts[index];
+                let note = format!(
+                    "reconciled {}: payout {} in block {}; business fee not counted",
+                    context,
+                    payout.amount,
+                    to_hex(payout.block_hash)
+                );
+                match database_engine
+                    .reconcile_tx_paid(tx.id, tx.version, to_hex(payout.block_hash), note)
+                    .await
+                {
+                    Some(_) => {
+                        info!("Tx {} was paid in block {}, marked PROCESSED.", tx.id, to_hex(payout.block_hash));
+                        "processed"
+                    }
+                    None => "unresolved",
+                }
+            }
+            Resolution::Unpaid(reason) => match database_engine.release_tx_claim(tx.id, tx.version).await {
+                Some(_) => {
+                    info!("Tx {} was not paid ({}), back to TO_PROCESS.", tx.id, reason);
+                    "requeued"
+                }
+                None => "unresolved",
+            },
+            Resolution::Unresolved(reason) => {
+                let message = format!("Reconciliation: {reason}, check it manually");
+                warn!("Tx {}: {}", tx.id, message);
+                database_engine.update_tx_with_error(tx.id, message).await;
+                "unresolved"
+            }
+        };
+        *outcomes.entry(outcome).or_default() += 1;
+    }
+
+    info!("Reconciliation of {} done: {:?}", name, outcomes);
+}
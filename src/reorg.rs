@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use log::{info, warn};
+use web3::api::Eth;
+use web3::types::{BlockHeader, BlockId, H256};
+use web3::Transport;
+
+use crate::config::{Network, Notification, ReorgQuarantine};
+use crate::database::DatabaseEngine;
+use crate::decoder::{Deposit, STATE_QUARANTINED, STATE_TO_PROCESS};
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+
+const DEFAULT_HISTORY_SIZE: usize = 256;
+
+/// Hashes of the latest heads seen, to notice when the chain switches forks.
+pub struct HeadHistory {
+    hashes: BTreeMap<u64, H256>,
+    capacity: usize,
+}
+
+impl HeadHistory {
+    pub fn new(capacity: Option<usize>) -> Self {
+        HeadHistory {
+            hashes: BTreeMap::new(),
+            capacity: capacity.unwrap_or(DEFAULT_HISTORY_SIZE),
+        }
+    }
+
+    /// Records a new head and returns the range of previously seen blocks
+    /// that are no longer part of the canonical chain, if any.
+    pub async fn observe<T: Transport>(&mut self, eth: &Eth<T>, header: &BlockHeader) -> Option<(u64, u64)> {
+        let (number, hash) = match (header.number, header.hash) {
+            (Some(number), Some(hash)) => (number.as_u64(), hash),
+            _ => return None,
+        };
+
+        let mut reorged = None;
+        if let Some(highest) = self.hashes.keys().next_back().copied() {
+            let parent_matches = self
+                .hashes
+                .get(&number.saturating_sub(1))
+                .map_or(true, |seen| *seen == header.parent_hash);
+
+            if number <= highest || !parent_matches {
+                let ancestor = self.common_ancestor(eth, number, header.parent_hash).await;
+                reorged = Some((ancestor + 1, highest));
+            }
+        }
+
+        let first_stale = reorged.map_or(number, |(from, _)| from);
+        self.hashes.split_off(&first_stale);
+        self.hashes.insert(number, hash);
+        while self.hashes.len() > self.capacity {
+            let oldest = *self.hashes.keys().next().unwrap();
+            self.hashes.remove(&oldest);
+        }
+
+        reorged
+    }
+
+    /// Walks the new chain backwards until a block we already saw. Blocks
+    /// older than the history are assumed to be canonical.
+    async fn common_ancestor<T: Transport>(&self, eth: &Eth<T>, number: u64, parent_hash: H256) -> u64 {
+        let mut candidate = number.saturating_sub(1);
+        let mut canonical = parent_hash;
+
+        loop {
+            match self.hashes.get(&candidate) {
+                Some(seen) if *seen == canonical => return candidate,
+                Some(_) => {}
+                None => return candidate,
+            }
+
+            match eth.block(BlockId::Hash(canonical)).await {
+                Ok(Some(block)) => canonical = block.parent_hash,
+                _ => {
+                    // Without the new chain we can't tell where it forked;
+                    // consider the whole history affected.
+                    let oldest = self.hashes.keys().next().copied().unwrap_or(candidate);
+                    return oldest.saturating_sub(1);
+                }
+            }
+            candidate = candidate.saturating_sub(1);
+        }
+    }
+}
+
+/// Alerts about the reorg and, when it is deep enough, opens a quarantine
+/// window over the affected blocks.
+pub async fn handle_reorg(
+    network_config: &Network,
+    reorg_quarantine: &ReorgQuarantine,
+    (from_block, to_block): (u64, u64),
+    smtp_config: &Notification,
+    database_engine: &DatabaseEngine,
+) {
+    let depth = to_block + 1 - from_block;
+    metrics::REORGS
+        .with_label_values(&[&network_config.name])
+        .inc();
+
+    if depth < reorg_quarantine.depth_threshold {
+        info!(
+            "Reorg of {} block(s) on {} ({} to {})",
+            depth, network_config.network, from_block, to_block
+        );
+        return;
+    }
+
+    let held = database_engine
+        .insert_reorg_quarantine(
+            &network_config.name,
+            from_block,
+            to_block,
+            60 * reorg_quarantine.quarantine_in_minutes,
+        )
+        .await;
+
+    let message = format!(
+        "Reorg of {} blocks on {} ({} to {}). Deposits from these blocks are held for {} minutes; {} already recorded were quarantined.",
+        depth,
+        network_config.network,
+        from_block,
+        to_block,
+        reorg_quarantine.quarantine_in_minutes,
+        held
+    );
+    warn!("{}", message);
+    notify(smtp_config, "Deep reorg detected!", &message).await;
+}
+
+/// Marks as quarantined the deposits coming from a block under an active
+/// quarantine window.
+pub async fn apply_quarantines(
+    deposits: Vec<Deposit>,
+    network_config: &Network,
+    database_engine: &DatabaseEngine,
+) -> Vec<Deposit> {
+    if network_config.reorg_quarantine.is_none() || deposits.is_empty() {
+        return deposits;
+    }
+
+    let quarantines = database_engine.active_quarantines(&network_config.name).await;
+
+    deposits
+        .into_iter()
+        .map(|mut deposit| {
+            let quarantined = deposit.block_number.map_or(false, |block| {
+                quarantines
+                    .iter()
+                    .any(|(from, to)| (*from..=*to).contains(&block))
+            });
+            if quarantined && deposit.state == STATE_TO_PROCESS {
+                deposit.state = STATE_QUARANTINED;
+            }
+            deposit
+        })
+        .collect()
+}
+
+pub async fn release_expired_quarantines(database_engine: Arc<DatabaseEngine>, mut ticker: Ticker) {
+    loop {
+        ticker.tick().await;
+
+        let released = database_engine.release_expired_quarantines().await;
+        if released > 0 {
+            info!("{} quarantined tx(s) released back to processing.", released);
+        }
+    }
+}
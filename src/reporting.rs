@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::Path;
+
+use log::info;
+
+use crate::config::Config;
+use crate::database::{DatabaseEngine, FeeInvoice};
+
+const FEE_INVOICE_HEADER: &str =
+    "id,scanner_name,period_start,period_end,tx_count,total_volume,fee_amount,glitch_tx_hash,token,decimals";
+
+fn fee_invoices_csv(invoices: &[FeeInvoice]) -> String {
+    let mut csv = format!("{FEE_INVOICE_HEADER}\n");
+    for invoice in invoices {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            invoice.id,
+            invoice.scanner_name,
+            invoice.period_start,
+            invoice.period_end,
+            invoice.tx_count,
+            invoice.total_volume,
+            invoice.fee_amount,
+            invoice.glitch_tx_hash,
+            invoice.token.as_deref().unwrap_or_default(),
+            invoice.decimals.map(|decimals| decimals.to_string()).unwrap_or_default()
+        ));
+    }
+    csv
+}
+
+/// Writes the fee invoices, optionally of a single pipeline, to `output`,
+/// with the token of each pipeline from the registry. Files ending in
+/// `.json` get JSON, anything else CSV.
+pub async fn export_fee_invoices(
+    database_engine: &DatabaseEngine,
+    config: &Config,
+    scanner_name: Option<String>,
+    output: &Path,
+) {
+    let mut invoices = database_engine.fee_invoices(scanner_name).await;
+    for invoice in invoices.iter_mut() {
+        let token = config
+            .networks
+            .iter()
+            .find(|network| network.name == invoice.scanner_name)
+            .and_then(|network| network.token(config));
+        invoice.token = token.map(|token| token.symbol.clone());
+        invoice.decimals = token.map(|token| token.decimals);
+    }
+
+    let contents = match output.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::to_string_pretty(&invoices).unwrap(),
+        _ => fee_invoices_csv(&invoices),
+    };
+
+    fs::write(output, contents).expect("Error while writing the fee invoices file!");
+
+    info!("{} fee invoice(s) written to {}", invoices.len(), output.display());
+}
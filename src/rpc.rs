@@ -0,0 +1,142 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use rand::Rng;
+use tokio::time::Duration;
+use web3::error::TransportError;
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::RpcRetry;
+use crate::metrics;
+
+/// JSON-RPC error code used by Infura/Alchemy style providers for rate limits.
+const LIMIT_EXCEEDED_CODE: i64 = -32005;
+
+struct Budget {
+    window_start: DateTime<Utc>,
+    used: u32,
+}
+
+/// Retries rate-limited and timed out web3 calls with jittered exponential
+/// backoff, spending from a per-minute retry budget so a throttling provider
+/// isn't hammered.
+pub struct ThrottledRpc {
+    network: String,
+    policy: RpcRetry,
+    timeout: Duration,
+    budget: Mutex<Budget>,
+    clock: Arc<dyn Clock>,
+}
+
+pub fn is_rate_limited(error: &web3::Error) -> bool {
+    match error {
+        web3::Error::Transport(TransportError::Code(429)) => true,
+        web3::Error::Rpc(e) => {
+            e.code.code() == LIMIT_EXCEEDED_CODE
+                || e.message.to_lowercase().contains("rate limit")
+                || e.message.to_lowercase().contains("too many requests")
+        }
+        _ => false,
+    }
+}
+
+impl ThrottledRpc {
+    pub fn new(network: String, policy: Option<RpcRetry>, timeout: Duration) -> Self {
+        Self::with_clock(network, policy, timeout, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(network: String, policy: Option<RpcRetry>, timeout: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            network,
+            policy: policy.unwrap_or_default(),
+            timeout,
+            budget: Mutex::new(Budget {
+                window_start: clock.now(),
+                used: 0,
+            }),
+            clock,
+        }
+    }
+
+    /// Budget of a single call.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn take_retry(&self) -> bool {
+        let mut budget = self.budget.lock().unwrap();
+        let now = self.clock.now();
+
+        if now - budget.window_start >= chrono::Duration::seconds(60) {
+            budget.window_start = now;
+            budget.used = 0;
+        }
+
+        let available = budget.used < self.policy.retries_per_minute;
+        if available {
+            budget.used += 1;
+        }
+
+        metrics::RPC_RETRY_BUDGET
+            .with_label_values(&[&self.network])
+            .set((self.policy.retries_per_minute - budget.used) as i64);
+
+        available
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .policy
+            .base_delay_in_ms
+            .saturating_mul(2_u64.saturating_pow(attempt))
+            .min(self.policy.max_delay_in_ms);
+        let jitter = rand::thread_rng().gen_range(0..=delay / 2);
+
+        Duration::from_millis(delay / 2 + jitter)
+    }
+
+    pub async fn call<T, F, Fut>(&self, operation: &str, request: F) -> web3::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = web3::Result<T>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let (reason, error) = match tokio::time::timeout(self.timeout, request()).await {
+                Ok(Err(e)) if is_rate_limited(&e) => {
+                    metrics::RPC_RATE_LIMITED
+                        .with_label_values(&[&self.network])
+                        .inc();
+                    ("Rate limited", e)
+                }
+                Ok(result) => return result,
+                Err(_) => {
+                    metrics::RPC_TIMEOUTS
+                        .with_label_values(&[&self.network])
+                        .inc();
+                    let message = format!("{} timed out after {:?}", operation, self.timeout);
+                    ("Timed out", web3::Error::Transport(TransportError::Message(message)))
+                }
+            };
+
+            if !self.take_retry() {
+                warn!(
+                    "Retry budget exhausted on {} for {}, giving up until the next window.",
+                    self.network, operation
+                );
+                return Err(error);
+            }
+
+            let delay = self.backoff(attempt);
+            warn!(
+                "{} on {} during {}, retrying in {:?}.",
+                reason, self.network, operation, delay
+            );
+            self.clock.sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
@@ -1,62 +1,98 @@
-use crate::balance_monitor::monitor_balance;
 use crate::block_listener::listen_blocks_v2;
+use crate::compliance::ComplianceScreening;
+use crate::config::{ Config, Network, Notification };
 use crate::database::DatabaseEngine;
-use crate::glitch::{ fee_payer_v2, run_network_listener };
-use crate::Config;
-use log::info;
+use crate::decoder::SanityChecks;
+use crate::rpc::ThrottledRpc;
+use crate::scheduler::Scheduler;
+use crate::substrate_scanner::SubstrateScanner;
+use futures::future::{ BoxFuture, FutureExt };
 use std::sync::Arc;
-use tokio::time::{ sleep, Duration };
+use tokio::time::Duration;
 
-pub struct ScannerV2 {}
-
-impl ScannerV2 {
-    pub async fn run(config: Config) {
-        info!("Scanner running...");
-
-        info!("Found {} network{}to listen!", config.networks.len(), if config.networks.len() > 1 {
-            "s "
-        } else {
-            " "
-        });
+/// Source side of a pipeline: watches a chain for deposits and records them
+/// in the store, where the payers pick them up.
+pub trait DepositSource: Send {
+    fn run(self: Box<Self>) -> BoxFuture<'static, ()>;
+}
 
-        let database_engine = Arc::new(DatabaseEngine::new(config.db));
+/// Builds the deposit source configured for the network: substrate events
+/// when `substrate_source` is set, EVM logs otherwise.
+pub fn deposit_source(
+    config: &Config,
+    network_config: &Network,
+    database_engine: Arc<DatabaseEngine>,
+    scheduler: &Scheduler
+) -> Box<dyn DepositSource> {
+    match &network_config.substrate_source {
+        Some(source) => Box::new(
+            SubstrateScanner::new(
+                network_config.clone(),
+                source.clone(),
+                SanityChecks::new(config, network_config),
+                ComplianceScreening::for_config(config.compliance.as_ref()),
+                database_engine,
+                scheduler.ticker(
+                    format!("substrate_scanner:{}", network_config.name),
+                    Duration::from_secs(6),
+                    Duration::from_millis(500)
+                )
+            )
+        ),
+        None => Box::new(Scanner::new(config, network_config, database_engine)),
+    }
+}
 
-        config.networks.iter().for_each(|network_config| {
-            tokio::task::spawn(listen_blocks_v2(network_config.clone(), database_engine.clone()));
+/// Watches one Ethereum network for deposits and records them in the store.
+pub struct Scanner {
+    network_config: Network,
+    sanity_checks: SanityChecks,
+    compliance: Option<Arc<ComplianceScreening>>,
+    smtp_config: Notification,
+    rpc: Arc<ThrottledRpc>,
+    new_head_timeout: Duration,
+    database_engine: Arc<DatabaseEngine>,
+}
 
-            tokio::task::spawn(
-                run_network_listener(
-                    network_config.name.clone(),
-                    config.glitch_private_key.clone().unwrap(),
-                    network_config.ws_glitch_node.clone(),
-                    config.business_fee,
-                    config.glitch_gas,
-                    database_engine.clone()
-                )
-            );
+impl Scanner {
+    pub fn new(config: &Config, network_config: &Network, database_engine: Arc<DatabaseEngine>) -> Self {
+        let timeouts = config.timeouts.clone().unwrap_or_default();
+        Self {
+            network_config: network_config.clone(),
+            sanity_checks: SanityChecks::new(config, network_config),
+            compliance: ComplianceScreening::for_config(config.compliance.as_ref()),
+            smtp_config: config.notifications.clone(),
+            rpc: Arc::new(ThrottledRpc::new(
+                network_config.name.clone(),
+                config.rpc_retry.clone(),
+                timeouts.eth_rpc(),
+            )),
+            new_head_timeout: timeouts.eth_new_head(),
+            database_engine,
+        }
+    }
 
-            tokio::task::spawn(
-                fee_payer_v2(
-                    database_engine.clone(),
-                    config.interval_days_for_transfer,
-                    network_config.ws_glitch_node.clone(),
-                    network_config.name.clone(),
-                    config.glitch_private_key.clone().unwrap(),
-                    config.glitch_fee_address.clone()
-                )
-            );
+    pub async fn run(self) {
+        listen_blocks_v2(
+            self.network_config,
+            self.sanity_checks,
+            self.compliance,
+            self.smtp_config,
+            self.rpc,
+            self.new_head_timeout,
+            self.database_engine
+        ).await
+    }
+}
 
-            tokio::task::spawn(
-                monitor_balance(
-                    network_config.ws_glitch_node.clone(),
-                    config.glitch_private_key.clone().unwrap(),
-                    config.notifications.clone()
-                )
-            );
-        });
+impl DepositSource for Scanner {
+    fn run(self: Box<Self>) -> BoxFuture<'static, ()> {
+        Scanner::run(*self).boxed()
+    }
+}
 
-        loop {
-            sleep(Duration::from_millis(1000)).await;
-        }
+impl DepositSource for SubstrateScanner {
+    fn run(self: Box<Self>) -> BoxFuture<'static, ()> {
+        SubstrateScanner::run(*self).boxed()
     }
 }
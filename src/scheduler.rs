@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::info;
+use rand::Rng;
+use serde_derive::Serialize;
+use tokio::time::{sleep, Duration};
+
+use crate::metrics;
+
+/// Shared controls of a periodic job, changed at runtime from the admin API.
+pub struct JobHandle {
+    name: String,
+    paused: AtomicBool,
+    interval_in_ms: AtomicU64,
+    jitter_in_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JobStatus {
+    pub name: String,
+    pub paused: bool,
+    pub interval_in_ms: u64,
+}
+
+impl JobHandle {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        metrics::JOB_PAUSED.with_label_values(&[&self.name]).set(1);
+        info!("Job {} paused.", self.name);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        metrics::JOB_PAUSED.with_label_values(&[&self.name]).set(0);
+        info!("Job {} resumed.", self.name);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval_in_ms
+            .store(interval.as_millis() as u64, Ordering::SeqCst);
+        info!("Job {} now runs every {:?}.", self.name, interval);
+    }
+
+    fn next_delay(&self) -> Duration {
+        let jitter = if self.jitter_in_ms > 0 {
+            rand::thread_rng().gen_range(0..=self.jitter_in_ms)
+        } else {
+            0
+        };
+
+        Duration::from_millis(self.interval_in_ms.load(Ordering::SeqCst) + jitter)
+    }
+
+    fn status(&self) -> JobStatus {
+        JobStatus {
+            name: self.name.clone(),
+            paused: self.is_paused(),
+            interval_in_ms: self.interval_in_ms.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Drives one periodic loop. The first tick completes immediately, like
+/// `tokio::time::interval`; later ones wait for the interval plus jitter and
+/// for the job to be resumed if it is paused.
+pub struct Ticker {
+    handle: Arc<JobHandle>,
+    first: bool,
+    last_run: Option<Instant>,
+}
+
+impl Ticker {
+    pub async fn tick(&mut self) {
+        let delay = if self.first { Duration::ZERO } else { self.handle.next_delay() };
+        self.tick_on(sleep(delay)).await
+    }
+
+    /// Like `tick`, but the loop runs when `next` completes instead of after
+    /// the interval, e.g. on a new block. Its output is returned.
+    pub async fn tick_on<F: Future>(&mut self, next: F) -> F::Output {
+        if let Some(started) = self.last_run.take() {
+            metrics::JOB_DURATION
+                .with_label_values(&[&self.handle.name])
+                .observe(started.elapsed().as_secs_f64());
+            metrics::JOB_RUNS
+                .with_label_values(&[&self.handle.name])
+                .inc();
+        }
+
+        let output = next.await;
+        self.first = false;
+
+        while self.handle.is_paused() {
+            sleep(Duration::from_secs(1)).await;
+        }
+
+        self.last_run = Some(Instant::now());
+        output
+    }
+
+    pub fn handle(&self) -> Arc<JobHandle> {
+        self.handle.clone()
+    }
+}
+
+/// Registry of every periodic job of the bridge.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Mutex<BTreeMap<String, Arc<JobHandle>>>,
+}
+
+impl Scheduler {
+    /// The ticker of the job `name`. A task restarted by the supervisor asks
+    /// for it again and gets the same controls back, paused or not.
+    pub fn ticker(&self, name: String, interval: Duration, jitter: Duration) -> Ticker {
+        let handle = self
+            .jobs
+            .lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_insert_with(|| {
+                Arc::new(JobHandle {
+                    name,
+                    paused: AtomicBool::new(false),
+                    interval_in_ms: AtomicU64::new(interval.as_millis() as u64),
+                    jitter_in_ms: jitter.as_millis() as u64,
+                })
+            })
+            .clone();
+
+        Ticker {
+            handle,
+            first: true,
+            last_run: None,
+        }
+    }
+
+    pub fn job(&self, name: &str) -> Option<Arc<JobHandle>> {
+        self.jobs.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn jobs(&self) -> Vec<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|job| job.status())
+            .collect()
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use log::{info, warn};
+
+use crate::database::DatabaseEngine;
+
+/// A table as the code expects it after every migration in `db/` is applied.
+/// Column types are written the way `information_schema` reports them.
+pub struct ExpectedTable {
+    pub name: &'static str,
+    pub columns: &'static [(&'static str, &'static str)],
+    pub indexes: &'static [&'static str],
+}
+
+/// Keep in sync with the migrations in `db/`.
+pub const EXPECTED_SCHEMA: &[ExpectedTable] = &[
+    ExpectedTable {
+        name: "scanner_state",
+        columns: &[
+            ("id", "int unsigned"),
+            ("name", "varchar(50)"),
+            ("network", "varchar(50)"),
+            ("monitor_address", "varchar(66)"),
+            ("accumulated_fees", "varchar(255)"),
+            ("last_block", "int unsigned"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "tx",
+        columns: &[
+            ("id", "int unsigned"),
+            ("tx_eth_hash", "varchar(66)"),
+            ("tx_glitch_hash", "varchar(66)"),
+            ("from_eth_address", "varchar(66)"),
+            ("to_glitch_address", "varchar(255)"),
+            ("amount", "decimal(38,0)"),
+            ("business_fee_amount", "decimal(38,0)"),
+            ("business_fee_percentage", "varchar(255)"),
+            (
+                "state",
+                "enum('TO_PROCESS','PROCESSING','PROCESSED','SUSPICIOUS','QUARANTINED','TEST','CANCELLED')",
+            ),
+            ("error", "text"),
+            ("time", "timestamp"),
+            ("net_amount", "varchar(66)"),
+            ("extrinsic_hash", "varchar(255)"),
+            ("scanner_name", "varchar(50)"),
+            ("submitted_at", "timestamp"),
+            ("finalized_at", "timestamp"),
+            ("sla_alerted", "tinyint(1)"),
+            ("wich_transaction_fee", "int unsigned"),
+            ("network_fee", "decimal(38,0)"),
+            ("network_fee_estimated", "decimal(38,0)"),
+            ("eth_block_number", "bigint unsigned"),
+            ("decoder_version", "varchar(50)"),
+            ("payout_version", "varchar(50)"),
+            ("version", "int unsigned"),
+            ("unlock_at", "bigint"),
+            ("amount_breakdown", "text"),
+            ("event_version", "tinyint unsigned"),
+            ("eth_fee", "decimal(38,0)"),
+            ("labels", "varchar(255)"),
+            ("ack_tx_hash", "varchar(66)"),
+            ("glitch_block_number", "int unsigned"),
+            ("extrinsic_index", "int unsigned"),
+            ("transfer_event", "text"),
+            ("source_gas_used", "bigint unsigned"),
+            ("source_gas_price", "decimal(38,0)"),
+            ("source_value", "decimal(38,0)"),
+            (
+                "failure_kind",
+                "enum('INSUFFICIENT_FUNDS','BAD_DESTINATION','WOULD_REAP_SIGNER','REJECTED')",
+            ),
+            ("eth_block_time", "bigint"),
+        ],
+        indexes: &[
+            "PRIMARY",
+            "fk_fee_transaction",
+            "tx_decoder_version",
+            "tx_payout_version",
+            "tx_extrinsic_hash",
+        ],
+    },
+    ExpectedTable {
+        name: "fee_transaction",
+        columns: &[
+            ("id", "int unsigned"),
+            ("hash", "varchar(66)"),
+            ("amount", "varchar(255)"),
+            ("time", "timestamp"),
+            ("scanner_name", "varchar(50)"),
+            ("confirmation", "enum('CONFIRMED','DISCREPANCY')"),
+            ("discrepancy", "text"),
+        ],
+        indexes: &["PRIMARY", "fee_transaction_hash"],
+    },
+    ExpectedTable {
+        name: "supply_check",
+        columns: &[
+            ("id", "int unsigned"),
+            ("locked", "varchar(255)"),
+            ("minted", "varchar(255)"),
+            ("delta", "varchar(255)"),
+            ("within_tolerance", "tinyint(1)"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "reorg_quarantine",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("from_block", "bigint unsigned"),
+            ("to_block", "bigint unsigned"),
+            ("until", "timestamp"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "top_up_request",
+        columns: &[
+            ("id", "int unsigned"),
+            ("hot_wallet", "varchar(48)"),
+            ("amount", "varchar(255)"),
+            ("method", "varchar(20)"),
+            ("reference", "text"),
+            ("error", "text"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "scanner_error",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("kind", "enum('RPC','DECODE','SKIPPED_BLOCK')"),
+            ("block_number", "bigint unsigned"),
+            ("message", "text"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "scanner_error_time"],
+    },
+    ExpectedTable {
+        name: "fee_invoice",
+        columns: &[
+            ("id", "int unsigned"),
+            ("fee_transaction_id", "int unsigned"),
+            ("scanner_name", "varchar(255)"),
+            ("period_start", "timestamp"),
+            ("period_end", "timestamp"),
+            ("tx_count", "int unsigned"),
+            ("total_volume", "varchar(255)"),
+            ("fee_amount", "varchar(255)"),
+            ("glitch_tx_hash", "varchar(66)"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "fk_fee_invoice_transaction"],
+    },
+    ExpectedTable {
+        name: "audit_report",
+        columns: &[
+            ("id", "int unsigned"),
+            ("supply_ok", "tinyint(1)"),
+            ("fee_drift_ok", "tinyint(1)"),
+            ("stuck_txs", "int unsigned"),
+            ("passed", "tinyint(1)"),
+            ("report", "text"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "decoder_divergence",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("tx_eth_hash", "varchar(66)"),
+            ("log_index", "bigint unsigned"),
+            ("block_number", "bigint unsigned"),
+            ("difference", "text"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "skipped_log",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("reason", "enum('UNKNOWN_EVENT','UNDECODABLE','FILTERED_SENDER')"),
+            ("tx_eth_hash", "varchar(66)"),
+            ("log_index", "bigint unsigned"),
+            ("block_number", "bigint unsigned"),
+            ("detail", "text"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "skipped_log_tx_eth_hash"],
+    },
+    ExpectedTable {
+        name: "crash_report",
+        columns: &[
+            ("id", "int unsigned"),
+            ("message", "text"),
+            ("location", "varchar(255)"),
+            ("thread", "varchar(255)"),
+            ("tx_id", "int unsigned"),
+            ("backtrace", "text"),
+            ("build_version", "varchar(50)"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "instance_lease",
+        columns: &[
+            ("name", "varchar(50)"),
+            ("holder", "varchar(66)"),
+            ("expires_at", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "fee_policy",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("business_fee_percentage", "varchar(255)"),
+            ("fee_destination", "varchar(255)"),
+            ("effective_from", "timestamp"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "fee_policy_scanner_effective_from"],
+    },
+    ExpectedTable {
+        name: "deposit_screening",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("tx_eth_hash", "varchar(66)"),
+            ("screener", "varchar(50)"),
+            ("verdict", "enum('allow','hold','error')"),
+            ("risk", "varchar(255)"),
+            ("detail", "text"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "deposit_screening_tx_eth_hash"],
+    },
+    ExpectedTable {
+        name: "withdrawal",
+        columns: &[
+            ("id", "int unsigned"),
+            ("event_id", "varchar(66)"),
+            ("scanner_name", "varchar(50)"),
+            ("from_glitch_address", "varchar(66)"),
+            ("to_eth_address", "varchar(42)"),
+            ("amount", "decimal(38,0)"),
+            ("glitch_block_number", "int unsigned"),
+            ("state", "enum('TO_PROCESS','PROCESSING','PROPOSED','PROCESSED','FAILED')"),
+            ("eth_tx_hash", "varchar(66)"),
+            ("error", "text"),
+            ("version", "int unsigned"),
+            ("time", "timestamp"),
+            ("safe_tx_hash", "varchar(66)"),
+        ],
+        indexes: &["PRIMARY", "withdrawal_event_id"],
+    },
+    ExpectedTable {
+        name: "fee_adjustment",
+        columns: &[
+            ("id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("amount", "varchar(255)"),
+            ("reason", "text"),
+            ("requested_by", "varchar(255)"),
+            ("reference", "varchar(255)"),
+            ("accumulated_before", "varchar(255)"),
+            ("accumulated_after", "varchar(255)"),
+            ("fee_transaction_id", "int unsigned"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "fee_adjustment_reference", "fk_fee_adjustment_transaction"],
+    },
+    ExpectedTable {
+        name: "tx_event",
+        columns: &[
+            ("sequence", "bigint unsigned"),
+            ("tx_id", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("tx_eth_hash", "varchar(66)"),
+            ("previous_state", "varchar(20)"),
+            ("state", "varchar(20)"),
+            ("tx_glitch_hash", "varchar(66)"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "tx_event_tx_id"],
+    },
+    ExpectedTable {
+        name: "glitch_outbox",
+        columns: &[
+            ("id", "int unsigned"),
+            ("tx_id", "int unsigned"),
+            ("tx_version", "int unsigned"),
+            ("scanner_name", "varchar(50)"),
+            ("recipient", "text"),
+            ("amount", "decimal(38,0)"),
+            ("business_fee_amount", "decimal(38,0)"),
+            ("business_fee_percentage", "varchar(255)"),
+            ("estimated_fee", "decimal(38,0)"),
+            ("amount_breakdown", "text"),
+            ("tx_detected_at", "bigint"),
+            (
+                "state",
+                "enum('PENDING','SUBMITTING','INCLUDED','NOT_INCLUDED','UNKNOWN','FAILED')",
+            ),
+            ("extrinsic_hash", "varchar(66)"),
+            ("block_hash", "varchar(66)"),
+            ("error", "text"),
+            ("time", "timestamp"),
+            ("updated_at", "timestamp"),
+        ],
+        indexes: &["PRIMARY", "glitch_outbox_state", "glitch_outbox_tx_id", "glitch_outbox_extrinsic_hash"],
+    },
+    ExpectedTable {
+        name: "token",
+        columns: &[
+            ("symbol", "varchar(20)"),
+            ("eth_address", "varchar(42)"),
+            ("decimals", "tinyint unsigned"),
+            ("min_amount", "decimal(38,0)"),
+            ("max_amount", "decimal(38,0)"),
+            ("business_fee", "double"),
+            ("glitch_asset_id", "int unsigned"),
+            ("time", "timestamp"),
+        ],
+        indexes: &["PRIMARY"],
+    },
+    ExpectedTable {
+        name: "event_cursor",
+        columns: &[("name", "varchar(50)"), ("last_sequence", "bigint unsigned")],
+        indexes: &["PRIMARY"],
+    },
+];
+
+/// MySQL 5.7 reports a display width for integer types (`int(10) unsigned`)
+/// that 8.0 omits. Booleans keep theirs, `tinyint(1)`, in both.
+fn normalize_type(column_type: &str) -> String {
+    let column_type = column_type.to_lowercase();
+    for integer in ["int", "bigint", "smallint", "mediumint"] {
+        if let Some(rest) = column_type.strip_prefix(&format!("{integer}(")) {
+            if let Some(close) = rest.find(')') {
+                return format!("{integer}{}", &rest[close + 1..]);
+            }
+        }
+    }
+    column_type
+}
+
+/// Differences between the expected schema and the live one. Extra tables
+/// and columns are not drift, the bridge just doesn't use them.
+pub fn diff_schema(
+    live_columns: &[(String, String, String)],
+    live_indexes: &[(String, String)],
+) -> Vec<String> {
+    let mut tables: BTreeMap<&str, BTreeMap<&str, String>> = BTreeMap::new();
+    for (table, column, column_type) in live_columns {
+        tables
+            .entry(table.as_str())
+            .or_default()
+            .insert(column.as_str(), normalize_type(column_type));
+    }
+    let indexes: BTreeSet<(&str, &str)> = live_indexes
+        .iter()
+        .map(|(table, index)| (table.as_str(), index.as_str()))
+        .collect();
+
+    let mut drift = Vec::new();
+    for expected in EXPECTED_SCHEMA {
+        let columns = match tables.get(expected.name) {
+            Some(columns) => columns,
+            None => {
+                drift.push(format!("Missing table {}", expected.name));
+                continue;
+            }
+        };
+
+        for (column, expected_type) in expected.columns {
+            match columns.get(column) {
+                None => drift.push(format!("Missing column {}.{}", expected.name, column)),
+                Some(live_type) if live_type != expected_type => drift.push(format!(
+                    "Column {}.{} is {}, {} expected",
+                    expected.name, column, live_type, expected_type
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for index in expected.indexes {
+            if !indexes.contains(&(expected.name, *index)) {
+                drift.push(format!("Missing index {} on {}", index, expected.name));
+            }
+        }
+    }
+    drift
+}
+
+/// Compares the live database against the expected schema. Returns whether
+/// they match.
+pub async fn check_schema(database_engine: &DatabaseEngine) -> bool {
+    let columns = database_engine.schema_columns().await;
+    let indexes = database_engine.schema_indexes().await;
+
+    let drift = diff_schema(&columns, &indexes);
+    for difference in &drift {
+        warn!("{}", difference);
+    }
+
+    if drift.is_empty() {
+        info!("The database schema matches the expected one.");
+    } else {
+        warn!("{} difference(s) with the expected schema found.", drift.len());
+    }
+    drift.is_empty()
+}
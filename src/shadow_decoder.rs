@@ -0,0 +1,151 @@
+use web3::ethabi::{self, param_type::Reader, ParamType, Token};
+use web3::types::Log;
+
+use crate::config::Network;
+use crate::decoder::{
+    max_stored_amount, unlock_time, Deposit, DepositEvent, EventLayout, EVENT_VERSION_V1, MAX_GLITCH_ADDRESS_LEN,
+    STATE_SUSPICIOUS, STATE_TO_PROCESS,
+};
+use crate::types::{h256_to_address, to_hex};
+
+/// Independent decoder of deposit logs built on `ethabi` and the configured
+/// event signature. It runs next to the active decoder to validate parsing
+/// changes on live traffic; its output is only compared, never stored.
+pub struct ShadowDecoder {
+    params: Vec<ParamType>,
+    layout: EventLayout,
+}
+
+impl ShadowDecoder {
+    /// The shadow decoder of an event of the network, if it has
+    /// `shadow_decoding` on. It shares the layout of the event, telling which
+    /// input is which, but decodes the data on its own.
+    pub fn for_network(network_config: &Network, event: &DepositEvent) -> Option<Self> {
+        if !network_config.shadow_decoding.unwrap_or(false) {
+            return None;
+        }
+
+        let inputs = event
+            .signature
+            .split_once('(')
+            .and_then(|(_, rest)| rest.strip_suffix(')'))
+            .expect("Invalid event signature!");
+        // The first input, the sender, is indexed and comes in a topic.
+        let params = inputs
+            .split(',')
+            .skip(1)
+            .map(|param| Reader::read(param.trim()).expect("Invalid event signature!"))
+            .collect();
+
+        Some(Self {
+            params,
+            layout: event.layout.clone(),
+        })
+    }
+
+    fn decode(&self, log: &Log) -> Result<Deposit, String> {
+        let tx_eth_hash = log
+            .transaction_hash
+            .ok_or_else(|| "Log without transaction hash".to_string())?;
+        let from = *log
+            .topics
+            .get(1)
+            .ok_or_else(|| "Log without sender topic".to_string())?;
+
+        let tokens = ethabi::decode(&self.params, &log.data.0).map_err(|e| format!("{e:?}"))?;
+        let uint = |index: usize| match tokens.get(index) {
+            Some(Token::Uint(value)) => Ok(*value),
+            other => Err(format!("Unexpected event input {other:?}")),
+        };
+
+        let glitch_address = match tokens.get(self.layout.recipient) {
+            Some(Token::String(value)) => value.clone(),
+            _ => return Err("No recipient in the event".to_string()),
+        };
+        let amount = uint(self.layout.amount)?;
+        let unlock_at = self.layout.unlock_at.map(uint).transpose()?;
+        let eth_fee = self.layout.eth_fee.map(uint).transpose()?;
+        let unlock_at = unlock_at
+            .filter(|unlock_at| !unlock_at.is_zero())
+            .map(unlock_time);
+        let state = match (&unlock_at, eth_fee) {
+            (Some(Err(_)), _) => STATE_SUSPICIOUS,
+            (_, Some(eth_fee)) if eth_fee > max_stored_amount() => STATE_SUSPICIOUS,
+            _ => STATE_TO_PROCESS,
+        };
+        let unlock_at = unlock_at.and_then(Result::ok);
+
+        Ok(Deposit {
+            tx_eth_hash: to_hex(tx_eth_hash),
+            from_eth_address: h256_to_address(from),
+            amount,
+            glitch_address,
+            state,
+            note: None,
+            block_number: log.block_number.map(|number| number.as_u64()),
+            block_time: None,
+            unlock_at,
+            eth_fee,
+            event_version: EVENT_VERSION_V1,
+            labels: Vec::new(),
+        })
+    }
+
+    /// Decodes the log and describes how the result differs from the active
+    /// decoder's, if it does. Recipients the active decoder rejected are
+    /// only compared on being rejected.
+    pub fn divergence(&self, log: &Log, active: &Result<Deposit, String>) -> Option<String> {
+        let shadow = self.decode(log);
+
+        let (active, shadow) = match (active, &shadow) {
+            (Ok(active), Ok(shadow)) => (active, shadow),
+            (Err(_), Err(_)) => return None,
+            (Ok(_), Err(e)) => return Some(format!("only the shadow decoder failed: {e}")),
+            (Err(e), Ok(_)) => return Some(format!("only the active decoder failed: {e}")),
+        };
+
+        let mut differences = Vec::new();
+        if active.tx_eth_hash != shadow.tx_eth_hash {
+            differences.push(format!("tx hash {} vs {}", active.tx_eth_hash, shadow.tx_eth_hash));
+        }
+        if active.from_eth_address != shadow.from_eth_address {
+            differences.push(format!(
+                "sender {} vs {}",
+                active.from_eth_address, shadow.from_eth_address
+            ));
+        }
+        if active.amount != shadow.amount {
+            differences.push(format!("amount {} vs {}", active.amount, shadow.amount));
+        }
+        if active.unlock_at != shadow.unlock_at {
+            differences.push(format!("unlock at {:?} vs {:?}", active.unlock_at, shadow.unlock_at));
+        }
+        if active.eth_fee != shadow.eth_fee {
+            differences.push(format!("ETH fee {:?} vs {:?}", active.eth_fee, shadow.eth_fee));
+        }
+
+        let shadow_accepts = shadow.state == STATE_TO_PROCESS
+            && shadow.glitch_address.len() <= MAX_GLITCH_ADDRESS_LEN
+            && !shadow.glitch_address.chars().any(char::is_control)
+            && !shadow.glitch_address.contains(char::REPLACEMENT_CHARACTER);
+        let active_accepts = active.state == STATE_TO_PROCESS;
+        if active_accepts != shadow_accepts {
+            differences.push(format!(
+                "recipient {} by the active decoder, {} by the shadow",
+                if active_accepts { "accepted" } else { "rejected" },
+                if shadow_accepts { "accepted" } else { "rejected" }
+            ));
+        } else if active_accepts && active.glitch_address != shadow.glitch_address {
+            differences.push(format!(
+                "recipient {} vs {}",
+                active.glitch_address, shadow.glitch_address
+            ));
+        }
+
+        if differences.is_empty() {
+            None
+        } else {
+            Some(differences.join(", "))
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Tripwire on the Glitch signer of a pipeline. Every outgoing transfer of
+//! the signer in a finalized block has to be a payout or a fee settlement
+//! recorded by the bridge, or the canary; any other one means someone else
+//! holds the key, so it is alerted.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use log::{debug, error, info};
+use sp_core::{crypto::{AccountId32, Pair}, sr25519, H256};
+use substrate_api_client::{rpc::WsRpcClient, Api};
+
+use crate::config::{Canary, Network, Notification, SignerActivity};
+use crate::database::DatabaseEngine;
+use crate::glitch_events::{
+    block_events, block_extrinsic_hashes, extrinsic_index, finalized_number, read_account, read_u128,
+};
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+use crate::types::{account_id_to_ss58, parse_glitch_address, to_hex, GlitchApi};
+
+/// Scanner state row of the signer activity of a pipeline.
+pub fn signer_activity_scanner_name(network_name: &str) -> String {
+    format!("signer_activity:{network_name}")
+}
+
+/// An outgoing transfer of the signer, native or of the assets pallet.
+#[derive(Debug, Clone)]
+struct SignerTransfer {
+    block_number: u32,
+    block_hash: String,
+    /// None if the transfer was not made by an extrinsic of the block.
+    extrinsic_hash: Option<String>,
+    to: AccountId32,
+    amount: u128,
+    asset_id: Option<u32>,
+}
+
+impl SignerTransfer {
+    fn describe(&self) -> String {
+        let asset = self.asset_id.map_or(String::new(), |asset_id| format!(" of asset {asset_id}"));
+        format!(
+            "{}{} to {} in block {} ({}), extrinsic {}",
+            self.amount,
+            asset,
+            account_id_to_ss58(&self.to),
+            self.block_number,
+            self.block_hash,
+            self.extrinsic_hash.as_deref().unwrap_or("unknown")
+        )
+    }
+}
+
+/// `Balances.Transfer` and `Assets.Transferred` events from `signer` in a
+/// block, None if the block can't be read.
+fn block_transfers(api: &GlitchApi, signer: &AccountId32, block_number: u32) -> Option<Vec<SignerTransfer>> {
+    let block_hash: H256 = api.get_block_hash(Some(block_number)).ok()??;
+
+    let outgoing: Vec<(Option<u32>, AccountId32, u128, Option<u32>)> = block_events(api, block_hash)
+        .iter()
+        .filter_map(|(phase, event)| {
+            let (asset_id, offset) = match (event.pallet.as_str(), event.variant.as_str()) {
+                // Balances.Transfer { from, to, amount }
+                ("Balances", "Transfer") => (None, 0),
+                // Assets.Transferred { asset_id, from, to, amount }
+                ("Assets", "Transferred") => (Some(u32::from_le_bytes(event.data.get(0..4)?.try_into().ok()?)), 4),
+                _ => return None,
+            };
+            if read_account(&event.data, offset).as_ref() != Some(signer) {
+                return None;
+            }
+            let to = read_account(&event.data, offset + 32)?;
+            let amount = read_u128(&event.data, offset + 64)?;
+            Some((extrinsic_index(phase), to, amount, asset_id))
+        })
+        .collect();
+    if outgoing.is_empty() {
+        return Some(vec![]);
+    }
+
+    let extrinsic_hashes = block_extrinsic_hashes(api, block_hash)?;
+    let transfers = outgoing
+        .into_iter()
+        .map(|(extrinsic_index, to, amount, asset_id)| SignerTransfer {
+            block_number,
+            block_hash: to_hex(block_hash),
+            extrinsic_hash: extrinsic_index.and_then(|index| extrinsic_hashes.get(index as usize).cloned()),
+            to,
+            amount,
+            asset_id,
+        })
+        .collect();
+    Some(transfers)
+}
+
+/// Scans the finalized blocks for outgoing transfers of the signer of the
+/// pipeline and alerts those not matching a bridge record within the grace
+/// period. The scan position is kept in its own `scanner_state` row, never
+/// past a transfer still waiting for its record, so none is missed across
+/// restarts.
+pub async fn monitor_signer_activity(
+    signer_activity: SignerActivity,
+    network_config: Network,
+    glitch_pk: String,
+    canary: Option<Canary>,
+    notifications: Notification,
+    database_engine: Arc<DatabaseEngine>,
+    mut ticker: Ticker,
+) {
+    let name = signer_activity_scanner_name(&network_config.name);
+    let signer: sr25519::Pair = Pair::from_string(&glitch_pk, None).unwrap();
+    let signer_account = AccountId32::from(signer.public());
+    let api: GlitchApi = match Api::new(WsRpcClient::new(&network_config.glitch_node_url())) {
+        Ok(api) => api,
+        Err(e) => {
+            // Restarted by the supervisor.
+            error!("Error connecting with the Glitch node of {}: {:?}", network_config.name, e);
+            return;
+        }
+    };
+
+    let canary = canary.and_then(|canary| {
+        parse_glitch_address(&canary.glitch_address, None)
+            .ok()
+            .map(|public| (AccountId32::from(public), canary.amount))
+    });
+    let mut alert_config = notifications.clone();
+    if let Some(escalate_to) = signer_activity.escalate_to.clone() {
+        alert_config.send_to = escalate_to;
+    }
+    let grace = signer_activity.grace();
+
+    let known = database_engine
+        .exists_network_state(&name, "glitch", &account_id_to_ss58(&signer_account))
+        .await;
+    let mut last_block = if known {
+        database_engine.get_last_block(&name).await
+    } else {
+        finalized_number(&api).unwrap_or(0)
+    };
+    let mut saved_block = None;
+    info!("Signer activity monitor of {} running!", network_config.name);
+
+    let mut pending: Vec<(SignerTransfer, Instant)> = Vec::new();
+    loop {
+        ticker.tick().await;
+
+        let finalized = match finalized_number(&api) {
+            Some(number) => number,
+            None => {
+                // Restarted by the supervisor, on a new connection. Transfers
+                // still waiting are found again from the saved checkpoint.
+                error!("Error reading the finalized head of Glitch for {}", network_config.name);
+                return;
+            }
+        };
+
+        for block_number in last_block + 1..=finalized {
+            match block_transfers(&api, &signer_account, block_number) {
+                Some(transfers) => pending.extend(transfers.into_iter().map(|transfer| (transfer, Instant::now()))),
+                None => {
+                    error!("Error reading Glitch block {}, it will be retried.", block_number);
+                    break;
+                }
+            }
+            last_block = block_number;
+        }
+
+        let mut unexpected = Vec::new();
+        let mut waiting = Vec::new();
+        for (transfer, seen_at) in pending.drain(..) {
+            let is_canary = transfer.asset_id.is_none()
+                && canary.as_ref().map_or(false, |(to, amount)| *to == transfer.to && *amount == transfer.amount);
+            let is_bridge_transfer = if is_canary {
+                Some(true)
+            } else {
+                database_engine
+                    .is_bridge_transfer(transfer.extrinsic_hash.as_deref(), &transfer.block_hash, transfer.amount)
+                    .await
+            };
+            match is_bridge_transfer {
+                Some(true) => debug!("Signer transfer of {} matched: {}", network_config.name, transfer.describe()),
+                Some(false) if seen_at.elapsed() >= grace => unexpected.push(transfer),
+                // Not matched yet, or not looked up: checked again next time.
+                _ => waiting.push((transfer, seen_at)),
+            }
+        }
+        pending = waiting;
+
+        let checkpoint = pending
+            .iter()
+            .map(|(transfer, _)| transfer.block_number - 1)
+            .min()
+            .unwrap_or(last_block);
+        if saved_block != Some(checkpoint) {
+            database_engine.set_last_block(&name, checkpoint).await;
+            saved_block = Some(checkpoint);
+        }
+
+        if unexpected.is_empty() {
+            continue;
+        }
+        metrics::UNEXPECTED_SIGNER_TRANSFERS
+            .with_label_values(&[&network_config.name])
+            .inc_by(unexpected.len() as u64);
+
+        let message = format!(
+            "{} outgoing transfer(s) of the signer {} of {} match no payout, fee settlement or canary of the bridge. The signer key may be compromised:\n{}",
+            unexpected.len(),
+            account_id_to_ss58(&signer_account),
+            network_config.name,
+            unexpected.iter().map(SignerTransfer::describe).collect::<Vec<_>>().join("\n")
+        );
+        error!("{}", message);
+        notify(&alert_config, "Unexpected transfers from the bridge signer!", &message).await;
+    }
+}
@@ -0,0 +1,81 @@
+//! Trail of the logs the scanners ignore. Each one is counted, logged as a
+//! JSON object on the `skipped` log target and, for the pipelines with
+//! `record_skipped_logs`, kept in `skipped_log`, so a deposit that was never
+//! picked up can be told apart from one that never reached the bridge.
+
+use log::info;
+use serde_derive::Serialize;
+use serde_json::json;
+use web3::types::Log;
+
+use crate::database::DatabaseEngine;
+use crate::decoder::Deposit;
+use crate::logger::SKIPPED_LOG_TARGET;
+use crate::metrics;
+use crate::types::to_hex;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SkipReason {
+    /// The log has no topic of a deposit event of the pipeline.
+    UnknownEvent,
+    /// The log has the topic but not the layout of the event.
+    Undecodable,
+    /// The sender is filtered out and its deposits skipped.
+    FilteredSender,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::UnknownEvent => "UNKNOWN_EVENT",
+            SkipReason::Undecodable => "UNDECODABLE",
+            SkipReason::FilteredSender => "FILTERED_SENDER",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedLog {
+    pub scanner_name: String,
+    pub reason: SkipReason,
+    pub tx_eth_hash: Option<String>,
+    pub log_index: Option<u64>,
+    pub block_number: Option<u64>,
+    pub detail: String,
+}
+
+impl SkippedLog {
+    pub fn of_log(scanner_name: &str, log: &Log, reason: SkipReason, detail: String) -> Self {
+        Self {
+            scanner_name: scanner_name.to_string(),
+            reason,
+            tx_eth_hash: log.transaction_hash.map(to_hex),
+            log_index: log.log_index.map(|index| index.as_u64()),
+            block_number: log.block_number.map(|number| number.as_u64()),
+            detail,
+        }
+    }
+
+    pub fn of_deposit(scanner_name: &str, deposit: &Deposit, reason: SkipReason, detail: String) -> Self {
+        Self {
+            scanner_name: scanner_name.to_string(),
+            reason,
+            tx_eth_hash: Some(deposit.tx_eth_hash.clone()),
+            log_index: None,
+            block_number: deposit.block_number,
+            detail,
+        }
+    }
+}
+
+pub async fn record_skipped_log(skipped: SkippedLog, persist: bool, database_engine: &DatabaseEngine) {
+    metrics::SKIPPED_LOGS
+        .with_label_values(&[&skipped.scanner_name, skipped.reason.as_str()])
+        .inc();
+    info!(target: SKIPPED_LOG_TARGET, "{}", json!(skipped));
+
+    if persist {
+        database_engine.insert_skipped_log(&skipped).await;
+    }
+}
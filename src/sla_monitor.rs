@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::config::Notification;
+use crate::database::DatabaseEngine;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+
+/// Periodically looks for deposits whose end-to-end time (detection to
+/// finalization, or to now if still pending) exceeds the configured SLA.
+pub async fn monitor_sla(
+    database_engine: Arc<DatabaseEngine>,
+    smtp_config: Notification,
+    mut ticker: Ticker,
+) {
+    let sla_in_minutes = match smtp_config.sla_in_minutes {
+        Some(minutes) => minutes,
+        None => return,
+    };
+
+    info!("SLA monitor running with a limit of {} minutes!", sla_in_minutes);
+
+    loop {
+        ticker.tick().await;
+
+        let breaching = database_engine.txs_breaching_sla(sla_in_minutes * 60).await;
+        if breaching.is_empty() {
+            continue;
+        }
+
+        let ids = breaching
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let message = format!(
+            "{} deposit(s) exceeded the bridge SLA of {} minutes. Tx ids: {}",
+            breaching.len(),
+            sla_in_minutes,
+            ids
+        );
+        warn!("{}", message);
+
+        notify(&smtp_config, "Bridge SLA breached!", &message).await;
+        database_engine.mark_sla_alerted(&breaching).await;
+    }
+}
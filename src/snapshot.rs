@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+use sp_core::crypto::{Pair, Ss58Codec};
+use sp_core::sr25519;
+
+use crate::database::{DatabaseEngine, NetworkStateRow, PendingTxRow};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StateSnapshot {
+    pub created_at: String,
+    pub network_states: Vec<NetworkStateRow>,
+    pub pending_txs: Vec<PendingTxRow>,
+}
+
+/// Snapshot plus an sr25519 signature of its JSON made with the Glitch
+/// signer key, so a tampered or foreign file is rejected on import.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SignedSnapshot {
+    pub snapshot: StateSnapshot,
+    pub signer: String,
+    pub signature: String,
+}
+
+pub async fn export_state(database_engine: &DatabaseEngine, glitch_pk: &str, output: &Path) {
+    let signer: sr25519::Pair = Pair::from_string(glitch_pk, None).unwrap();
+    let (network_states, pending_txs) = database_engine.export_state().await;
+
+    let snapshot = StateSnapshot {
+        created_at: Utc::now().to_rfc3339(),
+        network_states,
+        pending_txs,
+    };
+
+    let payload = serde_json::to_vec(&snapshot).unwrap();
+    let signature = signer.sign(&payload);
+
+    let signed = SignedSnapshot {
+        snapshot,
+        signer: signer.public().to_ss58check(),
+        signature: hex::encode(signature.0),
+    };
+
+    fs::write(output, serde_json::to_string_pretty(&signed).unwrap())
+        .expect("Error while writing the snapshot file!");
+
+    info!(
+        "Snapshot with {} scanner state(s) and {} pending tx(s) written to {}",
+        signed.snapshot.network_states.len(),
+        signed.snapshot.pending_txs.len(),
+        output.display()
+    );
+}
+
+pub async fn import_state(
+    database_engine: &DatabaseEngine,
+    glitch_pk: &str,
+    input: &Path,
+) -> Result<(), String> {
+    let signer: sr25519::Pair = Pair::from_string(glitch_pk, None).unwrap();
+    let data = fs::read_to_string(input).map_err(|e| format!("Error reading snapshot: {e}"))?;
+    let signed: SignedSnapshot =
+        serde_json::from_str(&data).map_err(|e| format!("Error parsing snapshot: {e}"))?;
+
+    let signature_bytes: [u8; 64] = hex::decode(&signed.signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or("Invalid snapshot signature encoding")?;
+    let signature = sr25519::Signature::from_raw(signature_bytes);
+    let payload = serde_json::to_vec(&signed.snapshot).unwrap();
+
+    if !sr25519::Pair::verify(&signature, &payload, &signer.public()) {
+        return Err(format!(
+            "Snapshot signature is not valid for the configured signer (signed by {})",
+            signed.signer
+        ));
+    }
+
+    database_engine
+        .import_state(&signed.snapshot.network_states, &signed.snapshot.pending_txs)
+        .await
+        .map_err(|e| format!("Error restoring snapshot: {e}"))?;
+
+    info!(
+        "Snapshot from {} restored: {} scanner state(s), {} pending tx(s).",
+        signed.snapshot.created_at,
+        signed.snapshot.network_states.len(),
+        signed.snapshot.pending_txs.len()
+    );
+
+    Ok(())
+}
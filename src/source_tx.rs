@@ -0,0 +1,62 @@
+use log::warn;
+use web3::api::Eth;
+use web3::types::{TransactionId, H256};
+
+use crate::config::Network;
+use crate::database::DatabaseEngine;
+use crate::rpc::ThrottledRpc;
+use crate::types::EthTransport;
+
+/// Records the gas used, effective gas price and ETH value of the source
+/// transactions of just stored deposits, when `track_source_tx` is set. A
+/// transaction that can't be read is left without them.
+pub async fn record_source_txs(
+    eth: &Eth<EthTransport>,
+    rpc: &ThrottledRpc,
+    network_config: &Network,
+    tx_eth_hashes: Vec<String>,
+    database_engine: &DatabaseEngine,
+) {
+    if !network_config.track_source_tx.unwrap_or(false) {
+        return;
+    }
+
+    let mut tx_eth_hashes = tx_eth_hashes;
+    tx_eth_hashes.dedup();
+    for tx_eth_hash in tx_eth_hashes {
+        let hash: H256 = match tx_eth_hash.trim_start_matches("0x").parse() {
+            Ok(hash) => hash,
+            Err(_) => continue,
+        };
+
+        let receipt = rpc.call("eth_getTransactionReceipt", || eth.transaction_receipt(hash)).await;
+        let transaction = rpc
+            .call("eth_getTransactionByHash", || eth.transaction(TransactionId::Hash(hash)))
+            .await;
+        let (receipt, transaction) = match (receipt, transaction) {
+            (Ok(Some(receipt)), Ok(Some(transaction))) => (receipt, transaction),
+            (receipt, transaction) => {
+                warn!(
+                    "Source transaction {} of {} not recorded: {:?} / {:?}",
+                    tx_eth_hash,
+                    network_config.name,
+                    receipt.err(),
+                    transaction.err()
+                );
+                continue;
+            }
+        };
+
+        database_engine
+            .record_tx_source(
+                &tx_eth_hash,
+                receipt.gas_used.map(|gas_used| gas_used.low_u64()),
+                receipt
+                    .effective_gas_price
+                    .or(transaction.gas_price)
+                    .map(|gas_price| gas_price.to_string()),
+                Some(transaction.value.to_string()),
+            )
+            .await;
+    }
+}
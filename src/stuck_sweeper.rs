@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::config::{Config, StuckSweeper, SweepAction, SweepRule};
+use crate::database::{DatabaseEngine, StuckRow};
+use crate::metrics;
+use crate::notifications::notify;
+use crate::reconcile::reconcile_stuck_txs;
+use crate::scheduler::Ticker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Processing,
+    Held,
+    Outbox,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Processing => "processing",
+            Kind::Held => "held",
+            Kind::Outbox => "outbox",
+        }
+    }
+
+    fn describe(&self, row: &StuckRow) -> String {
+        let minutes = row.age_in_secs / 60;
+        match self {
+            Kind::Processing => format!("tx {} of {} PROCESSING for {} minutes", row.id, row.scanner_name, minutes),
+            Kind::Held => format!("tx {} of {} held for review for {} minutes", row.id, row.scanner_name, minutes),
+            Kind::Outbox => format!(
+                "outbox payout {} (tx {}) of {} not submitted for {} minutes",
+                row.id, row.tx_id, row.scanner_name, minutes
+            ),
+        }
+    }
+}
+
+async fn stuck_rows(database_engine: &DatabaseEngine, kind: Kind, rule: &SweepRule) -> Vec<StuckRow> {
+    let after_in_secs = rule.after_in_minutes * 60;
+    match kind {
+        Kind::Processing => database_engine.stuck_processing_txs(after_in_secs).await,
+        Kind::Held => database_engine.held_txs(after_in_secs).await,
+        Kind::Outbox => database_engine.stuck_outbox(after_in_secs).await,
+    }
+}
+
+async fn requeue(config: &Config, database_engine: &DatabaseEngine, kind: Kind, rows: &[StuckRow]) {
+    match kind {
+        Kind::Processing => {
+            for network_config in config.networks.iter() {
+                let ids: Vec<u128> = rows
+                    .iter()
+                    .filter(|row| row.scanner_name == network_config.name)
+                    .map(|row| row.id)
+                    .collect();
+                if !ids.is_empty() {
+                    reconcile_stuck_txs(config, network_config, database_engine, &ids).await;
+                }
+            }
+        }
+        // Refused when the config is loaded, a hold is lifted by an operator.
+        Kind::Held => {}
+        Kind::Outbox => {
+            for row in rows {
+                let reason = "Dropped by the stuck sweeper, the tx is paid again";
+                if database_engine
+                    .drop_outbox_payout(row.id as u64, row.tx_id, row.tx_version, reason)
+                    .await
+                {
+                    info!("Stuck sweeper: dropped {}, its tx is back in the queue.", kind.describe(row));
+                }
+            }
+        }
+    }
+}
+
+/// Finds the rows left in an intermediate state longer than their rule
+/// allows and remediates them. Requeued rows still stuck afterwards are
+/// alerted, so none of them is left without either a retry or an operator
+/// knowing. Each row is alerted once while it stays stuck.
+pub async fn sweep_stuck_rows(
+    stuck_sweeper: StuckSweeper,
+    config: Config,
+    database_engine: Arc<DatabaseEngine>,
+    mut ticker: Ticker,
+) {
+    let rules: Vec<(Kind, SweepRule)> = [
+        (Kind::Processing, stuck_sweeper.processing.clone()),
+        (Kind::Held, stuck_sweeper.held.clone()),
+        (Kind::Outbox, stuck_sweeper.outbox.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(kind, rule)| rule.map(|rule| (kind, rule)))
+    .collect();
+    if rules.is_empty() {
+        return;
+    }
+
+    let mut escalation_config = config.notifications.clone();
+    if let Some(escalate_to) = stuck_sweeper.escalate_to.clone() {
+        escalation_config.send_to = escalate_to;
+    }
+    info!("Stuck sweeper running!");
+
+    let mut alerted: HashSet<(Kind, u128)> = HashSet::new();
+    loop {
+        ticker.tick().await;
+
+        for (kind, rule) in rules.iter() {
+            let mut rows = stuck_rows(&database_engine, *kind, rule).await;
+            if rule.action == SweepAction::Requeue && !rows.is_empty() {
+                requeue(&config, &database_engine, *kind, &rows).await;
+                rows = stuck_rows(&database_engine, *kind, rule).await;
+            }
+            metrics::STUCK_ROWS
+                .with_label_values(&[kind.as_str()])
+                .set(rows.len() as i64);
+
+            alerted.retain(|(alerted_kind, id)| alerted_kind != kind || rows.iter().any(|row| row.id == *id));
+            let new: Vec<String> = rows
+                .iter()
+                .filter(|row| alerted.insert((*kind, row.id)))
+                .map(|row| kind.describe(row))
+                .collect();
+            if new.is_empty() {
+                continue;
+            }
+
+            let message = format!(
+                "{} row(s) stuck longer than {} minutes:\n{}",
+                new.len(),
+                rule.after_in_minutes,
+                new.join("\n")
+            );
+            warn!("{}", message);
+            match rule.action {
+                SweepAction::Escalate => notify(&escalation_config, "Escalation: bridge rows stuck!", &message).await,
+                _ => notify(&config.notifications, "Bridge rows stuck!", &message).await,
+            };
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use sp_core::{crypto::AccountId32, hashing::blake2_256, H256};
+use substrate_api_client::{rpc::WsRpcClient, Api};
+use web3::types::U256;
+
+use crate::compliance::{screen_deposits, ComplianceScreening};
+use crate::config::{Network, SubstrateSource};
+use crate::database::{DatabaseEngine, ScannerErrorKind};
+use crate::decoder::{Deposit, SanityChecks, EVENT_VERSION_V1, STATE_TO_PROCESS};
+use crate::glitch_events::{block_events, read_account, read_u128};
+use crate::scheduler::Ticker;
+use crate::skipped_logs::record_skipped_log;
+use crate::types::{account_id_from_ss58, account_id_to_ss58, to_hex, GlitchApi};
+
+/// Deposits made on a substrate source chain: transfers to the custody
+/// account, bridged to the same account of the sender on Glitch. Only
+/// finalized blocks are scanned, so no confirmations are needed.
+pub struct SubstrateScanner {
+    network_config: Network,
+    source: SubstrateSource,
+    sanity_checks: SanityChecks,
+    compliance: Option<Arc<ComplianceScreening>>,
+    database_engine: Arc<DatabaseEngine>,
+    ticker: Ticker,
+}
+
+impl SubstrateScanner {
+    pub fn new(
+        network_config: Network,
+        source: SubstrateSource,
+        sanity_checks: SanityChecks,
+        compliance: Option<Arc<ComplianceScreening>>,
+        database_engine: Arc<DatabaseEngine>,
+        ticker: Ticker,
+    ) -> Self {
+        Self {
+            network_config,
+            source,
+            sanity_checks,
+            compliance,
+            database_engine,
+            ticker,
+        }
+    }
+
+    fn finalized_number(api: &GlitchApi) -> Option<u32> {
+        let hash = api.get_finalized_head().ok()??;
+        api.get_storage_value("System", "Number", Some(hash)).ok()?
+    }
+
+    /// Transfer events `(from, to, amount)` to the custody account in a block.
+    fn block_deposits(&self, api: &GlitchApi, block_number: u32, custody: &AccountId32) -> Option<Vec<Deposit>> {
+        let block_hash: H256 = api.get_block_hash(Some(block_number)).ok()??;
+        let pallet = self.source.pallet.as_deref().unwrap_or("Balances");
+        let variant = self.source.event.as_deref().unwrap_or("Transfer");
+        let block_time = api
+            .get_storage_value::<u64>("Timestamp", "Now", Some(block_hash))
+            .ok()
+            .flatten()
+            .map(|millis| (millis / 1000) as i64);
+
+        let deposits = block_events(api, block_hash)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, event))| event.pallet == pallet && event.variant == variant)
+            .filter_map(|(index, (_, event))| {
+                let from = read_account(&event.data, 0)?;
+                let to = read_account(&event.data, 32)?;
+                let amount = read_u128(&event.data, 64)?;
+                if &to != custody {
+                    return None;
+                }
+
+                // The tx_eth_hash column identifies the source event.
+                let mut id = block_hash.as_bytes().to_vec();
+                id.extend_from_slice(&(index as u32).to_le_bytes());
+
+                Some(Deposit {
+                    tx_eth_hash: to_hex(H256(blake2_256(&id))),
+                    from_eth_address: account_id_to_ss58(&from),
+                    amount: U256::from(amount),
+                    glitch_address: account_id_to_ss58(&from),
+                    state: STATE_TO_PROCESS,
+                    note: None,
+                    block_number: Some(block_number as u64),
+                    block_time,
+                    unlock_at: None,
+                    eth_fee: None,
+                    event_version: EVENT_VERSION_V1,
+                    labels: Vec::new(),
+                })
+            })
+            .collect();
+
+        Some(deposits)
+    }
+
+    pub async fn run(mut self) {
+        info!(
+            "Running substrate block scanner to network {}",
+            self.network_config.network
+        );
+
+        let name = self.network_config.name.clone();
+        let record_skipped_logs = self.network_config.record_skipped_logs.unwrap_or(false);
+        let custody = account_id_from_ss58(&self.network_config.monitor_address)
+            .expect("The monitor address of a substrate source must be SS58!");
+        let api: GlitchApi = Api::new(WsRpcClient::new(&self.network_config.eth_node_url()))
+            .expect("Error connecting with the substrate source node!");
+
+        let known = self
+            .database_engine
+            .exists_network_state(&name, &self.network_config.network, &self.network_config.monitor_address)
+            .await;
+
+        let mut last_block = if known {
+            self.database_engine.get_last_block(&name).await
+        } else {
+            // New scanners start at the current finalized block.
+            Self::finalized_number(&api).unwrap_or(0)
+        };
+
+        loop {
+            self.ticker.tick().await;
+
+            let finalized = match Self::finalized_number(&api) {
+                Some(number) => number,
+                None => {
+                    error!("Error reading the finalized head of {}", self.network_config.network);
+                    self.database_engine
+                        .insert_scanner_error(&name, ScannerErrorKind::Rpc, None, "Error reading the finalized head")
+                        .await;
+                    continue;
+                }
+            };
+
+            for block_number in last_block + 1..=finalized {
+                let deposits = match self.block_deposits(&api, block_number, &custody) {
+                    Some(deposits) => deposits,
+                    None => {
+                        error!(
+                            "Error reading block {} of {}, it will be retried.",
+                            block_number, self.network_config.network
+                        );
+                        self.database_engine
+                            .insert_scanner_error(
+                                &name,
+                                ScannerErrorKind::Rpc,
+                                Some(block_number as u64),
+                                "Error reading the block events",
+                            )
+                            .await;
+                        break;
+                    }
+                };
+
+                if !deposits.is_empty() {
+                    info!("{} deposits found in block {}", deposits.len(), block_number);
+                }
+                let mut screened = Vec::with_capacity(deposits.len());
+                for deposit in deposits {
+                    match self.sanity_checks.screen(deposit) {
+                        Ok(deposit) => screened.push(deposit),
+                        Err(skipped) => record_skipped_log(skipped, record_skipped_logs, &self.database_engine).await,
+                    }
+                }
+                let mut deposits = screen_deposits(
+                    screened,
+                    &self.network_config,
+                    self.compliance.as_deref(),
+                    &self.database_engine,
+                )
+                .await;
+                // This task is the only one inserting the deposits of the
+                // pipeline, so the cap reads every earlier one.
+                self.sanity_checks.apply_daily_cap(&self.database_engine, &mut deposits).await;
+
+                if !self
+                    .database_engine
+                    .update_block_and_insert_txs(name.clone(), block_number, deposits)
+                    .await
+                {
+                    error!("The deposits of block {} of {} were not stored, it will be retried.", block_number, name);
+                    break;
+                }
+                last_block = block_number;
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+
+use futures::FutureExt;
+use log::{error, info, warn};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Duration, Instant};
+use tracing::{info_span, Instrument};
+
+use crate::metrics;
+
+/// First delay before restarting a task with `RestartPolicy::Backoff`,
+/// doubled on every consecutive failure up to `MAX_BACKOFF`.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// A task running this long before ending starts again from `MIN_BACKOFF`.
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+/// How long the tasks of a stage get to stop before the next stage is.
+const STAGE_GRACE: Duration = Duration::from_secs(30);
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// What to do when a task ends or panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Restart right away (after `MIN_BACKOFF`).
+    Always,
+    /// Restart after an exponentially growing delay.
+    Backoff,
+    /// Leave it stopped.
+    Never,
+}
+
+/// Shutdown order: deposits stop coming in first, then payouts and fees,
+/// and the monitors and API last so the shutdown can be followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Ingest,
+    Payout,
+    Fees,
+    Service,
+}
+
+const STAGES: [Stage; 4] = [Stage::Ingest, Stage::Payout, Stage::Fees, Stage::Service];
+
+/// Resolves once the supervisor tells the task to stop. Tasks built with
+/// `Supervisor::supervise_draining` await it where stopping is safe.
+pub async fn stopped(stop: &mut watch::Receiver<bool>) {
+    while !*stop.borrow() {
+        if stop.changed().await.is_err() {
+            // The supervisor is gone, nothing will tell the task to stop.
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Runs the task built by `factory` until told to stop, restarting it as the
+/// policy says. `glitch_bridge_task_up` tells whether it is running. A
+/// draining task is told to stop and waited for; any other is dropped.
+async fn supervise<F, Fut>(
+    name: String,
+    policy: RestartPolicy,
+    draining: bool,
+    mut stop: watch::Receiver<bool>,
+    mut factory: F,
+) where
+    F: FnMut(watch::Receiver<bool>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        metrics::TASK_UP.with_label_values(&[&name]).set(1);
+        let started = Instant::now();
+        let task = AssertUnwindSafe(factory(stop.clone())).catch_unwind();
+        tokio::pin!(task);
+        let result = tokio::select! {
+            result = &mut task => result.map_err(panic_message),
+            _ = stop.changed() => {
+                if draining {
+                    info!("Task {} stopping", name);
+                    let _ = task.await;
+                }
+                metrics::TASK_UP.with_label_values(&[&name]).set(0);
+                info!("Task {} stopped", name);
+                return;
+            }
+        };
+        metrics::TASK_UP.with_label_values(&[&name]).set(0);
+
+        match result {
+            Ok(()) => warn!("Task {} finished", name),
+            Err(message) => error!("Task {} panicked: {}", name, message),
+        }
+
+        let delay = match policy {
+            RestartPolicy::Never => return,
+            RestartPolicy::Always => MIN_BACKOFF,
+            RestartPolicy::Backoff => {
+                if started.elapsed() >= HEALTHY_RUN {
+                    backoff = MIN_BACKOFF;
+                }
+                let delay = backoff;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                delay
+            }
+        };
+
+        warn!("Restarting task {} in {:?}", name, delay);
+        metrics::TASK_RESTARTS.with_label_values(&[&name]).inc();
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = stop.changed() => return,
+        }
+    }
+}
+
+/// Resolves on Ctrl-C or, on unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("Could not listen for SIGTERM!");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Owns the long running tasks of the bridge so that a task ending, or
+/// panicking, is logged with its name instead of going unnoticed, restarts
+/// them according to their policy and stops them stage by stage on shutdown.
+pub struct Supervisor {
+    tasks: JoinSet<Stage>,
+    running: HashMap<Stage, usize>,
+    stop: HashMap<Stage, watch::Sender<bool>>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            tasks: JoinSet::new(),
+            running: HashMap::new(),
+            stop: STAGES.iter().map(|stage| (*stage, watch::channel(false).0)).collect(),
+        }
+    }
+
+    /// Spawns a task that is not restarted, stopped with the services.
+    pub fn spawn<F>(&mut self, name: impl Into<String>, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut task = Some(task);
+        self.supervise(name, Stage::Service, RestartPolicy::Never, move || {
+            task.take().expect("A task without restarts is only started once!")
+        });
+    }
+
+    /// Spawns the task built by `factory`, built again on every restart,
+    /// inside a span named after it so everything it logs through `tracing`
+    /// carries the task name.
+    pub fn supervise<F, Fut>(&mut self, name: impl Into<String>, stage: Stage, policy: RestartPolicy, mut factory: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_supervised(name.into(), stage, policy, false, move |_| factory());
+    }
+
+    /// Like `supervise`, for tasks that must not be dropped halfway, e.g.
+    /// between submitting a payout and recording it. The task gets the stop
+    /// signal, to wait on with `stopped`, and is left to finish within the
+    /// grace period of its stage.
+    pub fn supervise_draining<F, Fut>(&mut self, name: impl Into<String>, stage: Stage, policy: RestartPolicy, factory: F)
+    where
+        F: FnMut(watch::Receiver<bool>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_supervised(name.into(), stage, policy, true, factory);
+    }
+
+    fn spawn_supervised<F, Fut>(&mut self, name: String, stage: Stage, policy: RestartPolicy, draining: bool, factory: F)
+    where
+        F: FnMut(watch::Receiver<bool>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let span = info_span!("task", name = %name);
+        let stop = self.stop[&stage].subscribe();
+
+        *self.running.entry(stage).or_default() += 1;
+        self.tasks.spawn(
+            async move {
+                supervise(name, policy, draining, stop, factory).await;
+                stage
+            }
+            .instrument(span),
+        );
+    }
+
+    fn joined(&mut self, joined: Result<Stage, tokio::task::JoinError>) {
+        match joined {
+            Ok(stage) => *self.running.entry(stage).or_default() -= 1,
+            Err(e) => error!("Task failed: {}", e),
+        }
+    }
+
+    /// Waits for a shutdown signal, then stops the tasks stage by stage.
+    /// Tasks left running after the grace period are aborted.
+    pub async fn run(mut self) {
+        let signal = shutdown_signal();
+        tokio::pin!(signal);
+
+        loop {
+            tokio::select! {
+                Some(joined) = self.tasks.join_next() => self.joined(joined),
+                _ = &mut signal => break,
+            }
+        }
+
+        info!("Shutting down the bridge...");
+        for stage in STAGES {
+            let _ = self.stop[&stage].send(true);
+
+            let grace = sleep(STAGE_GRACE);
+            tokio::pin!(grace);
+            while self.running.get(&stage).copied().unwrap_or(0) > 0 {
+                tokio::select! {
+                    joined = self.tasks.join_next() => match joined {
+                        Some(joined) => self.joined(joined),
+                        None => break,
+                    },
+                    _ = &mut grace => {
+                        warn!("{:?} tasks did not stop within {:?}, aborting them.", stage, STAGE_GRACE);
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.tasks.abort_all();
+        info!("Bridge stopped.");
+    }
+}
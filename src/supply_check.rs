@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use web3::api::{Eth, Namespace};
+use web3::types::{BlockId, Bytes, CallRequest, H160, U256};
+
+use crate::config::{Network, Notification, SupplyCheck};
+use crate::database::DatabaseEngine;
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::Ticker;
+use crate::types::{eth_transport, u256_to_u128, EthTransport};
+
+/// Selector of the ERC20 `balanceOf(address)` function.
+const BALANCE_OF_SELECTOR: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Token balance of `holder` on the network, at `block` or the latest one.
+pub(crate) async fn token_balance(
+    eth: &Eth<EthTransport>,
+    network: &Network,
+    token_address: &str,
+    holder_address: &str,
+    block: Option<BlockId>,
+) -> Result<u128, String> {
+    let token: H160 = token_address
+        .parse()
+        .map_err(|e| format!("Invalid token address {token_address}: {e:?}"))?;
+    let holder: H160 = holder_address
+        .parse()
+        .map_err(|e| format!("Invalid holder address {holder_address}: {e:?}"))?;
+
+    let mut data = BALANCE_OF_SELECTOR.to_vec();
+    data.extend_from_slice(&[0_u8; 12]);
+    data.extend_from_slice(holder.as_bytes());
+
+    let request = CallRequest {
+        to: Some(token),
+        data: Some(Bytes(data)),
+        ..Default::default()
+    };
+
+    let result = eth
+        .call(request, block)
+        .await
+        .map_err(|e| format!("Error calling balanceOf on {}: {e:?}", network.network))?;
+
+    u256_to_u128(U256::from_big_endian(&result.0))
+        .ok_or_else(|| format!("Balance on {} does not fit in u128", network.network))
+}
+
+/// Whether the network has the token and custody addresses the check reads.
+pub fn is_checked(network: &Network) -> bool {
+    network.token_address.is_some() && network.custody_address.is_some()
+}
+
+async fn locked_balance(network: &Network, token_address: &str, custody_address: &str) -> Result<u128, String> {
+    let transport = eth_transport(&network.eth_node_url(), network.eth_headers())
+        .await
+        .map_err(|e| format!("Error connecting with {} network: {e:?}", network.network))?;
+    let eth = Eth::new(transport);
+
+    token_balance(&eth, network, token_address, custody_address, None).await
+}
+
+/// Tokens locked in the ETH custody accounts against the total bridged to
+/// Glitch, paid out or owed to the deposits not paid yet.
+pub struct SupplyState {
+    pub locked: u128,
+    pub minted: u128,
+    pub delta: i128,
+    pub within_tolerance: bool,
+}
+
+/// Reads both sides of the invariant, over the networks with a token and a
+/// custody address. Fails if any locked balance can't be read, since a
+/// partial sum would look like a drift, or if no network can be checked.
+pub async fn evaluate_supply(
+    networks: &[Network],
+    database_engine: &DatabaseEngine,
+    tolerance_in_wei: u128,
+) -> Result<SupplyState, String> {
+    let mut locked = 0_u128;
+    let mut checked = Vec::new();
+    let mut errors = Vec::new();
+    for network in networks {
+        let (token_address, custody_address) = match (&network.token_address, &network.custody_address) {
+            (Some(token), Some(custody)) => (token, custody),
+            _ => continue,
+        };
+        checked.push(network.name.as_str());
+        match locked_balance(network, token_address, custody_address).await {
+            Ok(balance) => locked += balance,
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if checked.is_empty() {
+        return Err("No network has a token_address and a custody_address".to_string());
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+
+    let minted = database_engine.get_total_bridged(&checked).await;
+    let delta = locked as i128 - minted as i128;
+
+    Ok(SupplyState {
+        locked,
+        minted,
+        delta,
+        within_tolerance: delta.unsigned_abs() <= tolerance_in_wei,
+    })
+}
+
+/// Compares the tokens locked in the ETH custody accounts against the total
+/// bridged to Glitch and alerts when they drift apart.
+pub async fn check_supply_invariant(
+    supply_check: SupplyCheck,
+    networks: Vec<Network>,
+    database_engine: Arc<DatabaseEngine>,
+    smtp_config: Notification,
+    mut ticker: Ticker,
+) {
+    info!("Supply invariant check running now!");
+
+    let tolerance_in_wei = (supply_check.tolerance * (10_f64).powf(18.0)) as u128;
+    loop {
+        ticker.tick().await;
+
+        let SupplyState {
+            locked,
+            minted,
+            delta,
+            within_tolerance,
+        } = match evaluate_supply(&networks, &database_engine, tolerance_in_wei).await {
+            Ok(state) => state,
+            Err(e) => {
+                error!("{e}");
+                warn!("Skipping the supply invariant check, locked balance is incomplete.");
+                continue;
+            }
+        };
+
+        metrics::SUPPLY_DELTA.set(delta as f64 / (10_f64).powf(18.0));
+        database_engine
+            .insert_supply_check(locked, minted, delta, within_tolerance)
+            .await;
+
+        if within_tolerance {
+            info!("Supply invariant holds (locked {}, bridged {}).", locked, minted);
+        } else {
+            let message = format!(
+                "Bridge supply invariant drifted beyond tolerance: locked on Ethereum {}, bridged to Glitch {}, delta {}.",
+                locked, minted, delta
+            );
+            warn!("{}", message);
+            notify(&smtp_config, "Bridge supply invariant broken!", &message).await;
+        }
+    }
+}
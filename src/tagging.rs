@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use log::info;
+use web3::types::U256;
+
+use crate::config::{Config, Network, TagAction};
+use crate::decoder::{Deposit, STATE_SUSPICIOUS, STATE_TO_PROCESS};
+
+/// Separator of the labels stored in `tx.labels`.
+const LABEL_SEPARATOR: char = ',';
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    label: String,
+    min_amount: Option<U256>,
+    max_amount: Option<U256>,
+    senders: Option<Vec<String>>,
+    action: Option<TagAction>,
+}
+
+impl CompiledRule {
+    fn matches(&self, deposit: &Deposit) -> bool {
+        self.min_amount.map_or(true, |min| deposit.amount >= min)
+            && self.max_amount.map_or(true, |max| deposit.amount <= max)
+            && self.senders.as_ref().map_or(true, |senders| {
+                senders
+                    .iter()
+                    .any(|sender| sender.eq_ignore_ascii_case(&deposit.from_eth_address))
+            })
+    }
+}
+
+fn parse_amount(amount: &Option<String>, label: &str) -> Option<U256> {
+    amount.as_ref().map(|amount| {
+        U256::from_dec_str(amount).unwrap_or_else(|_| panic!("Invalid amount {amount} in tag rule {label}!"))
+    })
+}
+
+/// The `tag_rules` of the config that apply to a pipeline, matched against
+/// every deposit it scans.
+#[derive(Debug, Clone, Default)]
+pub struct DepositTagger {
+    rules: Vec<CompiledRule>,
+}
+
+impl DepositTagger {
+    pub fn new(config: &Config, network_config: &Network) -> Self {
+        let token = network_config.token_address.as_deref();
+        let rules = config
+            .tag_rules
+            .iter()
+            .flatten()
+            .filter(|rule| {
+                rule.tokens.as_ref().map_or(true, |tokens| {
+                    token.map_or(false, |token| tokens.iter().any(|listed| listed.eq_ignore_ascii_case(token)))
+                })
+            })
+            .map(|rule| CompiledRule {
+                label: rule.label.clone(),
+                min_amount: parse_amount(&rule.min_amount, &rule.label),
+                max_amount: parse_amount(&rule.max_amount, &rule.label),
+                senders: rule.senders.clone(),
+                action: rule.action,
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Adds the labels of every matching rule. A `manual_review` label holds
+    /// a deposit that would otherwise be paid as SUSPICIOUS.
+    pub fn tag(&self, deposit: &mut Deposit) {
+        let matched: Vec<(String, Option<TagAction>)> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(deposit))
+            .map(|rule| (rule.label.clone(), rule.action))
+            .collect();
+
+        for (label, action) in matched {
+            if action == Some(TagAction::ManualReview) && deposit.state == STATE_TO_PROCESS {
+                info!("Deposit {} tagged {}, held for review.", deposit.tx_eth_hash, label);
+                deposit.state = STATE_SUSPICIOUS;
+                deposit.note = Some(format!("Tagged {}, manual review required", label));
+            }
+
+            if !deposit.labels.contains(&label) {
+                deposit.labels.push(label);
+            }
+        }
+    }
+}
+
+/// What each label means to the payer, from the `tag_rules` of the config.
+#[derive(Debug, Clone, Default)]
+pub struct LabelActions(HashMap<String, TagAction>);
+
+impl LabelActions {
+    pub fn new(config: &Config) -> Self {
+        Self(
+            config
+                .tag_rules
+                .iter()
+                .flatten()
+                .filter_map(|rule| rule.action.map(|action| (rule.label.clone(), action)))
+                .collect(),
+        )
+    }
+
+    pub fn any(&self, labels: &[String], action: TagAction) -> bool {
+        labels.iter().any(|label| self.0.get(label) == Some(&action))
+    }
+}
+
+pub fn join_labels(labels: &[String]) -> Option<String> {
+    (!labels.is_empty()).then(|| labels.join(&LABEL_SEPARATOR.to_string()))
+}
+
+pub fn split_labels(labels: Option<String>) -> Vec<String> {
+    labels
+        .map(|labels| {
+            labels
+                .split(LABEL_SEPARATOR)
+                .filter(|label| !label.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
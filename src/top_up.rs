@@ -0,0 +1,158 @@
+use codec::Compact;
+use log::{error, info, warn};
+use serde_json::json;
+use sp_core::{crypto::Pair, sr25519};
+use substrate_api_client::{
+    compose_call, compose_extrinsic, rpc::WsRpcClient, AccountId, Api, GenericAddress,
+    PlainTipExtrinsicParams, UncheckedExtrinsicV4, XtStatus,
+};
+
+use crate::config::{Notification, TopUp};
+use crate::database::DatabaseEngine;
+use crate::notifications::notify;
+use crate::types::{account_id_from_ss58, account_id_to_ss58, check_genesis_hash, to_hex, GlitchApi};
+
+const GLCH: f64 = 1_000_000_000_000_000_000.0;
+
+/// Transfers `amount` from the treasury to the hot wallet through a
+/// `Proxy.proxy` call signed by a delegate of the treasury.
+fn request_via_proxy(
+    glitch_node: &str,
+    glitch_genesis_hash: Option<&str>,
+    proxy_private_key: &str,
+    treasury_address: &str,
+    hot_wallet: &AccountId,
+    amount: u128,
+) -> Result<String, String> {
+    let treasury = account_id_from_ss58(treasury_address)?;
+    let delegate: sr25519::Pair = Pair::from_string(proxy_private_key, None)
+        .map_err(|e| format!("Invalid top-up proxy key: {e:?}"))?;
+
+    let api: GlitchApi = Api::<_, _, PlainTipExtrinsicParams>::new(WsRpcClient::new(glitch_node))
+        .map(|api| api.set_signer(delegate))
+        .map_err(|e| format!("Error connecting with the Glitch node: {e:?}"))?;
+    check_genesis_hash(&api, glitch_genesis_hash)?;
+
+    let transfer = compose_call!(
+        api.metadata,
+        "Balances",
+        "transfer",
+        GenericAddress::Id(hot_wallet.clone()),
+        Compact(amount)
+    );
+    // force_proxy_type: None
+    let xt: UncheckedExtrinsicV4<_, _> = compose_extrinsic!(
+        api,
+        "Proxy",
+        "proxy",
+        GenericAddress::Id(treasury),
+        None::<u8>,
+        transfer
+    );
+
+    match api.send_extrinsic(xt.hex_encode(), XtStatus::Finalized) {
+        Ok(Some(hash)) => Ok(to_hex(hash)),
+        Ok(None) => Err("The top-up extrinsic was not finalized".to_string()),
+        Err(e) => Err(format!("Top-up extrinsic error: {e:?}")),
+    }
+}
+
+/// Opens a ticket for the treasury operators instead of moving funds.
+async fn request_via_webhook(
+    webhook_url: &str,
+    hot_wallet: &AccountId,
+    balance: u128,
+    amount: u128,
+) -> Result<String, String> {
+    let body = json!({
+        "hot_wallet": account_id_to_ss58(hot_wallet),
+        "balance": balance.to_string(),
+        "requested_amount": amount.to_string(),
+    });
+
+    let response = reqwest::Client::new()
+        .post(webhook_url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Top-up webhook error: {e}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Top-up webhook answered {}", response.status()));
+    }
+
+    Ok(response.text().await.unwrap_or_default())
+}
+
+/// Requests a top-up of the hot wallet when its balance is below the
+/// threshold. Requests are recorded in the store, and a new one is only made
+/// once the cooldown of the previous request is over, even across restarts.
+pub async fn top_up_if_needed(
+    top_up: &TopUp,
+    glitch_node: &str,
+    glitch_genesis_hash: Option<&str>,
+    hot_wallet: &AccountId,
+    balance: u128,
+    smtp_config: &Notification,
+    database_engine: &DatabaseEngine,
+) {
+    if balance as f64 > top_up.threshold * GLCH {
+        return;
+    }
+
+    let hot_wallet_address = account_id_to_ss58(hot_wallet);
+    if database_engine
+        .recent_top_up_request(&hot_wallet_address, 60 * top_up.cooldown_in_minutes)
+        .await
+    {
+        return;
+    }
+
+    let amount = (top_up.amount * GLCH) as u128;
+    info!(
+        "Requesting a top-up of {} GLCH for {} (balance {})",
+        top_up.amount, hot_wallet_address, balance
+    );
+
+    let (method, result) = match (&top_up.proxy_private_key, &top_up.treasury_address, &top_up.webhook_url) {
+        (Some(proxy_private_key), Some(treasury_address), _) => (
+            "proxy",
+            request_via_proxy(glitch_node, glitch_genesis_hash, proxy_private_key, treasury_address, hot_wallet, amount),
+        ),
+        (_, _, Some(webhook_url)) => (
+            "webhook",
+            request_via_webhook(webhook_url, hot_wallet, balance, amount).await,
+        ),
+        _ => {
+            warn!("Top-up is enabled but neither a treasury proxy nor a webhook is configured.");
+            return;
+        }
+    };
+
+    let message = match &result {
+        Ok(reference) => format!(
+            "Top-up of {} GLCH for {} requested via {}: {}",
+            top_up.amount, hot_wallet_address, method, reference
+        ),
+        Err(e) => format!(
+            "Top-up of {} GLCH for {} via {} failed: {}",
+            top_up.amount, hot_wallet_address, method, e
+        ),
+    };
+
+    match &result {
+        Ok(_) => info!("{}", message),
+        Err(_) => error!("{}", message),
+    }
+
+    database_engine
+        .insert_top_up_request(
+            &hot_wallet_address,
+            amount,
+            method,
+            result.as_ref().ok().map(String::as_str),
+            result.as_ref().err().map(String::as_str),
+        )
+        .await;
+    notify(smtp_config, "Bridge hot wallet top-up requested", &message).await;
+}
@@ -0,0 +1,156 @@
+//! Conversions between the web3 (Ethereum) and sp_core (Glitch) types used
+//! across the bridge. Everything that can overflow returns an `Option`.
+
+use std::collections::BTreeMap;
+use std::fmt::LowerHex;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Url;
+
+use sp_core::crypto::{AccountId32, Ss58AddressFormat, Ss58Codec};
+use substrate_api_client::{rpc::WsRpcClient, Api, BaseExtrinsicParams, PlainTip};
+use sp_core::sr25519::{self, Public};
+use web3::error::TransportError;
+use web3::transports::{Either, Http, WebSocket};
+use web3::types::{H160, H256, U256};
+
+pub type GlitchApi = Api<sr25519::Pair, WsRpcClient, BaseExtrinsicParams<PlainTip>>;
+
+/// Connection to an Ethereum node, over a websocket or over HTTP.
+pub type EthTransport = Either<WebSocket, Http>;
+
+/// Connects to the Ethereum node at `url`: over HTTP for `http(s)` URLs,
+/// which can't subscribe to heads but take `headers`, and over a websocket
+/// otherwise.
+pub async fn eth_transport(url: &str, headers: Option<&BTreeMap<String, String>>) -> web3::Result<EthTransport> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return WebSocket::new(url).await.map(Either::Left);
+    }
+    let headers = match headers {
+        Some(headers) => headers,
+        None => return Http::new(url).map(Either::Right),
+    };
+
+    let invalid = |e: String| web3::Error::Transport(TransportError::Message(e));
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(format!("Invalid header {name}: {e}")))?;
+        let value = HeaderValue::from_str(value).map_err(|e| invalid(format!("Invalid value of header {name}: {e}")))?;
+        header_map.insert(name, value);
+    }
+    let client = reqwest::Client::builder()
+        .default_headers(header_map)
+        .build()
+        .map_err(|e| invalid(e.to_string()))?;
+    let url = Url::parse(url).map_err(|e| invalid(format!("Invalid URL {url}: {e}")))?;
+    Ok(Either::Right(Http::with_client(client, url)))
+}
+
+/// Checks that the node behind `api` is on the Glitch chain the config
+/// expects, before anything is signed for it.
+pub fn check_genesis_hash(api: &GlitchApi, expected: Option<&str>) -> Result<(), String> {
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+
+    let genesis_hash = to_hex(api.genesis_hash);
+    if !genesis_hash.eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "The Glitch node has genesis hash {genesis_hash}, {expected} expected"
+        ));
+    }
+    Ok(())
+}
+
+/// `0x`-prefixed lowercase hex, as stored for hashes and addresses.
+pub fn to_hex<T: LowerHex>(value: T) -> String {
+    format!("{:#x}", value)
+}
+
+/// Ethereum address held in the low 20 bytes of an indexed topic.
+pub fn h256_to_address(h: H256) -> String {
+    to_hex(H160::from(h))
+}
+
+pub fn u256_to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.low_u128())
+    }
+}
+
+pub fn u256_to_usize(value: U256) -> Option<usize> {
+    if value > U256::from(usize::MAX) {
+        None
+    } else {
+        Some(value.low_u64() as usize)
+    }
+}
+
+pub fn account_id_from_ss58(address: &str) -> Result<AccountId32, String> {
+    AccountId32::from_ss58check(address).map_err(|e| format!("Invalid SS58 address {address}: {e:?}"))
+}
+
+pub fn account_id_to_ss58(account_id: &AccountId32) -> String {
+    account_id.to_ss58check()
+}
+
+/// Prefix of generic Substrate SS58 addresses (`5...`), which wallets use
+/// when they don't know the network.
+pub const GENERIC_SS58_PREFIX: u16 = 42;
+
+/// Checks the network prefix of an SS58 address against the Glitch one.
+/// Generic Substrate addresses are accepted, they are normalized to the
+/// Glitch prefix when stored; addresses of any other network are rejected.
+pub fn check_ss58_prefix(address: &str, address_prefix: u16, ss58_prefix: Option<u16>) -> Result<(), String> {
+    match ss58_prefix {
+        Some(prefix) if address_prefix != prefix && address_prefix != GENERIC_SS58_PREFIX => Err(format!(
+            "SS58 address {address} has network prefix {address_prefix}, expected {prefix}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Parses a Glitch recipient given either as SS58 or as a `0x`-prefixed
+/// 32 byte hex public key. When `ss58_prefix` is set, SS58 addresses of other
+/// networks are rejected (see `check_ss58_prefix`).
+pub fn parse_glitch_address(address: &str, ss58_prefix: Option<u16>) -> Result<Public, String> {
+    let address = address.trim();
+
+    if let Some(digits) = address.strip_prefix("0x") {
+        let bytes = hex::decode(digits).map_err(|e| format!("Invalid hex public key {address}: {e}"))?;
+        let raw: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| format!("Hex public key {address} is not 32 bytes long"))?;
+        return Ok(Public::from_raw(raw));
+    }
+
+    let (public, format) = Public::from_ss58check_with_version(address)
+        .map_err(|e| format!("Invalid SS58 address {address}: {e:?}"))?;
+    check_ss58_prefix(address, format.prefix(), ss58_prefix)?;
+    Ok(public)
+}
+
+/// Network prefix of an SS58 address, None for hex public keys and
+/// anything unparseable.
+pub fn ss58_prefix_of(address: &str) -> Option<u16> {
+    Public::from_ss58check_with_version(address.trim())
+        .ok()
+        .map(|(_, format)| format.prefix())
+}
+
+/// Parses a Glitch recipient and gives it back in the SS58 form stored by
+/// the bridge, with the Glitch prefix when known.
+pub fn normalize_glitch_address(address: &str, ss58_prefix: Option<u16>) -> Result<String, String> {
+    parse_glitch_address(address, ss58_prefix).map(|public| public_to_ss58(&public, ss58_prefix))
+}
+
+/// SS58 form of a Glitch public key, with the network prefix when known.
+pub fn public_to_ss58(public: &Public, ss58_prefix: Option<u16>) -> String {
+    match ss58_prefix {
+        Some(prefix) => public.to_ss58check_with_version(Ss58AddressFormat::custom(prefix)),
+        None => public.to_ss58check(),
+    }
+}
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use web3::api::{Eth, Namespace};
+use web3::signing::keccak256;
+use web3::types::{BlockNumber, FilterBuilder, H160, H256, U256, U64};
+
+use crate::config::{Network, Notification, ProxyWatch};
+use crate::metrics;
+use crate::notifications::notify;
+use crate::scheduler::{Scheduler, Ticker};
+use crate::types::{eth_transport, to_hex, EthTransport};
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+const IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+const UPGRADED_EVENT_SIGNATURE: &str = "Upgraded(address)";
+
+async fn current_implementation(eth: &Eth<EthTransport>, proxy: H160) -> Result<H160, String> {
+    let slot = U256::from_str_radix(IMPLEMENTATION_SLOT.trim_start_matches("0x"), 16).unwrap();
+    eth.storage(proxy, slot, None)
+        .await
+        .map(H160::from)
+        .map_err(|e| format!("Error reading the implementation slot: {e:?}"))
+}
+
+/// Alerts about the new implementation and, if configured, pauses the payouts
+/// of the network until an operator resumes the `payer:{name}` job.
+async fn on_upgrade(
+    network_config: &Network,
+    proxy_watch: &ProxyWatch,
+    implementation: H160,
+    upgrade_tx: Option<H256>,
+    smtp_config: &Notification,
+    scheduler: &Scheduler,
+) {
+    metrics::CONTRACT_UPGRADES
+        .with_label_values(&[&network_config.name])
+        .inc();
+
+    let payer_job = format!("payer:{}", network_config.name);
+    let paused = proxy_watch.pause_on_upgrade
+        && match scheduler.job(&payer_job) {
+            Some(job) => {
+                job.pause();
+                true
+            }
+            None => false,
+        };
+
+    let message = format!(
+        "The proxy {} on {} now points to implementation {}{}. {}",
+        proxy_watch.address,
+        network_config.network,
+        to_hex(implementation),
+        upgrade_tx
+            .map(|hash| format!(" (upgrade tx {})", to_hex(hash)))
+            .unwrap_or_default(),
+        if paused {
+            format!("Payouts are paused until the event layout is confirmed compatible and the {payer_job} job is resumed.")
+        } else {
+            "Payouts continue; confirm the event layout is still compatible.".to_string()
+        }
+    );
+    warn!("{}", message);
+    notify(smtp_config, "Bridge contract upgraded!", &message).await;
+}
+
+/// Watches the upgradeable proxy of the monitored contract through its
+/// `Upgraded` events, and checks the EIP-1967 slot at startup to catch
+/// upgrades that happened while the bridge was down.
+pub async fn monitor_upgrades(
+    network_config: Network,
+    smtp_config: Notification,
+    scheduler: Arc<Scheduler>,
+    mut ticker: Ticker,
+) {
+    let proxy_watch = match network_config.proxy.clone() {
+        Some(proxy_watch) => proxy_watch,
+        None => return,
+    };
+    let proxy: H160 = proxy_watch.address.parse().expect("Invalid proxy address!");
+    let topic = H256::from(keccak256(UPGRADED_EVENT_SIGNATURE.as_bytes()));
+
+    let mut known_implementation: Option<H160> = proxy_watch
+        .expected_implementation
+        .as_ref()
+        .map(|address| address.parse().expect("Invalid expected implementation address!"));
+    let mut last_checked_block: Option<U64> = None;
+
+    loop {
+        ticker.tick().await;
+
+        let transport = match eth_transport(&network_config.eth_node_url(), network_config.eth_headers()).await {
+            Ok(transport) => transport,
+            Err(e) => {
+                error!("Error connecting with {} network: {:?}", network_config.network, e);
+                continue;
+            }
+        };
+        let eth = Eth::new(transport);
+
+        let latest = match eth.block_number().await {
+            Ok(block) => block,
+            Err(e) => {
+                error!("Error reading the block number of {}: {:?}", network_config.network, e);
+                continue;
+            }
+        };
+
+        let from_block = match last_checked_block {
+            Some(block) => block + 1,
+            None => {
+                match current_implementation(&eth, proxy).await {
+                    Ok(implementation) => {
+                        if known_implementation.map_or(false, |known| known != implementation) {
+                            on_upgrade(
+                                &network_config,
+                                &proxy_watch,
+                                implementation,
+                                None,
+                                &smtp_config,
+                                &scheduler,
+                            )
+                            .await;
+                        }
+                        info!(
+                            "Proxy {} on {} points to implementation {}",
+                            proxy_watch.address,
+                            network_config.network,
+                            to_hex(implementation)
+                        );
+                        known_implementation = Some(implementation);
+                    }
+                    Err(e) => {
+                        error!("{} on {}", e, network_config.network);
+                        continue;
+                    }
+                }
+                last_checked_block = Some(latest);
+                continue;
+            }
+        };
+
+        if from_block > latest {
+            continue;
+        }
+
+        let filter = FilterBuilder::default()
+            .address(vec![proxy])
+            .from_block(BlockNumber::Number(from_block))
+            .to_block(BlockNumber::Number(latest))
+            .topics(Some(vec![topic]), None, None, None)
+            .build();
+
+        match eth.logs(filter).await {
+            Ok(logs) => {
+                for log in logs {
+                    let implementation = match log.topics.get(1) {
+                        Some(topic) => H160::from(*topic),
+                        None => continue,
+                    };
+                    if known_implementation == Some(implementation) {
+                        continue;
+                    }
+
+                    on_upgrade(
+                        &network_config,
+                        &proxy_watch,
+                        implementation,
+                        log.transaction_hash,
+                        &smtp_config,
+                        &scheduler,
+                    )
+                    .await;
+                    known_implementation = Some(implementation);
+                }
+                last_checked_block = Some(latest);
+            }
+            Err(e) => error!(
+                "Error reading Upgraded events on {}: {:?}",
+                network_config.network, e
+            ),
+        }
+    }
+}
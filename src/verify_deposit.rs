@@ -0,0 +1,175 @@
+//! Re-derives a deposit from the chain to settle disputes about its
+//! recipient or amount: the logs of the transaction are fetched again,
+//! decoded and screened by the current decoder, and compared field by field
+//! with the rows stored for it.
+
+use log::{error, info, warn};
+use web3::api::{Eth, Namespace};
+use web3::types::H256;
+
+use crate::config::{Config, Network};
+use crate::database::{DatabaseEngine, StoredDeposit};
+use crate::decoder::{Deposit, DepositEvent, SanityChecks};
+use crate::types::eth_transport;
+
+/// `(field, stored, on chain)` of a deposit.
+fn fields(stored: Option<&StoredDeposit>, decoded: Option<&Deposit>) -> Vec<(&'static str, Option<String>, Option<String>)> {
+    vec![
+        (
+            "from_eth_address",
+            stored.and_then(|stored| stored.from_eth_address.clone()),
+            decoded.map(|deposit| deposit.from_eth_address.clone()),
+        ),
+        (
+            "to_glitch_address",
+            stored.and_then(|stored| stored.glitch_address.clone()),
+            decoded.map(|deposit| deposit.glitch_address.clone()),
+        ),
+        (
+            "amount",
+            stored.and_then(|stored| stored.amount.clone()),
+            decoded.map(|deposit| deposit.amount.to_string()),
+        ),
+        (
+            "eth_fee",
+            stored.and_then(|stored| stored.eth_fee.clone()),
+            decoded.and_then(|deposit| deposit.eth_fee.map(|fee| fee.to_string())),
+        ),
+        (
+            "unlock_at",
+            stored.and_then(|stored| stored.unlock_at.map(|unlock_at| unlock_at.to_string())),
+            decoded.and_then(|deposit| deposit.unlock_at.map(|unlock_at| unlock_at.to_string())),
+        ),
+        (
+            "eth_block_number",
+            stored.and_then(|stored| stored.block_number.map(|number| number.to_string())),
+            decoded.and_then(|deposit| deposit.block_number.map(|number| number.to_string())),
+        ),
+        (
+            "event_version",
+            stored.and_then(|stored| stored.event_version.map(|version| version.to_string())),
+            decoded.map(|deposit| deposit.event_version.to_string()),
+        ),
+    ]
+}
+
+/// Deposits the current decoder derives from the logs of the transaction on
+/// the network. Deposits dropped by the sender filter are left out.
+async fn chain_deposits(config: &Config, network: &Network, hash: H256) -> Result<Vec<Deposit>, String> {
+    let transport = eth_transport(&network.eth_node_url(), network.eth_headers())
+        .await
+        .map_err(|e| format!("Error connecting with {} network: {e:?}", network.network))?;
+    let eth = Eth::new(transport);
+
+    let receipt = eth
+        .transaction_receipt(hash)
+        .await
+        .map_err(|e| format!("Error reading the receipt on {}: {e:?}", network.network))?
+        .ok_or_else(|| format!("No transaction {hash:?} on {}", network.network))?;
+
+    let events = DepositEvent::for_network(network);
+    let sanity_checks = SanityChecks::new(config, network);
+    let mut deposits = Vec::new();
+    for log in receipt.logs.iter() {
+        if let Some(event) = events.iter().find(|event| event.matches(log)) {
+            match event.decode(log) {
+                Ok(deposit) => match sanity_checks.screen(deposit) {
+                    Ok(deposit) => deposits.push(deposit),
+                    Err(skipped) => warn!("Log {:?} is skipped: {}", log.log_index, skipped.detail),
+                },
+                Err(e) => warn!("Log {:?} no longer decodes: {}", log.log_index, e),
+            }
+        }
+    }
+    Ok(deposits)
+}
+
+/// Prints the stored rows of the deposit next to what the chain shows now.
+/// Returns whether they all match.
+pub async fn verify_deposit(
+    config: &Config,
+    database_engine: &DatabaseEngine,
+    tx_eth_hash: &str,
+    network: Option<String>,
+) -> bool {
+    let hash: H256 = match tx_eth_hash.trim_start_matches("0x").parse() {
+        Ok(hash) => hash,
+        Err(_) => {
+            error!("{} is not a transaction hash", tx_eth_hash);
+            return false;
+        }
+    };
+    let stored = database_engine.stored_deposits(tx_eth_hash).await;
+    if stored.is_empty() {
+        warn!("No deposit stored for {}", tx_eth_hash);
+    }
+
+    // The pipeline the rows were stored by, or the one given.
+    let names: Vec<String> = match network {
+        Some(network) => vec![network],
+        None => {
+            let mut names: Vec<String> = stored.iter().filter_map(|row| row.scanner_name.clone()).collect();
+            names.dedup();
+            names
+        }
+    };
+    if names.is_empty() {
+        error!("The pipeline of {} is unknown, give it with --network", tx_eth_hash);
+        return false;
+    }
+
+    let mut matches = true;
+    for name in names {
+        let network = match config.networks.iter().find(|network| network.name == name) {
+            Some(network) if network.substrate_source.is_none() => network,
+            Some(_) => {
+                warn!("{} is a substrate source, its deposits can't be verified by hash.", name);
+                continue;
+            }
+            None => {
+                error!("No network {} in the config", name);
+                matches = false;
+                continue;
+            }
+        };
+
+        let decoded = match chain_deposits(config, network, hash).await {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                error!("{}", e);
+                matches = false;
+                continue;
+            }
+        };
+        let rows: Vec<&StoredDeposit> = stored
+            .iter()
+            .filter(|row| row.scanner_name.as_deref() == Some(name.as_str()))
+            .collect();
+
+        for index in 0..rows.len().max(decoded.len()) {
+            let (row, deposit) = (rows.get(index).copied(), decoded.get(index));
+            info!(
+                "{} deposit {} of {}: stored as {} (decoded by {}), the current decoder would store it as {}",
+                name,
+                index + 1,
+                tx_eth_hash,
+                row.map_or("nothing".to_string(), |row| format!("tx {} {}", row.id, row.state)),
+                row.and_then(|row| row.decoder_version.clone()).unwrap_or_else(|| "unknown".to_string()),
+                deposit.map_or("nothing", |deposit| deposit.state)
+            );
+            for (field, stored_value, chain_value) in fields(row, deposit) {
+                let same = stored_value == chain_value;
+                matches &= same;
+                info!(
+                    "  {:<18} {:<50} {:<50} {}",
+                    field,
+                    stored_value.as_deref().unwrap_or("-"),
+                    chain_value.as_deref().unwrap_or("-"),
+                    if same { "ok" } else { "MISMATCH" }
+                );
+            }
+        }
+    }
+
+    matches
+}
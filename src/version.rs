@@ -0,0 +1,4 @@
+/// Version of the running build, `semver+git hash`. Stamped on every tx by
+/// the decoder and by the payout, so a replay after a bugfix can target only
+/// the rows handled by the affected builds.
+pub const BUILD_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("GIT_HASH"));
@@ -0,0 +1,91 @@
+//! Delivers the lifecycle events of the txs to a webhook in `sequence`
+//! order. The last delivered sequence is kept in `event_cursor`, so a
+//! restart resumes where delivery stopped; an event is only passed once the
+//! endpoint accepts it, and each payload carries the sequence of the event
+//! before it so consumers can tell a skipped number from a lost one.
+
+use std::sync::Arc;
+
+use log::{info, warn};
+use serde_json::json;
+use tokio::time::Duration;
+
+use crate::config::Webhooks;
+use crate::database::{DatabaseEngine, TxEvent};
+use crate::scheduler::Ticker;
+
+const CURSOR_NAME: &str = "webhooks";
+const BATCH_SIZE: u32 = 100;
+const REQUEST_TIMEOUT_IN_SECS: u64 = 10;
+
+async fn deliver(
+    client: &reqwest::Client,
+    webhooks: &Webhooks,
+    auth_token: Option<&str>,
+    event: &TxEvent,
+    previous_sequence: u64,
+) -> Result<(), String> {
+    let body = json!({
+        "sequence": event.sequence,
+        "previous_sequence": previous_sequence,
+        "type": "tx.state_changed",
+        "event": event,
+    });
+
+    let mut request = client
+        .post(&webhooks.url)
+        .header("Idempotency-Key", event.sequence.to_string())
+        .json(&body);
+    if let Some(auth_token) = auth_token {
+        request = request.bearer_auth(auth_token);
+    }
+    let response = request.send().await.map_err(|e| format!("Webhook request error: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!("Webhook answered {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Posts every new tx event to the webhook, in order. A missing sequence
+/// number holds the delivery back until the event after it is older than
+/// the gap timeout: it may belong to a write not committed yet, and is only
+/// skipped once it's clearly rolled back.
+pub async fn deliver_webhooks(webhooks: Webhooks, database_engine: Arc<DatabaseEngine>, mut ticker: Ticker) {
+    let auth_token = webhooks.auth_token_env.as_ref().map(|token_env| {
+        std::env::var(token_env).unwrap_or_else(|_| panic!("The webhook token {token_env} is not set!"))
+    });
+    let gap_timeout = webhooks.gap_timeout_in_secs.unwrap_or(30) as i64;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_IN_SECS))
+        .build()
+        .unwrap();
+
+    let mut cursor = database_engine.event_cursor(CURSOR_NAME).await;
+    info!("Delivering tx events to the webhook after sequence {}", cursor);
+
+    loop {
+        ticker.tick().await;
+
+        for event in database_engine.tx_events(cursor, BATCH_SIZE).await {
+            if event.sequence != cursor + 1 {
+                if event.age_in_secs < gap_timeout {
+                    break;
+                }
+                // A write committed since the batch was read may have filled
+                // the gap, the next tick delivers it.
+                let first = database_engine.tx_events(cursor, 1).await;
+                if first.first().map(|first| first.sequence) != Some(event.sequence) {
+                    break;
+                }
+                warn!("Tx events {} to {} never showed up, skipped.", cursor + 1, event.sequence - 1);
+            }
+
+            if let Err(e) = deliver(&client, &webhooks, auth_token.as_deref(), &event, cursor).await {
+                warn!("Tx event {} not delivered, retrying: {}", event.sequence, e);
+                break;
+            }
+            cursor = event.sequence;
+            database_engine.advance_event_cursor(CURSOR_NAME, cursor).await;
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use glitch_bridge::clock::{Clock, ManualClock};
+use glitch_bridge::config::RpcRetry;
+use glitch_bridge::glitch::is_time_to_pay_fee_v2;
+use glitch_bridge::rpc::ThrottledRpc;
+use std::time::Duration;
+use web3::error::TransportError;
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn rate_limited() -> web3::Error {
+    web3::Error::Transport(TransportError::Code(429))
+}
+
+#[test]
+fn manual_clock_only_moves_when_told() {
+    let clock = ManualClock::new(start());
+    assert_eq!(clock.now(), start());
+
+    clock.advance(Duration::from_secs(90));
+    assert_eq!(clock.now(), start() + chrono::Duration::seconds(90));
+
+    clock.set(start());
+    assert_eq!(clock.now(), start());
+}
+
+/// A day is 86 400 seconds: the fee is not due 86 000 seconds after the
+/// last payment, as it was before the clock was injected.
+#[test]
+fn fee_interval_is_whole_days() {
+    let last_payment = start();
+    let clock = ManualClock::new(last_payment + chrono::Duration::seconds(86_000));
+    assert!(!is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+
+    clock.set(last_payment + chrono::Duration::seconds(86_399));
+    assert!(!is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+
+    clock.set(last_payment + chrono::Duration::seconds(86_400));
+    assert!(is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+
+    clock.set(last_payment + chrono::Duration::seconds(3 * 86_400 - 1));
+    assert!(!is_time_to_pay_fee_v2(&clock, Some(last_payment), 3));
+}
+
+#[tokio::test]
+async fn rate_limited_calls_back_off_on_the_clock() {
+    let clock = Arc::new(ManualClock::new(start()));
+    let policy = RpcRetry {
+        retries_per_minute: 3,
+        base_delay_in_ms: 1_000,
+        max_delay_in_ms: 3_000,
+    };
+    let rpc = ThrottledRpc::with_clock("test".to_string(), Some(policy), Duration::from_secs(5), clock.clone());
+    let calls = AtomicU32::new(0);
+
+    let result = rpc
+        .call("eth_blockNumber", || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match call {
+                    0 | 1 => Err(rate_limited()),
+                    _ => Ok(call),
+                }
+            }
+        })
+        .await;
+
+    assert_eq!(result.unwrap(), 2);
+    // Delays of 1 s then 2 s, each between half and all of it.
+    let slept = (clock.now() - start()).num_milliseconds();
+    assert!((1_500..=3_000).contains(&slept), "slept {slept} ms");
+}
+
+#[tokio::test]
+async fn retry_budget_refills_every_minute() {
+    let clock = Arc::new(ManualClock::new(start()));
+    let policy = RpcRetry {
+        retries_per_minute: 2,
+        base_delay_in_ms: 10,
+        max_delay_in_ms: 10,
+    };
+    let rpc = ThrottledRpc::with_clock("test".to_string(), Some(policy), Duration::from_secs(5), clock.clone());
+    let calls = AtomicU32::new(0);
+    let always_limited = || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        async { Err::<(), _>(rate_limited()) }
+    };
+
+    assert!(rpc.call("eth_getLogs", always_limited).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // The budget is spent for this minute: no retry.
+    assert!(rpc.call("eth_getLogs", always_limited).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    clock.advance(Duration::from_secs(60));
+    assert!(rpc.call("eth_getLogs", always_limited).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 7);
+}
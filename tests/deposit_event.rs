@@ -0,0 +1,132 @@
+use glitch_bridge::decoder::{decode_deposit, DepositEvent, EventLayout, EVENT_VERSION_V2};
+use serde_json::json;
+use web3::ethabi::{encode, Token};
+use web3::signing::keccak256;
+use web3::types::{Log, H160, H256, U256};
+
+const RECIPIENT: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const CONTRACT: &str = "0x4b9a9b7ea8eab5ef2a1a3bd5f5d2e3b4a8e1c0f1";
+const AMOUNT: u64 = 1_000_000_000_000_000_000;
+const UNLOCK_AT: u64 = 1_900_000_000;
+const ETH_FEE: u64 = 5_000_000_000_000_000;
+
+/// Log of the event with the given non-indexed inputs, as returned by
+/// eth_getLogs.
+fn event_log(event: &DepositEvent, inputs: &[Token]) -> Log {
+    serde_json::from_value(json!({
+        "address": CONTRACT,
+        "topics": [
+            event.topic,
+            "0x000000000000000000000000b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0"
+        ],
+        "data": format!("0x{}", hex::encode(encode(inputs))),
+        "blockNumber": "0x10",
+        "transactionHash": "0x7a1c9e3f5b2d4a6c8e0f1b3d5a7c9e1f3b5d7a9c1e3f5b7d9a1c3e5f7b9d1a3c",
+        "logIndex": "0x0",
+        "removed": false
+    }))
+    .unwrap()
+}
+
+fn event(signature: &str) -> DepositEvent {
+    DepositEvent::new(EVENT_VERSION_V2, CONTRACT.parse::<H160>().unwrap(), signature)
+}
+
+fn uint(value: u64) -> Token {
+    Token::Uint(U256::from(value))
+}
+
+#[test]
+fn decodes_the_original_event() {
+    let event = event("TransferToGlitch(address,string,uint256)");
+    let log = event_log(&event, &[Token::String(RECIPIENT.to_string()), uint(AMOUNT)]);
+
+    let deposit = event.decode(&log).unwrap();
+    assert_eq!(deposit.glitch_address, RECIPIENT);
+    assert_eq!(deposit.amount, U256::from(AMOUNT));
+    assert_eq!(deposit.unlock_at, None);
+    assert_eq!(deposit.eth_fee, None);
+    assert_eq!(deposit.event_version, EVENT_VERSION_V2);
+}
+
+#[test]
+fn decodes_the_event_with_an_unlock_time() {
+    let event = event("TransferToGlitch(address,string,uint256,uint256)");
+    let log = event_log(&event, &[Token::String(RECIPIENT.to_string()), uint(AMOUNT), uint(UNLOCK_AT)]);
+
+    let deposit = event.decode(&log).unwrap();
+    assert_eq!(deposit.amount, U256::from(AMOUNT));
+    assert_eq!(deposit.unlock_at, Some(UNLOCK_AT));
+    assert_eq!(deposit.eth_fee, None);
+}
+
+#[test]
+fn decodes_the_event_with_an_unlock_time_and_a_fee() {
+    let event = event("TransferToGlitch(address,string,uint256,uint256,uint256)");
+    let log = event_log(
+        &event,
+        &[Token::String(RECIPIENT.to_string()), uint(AMOUNT), uint(UNLOCK_AT), uint(ETH_FEE)],
+    );
+
+    let deposit = event.decode(&log).unwrap();
+    assert_eq!(deposit.amount, U256::from(AMOUNT));
+    assert_eq!(deposit.unlock_at, Some(UNLOCK_AT));
+    assert_eq!(deposit.eth_fee, Some(U256::from(ETH_FEE)));
+}
+
+/// Same types as the event with an unlock time: only the names tell the
+/// fourth input is the fee.
+#[test]
+fn decodes_the_event_with_only_a_fee_by_its_names() {
+    let event = event("TransferToGlitch(address indexed sender, string recipient, uint256 amount, uint256 ethFee)");
+    assert_eq!(event.signature, "TransferToGlitch(address,string,uint256,uint256)");
+    assert_eq!(
+        event.topic,
+        H256::from(keccak256(b"TransferToGlitch(address,string,uint256,uint256)"))
+    );
+    let log = event_log(&event, &[Token::String(RECIPIENT.to_string()), uint(AMOUNT), uint(ETH_FEE)]);
+
+    let deposit = event.decode(&log).unwrap();
+    assert_eq!(deposit.amount, U256::from(AMOUNT));
+    assert_eq!(deposit.unlock_at, None);
+    assert_eq!(deposit.eth_fee, Some(U256::from(ETH_FEE)));
+}
+
+#[test]
+fn decodes_named_inputs_in_any_order() {
+    let event = event("Deposit(address,uint256 ethFee,string,uint256 amount)");
+    let log = event_log(&event, &[uint(ETH_FEE), Token::String(RECIPIENT.to_string()), uint(AMOUNT)]);
+
+    let deposit = event.decode(&log).unwrap();
+    assert_eq!(deposit.glitch_address, RECIPIENT);
+    assert_eq!(deposit.amount, U256::from(AMOUNT));
+    assert_eq!(deposit.eth_fee, Some(U256::from(ETH_FEE)));
+}
+
+#[test]
+fn rejects_logs_of_another_layout() {
+    let with_unlock = event("TransferToGlitch(address,string,uint256,uint256)");
+    let original = event("TransferToGlitch(address,string,uint256)");
+    let log = event_log(&original, &[Token::String(RECIPIENT.to_string()), uint(AMOUNT)]);
+
+    assert!(with_unlock.decode(&log).is_err());
+}
+
+#[test]
+fn infers_the_layout_without_an_event() {
+    let event = event("TransferToGlitch(address,string,uint256,uint256,uint256)");
+    let log = event_log(
+        &event,
+        &[Token::String(RECIPIENT.to_string()), uint(AMOUNT), uint(UNLOCK_AT), uint(ETH_FEE)],
+    );
+
+    assert_eq!(decode_deposit(&log).unwrap().eth_fee, Some(U256::from(ETH_FEE)));
+}
+
+#[test]
+fn refuses_signatures_that_are_not_deposit_events() {
+    assert!(EventLayout::parse("TransferToGlitch(address,uint256)").is_err());
+    assert!(EventLayout::parse("TransferToGlitch(address,string)").is_err());
+    assert!(EventLayout::parse("TransferToGlitch(address,string,uint256,uint256 memo)").is_err());
+    assert!(EventLayout::parse("TransferToGlitch(address,string,uint256,uint256,uint256,uint256)").is_err());
+}
@@ -0,0 +1,73 @@
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use glitch_bridge::clock::ManualClock;
+use glitch_bridge::config::parse_fee_interval;
+use glitch_bridge::glitch::{is_fee_due, is_time_to_pay_fee_v2};
+use std::time::Duration;
+
+fn utc(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+#[test]
+fn fee_is_due_without_previous_payment() {
+    let clock = ManualClock::new(utc("2023-01-01T00:00:00Z"));
+
+    assert!(is_time_to_pay_fee_v2(&clock, None, 1));
+}
+
+/// Central Europe moves from UTC+1 to UTC+2 at 2023-03-26T01:00Z, a local day
+/// of 23 hours. The interval must still be 24 hours.
+#[test]
+fn interval_spans_spring_forward() {
+    let last_payment = utc("2023-03-25T12:00:00+01:00");
+    let clock = ManualClock::new(utc("2023-03-26T12:59:59+02:00"));
+    assert!(!is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+
+    clock.set(utc("2023-03-26T13:00:00+02:00"));
+    assert!(is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+}
+
+/// Central Europe moves back from UTC+2 to UTC+1 at 2023-10-29T01:00Z, a local
+/// day of 25 hours.
+#[test]
+fn interval_spans_fall_back() {
+    let last_payment = utc("2023-10-28T12:00:00+02:00");
+    let clock = ManualClock::new(utc("2023-10-29T10:59:59+01:00"));
+    assert!(!is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+
+    clock.set(utc("2023-10-29T11:00:00+01:00"));
+    assert!(is_time_to_pay_fee_v2(&clock, Some(last_payment), 1));
+}
+
+/// The same instant written in different offsets gives the same answer.
+#[test]
+fn offset_of_the_host_does_not_matter() {
+    let last_payment = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+    let now = FixedOffset::west_opt(5 * 3600)
+        .unwrap()
+        .with_ymd_and_hms(2023, 6, 7, 19, 0, 0)
+        .unwrap();
+    let clock = ManualClock::new(now.with_timezone(&Utc));
+
+    assert!(is_time_to_pay_fee_v2(&clock, Some(last_payment), 7));
+    assert!(!is_time_to_pay_fee_v2(&clock, Some(last_payment), 8));
+}
+
+#[test]
+fn sub_day_interval() {
+    let last_payment = utc("2023-06-01T00:00:00Z");
+    let interval = parse_fee_interval("12h").unwrap();
+    let clock = ManualClock::new(utc("2023-06-01T11:59:59Z"));
+    assert!(!is_fee_due(&clock, Some(last_payment), interval));
+
+    clock.set(utc("2023-06-01T12:00:00Z"));
+    assert!(is_fee_due(&clock, Some(last_payment), interval));
+}
+
+#[test]
+fn fee_intervals_are_validated() {
+    assert_eq!(parse_fee_interval("3d"), Ok(Duration::from_secs(3 * 86_400)));
+    assert_eq!(parse_fee_interval("1d 6h"), Ok(Duration::from_secs(30 * 3600)));
+    assert!(parse_fee_interval("30s").is_err());
+    assert!(parse_fee_interval("daily").is_err());
+}
@@ -0,0 +1,43 @@
+use glitch_bridge::migrate::statements;
+
+#[test]
+fn splits_on_the_delimiter_outside_quotes_and_comments() {
+    let sql = "-- A comment; with a semicolon\n\
+        INSERT INTO note VALUES ('a;b', \"it''s; fine\", 'c\\';d'); # trailing; comment\n\
+        /* a block;\n comment */ UPDATE note SET text = `x;y`;";
+
+    assert_eq!(
+        statements(sql),
+        vec![
+            "INSERT INTO note VALUES ('a;b', \"it''s; fine\", 'c\\';d')",
+            "UPDATE note SET text = `x;y`",
+        ]
+    );
+}
+
+#[test]
+fn keeps_trigger_bodies_written_with_another_delimiter() {
+    let sql = "DELIMITER //\n\
+        CREATE TRIGGER note_on_insert AFTER INSERT ON note FOR EACH ROW BEGIN\n\
+        \tINSERT INTO log VALUES (NEW.id);\n\
+        \tINSERT INTO log VALUES (NEW.id + 1);\n\
+        END//\n\
+        DELIMITER ;\n\
+        SELECT 1;";
+
+    let statements = statements(sql);
+    assert_eq!(statements.len(), 2);
+    assert!(statements[0].starts_with("CREATE TRIGGER note_on_insert"));
+    assert!(statements[0].ends_with("END"));
+    assert_eq!(statements[0].matches(';').count(), 2);
+    assert_eq!(statements[1], "SELECT 1");
+}
+
+#[test]
+fn splits_the_shipped_trigger_migration() {
+    let statements = statements(include_str!("../db/add_tx_event.sql"));
+
+    assert_eq!(statements.len(), 4);
+    assert!(statements[2].starts_with("CREATE TRIGGER tx_event_on_insert"));
+    assert!(statements[3].ends_with("WHERE NOT (OLD.state <=> NEW.state)"));
+}
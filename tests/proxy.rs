@@ -0,0 +1,66 @@
+use glitch_bridge::config::Proxy;
+use glitch_bridge::proxy::{bypassed, configure, route, with_host, without_token};
+
+fn proxy(no_proxy: &[&str]) -> Proxy {
+    Proxy {
+        url: "http://127.0.0.1:3128".to_string(),
+        no_proxy: Some(no_proxy.iter().map(|host| host.to_string()).collect()),
+    }
+}
+
+#[test]
+fn with_host_rewrites_only_the_host_of_the_head() {
+    let head = b"POST / HTTP/1.1\r\nhost: 127.0.0.1:41234\r\nContent-Length: 2\r\n\r\n{}";
+
+    assert_eq!(
+        with_host(head, "node.example.com"),
+        b"POST / HTTP/1.1\r\nHost: node.example.com\r\nContent-Length: 2\r\n\r\n{}".to_vec()
+    );
+}
+
+#[test]
+fn without_token_strips_the_prefix_of_the_tunnel() {
+    let token = "0123abcd";
+
+    assert_eq!(
+        without_token(b"GET /0123abcd/ws?key=1 HTTP/1.1\r\nHost: a\r\n\r\n", token).unwrap(),
+        b"GET /ws?key=1 HTTP/1.1\r\nHost: a\r\n\r\n".to_vec()
+    );
+    assert_eq!(
+        without_token(b"POST /0123abcd HTTP/1.1\r\n\r\n", token).unwrap(),
+        b"POST / HTTP/1.1\r\n\r\n".to_vec()
+    );
+    assert!(without_token(b"GET /ws HTTP/1.1\r\n\r\n", token).is_err());
+    assert!(without_token(b"GET /0123abcdef/ws HTTP/1.1\r\n\r\n", token).is_err());
+}
+
+#[test]
+fn bypasses_the_no_proxy_hosts_and_their_subdomains() {
+    let internal = proxy(&["internal.example", ".Corp.example"]);
+
+    assert!(bypassed(&internal, "internal.example"));
+    assert!(bypassed(&internal, "node.internal.example"));
+    assert!(bypassed(&internal, "node.corp.example"));
+    assert!(!bypassed(&internal, "notinternal.example"));
+    assert!(!bypassed(&internal, "node.example.com"));
+    assert!(bypassed(&proxy(&["*"]), "node.example.com"));
+}
+
+#[test]
+fn routes_proxied_nodes_through_a_local_tunnel() {
+    configure(Some(proxy(&["direct.example"])));
+
+    assert_eq!(route("wss://direct.example/ws"), "wss://direct.example/ws");
+    assert_eq!(route("not a url"), "not a url");
+
+    let routed = route("wss://node.example.com/ws");
+    assert!(routed.starts_with("ws://127.0.0.1:"), "{routed}");
+    assert!(routed.ends_with("/ws"), "{routed}");
+    // ws://127.0.0.1:port/<token>/ws
+    assert_eq!(routed.split('/').nth(3).map_or(0, str::len), 32, "{routed}");
+    // The same node keeps its tunnel, another scheme gets its own.
+    assert_eq!(route("wss://node.example.com/ws"), routed);
+    let http = route("https://node.example.com/");
+    assert!(http.starts_with("http://127.0.0.1:"), "{http}");
+    assert_ne!(http.split('/').nth(2), routed.split('/').nth(2));
+}
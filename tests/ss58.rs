@@ -0,0 +1,101 @@
+use glitch_bridge::config::FeeDestination;
+use glitch_bridge::types::{normalize_glitch_address, parse_glitch_address, public_to_ss58, ss58_prefix_of};
+use sp_core::crypto::AccountId32;
+use sp_core::sr25519::Public;
+
+/// Prefix used as the Glitch one in these tests.
+const GLITCH_PREFIX: u16 = 7;
+
+/// Alice's dev account in every format used below.
+const ALICE_HEX: &str = "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+const ALICE_GENERIC: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const ALICE_GLITCH: &str = "nJrsrH8dov9Z36kTDpabgCZT8CbK1FbmjJvfU6qbMTG4g4c";
+const ALICE_POLKADOT: &str = "15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5";
+const ALICE_KUSAMA: &str = "HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F";
+/// Two byte prefix encoding.
+const ALICE_PREFIX_1000: &str = "vji5kpxBaPKwct6PAdHiJUPCU1hqBEAPaLMF59sXAjn4NeEaJ";
+
+fn alice() -> Public {
+    let mut raw = [0u8; 32];
+    raw.copy_from_slice(&hex::decode(&ALICE_HEX[2..]).unwrap());
+    Public::from_raw(raw)
+}
+
+#[test]
+fn encodes_known_vectors() {
+    assert_eq!(public_to_ss58(&alice(), None), ALICE_GENERIC);
+    assert_eq!(public_to_ss58(&alice(), Some(GLITCH_PREFIX)), ALICE_GLITCH);
+    assert_eq!(public_to_ss58(&alice(), Some(0)), ALICE_POLKADOT);
+    assert_eq!(public_to_ss58(&alice(), Some(2)), ALICE_KUSAMA);
+    assert_eq!(public_to_ss58(&alice(), Some(1000)), ALICE_PREFIX_1000);
+}
+
+#[test]
+fn decodes_known_vectors() {
+    for address in [ALICE_HEX, ALICE_GENERIC, ALICE_GLITCH, ALICE_POLKADOT, ALICE_KUSAMA, ALICE_PREFIX_1000] {
+        assert_eq!(parse_glitch_address(address, None), Ok(alice()), "{address}");
+    }
+
+    assert_eq!(ss58_prefix_of(ALICE_GENERIC), Some(42));
+    assert_eq!(ss58_prefix_of(ALICE_GLITCH), Some(GLITCH_PREFIX));
+    assert_eq!(ss58_prefix_of(ALICE_PREFIX_1000), Some(1000));
+    assert_eq!(ss58_prefix_of(ALICE_HEX), None);
+}
+
+#[test]
+fn round_trips_every_prefix() {
+    for prefix in [0, 2, GLITCH_PREFIX, 42, 63, 64, 1000, 16383] {
+        let address = public_to_ss58(&alice(), Some(prefix));
+        assert_eq!(ss58_prefix_of(&address), Some(prefix));
+        assert_eq!(parse_glitch_address(&address, Some(prefix)), Ok(alice()));
+        assert_eq!(normalize_glitch_address(&address, Some(prefix)).as_deref(), Ok(address.as_str()));
+    }
+}
+
+#[test]
+fn normalizes_generic_and_hex_addresses_to_the_glitch_prefix() {
+    for address in [ALICE_GENERIC, ALICE_HEX, ALICE_GLITCH] {
+        assert_eq!(normalize_glitch_address(address, Some(GLITCH_PREFIX)).as_deref(), Ok(ALICE_GLITCH));
+    }
+    // Without a configured prefix everything is stored as generic SS58.
+    assert_eq!(normalize_glitch_address(ALICE_HEX, None).as_deref(), Ok(ALICE_GENERIC));
+    assert_eq!(normalize_glitch_address(ALICE_POLKADOT, None).as_deref(), Ok(ALICE_GENERIC));
+}
+
+#[test]
+fn rejects_other_networks_when_the_prefix_is_set() {
+    for address in [ALICE_POLKADOT, ALICE_KUSAMA, ALICE_PREFIX_1000] {
+        assert!(parse_glitch_address(address, Some(GLITCH_PREFIX)).is_err(), "{address}");
+        assert!(normalize_glitch_address(address, Some(GLITCH_PREFIX)).is_err(), "{address}");
+    }
+}
+
+#[test]
+fn rejects_malformed_addresses() {
+    let mut bad_checksum = ALICE_GENERIC.to_string();
+    bad_checksum.pop();
+    bad_checksum.push('Z');
+
+    for address in ["", "0x", "0xd435", &ALICE_HEX[..64], &bad_checksum, "not an address"] {
+        assert!(parse_glitch_address(address, None).is_err(), "{address}");
+    }
+}
+
+#[test]
+fn fee_destinations_follow_the_recipient_rules() {
+    let parse = |address: &str| serde_json::from_value::<FeeDestination>(serde_json::json!(address));
+
+    for address in [ALICE_HEX, ALICE_GENERIC, ALICE_GLITCH] {
+        let destination = parse(address).unwrap();
+        assert_eq!(destination.account_id(), AccountId32::from(alice()));
+        assert!(destination.check_ss58_prefix(Some(GLITCH_PREFIX)).is_ok(), "{address}");
+    }
+
+    let polkadot = parse(ALICE_POLKADOT).unwrap();
+    assert!(polkadot.check_ss58_prefix(Some(GLITCH_PREFIX)).is_err());
+    assert!(polkadot.check_ss58_prefix(None).is_ok());
+    // Written back as given.
+    assert_eq!(String::from(polkadot), ALICE_POLKADOT);
+
+    assert!(parse("not an address").is_err());
+}
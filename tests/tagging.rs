@@ -0,0 +1,35 @@
+use glitch_bridge::config::{check_tag_rules, TagAction, TagRule};
+
+fn rule(label: &str, action: Option<TagAction>) -> TagRule {
+    TagRule {
+        label: label.to_string(),
+        min_amount: None,
+        max_amount: None,
+        senders: None,
+        tokens: None,
+        action,
+    }
+}
+
+#[test]
+fn accepts_a_label_repeated_with_the_same_action() {
+    let rules = vec![
+        rule("vip", Some(TagAction::Priority)),
+        rule("vip", Some(TagAction::Priority)),
+        rule("internal", Some(TagAction::FeeExempt)),
+        rule("watch", None),
+        rule("watch", None),
+    ];
+
+    assert_eq!(check_tag_rules(&rules), Ok(()));
+}
+
+#[test]
+fn rejects_a_label_given_different_actions() {
+    let rules = vec![rule("vip", Some(TagAction::Priority)), rule("vip", Some(TagAction::FeeExempt))];
+    assert!(check_tag_rules(&rules).is_err());
+
+    // Deposits tagged by the rule without the action would not be held.
+    let rules = vec![rule("review", Some(TagAction::ManualReview)), rule("review", None)];
+    assert!(check_tag_rules(&rules).is_err());
+}
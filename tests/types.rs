@@ -0,0 +1,51 @@
+use glitch_bridge::types::{
+    account_id_from_ss58, account_id_to_ss58, h256_to_address, to_hex, u256_to_u128, u256_to_usize,
+};
+use web3::types::{H160, H256, U256};
+
+/// Alice's dev account.
+const ALICE_GENERIC: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const ALICE_HEX: &str = "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+
+#[test]
+fn formats_hashes_and_addresses_as_prefixed_lowercase_hex() {
+    let hash = H256::from_low_u64_be(0xABCDEF);
+    assert_eq!(
+        to_hex(hash),
+        "0x0000000000000000000000000000000000000000000000000000000000abcdef"
+    );
+    assert_eq!(to_hex(H160::repeat_byte(0xAB)), "0xabababababababababababababababababababab");
+    assert_eq!(to_hex(U256::from(255)), "0xff");
+}
+
+#[test]
+fn takes_the_address_from_the_low_bytes_of_a_topic() {
+    let mut topic = [0u8; 32];
+    topic[12..].copy_from_slice(&[0x11; 20]);
+
+    assert_eq!(h256_to_address(H256::from(topic)), "0x1111111111111111111111111111111111111111");
+}
+
+#[test]
+fn converts_u256_only_when_it_fits() {
+    assert_eq!(u256_to_u128(U256::from(u128::MAX)), Some(u128::MAX));
+    assert_eq!(u256_to_u128(U256::from(u128::MAX) + 1), None);
+    assert_eq!(u256_to_u128(U256::MAX), None);
+
+    assert_eq!(u256_to_usize(U256::from(usize::MAX)), Some(usize::MAX));
+    assert_eq!(u256_to_usize(U256::from(usize::MAX) + 1), None);
+}
+
+#[test]
+fn converts_account_ids_to_and_from_ss58() {
+    let account_id = account_id_from_ss58(ALICE_GENERIC).unwrap();
+
+    assert_eq!(to_hex(H256::from(<[u8; 32]>::from(account_id.clone()))), ALICE_HEX);
+    assert_eq!(account_id_to_ss58(&account_id), ALICE_GENERIC);
+}
+
+#[test]
+fn rejects_invalid_ss58() {
+    assert!(account_id_from_ss58("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQZ").is_err());
+    assert!(account_id_from_ss58("not an address").is_err());
+}